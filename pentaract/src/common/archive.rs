@@ -0,0 +1,892 @@
+use std::io::{Cursor, Read};
+
+use axum::body::Bytes;
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use crc::{Crc, Digest, CRC_32_ISO_HDLC};
+use flate2::read::GzDecoder;
+use tar::Archive;
+use tokio::sync::mpsc;
+use zip::ZipArchive;
+
+use crate::errors::{PentaractError, PentaractResult};
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+/// NOTE:
+///
+/// folders paths must end with a slash
+pub struct ArchiveEntry {
+    pub path: String,
+    pub size: u64,
+    pub modified_at: DateTime<Utc>,
+}
+
+impl ArchiveEntry {
+    fn is_dir(&self) -> bool {
+        self.path.ends_with('/')
+    }
+}
+
+/// Writes archives entry by entry, so files contents can be passed on as they arrive.
+///
+/// Every method returns bytes which have to be written right after the previous ones
+pub trait ArchiveWriter: Send {
+    fn start_entry(&mut self, entry: &ArchiveEntry) -> Vec<u8>;
+
+    /// Content is passed through as is
+    fn write(&mut self, data: &[u8]);
+
+    fn finish_entry(&mut self) -> PentaractResult<Vec<u8>>;
+
+    fn finish(&mut self) -> Vec<u8>;
+}
+
+/// Files are stored without compression, and their CRC-32 and sizes
+/// go to data descriptors after contents.
+///
+/// ZIP64 records are written only if something doesn't fit into 32 bits
+pub struct ZipWriter {
+    offset: u64,
+    entries: Vec<ZipEntry>,
+    current: Option<(ZipEntry, Digest<'static, u32>)>,
+}
+
+struct ZipEntry {
+    path: String,
+    is_dir: bool,
+    is_zip64: bool,
+    time: u16,
+    date: u16,
+    crc: u32,
+    size: u64,
+    offset: u64,
+}
+
+const ZIP_VERSION: u16 = 45;
+/// sizes are in data descriptors and names are in UTF-8
+const ZIP_FLAGS: u16 = 1 << 3 | 1 << 11;
+
+impl ZipWriter {
+    pub fn new() -> Self {
+        Self {
+            offset: 0,
+            entries: vec![],
+            current: None,
+        }
+    }
+
+    fn dos_datetime(dt: &DateTime<Utc>) -> (u16, u16) {
+        if dt.year() < 1980 {
+            return (0, 1 << 5 | 1);
+        }
+
+        let time = dt.hour() << 11 | dt.minute() << 5 | (dt.second() / 2);
+        let date = ((dt.year() - 1980) as u32).min(127) << 9 | dt.month() << 5 | dt.day();
+        (time as u16, date as u16)
+    }
+
+    fn push(&mut self, buf: Vec<u8>) -> Vec<u8> {
+        self.offset += buf.len() as u64;
+        buf
+    }
+}
+
+impl Default for ZipWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArchiveWriter for ZipWriter {
+    fn start_entry(&mut self, entry: &ArchiveEntry) -> Vec<u8> {
+        let (time, date) = Self::dos_datetime(&entry.modified_at);
+        let is_zip64 = entry.size >= u32::MAX as u64;
+        let zip_entry = ZipEntry {
+            path: entry.path.clone(),
+            is_dir: entry.is_dir(),
+            is_zip64,
+            time,
+            date,
+            crc: 0,
+            size: 0,
+            offset: self.offset,
+        };
+
+        let mut buf = vec![];
+        buf.extend(0x04034b50u32.to_le_bytes());
+        buf.extend(ZIP_VERSION.to_le_bytes());
+        buf.extend(ZIP_FLAGS.to_le_bytes());
+        // stored
+        buf.extend(0u16.to_le_bytes());
+        buf.extend(time.to_le_bytes());
+        buf.extend(date.to_le_bytes());
+        // CRC-32 and sizes are unknown yet
+        buf.extend(0u32.to_le_bytes());
+        let size = if is_zip64 { u32::MAX } else { 0 };
+        buf.extend(size.to_le_bytes());
+        buf.extend(size.to_le_bytes());
+        buf.extend((entry.path.len() as u16).to_le_bytes());
+        buf.extend((if is_zip64 { 20u16 } else { 0 }).to_le_bytes());
+        buf.extend(entry.path.as_bytes());
+        if is_zip64 {
+            buf.extend(0x0001u16.to_le_bytes());
+            buf.extend(16u16.to_le_bytes());
+            buf.extend(0u64.to_le_bytes());
+            buf.extend(0u64.to_le_bytes());
+        }
+
+        self.current = Some((zip_entry, CRC32.digest()));
+        self.push(buf)
+    }
+
+    fn write(&mut self, data: &[u8]) {
+        if let Some((entry, digest)) = self.current.as_mut() {
+            entry.size += data.len() as u64;
+            digest.update(data);
+        }
+        self.offset += data.len() as u64;
+    }
+
+    fn finish_entry(&mut self) -> PentaractResult<Vec<u8>> {
+        let Some((mut entry, digest)) = self.current.take() else {
+            return Ok(vec![]);
+        };
+        entry.crc = digest.finalize();
+
+        let mut buf = vec![];
+        buf.extend(0x08074b50u32.to_le_bytes());
+        buf.extend(entry.crc.to_le_bytes());
+        if entry.is_zip64 {
+            buf.extend(entry.size.to_le_bytes());
+            buf.extend(entry.size.to_le_bytes());
+        } else if entry.size < u32::MAX as u64 {
+            buf.extend((entry.size as u32).to_le_bytes());
+            buf.extend((entry.size as u32).to_le_bytes());
+        } else {
+            // the content turned out to be bigger than the entry was started for
+            return Err(PentaractError::Unknown);
+        }
+
+        self.entries.push(entry);
+        Ok(self.push(buf))
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        let central_directory_offset = self.offset;
+
+        let mut buf = vec![];
+        for entry in self.entries.iter() {
+            let mut extra = vec![];
+            let size = if entry.size >= u32::MAX as u64 {
+                extra.extend(entry.size.to_le_bytes());
+                extra.extend(entry.size.to_le_bytes());
+                u32::MAX
+            } else {
+                entry.size as u32
+            };
+            let offset = if entry.offset >= u32::MAX as u64 {
+                extra.extend(entry.offset.to_le_bytes());
+                u32::MAX
+            } else {
+                entry.offset as u32
+            };
+            if !extra.is_empty() {
+                let mut zip64_extra = vec![];
+                zip64_extra.extend(0x0001u16.to_le_bytes());
+                zip64_extra.extend((extra.len() as u16).to_le_bytes());
+                zip64_extra.extend(extra);
+                extra = zip64_extra;
+            }
+            let attributes: u32 = if entry.is_dir {
+                0o40755 << 16 | 0x10
+            } else {
+                0o100644 << 16
+            };
+
+            buf.extend(0x02014b50u32.to_le_bytes());
+            // made by unix
+            buf.extend((3 << 8 | ZIP_VERSION).to_le_bytes());
+            buf.extend(ZIP_VERSION.to_le_bytes());
+            buf.extend(ZIP_FLAGS.to_le_bytes());
+            buf.extend(0u16.to_le_bytes());
+            buf.extend(entry.time.to_le_bytes());
+            buf.extend(entry.date.to_le_bytes());
+            buf.extend(entry.crc.to_le_bytes());
+            buf.extend(size.to_le_bytes());
+            buf.extend(size.to_le_bytes());
+            buf.extend((entry.path.len() as u16).to_le_bytes());
+            buf.extend((extra.len() as u16).to_le_bytes());
+            // comment length, disk number and internal attributes
+            buf.extend(0u16.to_le_bytes());
+            buf.extend(0u16.to_le_bytes());
+            buf.extend(0u16.to_le_bytes());
+            buf.extend(attributes.to_le_bytes());
+            buf.extend(offset.to_le_bytes());
+            buf.extend(entry.path.as_bytes());
+            buf.extend(extra);
+        }
+
+        let central_directory_size = buf.len() as u64;
+        let entries_amount = self.entries.len() as u64;
+        let is_zip64 = entries_amount >= u16::MAX as u64
+            || central_directory_size >= u32::MAX as u64
+            || central_directory_offset >= u32::MAX as u64;
+
+        if is_zip64 {
+            let zip64_end_offset = central_directory_offset + central_directory_size;
+
+            buf.extend(0x06064b50u32.to_le_bytes());
+            buf.extend(44u64.to_le_bytes());
+            buf.extend((3 << 8 | ZIP_VERSION).to_le_bytes());
+            buf.extend(ZIP_VERSION.to_le_bytes());
+            buf.extend(0u32.to_le_bytes());
+            buf.extend(0u32.to_le_bytes());
+            buf.extend(entries_amount.to_le_bytes());
+            buf.extend(entries_amount.to_le_bytes());
+            buf.extend(central_directory_size.to_le_bytes());
+            buf.extend(central_directory_offset.to_le_bytes());
+
+            buf.extend(0x07064b50u32.to_le_bytes());
+            buf.extend(0u32.to_le_bytes());
+            buf.extend(zip64_end_offset.to_le_bytes());
+            buf.extend(1u32.to_le_bytes());
+        }
+
+        buf.extend(0x06054b50u32.to_le_bytes());
+        buf.extend(0u16.to_le_bytes());
+        buf.extend(0u16.to_le_bytes());
+        let entries_amount = entries_amount.min(u16::MAX as u64) as u16;
+        buf.extend(entries_amount.to_le_bytes());
+        buf.extend(entries_amount.to_le_bytes());
+        buf.extend((central_directory_size.min(u32::MAX as u64) as u32).to_le_bytes());
+        buf.extend((central_directory_offset.min(u32::MAX as u64) as u32).to_le_bytes());
+        buf.extend(0u16.to_le_bytes());
+
+        self.push(buf)
+    }
+}
+
+/// POSIX ustar archive, paths longer than 100 bytes and sizes
+/// over 8 GiB go to PAX extended headers
+pub struct TarWriter {
+    expected_size: u64,
+    written: u64,
+}
+
+const TAR_BLOCK: usize = 512;
+/// The biggest number fitting 11 octal digits
+const TAR_MAX_OCTAL: u64 = 0o77777777777;
+
+impl TarWriter {
+    pub fn new() -> Self {
+        Self {
+            expected_size: 0,
+            written: 0,
+        }
+    }
+
+    fn header(name: &str, size: u64, mtime: i64, typeflag: u8) -> [u8; TAR_BLOCK] {
+        let mut header = [0u8; TAR_BLOCK];
+        let mode: &[u8] = if typeflag == b'5' {
+            b"0000755"
+        } else {
+            b"0000644"
+        };
+
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..107].copy_from_slice(mode);
+        header[108..115].copy_from_slice(b"0000000");
+        header[116..123].copy_from_slice(b"0000000");
+        header[124..135].copy_from_slice(format!("{:011o}", size.min(TAR_MAX_OCTAL)).as_bytes());
+        header[136..147].copy_from_slice(
+            format!("{:011o}", (mtime.max(0) as u64).min(TAR_MAX_OCTAL)).as_bytes(),
+        );
+        header[156] = typeflag;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+
+        // checksum is counted with its own field filled with spaces
+        header[148..156].copy_from_slice(b"        ");
+        let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+        header[148..155].copy_from_slice(format!("{checksum:06o}\0").as_bytes());
+
+        header
+    }
+
+    /// PAX record is `<length> <key>=<value>\n` where the length counts itself as well
+    fn pax_record(key: &str, value: &str) -> String {
+        let len = key.len() + value.len() + 3;
+        let mut total = len + len.to_string().len();
+        if total.to_string().len() != len.to_string().len() {
+            total += 1;
+        }
+        format!("{total} {key}={value}\n")
+    }
+
+    fn padding(size: u64) -> Vec<u8> {
+        vec![0; (TAR_BLOCK - (size % TAR_BLOCK as u64) as usize) % TAR_BLOCK]
+    }
+
+    /// Names must fit 100 bytes cutting them by characters boundaries
+    fn short_name(path: &str) -> &str {
+        let mut end = path.len().min(100);
+        while !path.is_char_boundary(end) {
+            end -= 1;
+        }
+        &path[..end]
+    }
+}
+
+impl Default for TarWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArchiveWriter for TarWriter {
+    fn start_entry(&mut self, entry: &ArchiveEntry) -> Vec<u8> {
+        let mtime = entry.modified_at.timestamp();
+        let size = if entry.is_dir() { 0 } else { entry.size };
+
+        let mut records = String::new();
+        if entry.path.len() > 100 {
+            records.push_str(&Self::pax_record("path", &entry.path));
+        }
+        if size > TAR_MAX_OCTAL {
+            records.push_str(&Self::pax_record("size", &size.to_string()));
+        }
+
+        let mut buf = vec![];
+        if !records.is_empty() {
+            let pax_name = format!("PaxHeaders/{}", Self::short_name(&entry.path));
+            buf.extend(Self::header(
+                Self::short_name(&pax_name),
+                records.len() as u64,
+                mtime,
+                b'x',
+            ));
+            buf.extend(records.as_bytes());
+            buf.extend(Self::padding(records.len() as u64));
+        }
+
+        let typeflag = if entry.is_dir() { b'5' } else { b'0' };
+        buf.extend(Self::header(
+            Self::short_name(&entry.path),
+            size,
+            mtime,
+            typeflag,
+        ));
+
+        self.expected_size = size;
+        self.written = 0;
+        buf
+    }
+
+    fn write(&mut self, data: &[u8]) {
+        self.written += data.len() as u64;
+    }
+
+    /// Sizes are written in headers, so contents must have exactly the same sizes
+    fn finish_entry(&mut self) -> PentaractResult<Vec<u8>> {
+        if self.written != self.expected_size {
+            tracing::error!(
+                "archived file has {} bytes instead of {}",
+                self.written,
+                self.expected_size
+            );
+            return Err(PentaractError::Unknown);
+        }
+
+        Ok(Self::padding(self.written))
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        vec![0; TAR_BLOCK * 2]
+    }
+}
+
+/// Bounds of what an uploaded archive is extracted into,
+/// so archives inflating into much more than they take can't exhaust memory or quotas
+pub struct ExtractLimits {
+    /// total size of files contents
+    pub max_size: u64,
+    pub max_files: u64,
+    /// files, folders and skipped entries together
+    pub max_entries: u64,
+}
+
+/// What's left of the limits while entries are read
+struct ExtractBudget {
+    size: u64,
+    files: u64,
+    entries: u64,
+}
+
+impl ExtractBudget {
+    fn new(limits: ExtractLimits) -> Self {
+        Self {
+            size: limits.max_size,
+            files: limits.max_files,
+            entries: limits.max_entries,
+        }
+    }
+
+    /// `false` once there are more entries than allowed
+    fn take_entry(&mut self) -> bool {
+        self.entries
+            .checked_sub(1)
+            .map(|left| self.entries = left)
+            .is_some()
+    }
+
+    /// Reads an entry content without trusting the size the archive declares for it.
+    ///
+    /// Returns the reason if the content doesn't fit the limits
+    fn read<R: Read>(&mut self, reader: R) -> Result<Vec<u8>, (String, bool)> {
+        if self.files == 0 {
+            return Err(("archive has more files than allowed".to_owned(), true));
+        }
+
+        let mut content = vec![];
+        reader
+            .take(self.size.saturating_add(1))
+            .read_to_end(&mut content)
+            .map_err(|e| (e.to_string(), false))?;
+        if content.len() as u64 > self.size {
+            return Err(("archive content is larger than allowed".to_owned(), true));
+        }
+
+        self.size -= content.len() as u64;
+        self.files -= 1;
+        Ok(content)
+    }
+}
+
+const TOO_MANY_ENTRIES: &str = "archive has more entries than allowed";
+
+/// An entry read from an uploaded archive, paths are relative to the archive root.
+///
+/// Entries which cannot be extracted are passed on with the reason
+pub enum ExtractedEntry {
+    Folder(String),
+    File(String, Vec<u8>),
+    Skipped(String, String),
+}
+
+/// Reads a ZIP, TAR or gzipped TAR archive detecting its format by its content.
+///
+/// Entries are sent one by one, so only a single entry content is kept in memory.
+/// Once the limits are exceeded, the entry is skipped and the rest are not read.
+/// It's blocking, so it's meant to be run in a blocking thread
+pub fn extract(
+    data: Bytes,
+    limits: ExtractLimits,
+    tx: mpsc::Sender<ExtractedEntry>,
+) -> PentaractResult<()> {
+    let budget = ExtractBudget::new(limits);
+    if data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06") {
+        extract_zip(data, budget, tx)
+    } else if data.starts_with(&[0x1f, 0x8b]) {
+        extract_tar(GzDecoder::new(Cursor::new(data)), budget, tx)
+    } else if data.get(257..262) == Some(b"ustar") {
+        extract_tar(Cursor::new(data), budget, tx)
+    } else {
+        Err(PentaractError::InvalidArchive(
+            "only ZIP, TAR and TAR.GZ are supported".to_owned(),
+        ))
+    }
+}
+
+fn extract_zip(
+    data: Bytes,
+    mut budget: ExtractBudget,
+    tx: mpsc::Sender<ExtractedEntry>,
+) -> PentaractResult<()> {
+    let mut archive = ZipArchive::new(Cursor::new(data))
+        .map_err(|e| PentaractError::InvalidArchive(e.to_string()))?;
+
+    for i in 0..archive.len() {
+        let (name, mode) = match archive.by_index_raw(i) {
+            Ok(file) => (file.name().to_owned(), file.unix_mode()),
+            Err(e) => return Err(PentaractError::InvalidArchive(e.to_string())),
+        };
+        if !budget.take_entry() {
+            let _ = tx.blocking_send(ExtractedEntry::Skipped(name, TOO_MANY_ENTRIES.to_owned()));
+            break;
+        }
+
+        let mut is_exceeded = false;
+        let entry = match sanitize_path(&name) {
+            None => ExtractedEntry::Skipped(name, "path is out of the folder".to_owned()),
+            Some(path) if name.ends_with('/') => ExtractedEntry::Folder(path),
+            Some(_) if mode.is_some_and(|mode| mode & 0o170000 == 0o120000) => {
+                ExtractedEntry::Skipped(name, "symbolic links are not supported".to_owned())
+            }
+            Some(path) => match archive
+                .by_index(i)
+                .map_err(|e| (e.to_string(), false))
+                .and_then(|file| budget.read(file))
+            {
+                Ok(content) => ExtractedEntry::File(path, content),
+                Err((reason, is_limit)) => {
+                    is_exceeded = is_limit;
+                    ExtractedEntry::Skipped(name, reason)
+                }
+            },
+        };
+
+        // the receiver is gone
+        if tx.blocking_send(entry).is_err() || is_exceeded {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+fn extract_tar<R: Read>(
+    reader: R,
+    mut budget: ExtractBudget,
+    tx: mpsc::Sender<ExtractedEntry>,
+) -> PentaractResult<()> {
+    let mut archive = Archive::new(reader);
+    let entries = archive
+        .entries()
+        .map_err(|e| PentaractError::InvalidArchive(e.to_string()))?;
+
+    for (i, entry) in entries.enumerate() {
+        let mut entry = match entry {
+            Ok(entry) => entry,
+            // archive content cannot be trusted after the first broken entry
+            Err(e) if i == 0 => return Err(PentaractError::InvalidArchive(e.to_string())),
+            Err(e) => {
+                let _ = tx.blocking_send(ExtractedEntry::Skipped(String::new(), e.to_string()));
+                break;
+            }
+        };
+        let name = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+        let entry_type = entry.header().entry_type();
+        if !budget.take_entry() {
+            let _ = tx.blocking_send(ExtractedEntry::Skipped(name, TOO_MANY_ENTRIES.to_owned()));
+            break;
+        }
+
+        let mut is_exceeded = false;
+        let extracted = match sanitize_path(&name) {
+            None => ExtractedEntry::Skipped(name, "path is out of the folder".to_owned()),
+            Some(path) if entry_type.is_dir() => ExtractedEntry::Folder(path),
+            Some(path) if entry_type.is_file() => match budget.read(&mut entry) {
+                Ok(content) => ExtractedEntry::File(path, content),
+                Err((reason, is_limit)) => {
+                    is_exceeded = is_limit;
+                    ExtractedEntry::Skipped(name, reason)
+                }
+            },
+            Some(_) => {
+                ExtractedEntry::Skipped(name, "only files and folders are supported".to_owned())
+            }
+        };
+
+        if tx.blocking_send(extracted).is_err() || is_exceeded {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Normalizes a relative path sent by a client or stored in an archive,
+/// `None` means the path leads out of the target folder
+pub fn sanitize_path(path: &str) -> Option<String> {
+    if path.starts_with(['/', '\\']) {
+        return None;
+    }
+
+    let mut parts = vec![];
+    for part in path.split(['/', '\\']) {
+        match part {
+            "" | "." => (),
+            ".." => return None,
+            // windows drives, like `C:`
+            _ if parts.is_empty() && part.ends_with(':') => return None,
+            _ if part.chars().any(char::is_control) => return None,
+            _ => parts.push(part),
+        }
+    }
+
+    Some(parts.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn entry(path: &str, size: u64) -> ArchiveEntry {
+        ArchiveEntry {
+            path: path.to_owned(),
+            size,
+            modified_at: Utc.with_ymd_and_hms(2024, 5, 17, 12, 30, 42).unwrap(),
+        }
+    }
+
+    fn write_archive(writer: &mut dyn ArchiveWriter, files: &[(ArchiveEntry, &[u8])]) -> Vec<u8> {
+        let mut buf = vec![];
+        for (entry, content) in files {
+            buf.extend(writer.start_entry(entry));
+            writer.write(content);
+            buf.extend(content.iter());
+            buf.extend(writer.finish_entry().unwrap());
+        }
+        buf.extend(writer.finish());
+        buf
+    }
+
+    #[test]
+    fn zip_round_trip() {
+        let buf = write_archive(
+            &mut ZipWriter::new(),
+            &[
+                (entry("docs/", 0), b""),
+                (entry("docs/readme.txt", 5), b"hello"),
+                (entry("docs/тест.bin", 3), &[0, 1, 2]),
+            ],
+        );
+
+        let mut archive = ZipArchive::new(Cursor::new(buf)).unwrap();
+        assert_eq!(archive.len(), 3);
+        assert!(archive.by_index(0).unwrap().is_dir());
+
+        let mut file = archive.by_name("docs/readme.txt").unwrap();
+        let mut content = vec![];
+        file.read_to_end(&mut content).unwrap();
+        assert_eq!(content, b"hello");
+        assert_eq!(file.crc32(), CRC32.checksum(b"hello"));
+        let modified = file.last_modified();
+        assert_eq!(
+            (modified.year(), modified.month(), modified.day()),
+            (2024, 5, 17)
+        );
+        assert_eq!(
+            (modified.hour(), modified.minute(), modified.second()),
+            (12, 30, 42)
+        );
+        drop(file);
+
+        let mut content = vec![];
+        archive
+            .by_name("docs/тест.bin")
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, [0, 1, 2]);
+    }
+
+    /// Entries started for 4 GiB get ZIP64 local headers and data descriptors
+    #[test]
+    fn zip_round_trip_zip64_entry() {
+        let buf = write_archive(
+            &mut ZipWriter::new(),
+            &[
+                (entry("big.bin", u32::MAX as u64), b"not that big"),
+                (entry("small.txt", 2), b"ok"),
+            ],
+        );
+
+        // the descriptor of the first entry has 64-bit sizes
+        let descriptor = 30 + "big.bin".len() + 20 + "not that big".len();
+        assert_eq!(
+            &buf[descriptor..descriptor + 4],
+            &0x08074b50u32.to_le_bytes()
+        );
+        assert_eq!(&buf[descriptor + 8..descriptor + 16], &12u64.to_le_bytes());
+
+        let mut archive = ZipArchive::new(Cursor::new(buf)).unwrap();
+        for (name, expected) in [("big.bin", &b"not that big"[..]), ("small.txt", b"ok")] {
+            let mut content = vec![];
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_end(&mut content)
+                .unwrap();
+            assert_eq!(content, expected);
+        }
+    }
+
+    /// Too many entries for the classic end of central directory
+    #[test]
+    fn zip_round_trip_zip64_end_of_central_directory() {
+        let names: Vec<String> = (0..u16::MAX as u32 + 1).map(|i| format!("{i}")).collect();
+        let files: Vec<_> = names
+            .iter()
+            .map(|name| (entry(name, 0), &b""[..]))
+            .collect();
+        let buf = write_archive(&mut ZipWriter::new(), &files);
+
+        let mut archive = ZipArchive::new(Cursor::new(buf)).unwrap();
+        assert_eq!(archive.len(), names.len());
+        assert_eq!(archive.by_index(names.len() - 1).unwrap().name(), "65535");
+    }
+
+    #[test]
+    fn zip_entry_overflowing_its_size_fails() {
+        let mut writer = ZipWriter::new();
+        writer.start_entry(&entry("file", 1));
+        writer.current.as_mut().unwrap().0.size = u32::MAX as u64;
+
+        assert!(writer.finish_entry().is_err());
+    }
+
+    #[test]
+    fn tar_round_trip() {
+        let content = vec![7u8; 1000];
+        let buf = write_archive(
+            &mut TarWriter::new(),
+            &[
+                (entry("docs/", 0), b""),
+                (entry("docs/data.bin", 1000), &content),
+                (entry("docs/empty", 0), b""),
+            ],
+        );
+        assert_eq!(buf.len() % TAR_BLOCK, 0);
+
+        let mut archive = Archive::new(Cursor::new(buf));
+        let mut entries = archive.entries().unwrap().map(Result::unwrap);
+
+        let folder = entries.next().unwrap();
+        assert!(folder.header().entry_type().is_dir());
+        assert_eq!(folder.path_bytes().as_ref(), b"docs/");
+
+        let mut file = entries.next().unwrap();
+        assert!(file.header().entry_type().is_file());
+        assert_eq!(file.header().mtime().unwrap(), 1715949042);
+        let mut read = vec![];
+        file.read_to_end(&mut read).unwrap();
+        assert_eq!(read, content);
+        drop(file);
+
+        assert_eq!(entries.next().unwrap().size(), 0);
+        assert!(entries.next().is_none());
+    }
+
+    #[test]
+    fn tar_round_trip_pax_path() {
+        let path = format!("{}/{}", "ф".repeat(60), "file.txt");
+        let buf = write_archive(&mut TarWriter::new(), &[(entry(&path, 2), b"ok")]);
+
+        let mut archive = Archive::new(Cursor::new(buf));
+        let mut entries = archive.entries().unwrap().map(Result::unwrap);
+        let mut file = entries.next().unwrap();
+        assert_eq!(file.path_bytes().as_ref(), path.as_bytes());
+        let mut read = vec![];
+        file.read_to_end(&mut read).unwrap();
+        assert_eq!(read, b"ok");
+        drop(file);
+        assert!(entries.next().is_none());
+    }
+
+    /// Only the headers are checked, as the content would be over 8 GiB
+    #[test]
+    fn tar_pax_size() {
+        let size = TAR_MAX_OCTAL + 1;
+        let buf = TarWriter::new().start_entry(&entry("huge.bin", size));
+
+        let mut archive = Archive::new(Cursor::new(buf));
+        let file = archive.entries().unwrap().next().unwrap().unwrap();
+        assert_eq!(file.path_bytes().as_ref(), b"huge.bin");
+        assert_eq!(file.size(), size);
+    }
+
+    #[test]
+    fn tar_content_of_another_size_fails() {
+        let mut writer = TarWriter::new();
+        writer.start_entry(&entry("file", 3));
+        writer.write(b"ab");
+
+        assert!(writer.finish_entry().is_err());
+    }
+
+    fn extract_all(data: Vec<u8>, limits: ExtractLimits) -> Vec<ExtractedEntry> {
+        let (tx, mut rx) = mpsc::channel(100);
+        extract(data.into(), limits, tx).unwrap();
+
+        let mut entries = vec![];
+        while let Ok(entry) = rx.try_recv() {
+            entries.push(entry);
+        }
+        entries
+    }
+
+    fn limits(max_size: u64, max_files: u64, max_entries: u64) -> ExtractLimits {
+        ExtractLimits {
+            max_size,
+            max_files,
+            max_entries,
+        }
+    }
+
+    fn bomb(writer: &mut dyn ArchiveWriter) -> Vec<u8> {
+        let content = vec![0u8; 100];
+        write_archive(
+            writer,
+            &[
+                (entry("dir/", 0), b""),
+                (entry("a", 100), &content),
+                (entry("b", 100), &content),
+                (entry("c", 100), &content),
+            ],
+        )
+    }
+
+    #[test]
+    fn extract_within_limits() {
+        for data in [bomb(&mut ZipWriter::new()), bomb(&mut TarWriter::new())] {
+            let entries = extract_all(data, limits(300, 3, 4));
+            assert_eq!(entries.len(), 4);
+            assert!(matches!(&entries[0], ExtractedEntry::Folder(path) if path == "dir"));
+            assert!(
+                matches!(&entries[3], ExtractedEntry::File(path, data) if path == "c" && data.len() == 100)
+            );
+        }
+    }
+
+    #[test]
+    fn extract_stops_at_size_limit() {
+        for data in [bomb(&mut ZipWriter::new()), bomb(&mut TarWriter::new())] {
+            let entries = extract_all(data, limits(250, 10, 10));
+            assert_eq!(entries.len(), 4);
+            assert!(matches!(&entries[2], ExtractedEntry::File(path, _) if path == "b"));
+            assert!(matches!(&entries[3], ExtractedEntry::Skipped(path, _) if path == "c"));
+        }
+    }
+
+    #[test]
+    fn extract_stops_at_files_limit() {
+        for data in [bomb(&mut ZipWriter::new()), bomb(&mut TarWriter::new())] {
+            let entries = extract_all(data, limits(1000, 1, 10));
+            assert_eq!(entries.len(), 3);
+            assert!(matches!(&entries[2], ExtractedEntry::Skipped(path, _) if path == "b"));
+        }
+    }
+
+    #[test]
+    fn extract_stops_at_entries_limit() {
+        for data in [bomb(&mut ZipWriter::new()), bomb(&mut TarWriter::new())] {
+            let entries = extract_all(data, limits(1000, 10, 2));
+            assert_eq!(entries.len(), 3);
+            assert!(
+                matches!(&entries[2], ExtractedEntry::Skipped(path, reason) if path == "b" && reason == TOO_MANY_ENTRIES)
+            );
+        }
+    }
+
+    #[test]
+    fn pax_record_length_counts_itself() {
+        for value in ["", "a", "a".repeat(90).as_str(), "a".repeat(994).as_str()] {
+            let record = TarWriter::pax_record("path", value);
+            let (len, _) = record.split_once(' ').unwrap();
+            assert_eq!(len.parse::<usize>().unwrap(), record.len());
+        }
+    }
+}
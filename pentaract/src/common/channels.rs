@@ -1,7 +1,7 @@
 use tokio::sync::{mpsc, oneshot};
 use uuid::Uuid;
 
-use crate::errors::PentaractResult;
+use crate::{errors::PentaractResult, models::file_chunks::FileChunk};
 
 //////////////////////////////////////
 ///     Client schemas
@@ -14,20 +14,42 @@ pub struct ClientMessage {
 
 pub enum ClientData {
     UploadFile(UploadFileData),
+    UploadPart(UploadPartData),
     DownloadFile(DownloadFileData),
+    DownloadChunk(DownloadChunkData),
 }
 
+/// NOTE:
+///
+/// `version_id` is set when uploading a new version of an existing file,
+/// it becomes the current one only after the upload succeeded
 pub struct UploadFileData {
     pub file_id: Uuid,
+    pub version_id: Option<Uuid>,
     pub user_id: Uuid,
     pub file_data: Box<[u8]>,
 }
 
+/// Parts of multipart uploads are uploaded in chunks as they come,
+/// and the chunks are linked to the file once the upload is completed
+pub struct UploadPartData {
+    pub part_id: Uuid,
+    pub storage_id: Uuid,
+    pub part_data: Box<[u8]>,
+}
+
 pub struct DownloadFileData {
     pub file_id: Uuid,
+    pub version_id: Option<Uuid>,
     pub storage_id: Uuid,
     pub user_id: Uuid,
 }
+
+/// Single chunks are downloaded to stream files without keeping them in memory
+pub struct DownloadChunkData {
+    pub storage_id: Uuid,
+    pub chunk: FileChunk,
+}
 //////////////////////////////////////
 ///     Storage manager schemas
 //////////////////////////////////////
@@ -44,7 +66,9 @@ impl StorageManagerMessage {
 
 pub enum StorageManagerData {
     UploadFile(PentaractResult<()>),
+    UploadPart(PentaractResult<()>),
     DownloadFile(PentaractResult<Vec<u8>>),
+    DownloadChunk(PentaractResult<Vec<u8>>),
 }
 
 //////////////////////////////////////
@@ -0,0 +1,11 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64, Engine};
+use uuid::Uuid;
+
+/// Public links tokens are made of random bits of two UUIDs v4
+pub fn generate_token() -> String {
+    let bytes: Vec<u8> = [Uuid::new_v4(), Uuid::new_v4()]
+        .iter()
+        .flat_map(|id| id.into_bytes())
+        .collect();
+    BASE64.encode(bytes)
+}
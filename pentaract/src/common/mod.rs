@@ -1,8 +1,16 @@
 pub mod access;
+pub mod archive;
 pub mod channels;
 pub mod db;
 pub mod jwt_manager;
+pub mod links;
 pub mod password_manager;
 pub mod routing;
+pub mod s3;
+pub mod sigv4;
 pub mod telegram_api;
+pub mod thumbnails;
+pub mod token_cipher;
 pub mod types;
+pub mod url_signer;
+pub mod webdav;
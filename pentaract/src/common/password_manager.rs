@@ -1,4 +1,5 @@
 use pwhash::bcrypt;
+use sha2::{Digest, Sha256};
 
 use crate::errors::{PentaractError, PentaractResult};
 
@@ -19,4 +20,15 @@ impl PasswordManager {
             Err(PentaractError::NotAuthenticated)
         }
     }
+
+    /// Hash of random tokens, like app passwords.
+    ///
+    /// Tokens have enough entropy to be hashed without salt,
+    /// so they can be looked up by their hashes
+    pub fn hash_token(token: &str) -> String {
+        Sha256::digest(token.as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
 }
@@ -2,10 +2,13 @@ use std::sync::Arc;
 
 use axum::{
     extract::State,
-    headers::{authorization::Bearer, Authorization, HeaderMapExt},
-    http::{HeaderMap, HeaderValue, Request},
+    headers::{
+        authorization::{Basic, Bearer},
+        Authorization, HeaderMapExt,
+    },
+    http::{header, HeaderMap, HeaderValue, Request},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
 use reqwest::StatusCode;
 
@@ -15,6 +18,7 @@ use crate::{
         routing::app_state::AppState,
     },
     errors::{PentaractError, PentaractResult},
+    services::auth::AuthService,
 };
 
 /// Middleware that requires to be loggen in
@@ -30,6 +34,35 @@ pub async fn logged_in_required<B>(
     Ok(next.run(req).await)
 }
 
+/// Same as `logged_in_required`, but also accepts HTTP Basic credentials,
+/// and asks clients for them if they are missing
+pub async fn basic_auth_required<B>(
+    State(state): State<Arc<AppState>>,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let auth_user = match req.headers().typed_get::<Authorization<Basic>>() {
+        Some(credentials) => {
+            AuthService::new(&state.db)
+                .authenticate_basic(credentials.username(), credentials.password())
+                .await
+        }
+        None => authenticate(req.headers(), &state.config.secret_key),
+    };
+
+    match auth_user {
+        Ok(auth_user) => {
+            req.extensions_mut().insert(auth_user);
+            next.run(req).await
+        }
+        Err(e) => (
+            [(header::WWW_AUTHENTICATE, r#"Basic realm="Pentaract""#)],
+            <(StatusCode, String)>::from(e),
+        )
+            .into_response(),
+    }
+}
+
 #[inline]
 fn authenticate(headers: &HeaderMap<HeaderValue>, secret_key: &str) -> PentaractResult<AuthUser> {
     let auth_header = headers
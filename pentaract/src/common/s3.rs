@@ -0,0 +1,296 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use quick_xml::{escape::escape, events::Event, Reader};
+
+use crate::{
+    errors::PentaractError,
+    models::{files::S3Object, storages::StorageWithInfo},
+    schemas::s3::ObjectsListing,
+};
+
+const S3_NAMESPACE: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+const XML_DECLARATION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+
+/// Keys are url encoded if clients ask for it, as they may contain characters XML can't hold
+const KEY_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~')
+    .remove(b'/');
+
+/// NOTE:
+///
+/// errors are sent to S3 clients as XML with codes they know,
+/// so they tell missing buckets from missing keys and retry what can be retried
+#[derive(Debug)]
+pub struct S3Error {
+    pub status: StatusCode,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl S3Error {
+    pub fn new(status: StatusCode, code: &'static str, message: &str) -> Self {
+        Self {
+            status,
+            code,
+            message: message.to_owned(),
+        }
+    }
+
+    pub fn not_implemented() -> Self {
+        Self::new(
+            StatusCode::NOT_IMPLEMENTED,
+            "NotImplemented",
+            "A header or a query parameter you provided implies functionality that is not implemented",
+        )
+    }
+
+    pub fn malformed_xml() -> Self {
+        Self::new(
+            StatusCode::BAD_REQUEST,
+            "MalformedXML",
+            "The XML you provided was not well-formed",
+        )
+    }
+}
+
+impl From<PentaractError> for S3Error {
+    fn from(e: PentaractError) -> Self {
+        let message = e.to_string();
+        let (status, code) = match &e {
+            PentaractError::DoesNotExist(entity) if entity.contains("storage") => {
+                (StatusCode::NOT_FOUND, "NoSuchBucket")
+            }
+            PentaractError::DoesNotExist(entity) if entity.contains("multipart upload") => {
+                (StatusCode::NOT_FOUND, "NoSuchUpload")
+            }
+            PentaractError::DoesNotExist(_) => (StatusCode::NOT_FOUND, "NoSuchKey"),
+            PentaractError::NotAuthenticated => (StatusCode::FORBIDDEN, "AccessDenied"),
+            PentaractError::InvalidAccessKeyId => (StatusCode::FORBIDDEN, "InvalidAccessKeyId"),
+            // the error is shared with presigned URLs, so the message is the S3 one
+            PentaractError::InvalidSignature => {
+                return Self::new(
+                    StatusCode::FORBIDDEN,
+                    "SignatureDoesNotMatch",
+                    "The request signature does not match the one calculated with your secret",
+                )
+            }
+            PentaractError::RequestTimeTooSkewed => (StatusCode::FORBIDDEN, "RequestTimeTooSkewed"),
+            PentaractError::UrlExpired => (StatusCode::FORBIDDEN, "AccessDenied"),
+            PentaractError::InvalidPart(_) => (StatusCode::BAD_REQUEST, "InvalidPart"),
+            PentaractError::InvalidPartOrder => (StatusCode::BAD_REQUEST, "InvalidPartOrder"),
+            PentaractError::PartTooSmall(_) => (StatusCode::BAD_REQUEST, "EntityTooSmall"),
+            PentaractError::FileTooLarge(_) => (StatusCode::BAD_REQUEST, "EntityTooLarge"),
+            PentaractError::QuotaExceeded(_) => (StatusCode::FORBIDDEN, "QuotaExceeded"),
+            PentaractError::AlreadyExists(_) => (StatusCode::CONFLICT, "OperationAborted"),
+            PentaractError::StorageDoesNotHaveWorkers | PentaractError::NoStorageWorkers => {
+                (StatusCode::SERVICE_UNAVAILABLE, "ServiceUnavailable")
+            }
+            _ => {
+                // the rest are either client errors or internal ones
+                let (status, message) = <(StatusCode, String)>::from(e);
+                let code = if status.is_client_error() {
+                    "InvalidArgument"
+                } else {
+                    "InternalError"
+                };
+                return Self {
+                    status,
+                    code,
+                    message,
+                };
+            }
+        };
+
+        Self {
+            status,
+            code,
+            message,
+        }
+    }
+}
+
+impl IntoResponse for S3Error {
+    fn into_response(self) -> Response {
+        let body = format!(
+            "{XML_DECLARATION}<Error><Code>{}</Code><Message>{}</Message></Error>",
+            self.code,
+            escape(&self.message)
+        );
+
+        (self.status, xml_headers(), body).into_response()
+    }
+}
+
+pub fn xml_headers() -> [(header::HeaderName, &'static str); 1] {
+    [(header::CONTENT_TYPE, "application/xml")]
+}
+
+/// Body of responses to `ListBuckets`, buckets are storages named by their ids
+pub fn list_buckets(storages: &[(StorageWithInfo, DateTime<Utc>)], owner: &str) -> String {
+    let buckets: String = storages
+        .iter()
+        .map(|(storage, created_at)| {
+            format!(
+                "<Bucket><Name>{}</Name><CreationDate>{}</CreationDate></Bucket>",
+                storage.id,
+                iso_date(*created_at)
+            )
+        })
+        .collect();
+
+    format!(
+        r#"{XML_DECLARATION}<ListAllMyBucketsResult xmlns="{S3_NAMESPACE}"><Owner><ID>{0}</ID><DisplayName>{0}</DisplayName></Owner><Buckets>{buckets}</Buckets></ListAllMyBucketsResult>"#,
+        escape(owner)
+    )
+}
+
+/// Body of responses to `ListObjects` and `ListObjectsV2`
+pub fn list_objects(listing: &ObjectsListing) -> String {
+    let encode = |key: &str| match listing.is_url_encoded {
+        true => utf8_percent_encode(key, KEY_ENCODE_SET).to_string(),
+        false => escape(key).into_owned(),
+    };
+    let optional = |tag: &str, value: &Option<String>| match value {
+        Some(value) => format!("<{tag}>{}</{tag}>", encode(value)),
+        None => String::new(),
+    };
+
+    let mut xml = format!(
+        r#"{XML_DECLARATION}<ListBucketResult xmlns="{S3_NAMESPACE}"><Name>{}</Name><Prefix>{}</Prefix><MaxKeys>{}</MaxKeys><IsTruncated>{}</IsTruncated>"#,
+        listing.bucket,
+        encode(&listing.prefix),
+        listing.max_keys,
+        listing.is_truncated
+    );
+    xml += &optional("Delimiter", &listing.delimiter);
+    if listing.is_url_encoded {
+        xml += "<EncodingType>url</EncodingType>";
+    }
+
+    if listing.is_v2 {
+        xml += &format!("<KeyCount>{}</KeyCount>", listing.objects.len());
+        xml += &optional("StartAfter", &listing.start_after);
+        // tokens are not keys, so they are never url encoded
+        for (tag, token) in [
+            ("ContinuationToken", &listing.continuation_token),
+            ("NextContinuationToken", &listing.next_token),
+        ] {
+            if let Some(token) = token {
+                xml += &format!("<{tag}>{}</{tag}>", escape(token));
+            }
+        }
+    } else {
+        xml += &format!(
+            "<Marker>{}</Marker>",
+            encode(listing.marker.as_deref().unwrap_or_default())
+        );
+        xml += &optional("NextMarker", &listing.next_marker);
+    }
+
+    for object in &listing.objects {
+        if object.is_prefix {
+            xml += &format!(
+                "<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>",
+                encode(&object.key)
+            );
+        } else {
+            xml += &format!(
+                "<Contents><Key>{}</Key><LastModified>{}</LastModified><ETag>{}</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+                encode(&object.key),
+                iso_date(object.updated_at),
+                escape(&object.etag()),
+                object.size
+            );
+        }
+    }
+
+    xml + "</ListBucketResult>"
+}
+
+pub fn location_constraint() -> String {
+    format!(r#"{XML_DECLARATION}<LocationConstraint xmlns="{S3_NAMESPACE}"/>"#)
+}
+
+pub fn copy_object_result(object: &S3Object) -> String {
+    format!(
+        r#"{XML_DECLARATION}<CopyObjectResult xmlns="{S3_NAMESPACE}"><LastModified>{}</LastModified><ETag>{}</ETag></CopyObjectResult>"#,
+        iso_date(object.updated_at),
+        escape(&object.etag())
+    )
+}
+
+pub fn initiate_multipart_upload(bucket: &str, key: &str, upload_id: &str) -> String {
+    format!(
+        r#"{XML_DECLARATION}<InitiateMultipartUploadResult xmlns="{S3_NAMESPACE}"><Bucket>{bucket}</Bucket><Key>{}</Key><UploadId>{upload_id}</UploadId></InitiateMultipartUploadResult>"#,
+        escape(key)
+    )
+}
+
+pub fn complete_multipart_upload(bucket: &str, key: &str, object: &S3Object) -> String {
+    format!(
+        r#"{XML_DECLARATION}<CompleteMultipartUploadResult xmlns="{S3_NAMESPACE}"><Bucket>{bucket}</Bucket><Key>{}</Key><ETag>{}</ETag></CompleteMultipartUploadResult>"#,
+        escape(key),
+        escape(&object.etag())
+    )
+}
+
+/// Parses the body of `CompleteMultipartUpload` into part numbers and their ETags
+pub fn parse_complete_multipart_upload(body: &[u8]) -> Result<Vec<(i32, String)>, S3Error> {
+    let body = std::str::from_utf8(body).map_err(|_| S3Error::malformed_xml())?;
+    let mut reader = Reader::from_str(body);
+    reader.trim_text(true);
+
+    let mut parts = vec![];
+    let mut part: (Option<i32>, Option<String>) = (None, None);
+    let mut current_tag = Vec::new();
+    loop {
+        match reader.read_event().map_err(|_| S3Error::malformed_xml())? {
+            Event::Start(e) => {
+                current_tag = e.local_name().as_ref().to_vec();
+                if current_tag == b"Part" {
+                    part = (None, None);
+                }
+            }
+            Event::Text(text) => {
+                let text = text.unescape().map_err(|_| S3Error::malformed_xml())?;
+                match current_tag.as_slice() {
+                    b"PartNumber" => {
+                        part.0 = Some(text.trim().parse().map_err(|_| S3Error::malformed_xml())?)
+                    }
+                    b"ETag" => part.1 = Some(text.trim().to_owned()),
+                    _ => (),
+                }
+            }
+            Event::End(e) => {
+                if e.local_name().as_ref() == b"Part" {
+                    match part {
+                        (Some(number), Some(etag)) => parts.push((number, etag)),
+                        _ => return Err(S3Error::malformed_xml()),
+                    }
+                    part = (None, None);
+                }
+                current_tag.clear();
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+
+    if parts.is_empty() {
+        return Err(S3Error::malformed_xml());
+    }
+
+    Ok(parts)
+}
+
+/// Dates in listings, like `2009-10-12T17:50:30.000Z`
+pub fn iso_date(date: DateTime<Utc>) -> String {
+    date.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
@@ -3,6 +3,7 @@ use uuid::Uuid;
 
 use crate::{
     common::types::ChatId, errors::PentaractResult,
+    models::storage_workers_stats::InStorageWorkerUsage,
     services::storage_workers_scheduler::StorageWorkersScheduler,
 };
 
@@ -27,6 +28,9 @@ impl<'t> TelegramBotApi<'t> {
         chat_id: ChatId,
         storage_id: Uuid,
     ) -> PentaractResult<UploadSchema> {
+        let storage_worker = self.scheduler.get_token(storage_id, Some(chat_id)).await?;
+        let url = self.build_url("", "sendDocument", storage_worker.token);
+
         let chat_id = {
             // inserting 100 between minus sign and chat id
             // cause telegram devs are complete retards and it works this way only
@@ -37,25 +41,32 @@ impl<'t> TelegramBotApi<'t> {
             chat_id - (100 * ChatId::from(10).pow(n))
         };
 
-        let token = self.scheduler.get_token(storage_id).await?;
-        let url = self.build_url("", "sendDocument", token);
-
         let file_part = multipart::Part::bytes(file.to_vec()).file_name("pentaract_chunk.bin");
         let form = multipart::Form::new()
             .text("chat_id", chat_id.to_string())
             .part("document", file_part);
 
-        let response = reqwest::Client::new()
-            .post(url)
-            .multipart(form)
-            .send()
-            .await?;
+        let result = async {
+            let response = reqwest::Client::new()
+                .post(url)
+                .multipart(form)
+                .send()
+                .await?;
 
-        match response.error_for_status() {
-            // https://stackoverflow.com/a/32679930/12255756
-            Ok(r) => Ok(r.json::<UploadBodySchema>().await?.result.document),
-            Err(e) => Err(e.into()),
+            match response.error_for_status() {
+                // https://stackoverflow.com/a/32679930/12255756
+                Ok(r) => Ok(r.json::<UploadBodySchema>().await?.result.document),
+                Err(e) => Err(e.into()),
+            }
         }
+        .await;
+
+        let usage = InStorageWorkerUsage::new(file.len() as i64, 0, result.is_err());
+        self.scheduler
+            .report_usage(storage_worker.id, storage_id, usage)
+            .await;
+
+        result
     }
 
     pub async fn download(
@@ -64,27 +75,47 @@ impl<'t> TelegramBotApi<'t> {
         storage_id: Uuid,
     ) -> PentaractResult<Vec<u8>> {
         // getting file path
-        let token = self.scheduler.get_token(storage_id).await?;
-        let url = self.build_url("", "getFile", token);
+        let storage_worker = self.scheduler.get_token(storage_id, None).await?;
+        let url = self.build_url("", "getFile", storage_worker.token);
         // TODO: add retries with their number taking from env
-        let body: DownloadBodySchema = reqwest::Client::new()
-            .get(url)
-            .query(&[("file_id", telegram_file_id)])
-            .send()
-            .await?
-            .json()
-            .await?;
+        let body: PentaractResult<DownloadBodySchema> = async {
+            let body = reqwest::Client::new()
+                .get(url)
+                .query(&[("file_id", telegram_file_id)])
+                .send()
+                .await?
+                .json()
+                .await?;
+            Ok(body)
+        }
+        .await;
+
+        let usage = InStorageWorkerUsage::new(0, 0, body.is_err());
+        self.scheduler
+            .report_usage(storage_worker.id, storage_id, usage)
+            .await;
+        let body = body?;
 
         // downloading the file itself
-        let token = self.scheduler.get_token(storage_id).await?;
-        let url = self.build_url("file/", &body.result.file_path, token);
-        let file = reqwest::get(url)
-            .await?
-            .bytes()
-            .await
-            .map(|file| file.to_vec())?;
-
-        Ok(file)
+        let storage_worker = self.scheduler.get_token(storage_id, None).await?;
+        let url = self.build_url("file/", &body.result.file_path, storage_worker.token);
+        let file: PentaractResult<Vec<u8>> = async {
+            let file = reqwest::get(url)
+                .await?
+                .bytes()
+                .await
+                .map(|file| file.to_vec())?;
+            Ok(file)
+        }
+        .await;
+
+        let bytes_downloaded = file.as_ref().map(|file| file.len() as i64).unwrap_or(0);
+        let usage = InStorageWorkerUsage::new(0, bytes_downloaded, file.is_err());
+        self.scheduler
+            .report_usage(storage_worker.id, storage_id, usage)
+            .await;
+
+        file
     }
 
     /// Taking token by a value to force dropping it so it can be used only once
@@ -0,0 +1,120 @@
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Utc};
+use image::{imageops::FilterType, ImageFormat};
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::errors::{PentaractError, PentaractResult};
+
+/// Thumbnails fit into a square with such a side
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+/// Bigger images are not decoded to keep memory usage sane
+pub const MAX_IMAGE_SIZE: i64 = 50 * 1024 * 1024;
+
+const SUPPORTED_MIME_TYPES: [&str; 4] = ["image/jpeg", "image/png", "image/webp", "image/gif"];
+
+pub fn is_supported(mime_type: Option<&str>, size: i64) -> bool {
+    size <= MAX_IMAGE_SIZE && mime_type.is_some_and(|mime| SUPPORTED_MIME_TYPES.contains(&mime))
+}
+
+/// Thumbnails are encoded as PNG if images have transparency and as JPEG otherwise.
+///
+/// It's blocking, so it's meant to be run in a blocking thread
+pub fn generate(data: &[u8]) -> PentaractResult<Vec<u8>> {
+    let image = image::load_from_memory(data).map_err(|e| {
+        tracing::debug!("cannot decode image: {e}");
+        PentaractError::DoesNotExist("thumbnail of such file".to_owned())
+    })?;
+    let thumbnail = image.resize(THUMBNAIL_SIZE, THUMBNAIL_SIZE, FilterType::Triangle);
+
+    let mut buf = Cursor::new(vec![]);
+    let result = if thumbnail.color().has_alpha() {
+        thumbnail.write_to(&mut buf, ImageFormat::Png)
+    } else {
+        thumbnail.into_rgb8().write_to(&mut buf, ImageFormat::Jpeg)
+    };
+    result.map_err(|e| {
+        tracing::error!("{e}");
+        PentaractError::Unknown
+    })?;
+
+    Ok(buf.into_inner())
+}
+
+pub fn content_type(thumbnail: &[u8]) -> &'static str {
+    if thumbnail.starts_with(b"\x89PNG") {
+        "image/png"
+    } else {
+        "image/jpeg"
+    }
+}
+
+/// Thumbnails are kept on a local disk, so browsing images doesn't
+/// go through Telegram API and its rate limits
+pub struct ThumbnailsCache<'c> {
+    dir: &'c Path,
+}
+
+impl<'c> ThumbnailsCache<'c> {
+    pub fn new(dir: &'c str) -> Self {
+        Self {
+            dir: Path::new(dir),
+        }
+    }
+
+    /// Thumbnails made before the file content was updated are outdated
+    pub async fn get(&self, file_id: Uuid, updated_at: DateTime<Utc>) -> Option<Vec<u8>> {
+        let path = self.path(file_id);
+        let modified_at = fs::metadata(&path).await.ok()?.modified().ok()?;
+        if modified_at < SystemTime::from(updated_at) {
+            return None;
+        }
+
+        fs::read(path).await.ok()
+    }
+
+    pub async fn save(&self, file_id: Uuid, thumbnail: &[u8]) -> PentaractResult<()> {
+        // writing to a temporary file first, so readers never get a half-written one
+        let path = self.path(file_id);
+        let tmp_path = path.with_extension(format!("{}.tmp", Uuid::new_v4()));
+
+        let result = async {
+            fs::create_dir_all(self.dir).await?;
+            fs::write(&tmp_path, thumbnail).await?;
+            fs::rename(&tmp_path, &path).await
+        }
+        .await;
+
+        result.map_err(|e| {
+            tracing::error!("failed to save thumbnail: {e}");
+            PentaractError::Unknown
+        })
+    }
+
+    /// Generates a thumbnail in a blocking thread and saves it
+    pub async fn generate_and_save(
+        &self,
+        file_id: Uuid,
+        data: Vec<u8>,
+    ) -> PentaractResult<Vec<u8>> {
+        let thumbnail = tokio::task::spawn_blocking(move || generate(&data))
+            .await
+            .map_err(|e| {
+                tracing::error!("{e}");
+                PentaractError::Unknown
+            })??;
+
+        self.save(file_id, &thumbnail).await?;
+        Ok(thumbnail)
+    }
+
+    fn path(&self, file_id: Uuid) -> PathBuf {
+        self.dir.join(file_id.to_string())
+    }
+}
@@ -0,0 +1,80 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use sha2::{Digest, Sha256};
+
+use crate::errors::{PentaractError, PentaractResult};
+
+const NONCE_SIZE: usize = 12;
+
+/// Encrypts storage workers tokens so they are never stored as a plain text
+pub struct TokenCipher;
+
+impl TokenCipher {
+    pub fn encrypt(token: &str, key: &str) -> PentaractResult<String> {
+        let cipher = Self::build_cipher(key);
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+        let mut encrypted = cipher.encrypt(&nonce, token.as_bytes()).map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })?;
+
+        // storing nonce alongside with the encrypted token
+        let mut result = nonce.to_vec();
+        result.append(&mut encrypted);
+        Ok(BASE64.encode(result))
+    }
+
+    pub fn decrypt(encrypted_token: &str, key: &str) -> PentaractResult<String> {
+        let cipher = Self::build_cipher(key);
+
+        let data = BASE64
+            .decode(encrypted_token)
+            .map_err(|_| PentaractError::TokenDecryptionError)?;
+        if data.len() <= NONCE_SIZE {
+            return Err(PentaractError::TokenDecryptionError);
+        }
+        let (nonce, encrypted) = data.split_at(NONCE_SIZE);
+
+        cipher
+            .decrypt(Nonce::from_slice(nonce), encrypted)
+            .ok()
+            .and_then(|token| String::from_utf8(token).ok())
+            .ok_or(PentaractError::TokenDecryptionError)
+    }
+
+    /// Deterministic keyed hash of a token, used to keep tokens unique
+    /// since encrypted ones differ every time
+    pub fn hash(token: &str, key: &str) -> String {
+        let digest = Sha256::new()
+            .chain_update(key.as_bytes())
+            .chain_update(token.as_bytes())
+            .finalize();
+
+        digest.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    /// Hides everything except the bot id and the last 4 characters
+    ///
+    /// `123456:ABC-DEF1234ghIkl-zyx57W2v1u123ew11` -> `123456:****ew11`
+    pub fn mask(token: &str) -> String {
+        let (bot_id, secret) = token.split_once(':').unwrap_or(("", token));
+        let visible = secret.chars().count().saturating_sub(4);
+        let tail: String = secret.chars().skip(visible).collect();
+
+        if bot_id.is_empty() {
+            format!("****{tail}")
+        } else {
+            format!("{bot_id}:****{tail}")
+        }
+    }
+
+    #[inline]
+    fn build_cipher(key: &str) -> Aes256Gcm {
+        let key = Sha256::digest(key.as_bytes());
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+    }
+}
@@ -0,0 +1,62 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64, Engine};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::errors::{PentaractError, PentaractResult};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Signs presigned URLs, so they work without sessions until they expire.
+///
+/// Everything a URL gives access to is signed: the action, the file,
+/// the user on behalf of whom it's done and the expiration time
+pub struct UrlSigner;
+
+impl UrlSigner {
+    pub fn sign(
+        action: &str,
+        storage_id: Uuid,
+        path: &str,
+        user_id: Uuid,
+        expires: i64,
+        secret_key: &str,
+    ) -> String {
+        let mac = Self::build_mac(action, storage_id, path, user_id, expires, secret_key);
+
+        BASE64.encode(mac.finalize().into_bytes())
+    }
+
+    pub fn verify(
+        signature: &str,
+        action: &str,
+        storage_id: Uuid,
+        path: &str,
+        user_id: Uuid,
+        expires: i64,
+        secret_key: &str,
+    ) -> PentaractResult<()> {
+        let signature = BASE64
+            .decode(signature)
+            .map_err(|_| PentaractError::InvalidSignature)?;
+
+        // comparing in a constant time
+        Self::build_mac(action, storage_id, path, user_id, expires, secret_key)
+            .verify_slice(&signature)
+            .map_err(|_| PentaractError::InvalidSignature)
+    }
+
+    fn build_mac(
+        action: &str,
+        storage_id: Uuid,
+        path: &str,
+        user_id: Uuid,
+        expires: i64,
+        secret_key: &str,
+    ) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(secret_key.as_bytes())
+            .expect("HMAC can take a key of any size");
+        mac.update(format!("{action}\n{storage_id}\n{path}\n{user_id}\n{expires}").as_bytes());
+        mac
+    }
+}
@@ -0,0 +1,416 @@
+use chrono::{DateTime, Utc};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use quick_xml::{
+    escape::escape,
+    events::Event,
+    name::{Namespace, ResolveResult},
+    NsReader,
+};
+
+use crate::errors::{PentaractError, PentaractResult};
+
+const DAV_NAMESPACE: &[u8] = b"DAV:";
+
+/// Characters escaped in hrefs, slashes are kept since they separate segments
+const HREF_ESCAPED: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// Properties asked by `PROPFIND`, an empty body asks for all of them
+pub enum PropfindRequest {
+    AllProp,
+    Prop(Vec<PropName>),
+}
+
+pub struct PropName {
+    pub namespace: String,
+    pub name: String,
+}
+
+impl PropName {
+    fn is_dav(&self, name: &str) -> bool {
+        self.namespace.as_bytes() == DAV_NAMESPACE && self.name == name
+    }
+}
+
+/// `owner` is the text the client describes itself with
+pub struct LockInfo {
+    pub is_shared: bool,
+    pub owner: Option<String>,
+}
+
+/// A file or a collection as it's shown to clients
+pub struct DavResource {
+    pub href: String,
+    pub name: String,
+    pub is_collection: bool,
+    pub size: i64,
+    pub content_type: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub locks: Vec<ActiveLock>,
+}
+
+impl DavResource {
+    pub fn etag(&self) -> String {
+        format!(
+            "\"{:x}-{:x}\"",
+            self.updated_at.timestamp_micros(),
+            self.size
+        )
+    }
+}
+
+pub struct ActiveLock {
+    pub token: String,
+    pub root_href: String,
+    pub is_shared: bool,
+    pub is_deep: bool,
+    pub owner: Option<String>,
+    pub timeout_secs: i64,
+}
+
+/// Builds `207 Multi-Status` bodies
+pub struct Multistatus {
+    xml: String,
+}
+
+impl Multistatus {
+    pub fn new() -> Self {
+        Self {
+            xml: r#"<?xml version="1.0" encoding="utf-8"?><D:multistatus xmlns:D="DAV:">"#
+                .to_owned(),
+        }
+    }
+
+    /// Adds properties of the resource, the asked ones it doesn't have are reported as missing
+    pub fn add(&mut self, resource: &DavResource, request: &PropfindRequest) {
+        let props = Self::props(resource);
+
+        self.xml += &format!("<D:response><D:href>{}</D:href>", escape(&resource.href));
+        let (found, missing): (Vec<_>, Vec<_>) = match request {
+            PropfindRequest::AllProp => (props.into_iter().map(|(_, xml)| xml).collect(), vec![]),
+            PropfindRequest::Prop(names) => {
+                let mut found = vec![];
+                let mut missing = vec![];
+                for name in names {
+                    match props.iter().find(|(prop, _)| name.is_dav(prop)) {
+                        Some((_, xml)) => found.push(xml.clone()),
+                        None => missing.push(name),
+                    }
+                }
+                (found, missing)
+            }
+        };
+
+        if !found.is_empty() {
+            self.xml += "<D:propstat><D:prop>";
+            self.xml += &found.concat();
+            self.xml += "</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat>";
+        }
+        if !missing.is_empty() {
+            self.xml += "<D:propstat><D:prop>";
+            for name in missing {
+                self.xml += &format!(
+                    r#"<X:{} xmlns:X="{}"/>"#,
+                    escape(&name.name),
+                    escape(&name.namespace)
+                );
+            }
+            self.xml += "</D:prop><D:status>HTTP/1.1 404 Not Found</D:status></D:propstat>";
+        }
+        self.xml += "</D:response>";
+    }
+
+    pub fn finish(self) -> String {
+        self.xml + "</D:multistatus>"
+    }
+
+    fn props(resource: &DavResource) -> Vec<(&'static str, String)> {
+        let mut props = vec![
+            (
+                "displayname",
+                format!("<D:displayname>{}</D:displayname>", escape(&resource.name)),
+            ),
+            (
+                "creationdate",
+                format!(
+                    "<D:creationdate>{}</D:creationdate>",
+                    resource.created_at.format("%Y-%m-%dT%H:%M:%SZ")
+                ),
+            ),
+            (
+                "getlastmodified",
+                format!(
+                    "<D:getlastmodified>{}</D:getlastmodified>",
+                    http_date(resource.updated_at)
+                ),
+            ),
+            ("supportedlock", supported_lock()),
+            (
+                "lockdiscovery",
+                format!("<D:lockdiscovery>{}</D:lockdiscovery>", {
+                    resource.locks.iter().map(active_lock).collect::<String>()
+                }),
+            ),
+        ];
+
+        if resource.is_collection {
+            props.push((
+                "resourcetype",
+                "<D:resourcetype><D:collection/></D:resourcetype>".to_owned(),
+            ));
+        } else {
+            props.extend([
+                ("resourcetype", "<D:resourcetype/>".to_owned()),
+                (
+                    "getcontentlength",
+                    format!("<D:getcontentlength>{}</D:getcontentlength>", resource.size),
+                ),
+                (
+                    "getcontenttype",
+                    format!(
+                        "<D:getcontenttype>{}</D:getcontenttype>",
+                        escape(
+                            resource
+                                .content_type
+                                .as_deref()
+                                .unwrap_or("application/octet-stream")
+                        )
+                    ),
+                ),
+                (
+                    "getetag",
+                    format!("<D:getetag>{}</D:getetag>", escape(&resource.etag())),
+                ),
+            ]);
+        }
+
+        props
+    }
+}
+
+/// Body of responses to `LOCK`
+pub fn lock_discovery(lock: &ActiveLock) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?><D:prop xmlns:D="DAV:"><D:lockdiscovery>{}</D:lockdiscovery></D:prop>"#,
+        active_lock(lock)
+    )
+}
+
+fn active_lock(lock: &ActiveLock) -> String {
+    let scope = if lock.is_shared {
+        "shared"
+    } else {
+        "exclusive"
+    };
+    let depth = if lock.is_deep { "infinity" } else { "0" };
+    let owner = lock
+        .owner
+        .as_deref()
+        .map(|owner| format!("<D:owner>{}</D:owner>", escape(owner)))
+        .unwrap_or_default();
+
+    format!(
+        "<D:activelock>\
+            <D:locktype><D:write/></D:locktype>\
+            <D:lockscope><D:{scope}/></D:lockscope>\
+            <D:depth>{depth}</D:depth>\
+            {owner}\
+            <D:timeout>Second-{}</D:timeout>\
+            <D:locktoken><D:href>{}</D:href></D:locktoken>\
+            <D:lockroot><D:href>{}</D:href></D:lockroot>\
+        </D:activelock>",
+        lock.timeout_secs,
+        escape(&lock.token),
+        escape(&lock.root_href),
+    )
+}
+
+fn supported_lock() -> String {
+    ["exclusive", "shared"]
+        .iter()
+        .map(|scope| {
+            format!(
+                "<D:lockentry>\
+                    <D:lockscope><D:{scope}/></D:lockscope>\
+                    <D:locktype><D:write/></D:locktype>\
+                </D:lockentry>"
+            )
+        })
+        .fold("<D:supportedlock>".to_owned(), |xml, entry| xml + &entry)
+        + "</D:supportedlock>"
+}
+
+pub fn parse_propfind(body: &[u8]) -> PentaractResult<PropfindRequest> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(PropfindRequest::AllProp);
+    }
+
+    let body = std::str::from_utf8(body).map_err(|_| PentaractError::InvalidDavBody)?;
+    let mut reader = NsReader::from_str(body);
+    reader.trim_text(true);
+
+    let mut names = vec![];
+    let mut is_allprop = false;
+    // depth of elements inside `prop`, its direct children are names of properties
+    let mut prop_depth: Option<usize> = None;
+    loop {
+        let (ns, event) = reader
+            .read_resolved_event()
+            .map_err(|_| PentaractError::InvalidDavBody)?;
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let is_empty = matches!(event, Event::Empty(_));
+                let local_name = e.local_name();
+                match prop_depth {
+                    Some(0) => {
+                        names.push(PropName {
+                            namespace: namespace_of(&ns),
+                            name: String::from_utf8_lossy(local_name.as_ref()).into_owned(),
+                        });
+                        if !is_empty {
+                            prop_depth = Some(1);
+                        }
+                    }
+                    Some(depth) if !is_empty => prop_depth = Some(depth + 1),
+                    Some(_) => (),
+                    None if is_dav(&ns) && local_name.as_ref() == b"prop" && !is_empty => {
+                        prop_depth = Some(0)
+                    }
+                    None if is_dav(&ns)
+                        && matches!(local_name.as_ref(), b"allprop" | b"propname") =>
+                    {
+                        is_allprop = true
+                    }
+                    None => (),
+                }
+            }
+            Event::End(_) => {
+                prop_depth = match prop_depth {
+                    Some(0) | None => None,
+                    Some(depth) => Some(depth - 1),
+                };
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+
+    if is_allprop || names.is_empty() {
+        Ok(PropfindRequest::AllProp)
+    } else {
+        Ok(PropfindRequest::Prop(names))
+    }
+}
+
+/// Returns `None` for empty bodies, which are sent to refresh locks
+pub fn parse_lockinfo(body: &[u8]) -> PentaractResult<Option<LockInfo>> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+
+    let body = std::str::from_utf8(body).map_err(|_| PentaractError::InvalidDavBody)?;
+    let mut reader = NsReader::from_str(body);
+    reader.trim_text(true);
+
+    let mut info = LockInfo {
+        is_shared: false,
+        owner: None,
+    };
+    let mut in_lockscope = false;
+    let mut owner_depth: Option<usize> = None;
+    loop {
+        let (ns, event) = reader
+            .read_resolved_event()
+            .map_err(|_| PentaractError::InvalidDavBody)?;
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let is_empty = matches!(event, Event::Empty(_));
+                let local_name = e.local_name();
+                if let Some(depth) = owner_depth.filter(|_| !is_empty) {
+                    owner_depth = Some(depth + 1);
+                } else if in_lockscope && is_dav(&ns) && local_name.as_ref() == b"shared" {
+                    info.is_shared = true;
+                } else if is_dav(&ns) && local_name.as_ref() == b"lockscope" && !is_empty {
+                    in_lockscope = true;
+                } else if is_dav(&ns) && local_name.as_ref() == b"owner" && !is_empty {
+                    owner_depth = Some(0);
+                }
+            }
+            Event::Text(ref text) if owner_depth.is_some() => {
+                let text = text
+                    .unescape()
+                    .map_err(|_| PentaractError::InvalidDavBody)?;
+                info.owner.get_or_insert_with(String::new).push_str(&text);
+            }
+            Event::End(ref e) => {
+                owner_depth = match owner_depth {
+                    Some(0) | None => None,
+                    Some(depth) => Some(depth - 1),
+                };
+                if is_dav(&ns) && e.local_name().as_ref() == b"lockscope" {
+                    in_lockscope = false;
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+
+    Ok(Some(info))
+}
+
+/// Lock tokens submitted in the `If` header, like `(<opaquelocktoken:...>)`
+pub fn submitted_tokens(if_header: Option<&str>) -> Vec<String> {
+    let Some(if_header) = if_header else {
+        return vec![];
+    };
+
+    if_header
+        .split('<')
+        .skip(1)
+        .filter_map(|part| part.split_once('>').map(|(token, _)| token))
+        .filter(|token| token.starts_with("opaquelocktoken:"))
+        .map(str::to_owned)
+        .collect()
+}
+
+pub fn encode_href(path: &str) -> String {
+    utf8_percent_encode(path, HREF_ESCAPED).to_string()
+}
+
+pub fn decode_href(href: &str) -> PentaractResult<String> {
+    percent_decode_str(href)
+        .decode_utf8()
+        .map(|path| path.into_owned())
+        .map_err(|_| PentaractError::InvalidPath)
+}
+
+/// Date in the format of HTTP headers, like `Sun, 06 Nov 1994 08:49:37 GMT`
+pub fn http_date(date: DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+fn is_dav(ns: &ResolveResult) -> bool {
+    matches!(ns, ResolveResult::Bound(Namespace(ns)) if *ns == DAV_NAMESPACE)
+}
+
+fn namespace_of(ns: &ResolveResult) -> String {
+    match ns {
+        ResolveResult::Bound(Namespace(ns)) => String::from_utf8_lossy(ns).into_owned(),
+        _ => String::new(),
+    }
+}
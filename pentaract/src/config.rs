@@ -16,9 +16,16 @@ pub struct Config {
     pub access_token_expire_in_secs: u32,
     pub refresh_token_expire_in_days: u16,
     pub secret_key: String,
+    pub tokens_encryption_key: String,
 
     pub telegram_api_base_url: String,
     pub telegram_rate_limit: u8,
+
+    pub trash_retention_days: u16,
+    pub multipart_upload_expiry_days: u16,
+    pub max_drop_size: usize,
+
+    pub thumbnails_dir: String,
 }
 
 impl Config {
@@ -39,9 +46,17 @@ impl Config {
         let superuser_pass = Self::get_env_var("SUPERUSER_PASS")?;
         let access_token_expire_in_secs = Self::get_env_var("ACCESS_TOKEN_EXPIRE_IN_SECS")?;
         let refresh_token_expire_in_days = Self::get_env_var("REFRESH_TOKEN_EXPIRE_IN_DAYS")?;
-        let secret_key = Self::get_env_var("SECRET_KEY")?;
+        let secret_key: String = Self::get_env_var("SECRET_KEY")?;
+        let tokens_encryption_key =
+            Self::get_env_var_with_default("TOKENS_ENCRYPTION_KEY", secret_key.clone())?;
         let telegram_api_base_url = Self::get_env_var("TELEGRAM_API_BASE_URL")?;
         let telegram_rate_limit = Self::get_env_var_with_default("TELEGRAM_RATE_LIMIT", 18)?;
+        let trash_retention_days = Self::get_env_var_with_default("TRASH_RETENTION_DAYS", 30)?;
+        let multipart_upload_expiry_days =
+            Self::get_env_var_with_default("MULTIPART_UPLOAD_EXPIRY_DAYS", 7)?;
+        let max_drop_size = Self::get_env_var_with_default("MAX_DROP_SIZE", 100 * 1024 * 1024)?;
+        let thumbnails_dir =
+            Self::get_env_var_with_default("THUMBNAILS_DIR", "thumbnails".to_owned())?;
 
         Ok(Self {
             db_uri,
@@ -55,8 +70,13 @@ impl Config {
             access_token_expire_in_secs,
             refresh_token_expire_in_days,
             secret_key,
+            tokens_encryption_key,
             telegram_api_base_url,
             telegram_rate_limit,
+            trash_retention_days,
+            multipart_upload_expiry_days,
+            max_drop_size,
+            thumbnails_dir,
         })
     }
 
@@ -33,8 +33,70 @@ pub enum PentaractError {
     InvalidPath,
     #[error("Invalid folder name")]
     InvalidFolderName,
+    #[error("Invalid cursor")]
+    InvalidCursor,
+    #[error("Invalid search pattern")]
+    InvalidSearchPattern,
+    #[error("Invalid archive: {0}")]
+    InvalidArchive(String),
+    #[error("Share link must expire in the future and allow at least one download")]
+    InvalidShareLinkLimits,
+    #[error("Share link is expired")]
+    ShareLinkExpired,
+    #[error("Share link downloads limit is reached")]
+    ShareLinkDownloadsLimitReached,
+    #[error("Share link password is invalid")]
+    InvalidShareLinkPassword,
+    #[error(
+        "File drop must expire in the future and allow at least one file of at least one byte"
+    )]
+    InvalidFileDropLimits,
+    #[error("File drop is expired")]
+    FileDropExpired,
+    #[error("File is too large, the limit is {0} bytes")]
+    FileTooLarge(i64),
+    #[error("File drop accepts {0} more files at most")]
+    TooManyDroppedFiles(i32),
+    #[error("URL must expire in {0} seconds at most")]
+    InvalidUrlExpiration(i64),
+    #[error("URL signature is invalid")]
+    InvalidSignature,
+    #[error("URL is expired")]
+    UrlExpired,
+    #[error("{0} quota is exceeded")]
+    QuotaExceeded(String),
+    #[error("Quotas cannot be negative")]
+    InvalidQuota,
+    #[error("Batch cannot contain more than {0} operations")]
+    TooManyOperations(usize),
+    #[error("Transfers between storages cannot be done in a transaction")]
+    TransferNotTransactional,
+    #[error("App password name cannot be empty")]
+    InvalidAppPasswordName,
+    #[error("Request body is not valid WebDAV XML")]
+    InvalidDavBody,
+    #[error("Resource is locked")]
+    Locked,
+    #[error("S3 key name cannot be empty")]
+    InvalidS3KeyName,
+    #[error("Part number must be between 1 and {0}")]
+    InvalidPartNumber(i32),
+    #[error("Part {0} is not uploaded or its ETag does not match")]
+    InvalidPart(i32),
+    #[error("Parts must be listed in ascending order")]
+    InvalidPartOrder,
+    #[error("Part {0} is smaller than the minimum allowed size")]
+    PartTooSmall(i32),
+    #[error("Access key id does not exist")]
+    InvalidAccessKeyId,
+    #[error("Request time differs from the server one too much")]
+    RequestTimeTooSkewed,
     #[error("You cannot manage access of yourself")]
     CannotManageAccessOfYourself,
+    #[error("Storage worker token cannot be decrypted")]
+    TokenDecryptionError,
+    #[error("{0} files failed to be transferred")]
+    TransferFailed(usize),
     #[error("Storage does not have workers")]
     StorageDoesNotHaveWorkers,
     #[error("unknown error")]
@@ -55,11 +117,44 @@ impl From<PentaractError> for (StatusCode, String) {
             | PentaractError::StorageWorkerTokenConflict
             | PentaractError::StorageDoesNotHaveWorkers
             | PentaractError::CannotManageAccessOfYourself => (StatusCode::CONFLICT, e.to_string()),
-            PentaractError::NotAuthenticated => (StatusCode::UNAUTHORIZED, e.to_string()),
+            PentaractError::NotAuthenticated | PentaractError::InvalidShareLinkPassword => {
+                (StatusCode::UNAUTHORIZED, e.to_string())
+            }
+            PentaractError::InvalidSignature
+            | PentaractError::InvalidAccessKeyId
+            | PentaractError::RequestTimeTooSkewed => (StatusCode::FORBIDDEN, e.to_string()),
+            PentaractError::QuotaExceeded(_) => (StatusCode::INSUFFICIENT_STORAGE, e.to_string()),
+            PentaractError::ShareLinkExpired
+            | PentaractError::ShareLinkDownloadsLimitReached
+            | PentaractError::FileDropExpired
+            | PentaractError::UrlExpired => (StatusCode::GONE, e.to_string()),
+            PentaractError::FileTooLarge(_) => (StatusCode::PAYLOAD_TOO_LARGE, e.to_string()),
             PentaractError::DoesNotExist(_) => (StatusCode::NOT_FOUND, e.to_string()),
             PentaractError::HeaderMissed(_)
             | PentaractError::HeaderIsInvalid(..)
-            | PentaractError::InvalidFolderName => (StatusCode::BAD_REQUEST, e.to_string()),
+            | PentaractError::InvalidFolderName
+            | PentaractError::InvalidCursor
+            | PentaractError::InvalidSearchPattern
+            | PentaractError::InvalidArchive(_)
+            | PentaractError::InvalidShareLinkLimits
+            | PentaractError::InvalidFileDropLimits
+            | PentaractError::TooManyDroppedFiles(_)
+            | PentaractError::InvalidUrlExpiration(_)
+            | PentaractError::InvalidQuota
+            | PentaractError::TooManyOperations(_)
+            | PentaractError::TransferNotTransactional
+            | PentaractError::InvalidAppPasswordName
+            | PentaractError::InvalidDavBody
+            | PentaractError::InvalidS3KeyName
+            | PentaractError::InvalidPartNumber(_)
+            | PentaractError::InvalidPart(_)
+            | PentaractError::InvalidPartOrder
+            | PentaractError::PartTooSmall(_) => (StatusCode::BAD_REQUEST, e.to_string()),
+            PentaractError::Locked => (StatusCode::LOCKED, e.to_string()),
+            PentaractError::TransferFailed(_) => {
+                tracing::error!("{e}");
+                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            }
             _ => {
                 tracing::error!("{e}");
                 (
@@ -73,6 +168,9 @@ impl From<PentaractError> for (StatusCode, String) {
 
 impl From<reqwest::Error> for PentaractError {
     fn from(e: reqwest::Error) -> Self {
+        // urls contain bot tokens so they must never get into logs
+        let e = e.without_url();
+
         match e.status() {
             Some(e) if e.is_client_error() => PentaractError::TelegramAPIError(e.to_string()),
             Some(_) | None => {
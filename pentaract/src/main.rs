@@ -10,8 +10,13 @@ use crate::{
     common::{channels::ClientMessage, db::pool::get_pool, routing::app_state::AppState},
     config::Config,
     server::Server,
-    startup::{create_db, create_superuser, init_db},
+    startup::{
+        create_db, create_superuser, encrypt_storage_workers_tokens, init_db,
+        migrate_paths_to_folders,
+    },
     storage_manager::StorageManager,
+    trash_cleaner::TrashCleaner,
+    uploads_cleaner::UploadsCleaner,
 };
 
 mod common;
@@ -25,6 +30,8 @@ mod server;
 mod services;
 mod startup;
 mod storage_manager;
+mod trash_cleaner;
+mod uploads_cleaner;
 
 #[tokio::main]
 async fn main() {
@@ -61,6 +68,12 @@ async fn main() {
     // initing db
     init_db(&db).await;
 
+    // moving files left from older versions to folders
+    migrate_paths_to_folders(&db).await;
+
+    // encrypting storage workers tokens left from older versions
+    encrypt_storage_workers_tokens(&db, &config).await;
+
     // creating a superuser
     create_superuser(&db, &config).await;
 
@@ -79,6 +92,22 @@ async fn main() {
         manager.run().await;
     });
 
+    // running trash cleaner, retention of 0 days means keeping trash forever
+    if config.trash_retention_days > 0 {
+        let cleaner = TrashCleaner::new(db.clone(), config.trash_retention_days);
+
+        tracing::debug!("running trash cleaner");
+        tokio::spawn(async move { cleaner.run().await });
+    }
+
+    // running uploads cleaner, expiry of 0 days means keeping uploads until they are aborted
+    if config.multipart_upload_expiry_days > 0 {
+        let cleaner = UploadsCleaner::new(db.clone(), config.multipart_upload_expiry_days);
+
+        tracing::debug!("running uploads cleaner");
+        tokio::spawn(async move { cleaner.run().await });
+    }
+
     let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), config.port);
 
     let server = {
@@ -17,9 +17,14 @@ pub struct Access {
     pub access_type: AccessType,
 }
 
+/// `size` and `files_amount` are the usage of the user in the storage
 #[derive(Debug, sqlx::FromRow, Serialize)]
 pub struct UserWithAccess {
     pub id: Uuid,
     pub email: String,
     pub access_type: AccessType,
+    pub files_amount: i64,
+    pub size: i64,
+    pub max_size: Option<i64>,
+    pub max_files: Option<i64>,
 }
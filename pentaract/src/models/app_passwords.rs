@@ -0,0 +1,20 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct AppPassword {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// NOTE:
+///
+/// app passwords are random tokens, so they are stored as plain SHA-256 hashes
+/// and can be looked up by them
+pub struct InAppPassword {
+    pub user_id: uuid::Uuid,
+    pub name: String,
+    pub password_hash: String,
+}
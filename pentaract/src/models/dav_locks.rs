@@ -0,0 +1,27 @@
+use chrono::{DateTime, Utc};
+
+/// NOTE:
+///
+/// locks are bound to paths rather than files, since WebDAV locks URLs.
+/// Only folders (paths ending with a slash) can be locked deeply, with all their content
+#[derive(Debug, sqlx::FromRow)]
+pub struct DavLock {
+    pub storage_id: uuid::Uuid,
+    pub path: String,
+    pub token: String,
+    pub is_shared: bool,
+    pub is_deep: bool,
+    pub owner: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+pub struct InDavLock {
+    pub storage_id: uuid::Uuid,
+    pub path: String,
+    pub token: String,
+    pub is_shared: bool,
+    pub is_deep: bool,
+    pub owner: Option<String>,
+    pub created_by: uuid::Uuid,
+    pub expires_at: DateTime<Utc>,
+}
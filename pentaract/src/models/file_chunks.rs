@@ -1,11 +1,19 @@
-use crate::common::types::Position;
+use crate::common::types::{ChatId, Position};
 
+/// NOTE:
+///
+/// `chat_id` is `None` for chunks uploaded before storages got multiple chats,
+/// such chunks are stored in the storage primary chat.
+///
+/// `version_id` is `None` for chunks of the current file content
 #[derive(Debug, sqlx::FromRow)]
 pub struct FileChunk {
     pub id: uuid::Uuid,
     pub file_id: uuid::Uuid,
     pub telegram_file_id: String,
     pub position: Position,
+    pub chat_id: Option<ChatId>,
+    pub version_id: Option<uuid::Uuid>,
 }
 
 impl FileChunk {
@@ -14,12 +22,16 @@ impl FileChunk {
         file_id: uuid::Uuid,
         telegram_file_id: String,
         position: Position,
+        chat_id: Option<ChatId>,
+        version_id: Option<uuid::Uuid>,
     ) -> Self {
         Self {
             id,
             file_id,
             telegram_file_id,
             position,
+            chat_id,
+            version_id,
         }
     }
 }
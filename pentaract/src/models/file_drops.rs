@@ -0,0 +1,43 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// NOTE:
+///
+/// `path` is the current path of the folder files are dropped into,
+/// it's `None` if the folder is in trash
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct FileDrop {
+    pub id: uuid::Uuid,
+    pub storage_id: uuid::Uuid,
+    pub token: String,
+    pub path: Option<String>,
+    pub max_file_size: Option<i64>,
+    pub max_files: Option<i32>,
+    pub files_amount: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_by: uuid::Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+impl FileDrop {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+            || self.files_left().is_some_and(|files_left| files_left <= 0)
+    }
+
+    pub fn files_left(&self) -> Option<i32> {
+        self.max_files
+            .map(|max_files| (max_files - self.files_amount).max(0))
+    }
+}
+
+pub struct InFileDrop {
+    pub storage_id: uuid::Uuid,
+    pub token: String,
+    pub folder_id: uuid::Uuid,
+    pub max_file_size: Option<i64>,
+    pub max_files: Option<i32>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_by: uuid::Uuid,
+}
@@ -0,0 +1,13 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Previous content of a file, that was uploaded at `created_at` and replaced at `replaced_at`
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct FileVersion {
+    pub id: uuid::Uuid,
+    pub file_id: uuid::Uuid,
+    pub size: i64,
+    pub created_at: DateTime<Utc>,
+    pub replaced_at: DateTime<Utc>,
+    pub uploaded_by: Option<uuid::Uuid>,
+}
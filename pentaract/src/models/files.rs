@@ -1,46 +1,69 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
 use serde::Serialize;
+use sqlx::types::Json;
+
+/// Custom key/value attributes of files and folders
+pub type Metadata = Json<HashMap<String, String>>;
 
 pub struct InFile {
     pub path: String,
     pub size: i64,
     pub storage_id: uuid::Uuid,
+    pub uploaded_by: uuid::Uuid,
+    pub mime_type: Option<String>,
+    pub original_filename: Option<String>,
 }
 
 impl InFile {
-    pub fn new(path: String, size: i64, storage_id: uuid::Uuid) -> Self {
+    /// MIME type is detected by the original filename if it's given, or by the path otherwise
+    pub fn new(
+        path: String,
+        size: i64,
+        storage_id: uuid::Uuid,
+        uploaded_by: uuid::Uuid,
+        original_filename: Option<String>,
+    ) -> Self {
+        // folders don't have any type
+        let mime_type = (!path.ends_with('/')).then(|| {
+            mime_guess::from_path(original_filename.as_deref().unwrap_or(&path))
+                .first_or_octet_stream()
+                .to_string()
+        });
+
         Self {
             path,
             size,
             storage_id,
+            uploaded_by,
+            mime_type,
+            original_filename,
         }
     }
 }
 
-#[derive(Debug, sqlx::FromRow)]
+/// NOTE:
+///
+/// `uploaded_by` is `None` for files uploaded before it was tracked,
+/// or if the user was deleted.
+///
+/// `path` is not stored and built from the folders tree
+#[derive(Debug, Clone, sqlx::FromRow)]
 pub struct File {
     pub id: uuid::Uuid,
+    pub name: String,
     pub path: String,
     pub size: i64,
     pub storage_id: uuid::Uuid,
     pub is_uploaded: bool,
-}
-
-impl File {
-    pub fn new(
-        id: uuid::Uuid,
-        path: String,
-        size: i64,
-        storage_id: uuid::Uuid,
-        is_uploaded: bool,
-    ) -> Self {
-        Self {
-            id,
-            path,
-            size,
-            storage_id,
-            is_uploaded,
-        }
-    }
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub uploaded_by: Option<uuid::Uuid>,
+    pub mime_type: Option<String>,
+    pub original_filename: Option<String>,
+    pub tags: Vec<String>,
+    pub metadata: Metadata,
 }
 
 #[derive(Debug, sqlx::FromRow)]
@@ -48,18 +71,129 @@ pub struct DBFSElement {
     pub name: String,
     pub size: i64,
     pub is_file: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub uploaded_by: Option<uuid::Uuid>,
+    pub mime_type: Option<String>,
+    pub original_filename: Option<String>,
+    pub tags: Vec<String>,
+    pub metadata: Metadata,
 }
 
+/// NOTE:
+///
+/// folders have the latest `updated_at` of their files if it's later than their own one,
+/// and don't have `uploaded_by`, `mime_type` and `original_filename`
 #[derive(Debug, sqlx::FromRow, Serialize)]
 pub struct FSElement {
     pub path: String,
     pub name: String,
     pub size: i64,
     pub is_file: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub uploaded_by: Option<uuid::Uuid>,
+    pub mime_type: Option<String>,
+    pub original_filename: Option<String>,
+    pub tags: Vec<String>,
+    pub metadata: Metadata,
+}
+
+impl FSElement {
+    pub fn new(path: String, el: DBFSElement) -> Self {
+        Self {
+            path,
+            name: el.name,
+            size: el.size,
+            is_file: el.is_file,
+            created_at: el.created_at,
+            updated_at: el.updated_at,
+            uploaded_by: el.uploaded_by,
+            mime_type: el.mime_type,
+            original_filename: el.original_filename,
+            tags: el.tags,
+            metadata: el.metadata,
+        }
+    }
 }
 
+impl From<File> for FSElement {
+    fn from(file: File) -> Self {
+        Self {
+            path: file.path,
+            name: file.name,
+            size: file.size,
+            is_file: true,
+            created_at: file.created_at,
+            updated_at: file.updated_at,
+            uploaded_by: file.uploaded_by,
+            mime_type: file.mime_type,
+            original_filename: file.original_filename,
+            tags: file.tags,
+            metadata: file.metadata,
+        }
+    }
+}
+
+/// NOTE:
+///
+/// the lower `rank` is, the better the element matches the search,
+/// from 0 for the exact name to 3 for a match in parent folders names
 #[derive(Debug, sqlx::FromRow, Serialize)]
 pub struct SearchFSElement {
     pub path: String,
+    pub name: String,
+    #[serde(skip)]
+    pub rank: i32,
     pub is_file: bool,
+    pub size: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub uploaded_by: Option<uuid::Uuid>,
+    pub mime_type: Option<String>,
+    pub original_filename: Option<String>,
+    pub tags: Vec<String>,
+    pub metadata: Metadata,
+}
+
+/// NOTE:
+///
+/// `stored_amount` is how many times the content of the file is stored,
+/// files copied within storages share their content
+#[derive(Debug, sqlx::FromRow)]
+pub struct DuplicateFile {
+    pub storage_id: uuid::Uuid,
+    pub path: String,
+    pub size: i64,
+    pub content_hash: String,
+    pub stored_amount: i64,
+}
+
+/// NOTE:
+///
+/// a file as it's listed by the S3 API, its path is the object key.
+///
+/// Folders are listed as common prefixes with `is_prefix`, their keys end with a slash
+#[derive(Debug, sqlx::FromRow)]
+pub struct S3Object {
+    pub key: String,
+    pub is_prefix: bool,
+    pub size: i64,
+    pub updated_at: DateTime<Utc>,
+    pub mime_type: Option<String>,
+    pub content_hash: Option<String>,
+}
+
+impl S3Object {
+    /// Files uploaded before their contents were hashed get a weak ETag
+    pub fn etag(&self) -> String {
+        match &self.content_hash {
+            Some(content_hash) => format!("\"{content_hash}\""),
+            None => format!(
+                "W/\"{:x}-{:x}\"",
+                self.updated_at.timestamp_micros(),
+                self.size
+            ),
+        }
+    }
 }
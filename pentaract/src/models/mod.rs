@@ -1,6 +1,16 @@
 pub mod access;
+pub mod app_passwords;
+pub mod dav_locks;
 pub mod file_chunks;
+pub mod file_drops;
+pub mod file_versions;
 pub mod files;
+pub mod multipart_uploads;
+pub mod s3_keys;
+pub mod share_links;
+pub mod storage_chats;
 pub mod storage_workers;
+pub mod storage_workers_stats;
 pub mod storages;
+pub mod trash;
 pub mod users;
@@ -0,0 +1,43 @@
+use crate::common::types::{ChatId, Position};
+
+/// Upload of a file in parts, the file is created once all the parts are uploaded
+#[derive(Debug, sqlx::FromRow)]
+pub struct MultipartUpload {
+    pub id: uuid::Uuid,
+    pub path: String,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct MultipartPart {
+    pub part_number: i32,
+    pub size: i64,
+    pub etag: String,
+}
+
+/// Chunks of parts are linked to the file once the upload is completed
+#[derive(Debug, sqlx::FromRow)]
+pub struct MultipartPartChunk {
+    pub id: uuid::Uuid,
+    pub part_id: uuid::Uuid,
+    pub telegram_file_id: String,
+    pub position: Position,
+    pub chat_id: ChatId,
+}
+
+impl MultipartPartChunk {
+    pub fn new(
+        id: uuid::Uuid,
+        part_id: uuid::Uuid,
+        telegram_file_id: String,
+        position: Position,
+        chat_id: ChatId,
+    ) -> Self {
+        Self {
+            id,
+            part_id,
+            telegram_file_id,
+            position,
+            chat_id,
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct S3Key {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub access_key_id: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// NOTE:
+///
+/// unlike app passwords, secrets are needed to check requests signatures,
+/// so they are encrypted instead of hashed
+pub struct InS3Key {
+    pub user_id: uuid::Uuid,
+    pub name: String,
+    pub access_key_id: String,
+    pub encrypted_secret: String,
+}
+
+/// Owner of the key along with the secret to check signatures with
+#[derive(Debug, sqlx::FromRow)]
+pub struct S3Credentials {
+    pub user_id: uuid::Uuid,
+    pub email: String,
+    pub encrypted_secret: String,
+}
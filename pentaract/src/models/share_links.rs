@@ -0,0 +1,45 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// NOTE:
+///
+/// `path` is the current path of the shared file or folder,
+/// it's `None` if the shared one is in trash
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct ShareLink {
+    pub id: uuid::Uuid,
+    pub storage_id: uuid::Uuid,
+    pub token: String,
+    pub path: Option<String>,
+    pub is_file: bool,
+    #[serde(skip)]
+    pub password_hash: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_downloads: Option<i32>,
+    pub downloads_amount: i32,
+    pub created_by: uuid::Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ShareLink {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+
+    pub fn is_downloads_limit_reached(&self) -> bool {
+        self.max_downloads
+            .is_some_and(|max_downloads| self.downloads_amount >= max_downloads)
+    }
+}
+
+pub struct InShareLink {
+    pub storage_id: uuid::Uuid,
+    pub token: String,
+    pub file_id: Option<uuid::Uuid>,
+    pub folder_id: Option<uuid::Uuid>,
+    pub password_hash: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_downloads: Option<i32>,
+    pub created_by: uuid::Uuid,
+}
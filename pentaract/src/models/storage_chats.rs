@@ -0,0 +1,37 @@
+use serde::Serialize;
+
+use crate::common::types::ChatId;
+
+pub struct InStorageChat {
+    pub storage_id: uuid::Uuid,
+    pub chat_id: ChatId,
+}
+
+impl InStorageChat {
+    pub fn new(storage_id: uuid::Uuid, chat_id: ChatId) -> Self {
+        Self {
+            storage_id,
+            chat_id,
+        }
+    }
+}
+
+/// Additional chat of a storage
+///
+/// The primary one is kept in the `storages` table itself
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct StorageChat {
+    pub id: uuid::Uuid,
+    pub storage_id: uuid::Uuid,
+    pub chat_id: ChatId,
+}
+
+impl StorageChat {
+    pub fn new(id: uuid::Uuid, storage_id: uuid::Uuid, chat_id: ChatId) -> Self {
+        Self {
+            id,
+            storage_id,
+            chat_id,
+        }
+    }
+}
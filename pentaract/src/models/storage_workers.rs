@@ -1,9 +1,8 @@
-use serde::Serialize;
-
 pub struct InStorageWorker {
     pub name: String,
     pub user_id: uuid::Uuid,
     pub token: String,
+    pub token_hash: String,
     pub storage_id: Option<uuid::Uuid>,
 }
 
@@ -12,18 +11,23 @@ impl InStorageWorker {
         name: String,
         user_id: uuid::Uuid,
         token: String,
+        token_hash: String,
         storage_id: Option<uuid::Uuid>,
     ) -> Self {
         Self {
             name,
             user_id,
             token,
+            token_hash,
             storage_id,
         }
     }
 }
 
-#[derive(Debug, sqlx::FromRow, Serialize)]
+/// NOTE:
+///
+/// `token` is stored encrypted, so it must be decrypted before using it
+#[derive(Debug, sqlx::FromRow)]
 pub struct StorageWorker {
     pub id: uuid::Uuid,
     pub name: String,
@@ -51,6 +55,7 @@ impl StorageWorker {
 }
 
 #[derive(Debug, sqlx::FromRow)]
-pub struct StorageWorkerTokenOnly {
+pub struct StorageWorkerToken {
+    pub id: uuid::Uuid,
     pub token: String,
 }
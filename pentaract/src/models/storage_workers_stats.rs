@@ -0,0 +1,57 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Usage of a storage worker by a single Telegram API call
+pub struct InStorageWorkerUsage {
+    pub bytes_uploaded: i64,
+    pub bytes_downloaded: i64,
+    pub is_error: bool,
+}
+
+impl InStorageWorkerUsage {
+    pub fn new(bytes_uploaded: i64, bytes_downloaded: i64, is_error: bool) -> Self {
+        Self {
+            bytes_uploaded,
+            bytes_downloaded,
+            is_error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsGranularity {
+    #[default]
+    Hour,
+    Day,
+}
+
+impl StatsGranularity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Hour => "hour",
+            Self::Day => "day",
+        }
+    }
+}
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct StatsPoint {
+    pub dt: DateTime<Utc>,
+    pub calls: i64,
+    pub bytes_uploaded: i64,
+    pub bytes_downloaded: i64,
+    pub errors: i64,
+    pub throttled_waits: i64,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct StorageWorkerStatsPoint {
+    pub storage_worker_id: uuid::Uuid,
+    pub name: String,
+    pub dt: DateTime<Utc>,
+    pub calls: i64,
+    pub bytes_uploaded: i64,
+    pub bytes_downloaded: i64,
+    pub errors: i64,
+}
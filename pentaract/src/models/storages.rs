@@ -13,24 +13,56 @@ impl InStorage {
     }
 }
 
+/// NOTE:
+///
+/// `max_size` and `max_files` are the storage quotas, `None` means unlimited
 #[derive(Debug, Serialize, sqlx::FromRow)]
 pub struct Storage {
     pub id: uuid::Uuid,
     pub name: String,
     pub chat_id: ChatId,
+    pub versioning: bool,
+    pub max_size: Option<i64>,
+    pub max_files: Option<i64>,
 }
 
 impl Storage {
-    pub fn new(id: uuid::Uuid, name: String, chat_id: ChatId) -> Self {
-        Self { id, name, chat_id }
+    pub fn new(
+        id: uuid::Uuid,
+        name: String,
+        chat_id: ChatId,
+        versioning: bool,
+        max_size: Option<i64>,
+        max_files: Option<i64>,
+    ) -> Self {
+        Self {
+            id,
+            name,
+            chat_id,
+            versioning,
+            max_size,
+            max_files,
+        }
     }
 }
 
+/// NOTE:
+///
+/// `user_*` fields are the usage and the quotas of the requesting user in the storage.
+///
+/// Files in trash are not counted
 #[derive(Debug, Serialize, sqlx::FromRow)]
 pub struct StorageWithInfo {
     pub id: uuid::Uuid,
     pub name: String,
     pub chat_id: ChatId,
+    pub versioning: bool,
     pub files_amount: i64,
     pub size: i64,
+    pub max_size: Option<i64>,
+    pub max_files: Option<i64>,
+    pub user_files_amount: i64,
+    pub user_size: i64,
+    pub user_max_size: Option<i64>,
+    pub user_max_files: Option<i64>,
 }
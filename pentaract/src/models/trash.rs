@@ -0,0 +1,20 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Something deleted at once, like a file or a whole folder,
+/// whose files keep their original paths while being in trash
+#[derive(Debug, sqlx::FromRow)]
+pub struct Trash {
+    pub id: uuid::Uuid,
+    pub storage_id: uuid::Uuid,
+    pub path: String,
+}
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct TrashItem {
+    pub id: uuid::Uuid,
+    pub path: String,
+    pub deleted_at: DateTime<Utc>,
+    pub files_amount: i64,
+    pub size: i64,
+}
@@ -4,6 +4,7 @@ use uuid::Uuid;
 use crate::common::db::errors::map_not_found;
 use crate::errors::{PentaractError, PentaractResult};
 use crate::models::access::{AccessType, UserWithAccess};
+use crate::repositories::storages::StoragesRepository;
 use crate::schemas::access::GrantAccess;
 
 pub const TABLE: &str = "access";
@@ -68,13 +69,25 @@ impl<'d> AccessRepository<'d> {
         &self,
         storage_id: Uuid,
     ) -> PentaractResult<Vec<UserWithAccess>> {
+        let content = StoragesRepository::select_content();
+
         sqlx::query_as(
             format!(
                 "
-            SELECT u.id AS id, u.email AS email, a.access_type AS access_type
+            SELECT
+                u.id AS id,
+                u.email AS email,
+                a.access_type AS access_type,
+                COALESCE(SUM(c.files), 0)::BigInt AS files_amount,
+                COALESCE(SUM(c.size), 0)::BigInt AS size,
+                a.max_size AS max_size,
+                a.max_files AS max_files
             FROM {TABLE} a
             JOIN users u ON a.user_id = u.id
+            LEFT JOIN ({content}) c
+                ON c.storage_id = a.storage_id AND c.uploaded_by = u.id
             WHERE a.storage_id = $1
+            GROUP BY u.id, a.id
         "
             )
             .as_str(),
@@ -85,6 +98,37 @@ impl<'d> AccessRepository<'d> {
         .map_err(|e| map_not_found(e, "user"))
     }
 
+    pub async fn set_quota(
+        &self,
+        user_id: Uuid,
+        storage_id: Uuid,
+        max_size: Option<i64>,
+        max_files: Option<i64>,
+    ) -> PentaractResult<()> {
+        let result = sqlx::query(&format!(
+            "
+            UPDATE {TABLE}
+            SET max_size = $3, max_files = $4
+            WHERE user_id = $1 AND storage_id = $2
+            "
+        ))
+        .bind(user_id)
+        .bind(storage_id)
+        .bind(max_size)
+        .bind(max_files)
+        .execute(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "access"))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::DoesNotExist(format!(
+                "user with id \"{user_id}\" in the storage"
+            )));
+        }
+
+        Ok(())
+    }
+
     #[inline]
     pub async fn has_access(
         &self,
@@ -0,0 +1,93 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::common::db::errors::map_not_found;
+use crate::errors::{PentaractError, PentaractResult};
+use crate::models::{
+    app_passwords::{AppPassword, InAppPassword},
+    users::User,
+};
+
+pub const TABLE: &str = "app_passwords";
+
+pub struct AppPasswordsRepository<'d> {
+    db: &'d PgPool,
+}
+
+impl<'d> AppPasswordsRepository<'d> {
+    pub fn new(db: &'d PgPool) -> Self {
+        Self { db }
+    }
+
+    pub async fn create(&self, in_obj: InAppPassword) -> PentaractResult<AppPassword> {
+        let id = Uuid::new_v4();
+
+        sqlx::query_as(&format!(
+            "
+            INSERT INTO {TABLE} (id, user_id, name, password_hash)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, name, created_at, last_used_at;
+            "
+        ))
+        .bind(id)
+        .bind(in_obj.user_id)
+        .bind(in_obj.name)
+        .bind(in_obj.password_hash)
+        .fetch_one(self.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })
+    }
+
+    pub async fn list(&self, user_id: Uuid) -> PentaractResult<Vec<AppPassword>> {
+        sqlx::query_as(&format!(
+            "
+            SELECT id, name, created_at, last_used_at
+            FROM {TABLE}
+            WHERE user_id = $1
+            ORDER BY created_at DESC;
+            "
+        ))
+        .bind(user_id)
+        .fetch_all(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "app password"))
+    }
+
+    /// Finds the owner of the app password and marks the password as used
+    pub async fn use_password(&self, email: &str, password_hash: &str) -> PentaractResult<User> {
+        sqlx::query_as(&format!(
+            "
+            UPDATE {TABLE} a
+            SET last_used_at = NOW()
+            FROM users u
+            WHERE a.user_id = u.id AND u.email = $1 AND a.password_hash = $2
+            RETURNING u.*;
+            "
+        ))
+        .bind(email)
+        .bind(password_hash)
+        .fetch_one(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "app password"))
+    }
+
+    pub async fn delete(&self, id: Uuid, user_id: Uuid) -> PentaractResult<()> {
+        let result = sqlx::query(&format!(
+            "DELETE FROM {TABLE} WHERE id = $1 AND user_id = $2"
+        ))
+        .bind(id)
+        .bind(user_id)
+        .execute(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "app password"))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::DoesNotExist("app password".to_owned()));
+        }
+
+        Ok(())
+    }
+}
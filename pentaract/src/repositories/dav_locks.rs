@@ -0,0 +1,168 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::common::db::errors::map_not_found;
+use crate::errors::{PentaractError, PentaractResult};
+use crate::models::dav_locks::{DavLock, InDavLock};
+
+pub const TABLE: &str = "dav_locks";
+
+/// Locks which are not expired and lock `$2` itself, one of its folders deeply,
+/// or anything inside it if `$3` is set
+const OVERLAPPING: &str = "
+    storage_id = $1
+    AND expires_at > NOW()
+    AND (path = $2 OR (is_deep AND starts_with($2, path)) OR ($3 AND starts_with(path, $2)))
+";
+
+pub struct DavLocksRepository<'d> {
+    db: &'d PgPool,
+}
+
+impl<'d> DavLocksRepository<'d> {
+    pub fn new(db: &'d PgPool) -> Self {
+        Self { db }
+    }
+
+    /// Fails if there is a lock it conflicts with,
+    /// shared locks conflict only with exclusive ones
+    pub async fn create(&self, in_obj: InDavLock) -> PentaractResult<DavLock> {
+        let mut transaction = self.db.begin().await.map_err(|e| map_not_found(e, ""))?;
+
+        // locks of a storage are created one at a time, so they don't miss each other
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1::text))")
+            .bind(in_obj.storage_id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| map_not_found(e, "lock"))?;
+
+        let (is_locked,): (bool,) = sqlx::query_as(&format!(
+            "
+            SELECT COUNT(*) > 0
+            FROM {TABLE}
+            WHERE {OVERLAPPING} AND NOT (is_shared AND $4);
+            "
+        ))
+        .bind(in_obj.storage_id)
+        .bind(&in_obj.path)
+        .bind(in_obj.is_deep)
+        .bind(in_obj.is_shared)
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|e| map_not_found(e, "lock"))?;
+        if is_locked {
+            return Err(PentaractError::Locked);
+        }
+
+        let lock = sqlx::query_as(&format!(
+            "
+            INSERT INTO {TABLE}
+                (id, storage_id, path, token, is_shared, is_deep, owner, created_by, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING *;
+            "
+        ))
+        .bind(Uuid::new_v4())
+        .bind(in_obj.storage_id)
+        .bind(in_obj.path)
+        .bind(in_obj.token)
+        .bind(in_obj.is_shared)
+        .bind(in_obj.is_deep)
+        .bind(in_obj.owner)
+        .bind(in_obj.created_by)
+        .bind(in_obj.expires_at)
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(dbe) if dbe.is_foreign_key_violation() => {
+                PentaractError::DoesNotExist("such storage".to_string())
+            }
+            _ => {
+                tracing::error!("{e}");
+                PentaractError::Unknown
+            }
+        })?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| map_not_found(e, ""))?;
+
+        Ok(lock)
+    }
+
+    /// Lists locks of the path, including locks of the content if `with_content` is set
+    pub async fn list_overlapping(
+        &self,
+        storage_id: Uuid,
+        path: &str,
+        with_content: bool,
+    ) -> PentaractResult<Vec<DavLock>> {
+        sqlx::query_as(&format!(
+            "SELECT * FROM {TABLE} WHERE {OVERLAPPING} ORDER BY path"
+        ))
+        .bind(storage_id)
+        .bind(path)
+        .bind(with_content)
+        .fetch_all(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "lock"))
+    }
+
+    pub async fn refresh(
+        &self,
+        token: &str,
+        storage_id: Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> PentaractResult<DavLock> {
+        sqlx::query_as(&format!(
+            "
+            UPDATE {TABLE}
+            SET expires_at = $3
+            WHERE token = $1 AND storage_id = $2 AND expires_at > NOW()
+            RETURNING *;
+            "
+        ))
+        .bind(token)
+        .bind(storage_id)
+        .bind(expires_at)
+        .fetch_one(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "lock"))
+    }
+
+    pub async fn delete(&self, token: &str, storage_id: Uuid) -> PentaractResult<()> {
+        let result = sqlx::query(&format!(
+            "DELETE FROM {TABLE} WHERE token = $1 AND storage_id = $2"
+        ))
+        .bind(token)
+        .bind(storage_id)
+        .execute(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "lock"))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::DoesNotExist("lock".to_owned()));
+        }
+
+        Ok(())
+    }
+
+    /// Deletes locks of the path and of its content,
+    /// they are dropped once locked files are moved or deleted
+    pub async fn delete_by_path(&self, storage_id: Uuid, path: &str) -> PentaractResult<()> {
+        sqlx::query(&format!(
+            "
+            DELETE FROM {TABLE}
+            WHERE storage_id = $1 AND (path = $2 OR ($2 LIKE '%/' AND starts_with(path, $2)));
+            "
+        ))
+        .bind(storage_id)
+        .bind(path)
+        .execute(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "lock"))
+        .map(|_| ())
+    }
+}
@@ -0,0 +1,170 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::common::db::errors::map_not_found;
+use crate::errors::{PentaractError, PentaractResult};
+use crate::models::file_drops::{FileDrop, InFileDrop};
+use crate::repositories::folders::FOLDERS_TABLE;
+
+pub const TABLE: &str = "file_drops";
+
+pub struct FileDropsRepository<'d> {
+    db: &'d PgPool,
+}
+
+impl<'d> FileDropsRepository<'d> {
+    pub fn new(db: &'d PgPool) -> Self {
+        Self { db }
+    }
+
+    pub async fn create(&self, in_obj: InFileDrop) -> PentaractResult<FileDrop> {
+        let id = Uuid::new_v4();
+
+        sqlx::query(&format!(
+            "
+            INSERT INTO {TABLE}
+                (id, storage_id, token, folder_id, max_file_size, max_files, expires_at, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8);
+            "
+        ))
+        .bind(id)
+        .bind(in_obj.storage_id)
+        .bind(in_obj.token)
+        .bind(in_obj.folder_id)
+        .bind(in_obj.max_file_size)
+        .bind(in_obj.max_files)
+        .bind(in_obj.expires_at)
+        .bind(in_obj.created_by)
+        .execute(self.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })?;
+
+        self.get(id, in_obj.storage_id).await
+    }
+
+    pub async fn list(&self, storage_id: Uuid) -> PentaractResult<Vec<FileDrop>> {
+        let select = Self::select();
+
+        sqlx::query_as(&format!(
+            "{select} WHERE l.storage_id = $1 ORDER BY l.created_at DESC"
+        ))
+        .bind(storage_id)
+        .fetch_all(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "file drop"))
+    }
+
+    pub async fn get(&self, id: Uuid, storage_id: Uuid) -> PentaractResult<FileDrop> {
+        let select = Self::select();
+
+        sqlx::query_as(&format!("{select} WHERE l.id = $1 AND l.storage_id = $2"))
+            .bind(id)
+            .bind(storage_id)
+            .fetch_one(self.db)
+            .await
+            .map_err(|e| map_not_found(e, "file drop"))
+    }
+
+    pub async fn get_by_token(&self, token: &str) -> PentaractResult<FileDrop> {
+        let select = Self::select();
+
+        sqlx::query_as(&format!("{select} WHERE l.token = $1"))
+            .bind(token)
+            .fetch_one(self.db)
+            .await
+            .map_err(|e| map_not_found(e, "file drop"))
+    }
+
+    /// Takes a place for a file if the drop has any places left,
+    /// so concurrent uploads cannot exceed the files limit
+    pub async fn take_place(&self, id: Uuid) -> PentaractResult<()> {
+        let result = sqlx::query(&format!(
+            "
+            UPDATE {TABLE}
+            SET files_amount = files_amount + 1
+            WHERE
+                id = $1
+                AND (max_files IS NULL OR files_amount < max_files)
+                AND (expires_at IS NULL OR expires_at > NOW());
+            "
+        ))
+        .bind(id)
+        .execute(self.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })?;
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::FileDropExpired);
+        }
+
+        Ok(())
+    }
+
+    /// Gives a place back if the file was not saved
+    pub async fn release_place(&self, id: Uuid) -> PentaractResult<()> {
+        sqlx::query(&format!(
+            "UPDATE {TABLE} SET files_amount = GREATEST(files_amount - 1, 0) WHERE id = $1"
+        ))
+        .bind(id)
+        .execute(self.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })?;
+
+        Ok(())
+    }
+
+    pub async fn delete(&self, id: Uuid, storage_id: Uuid) -> PentaractResult<()> {
+        let result = sqlx::query(&format!(
+            "DELETE FROM {TABLE} WHERE id = $1 AND storage_id = $2"
+        ))
+        .bind(id)
+        .bind(storage_id)
+        .execute(self.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })?;
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::DoesNotExist("file drop".to_owned()));
+        }
+
+        Ok(())
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    ////    Helpers
+    /////////////////////////////////////////////////////////////////////
+
+    /// Selects drops with the current paths of their folders
+    fn select() -> String {
+        format!(
+            "
+            SELECT
+                l.id,
+                l.storage_id,
+                l.token,
+                (
+                    SELECT folder_path(d.id)
+                    FROM {FOLDERS_TABLE} d
+                    WHERE d.id = l.folder_id AND d.trash_id IS NULL
+                ) AS path,
+                l.max_file_size,
+                l.max_files,
+                l.files_amount,
+                l.expires_at,
+                l.created_by,
+                l.created_at
+            FROM {TABLE} l
+            "
+        )
+    }
+}
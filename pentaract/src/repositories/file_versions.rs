@@ -0,0 +1,369 @@
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::common::db::errors::map_not_found;
+use crate::errors::{PentaractError, PentaractResult};
+use crate::models::file_versions::FileVersion;
+use crate::repositories::{
+    files::{FilesRepository, CHUNKS_TABLE, FILES_TABLE},
+    folders::FoldersRepository,
+};
+
+pub const TABLE: &str = "file_versions";
+
+/// NOTE:
+///
+/// versions are created as not uploaded ones for the time their chunks are being uploaded,
+/// and they are hidden everywhere until they become the current file content
+pub struct FileVersionsRepository<'d> {
+    db: &'d PgPool,
+}
+
+impl<'d> FileVersionsRepository<'d> {
+    pub fn new(db: &'d PgPool) -> Self {
+        Self { db }
+    }
+
+    pub async fn create_pending(
+        &self,
+        file_id: Uuid,
+        size: i64,
+        content_hash: &str,
+    ) -> PentaractResult<Uuid> {
+        let id = Uuid::new_v4();
+
+        sqlx::query(&format!(
+            "
+            INSERT INTO {TABLE} (id, file_id, size, content_hash, is_uploaded)
+            VALUES ($1, $2, $3, $4, false)
+            "
+        ))
+        .bind(id)
+        .bind(file_id)
+        .bind(size)
+        .bind(content_hash)
+        .execute(self.db)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(dbe) if dbe.is_foreign_key_violation() => {
+                PentaractError::DoesNotExist("such file".to_string())
+            }
+            _ => {
+                tracing::error!("{e}");
+                PentaractError::Unknown
+            }
+        })?;
+
+        Ok(id)
+    }
+
+    pub async fn list(&self, file_id: Uuid) -> PentaractResult<Vec<FileVersion>> {
+        sqlx::query_as(&format!(
+            "
+            SELECT id, file_id, size, created_at, replaced_at, uploaded_by
+            FROM {TABLE}
+            WHERE file_id = $1 AND is_uploaded
+            ORDER BY replaced_at DESC;
+        "
+        ))
+        .bind(file_id)
+        .fetch_all(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "file versions"))
+    }
+
+    pub async fn get(&self, id: Uuid, storage_id: Uuid) -> PentaractResult<FileVersion> {
+        sqlx::query_as(&format!(
+            "
+            SELECT v.id, v.file_id, v.size, v.created_at, v.replaced_at, v.uploaded_by
+            FROM {TABLE} v
+            JOIN {FILES_TABLE} f ON f.id = v.file_id
+            WHERE v.id = $1 AND v.is_uploaded AND f.storage_id = $2 AND f.trash_id IS NULL;
+        "
+        ))
+        .bind(id)
+        .bind(storage_id)
+        .fetch_one(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "file version"))
+    }
+
+    /// Makes an uploaded pending version the current file content,
+    /// the previous content is kept as a version
+    pub async fn make_current(
+        &self,
+        id: Uuid,
+        file_id: Uuid,
+        uploaded_by: Uuid,
+    ) -> PentaractResult<()> {
+        let mut transaction = self.db.begin().await.map_err(|e| map_not_found(e, ""))?;
+
+        Self::archive_current(&mut transaction, file_id).await?;
+
+        sqlx::query(&format!(
+            "UPDATE {CHUNKS_TABLE} SET version_id = NULL WHERE version_id = $1"
+        ))
+        .bind(id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| map_not_found(e, "file chunks"))?;
+
+        Self::set_file_content(&mut transaction, id, uploaded_by).await?;
+
+        sqlx::query(&format!("DELETE FROM {TABLE} WHERE id = $1"))
+            .bind(id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| map_not_found(e, "file version"))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| map_not_found(e, ""))?;
+
+        Ok(())
+    }
+
+    /// Makes an uploaded pending version the current file content dropping the previous one,
+    /// it's for overwrites in storages without versioning
+    pub async fn replace_current(
+        &self,
+        id: Uuid,
+        file_id: Uuid,
+        uploaded_by: Uuid,
+    ) -> PentaractResult<()> {
+        let mut transaction = self.db.begin().await.map_err(|e| map_not_found(e, ""))?;
+
+        // locking the file, so concurrent changes don't mix their chunks
+        let result = sqlx::query(&format!(
+            "SELECT id FROM {FILES_TABLE} WHERE id = $1 FOR UPDATE"
+        ))
+        .bind(file_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| map_not_found(e, "file"))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::DoesNotExist("such file".to_string()));
+        }
+
+        sqlx::query(&format!(
+            "DELETE FROM {CHUNKS_TABLE} WHERE file_id = $1 AND version_id IS NULL"
+        ))
+        .bind(file_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| map_not_found(e, "file chunks"))?;
+
+        sqlx::query(&format!(
+            "UPDATE {CHUNKS_TABLE} SET version_id = NULL WHERE version_id = $1"
+        ))
+        .bind(id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| map_not_found(e, "file chunks"))?;
+
+        Self::set_file_content(&mut transaction, id, uploaded_by).await?;
+
+        sqlx::query(&format!("DELETE FROM {TABLE} WHERE id = $1"))
+            .bind(id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| map_not_found(e, "file version"))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| map_not_found(e, ""))?;
+
+        Ok(())
+    }
+
+    /// Makes a copy of the version the current file content reusing its chunks,
+    /// so the version itself stays in history as well as the replaced content
+    pub async fn restore(&self, version: &FileVersion, restored_by: Uuid) -> PentaractResult<()> {
+        let mut transaction = self.db.begin().await.map_err(|e| map_not_found(e, ""))?;
+
+        Self::archive_current(&mut transaction, version.file_id).await?;
+
+        sqlx::query(&format!(
+            "
+            INSERT INTO {CHUNKS_TABLE} (id, file_id, telegram_file_id, position, chat_id)
+            SELECT gen_random_uuid(), file_id, telegram_file_id, position, chat_id
+            FROM {CHUNKS_TABLE}
+            WHERE version_id = $1;
+            "
+        ))
+        .bind(version.id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })?;
+
+        Self::set_file_content(&mut transaction, version.id, restored_by).await?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| map_not_found(e, ""))?;
+
+        Ok(())
+    }
+
+    pub async fn delete(&self, id: Uuid, storage_id: Uuid) -> PentaractResult<()> {
+        let result = sqlx::query(&format!(
+            "
+            DELETE FROM {TABLE} v
+            USING {FILES_TABLE} f
+            WHERE v.id = $1 AND f.id = v.file_id AND f.storage_id = $2;
+            "
+        ))
+        .bind(id)
+        .bind(storage_id)
+        .execute(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "file version"))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::DoesNotExist(
+                "such file version".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Deletes versions of a file or of all the files of a folder (if `path` ends with a slash),
+    /// or of the whole storage if `path` is empty.
+    ///
+    /// Versions beyond `keep_last` latest ones of every file are deleted,
+    /// as well as the ones replaced more than `older_than_days` days ago.
+    ///
+    /// Returns amount of deleted versions
+    pub async fn prune(
+        &self,
+        storage_id: Uuid,
+        path: &str,
+        keep_last: Option<u32>,
+        older_than_days: Option<u32>,
+    ) -> PentaractResult<u64> {
+        // folders are matched by their subtrees, and files by their folders and names
+        let (path, name) = match path.ends_with('/') {
+            true => (path, ""),
+            false => FilesRepository::split_path(path),
+        };
+        let (subtree, where_path) = if path.is_empty() && name.is_empty() {
+            (String::new(), "$2 = '' AND $5 = ''")
+        } else if name.is_empty() {
+            let subtree = FoldersRepository::subtree_cte("folder_id_by_path($1, $2)", "$2");
+            (
+                format!("WITH RECURSIVE {subtree}"),
+                "$5 = '' AND f.folder_id IN (SELECT id FROM subtree)",
+            )
+        } else {
+            (
+                String::new(),
+                "f.folder_id = folder_id_by_path($1, $2) AND f.name = $5",
+            )
+        };
+
+        sqlx::query(&format!(
+            "
+            {subtree}
+            DELETE FROM {TABLE}
+            WHERE id IN (
+                SELECT id
+                FROM (
+                    SELECT
+                        v.id,
+                        v.replaced_at,
+                        ROW_NUMBER() OVER (PARTITION BY v.file_id ORDER BY v.replaced_at DESC) AS n
+                    FROM {TABLE} v
+                    JOIN {FILES_TABLE} f ON f.id = v.file_id
+                    WHERE f.storage_id = $1 AND v.is_uploaded AND {where_path}
+                ) AS ranked
+                WHERE n > $3 OR replaced_at < NOW() - make_interval(days => $4)
+            );
+            "
+        ))
+        .bind(storage_id)
+        .bind(path)
+        .bind(keep_last.map(|n| n as i64))
+        .bind(older_than_days.map(|days| days as i32))
+        .bind(name)
+        .execute(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "file versions"))
+        .map(|result| result.rows_affected())
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    ////    Helpers
+    /////////////////////////////////////////////////////////////////////
+
+    /// Turns the current file content into a version
+    async fn archive_current(
+        transaction: &mut Transaction<'_, Postgres>,
+        file_id: Uuid,
+    ) -> PentaractResult<()> {
+        let id = Uuid::new_v4();
+
+        // locking the file, so concurrent changes don't mix their chunks
+        let result = sqlx::query(&format!(
+            "
+            INSERT INTO {TABLE}
+                (id, file_id, size, content_hash, is_uploaded, created_at, uploaded_by)
+            SELECT $1, id, size, content_hash, true, updated_at, uploaded_by
+            FROM {FILES_TABLE}
+            WHERE id = $2
+            FOR UPDATE;
+            "
+        ))
+        .bind(id)
+        .bind(file_id)
+        .execute(&mut **transaction)
+        .await
+        .map_err(|e| map_not_found(e, "file"))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::DoesNotExist("such file".to_string()));
+        }
+
+        sqlx::query(&format!(
+            "UPDATE {CHUNKS_TABLE} SET version_id = $1 WHERE file_id = $2 AND version_id IS NULL"
+        ))
+        .bind(id)
+        .bind(file_id)
+        .execute(&mut **transaction)
+        .await
+        .map_err(|e| map_not_found(e, "file chunks"))
+        .map(|_| ())
+    }
+
+    /// Updates the file info after its content was replaced by the version one.
+    ///
+    /// The content is accounted per version: the current one is the usage of `uploaded_by`,
+    /// while the archived ones stay the usage of the users who uploaded them
+    async fn set_file_content(
+        transaction: &mut Transaction<'_, Postgres>,
+        version_id: Uuid,
+        uploaded_by: Uuid,
+    ) -> PentaractResult<()> {
+        sqlx::query(&format!(
+            "
+            UPDATE {FILES_TABLE} f
+            SET size = v.size, content_hash = v.content_hash, updated_at = NOW(), uploaded_by = $2
+            FROM {TABLE} v
+            WHERE v.id = $1 AND f.id = v.file_id;
+            "
+        ))
+        .bind(version_id)
+        .bind(uploaded_by)
+        .execute(&mut **transaction)
+        .await
+        .map_err(|e| map_not_found(e, "file"))
+        .map(|_| ())
+    }
+}
@@ -1,17 +1,27 @@
-use std::path::Path;
+use std::collections::HashMap;
 
-use sqlx::{PgPool, QueryBuilder};
+use sqlx::{types::Json, PgPool, Postgres, QueryBuilder, Transaction};
 use uuid::Uuid;
 
 use crate::common::db::errors::map_not_found;
 use crate::errors::{PentaractError, PentaractResult};
 use crate::models::file_chunks::FileChunk;
-use crate::models::files::{DBFSElement, FSElement, File, InFile, SearchFSElement};
+use crate::models::files::{
+    DBFSElement, DuplicateFile, FSElement, File, InFile, S3Object, SearchFSElement,
+};
+use crate::repositories::folders::{FoldersRepository, FOLDERS_TABLE};
+use crate::schemas::files::{
+    FSCursor, FSSortField, SearchCursor, SearchMode, SearchQuery, SortOrder,
+};
 
 pub const FILES_TABLE: &str = "files";
 pub const CHUNKS_TABLE: &str = "file_chunks";
 
 /// General repo for files and chunks since they share common logic
+///
+/// NOTE:
+///
+/// files are stored by their names inside folders, so their paths are built from the folders tree
 pub struct FilesRepository<'d> {
     db: &'d PgPool,
 }
@@ -21,32 +31,37 @@ impl<'d> FilesRepository<'d> {
         Self { db }
     }
 
-    pub async fn create_file(&self, in_obj: InFile) -> PentaractResult<File> {
-        self._create_file(in_obj, false).await
+    /// Starts a transaction for several changes which have to be applied together
+    pub async fn begin(&self) -> PentaractResult<Transaction<'d, Postgres>> {
+        self.db.begin().await.map_err(|e| map_not_found(e, ""))
     }
 
-    pub async fn create_folder(&self, in_obj: InFile) -> PentaractResult<File> {
-        self._create_file(in_obj, true).await
+    pub async fn commit(transaction: Transaction<'_, Postgres>) -> PentaractResult<()> {
+        transaction.commit().await.map_err(|e| map_not_found(e, ""))
     }
 
-    async fn _create_file(&self, in_obj: InFile, is_uploaded: bool) -> PentaractResult<File> {
+    /// Creates all the missing parent folders as well
+    pub async fn create_file(&self, in_obj: InFile) -> PentaractResult<File> {
         let id = Uuid::new_v4();
+        let (parent, name) = Self::split_path(&in_obj.path);
 
-        sqlx::query(
-            format!(
-                "
-                INSERT INTO {FILES_TABLE} (id, path, size, storage_id, is_uploaded)
-                VALUES ($1, $2, $3, $4, $5);
+        sqlx::query_as(&format!(
             "
-            )
-            .as_str(),
-        )
+            INSERT INTO {FILES_TABLE} AS f
+                (id, folder_id, name, size, storage_id, is_uploaded, uploaded_by, mime_type, original_filename)
+            VALUES ($1, ensure_folder_path($4, $2), $3, $5, $4, false, $6, $7, $8)
+            RETURNING f.*, $2 || f.name AS path;
+            "
+        ))
         .bind(id)
-        .bind(&in_obj.path)
-        .bind(in_obj.size)
+        .bind(parent)
+        .bind(name)
         .bind(in_obj.storage_id)
-        .bind(is_uploaded)
-        .execute(self.db)
+        .bind(in_obj.size)
+        .bind(in_obj.uploaded_by)
+        .bind(in_obj.mime_type)
+        .bind(in_obj.original_filename)
+        .fetch_one(self.db)
         .await
         .map_err(|e| match e {
             sqlx::Error::Database(dbe) if dbe.is_foreign_key_violation() => {
@@ -59,124 +74,28 @@ impl<'d> FilesRepository<'d> {
                 tracing::error!("{e}");
                 PentaractError::Unknown
             }
-        })?;
-
-        let storage = File::new(id, in_obj.path, in_obj.size, in_obj.storage_id, false);
-        Ok(storage)
+        })
     }
 
-    /// Creates a file even if the given path already exists
-    pub async fn create_file_anyway(&self, in_obj: InFile) -> PentaractResult<File> {
-        let id = Uuid::new_v4();
-
-        // lol/kek/sdf.nj.dskf/sdkl.fdsklf/lol .kek.dsf
-        let (path_with_stem, suffix) = {
-            let mut splited_path: Vec<_> = in_obj.path.split("/").collect();
-            let last = splited_path.last_mut().unwrap();
-            let mut suffix = String::new();
-            (*last, suffix) = last
-                .split_once(".")
-                .map(|(stem, suffix)| (stem, format!(".{suffix}")))
-                .unwrap_or((last, "".to_owned()));
-            (splited_path.join("/"), suffix)
-        };
-
-        println!("{path_with_stem} {suffix}");
-
-        let chars_to_skip = path_with_stem.len() + 3; // if the name is `kek` then it's gonna be a len of `kek (` + 1
-        let skip_chars_from_back = chars_to_skip + suffix.len();
+    pub async fn create_chunks_batch(&self, chunks: Vec<FileChunk>) -> PentaractResult<()> {
+        // empty files don't have chunks
+        if chunks.is_empty() {
+            return Ok(());
+        }
 
-        // https://www.db-fiddle.com/f/i6XCvTSi5cpAVu5AAfiNqm/16
-        sqlx::query_as(
+        QueryBuilder::new(
             format!(
-                r#"
-                INSERT INTO files (path, storage_id, id, size, is_uploaded)
-                WITH f AS (
-                    SELECT path
-                    FROM {FILES_TABLE}
-                    WHERE storage_id = $3 AND path ~ ('^(' || regexp_quote($1) || regexp_quote($2) || '|' || regexp_quote($1) || ' \(\d+\)' || regexp_quote($2) || ')$')
-                    ORDER BY path DESC
-                )
-                SELECT
-                    CASE
-                        WHEN NOT EXISTS(
-                            SELECT path
-                            FROM f
-                            WHERE path = $1 || $2
-                        ) THEN $1 || $2
-                        ELSE
-                            CASE
-                                WHEN COUNT(f) > 1 THEN (
-                                    WITH cte AS (
-                                        SELECT *
-                                        FROM (
-                                            SELECT SUBSTRING(f.path, {chars_to_skip}, LENGTH(f.path) - {skip_chars_from_back})::numeric AS i
-                                            FROM f
-                                            WHERE f.path != $1 || $2
-                                        ) AS n
-                                        WHERE i > 0
-                                        ORDER BY i
-                                    )
-                                    SELECT $1 || ' (' || COALESCE(t.next_i, (
-                                        SELECT cte.i + 1
-                                        FROM cte
-                                        ORDER BY cte.i DESC
-                                        LIMIT 1
-                                    )) || ')' || $2
-                                    FROM cte
-                                    FULL OUTER JOIN (
-                                        SELECT prev_i + 1 AS next_i
-                                        FROM (
-                                            SELECT LAG(i, 1, 0) OVER() AS prev_i, i
-                                            FROM cte
-                                        ) t
-                                        WHERE prev_i != t.i - 1
-                                        LIMIT 1
-                                    ) t ON cte.i = t.next_i
-                                    LIMIT 1
-                                )
-                                WHEN COUNT(f) = 1 THEN $1 || ' (1)' || $2
-                                ELSE $1 || $2
-                            END
-                    END,
-                    $3,
-                    $4,
-                    $5,
-                    false
-                FROM f
-                RETURNING *;
-            "#
+                "INSERT INTO {CHUNKS_TABLE} (id, file_id, telegram_file_id, position, chat_id, version_id)"
             )
             .as_str(),
         )
-        .bind(&path_with_stem)
-        .bind(&suffix)
-        .bind(in_obj.storage_id)
-        .bind(id)
-        .bind(in_obj.size)
-        .fetch_one(self.db)
-        .await
-        .map_err(|e| match e {
-            sqlx::Error::Database(dbe) if dbe.is_foreign_key_violation() => {
-                PentaractError::DoesNotExist("such storage".to_string())
-            }
-            _ => {
-                tracing::error!("{e}");
-                PentaractError::Unknown
-            }
-        })
-    }
-
-    pub async fn create_chunks_batch(&self, chunks: Vec<FileChunk>) -> PentaractResult<()> {
-        QueryBuilder::new(
-            format!("INSERT INTO {CHUNKS_TABLE} (id, file_id, telegram_file_id, position)")
-                .as_str(),
-        )
         .push_values(chunks, |mut q, chunk| {
             q.push_bind(chunk.id)
                 .push_bind(chunk.file_id)
                 .push_bind(chunk.telegram_file_id)
-                .push_bind(chunk.position);
+                .push_bind(chunk.position)
+                .push_bind(chunk.chat_id)
+                .push_bind(chunk.version_id);
         })
         .build()
         .execute(self.db)
@@ -186,6 +105,8 @@ impl<'d> FilesRepository<'d> {
         Ok(())
     }
 
+    /// Lists a page of the folder content going after `cursor`
+    ///
     /// NOTE:
     ///
     /// `prefix` must be without leading and trailing slashes
@@ -193,147 +114,1096 @@ impl<'d> FilesRepository<'d> {
         &self,
         storage_id: Uuid,
         prefix: &str,
+        sort_by: FSSortField,
+        order: SortOrder,
+        cursor: Option<&FSCursor>,
+        limit: i64,
     ) -> PentaractResult<Vec<FSElement>> {
-        let query = {
-            let adding_to_position = !prefix.is_empty() as usize + 1;
-            let split_position = prefix.matches("/").count() + adding_to_position;
-            let split_part = format!("SPLIT_PART(path, '/', {split_position})");
-            let path_filter = if prefix.is_empty() {
-                ""
-            } else {
-                "AND path LIKE $1 || '%'"
-            };
+        let prefix = if prefix.is_empty() {
+            prefix.to_string()
+        } else {
+            format!("{prefix}/")
+        };
+        let folder_id = self.get_folder_id(&prefix, storage_id).await?;
 
+        let (key, key_type) = match sort_by {
+            FSSortField::Name => ("name", "TEXT"),
+            FSSortField::Size => ("size", "BIGINT"),
+            FSSortField::CreatedAt => ("created_at", "TIMESTAMPTZ"),
+            FSSortField::UpdatedAt => ("updated_at", "TIMESTAMPTZ"),
+            FSSortField::Type => ("COALESCE(mime_type, '')", "TEXT"),
+        };
+        let (direction, operator) = match order {
+            SortOrder::Asc => ("ASC", ">"),
+            SortOrder::Desc => ("DESC", "<"),
+        };
+        // folders go first in any order
+        let after_cursor = if cursor.is_some() {
             format!(
-                "
+                "is_file > $3 OR (is_file = $3 AND ({key}, name) {operator} ($4::{key_type}, $5))"
+            )
+        } else {
+            "true".to_owned()
+        };
+
+        // folders info is aggregated from all the files of their subtrees
+        let query = format!(
+            "
+            WITH RECURSIVE subtree AS (
+                SELECT id AS root_id, id
+                FROM {FOLDERS_TABLE}
+                WHERE parent_id = $1 AND trash_id IS NULL
+                UNION ALL
+                SELECT s.root_id, d.id
+                FROM {FOLDERS_TABLE} d
+                JOIN subtree s ON d.parent_id = s.id
+                WHERE d.trash_id IS NULL
+            )
+            SELECT *
+            FROM (
+                SELECT
+                    d.name,
+                    false AS is_file,
+                    COALESCE(SUM(f.size), 0)::BigInt AS size,
+                    d.created_at,
+                    GREATEST(d.created_at, MAX(f.updated_at)) AS updated_at,
+                    NULL::UUID AS uploaded_by,
+                    NULL::VARCHAR AS mime_type,
+                    NULL::VARCHAR AS original_filename,
+                    d.tags,
+                    d.metadata
+                FROM {FOLDERS_TABLE} d
+                JOIN subtree s ON s.root_id = d.id
+                LEFT JOIN {FILES_TABLE} f ON f.folder_id = s.id AND f.is_uploaded AND f.trash_id IS NULL
+                GROUP BY d.id
+                UNION ALL
                 SELECT
-                    DISTINCT {split_part} AS name,
-                    $1 || {split_part} = path AS is_file,
-                    CASE
-                        WHEN $1 || {split_part} = path THEN size
-                        ELSE (SELECT SUM(size) FROM {FILES_TABLE} WHERE path LIKE $1 || {split_part} || '/' || '%')::BigInt
-                    END AS size
+                    name,
+                    true,
+                    size,
+                    created_at,
+                    updated_at,
+                    uploaded_by,
+                    mime_type,
+                    original_filename,
+                    tags,
+                    metadata
                 FROM {FILES_TABLE}
-                WHERE storage_id = $2 {path_filter} AND is_uploaded AND {split_part} <> '';
+                WHERE folder_id = $1 AND is_uploaded AND trash_id IS NULL
+            ) AS layer
+            WHERE {after_cursor}
+            ORDER BY is_file, {key} {direction}, name {direction}
+            LIMIT $2;
             "
-            )
-        };
+        );
 
-        let prefix = if prefix.is_empty() {
-            prefix.to_string()
-        } else {
-            format!("{prefix}/")
-        };
+        let mut query = sqlx::query_as::<_, DBFSElement>(&query)
+            .bind(folder_id)
+            .bind(limit);
+        if let Some(cursor) = cursor {
+            query = query
+                .bind(cursor.is_file)
+                .bind(&cursor.key)
+                .bind(&cursor.name);
+        }
 
-        let fs_layer = sqlx::query_as::<_, DBFSElement>(&query)
-            .bind(&prefix)
-            .bind(storage_id)
-            .fetch_all(self.db)
-            .await
-            .map_err(|e| {
+        let fs_layer = query.fetch_all(self.db).await.map_err(|e| match e {
+            // the cursor key does not match the sort field
+            sqlx::Error::Database(dbe) if dbe.code().is_some_and(|code| code.starts_with("22")) => {
+                PentaractError::InvalidCursor
+            }
+            _ => {
                 tracing::error!("{e}");
                 PentaractError::Unknown
-            })?;
+            }
+        })?;
         let fs_layer = fs_layer
             .into_iter()
-            .map(|el| {
-                let path = format!("{prefix}{}", el.name);
-                FSElement {
-                    path,
-                    name: el.name,
-                    is_file: el.is_file,
-                    size: el.size,
-                }
-            })
+            .map(|el| FSElement::new(format!("{prefix}{}", el.name), el))
             .collect();
 
         Ok(fs_layer)
     }
 
+    /// Counts folders and files of the folder
+    ///
+    /// NOTE:
+    ///
+    /// `prefix` must be without leading and trailing slashes
+    pub async fn count_dir(&self, storage_id: Uuid, prefix: &str) -> PentaractResult<i64> {
+        let prefix = if prefix.is_empty() {
+            prefix.to_string()
+        } else {
+            format!("{prefix}/")
+        };
+        let folder_id = self.get_folder_id(&prefix, storage_id).await?;
+
+        let (total,): (i64,) = sqlx::query_as(&format!(
+            "
+            SELECT
+                (SELECT COUNT(*) FROM {FOLDERS_TABLE} WHERE parent_id = $1 AND trash_id IS NULL)
+                + (
+                    SELECT COUNT(*)
+                    FROM {FILES_TABLE}
+                    WHERE folder_id = $1 AND is_uploaded AND trash_id IS NULL
+                );
+            "
+        ))
+        .bind(folder_id)
+        .fetch_one(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "folder"))?;
+
+        Ok(total)
+    }
+
+    /// Searches files and folders of the folder subtree.
+    ///
+    /// Results are ordered by rank and then by path,
+    /// folders paths are without trailing slashes just like in `list_dir`
     pub async fn search(
         &self,
-        search_path: &str,
-        path: &str,
         storage_id: Uuid,
+        path: &str,
+        query: &SearchQuery,
+        cursor: Option<&SearchCursor>,
+        limit: i64,
     ) -> PentaractResult<Vec<SearchFSElement>> {
-        sqlx::query_as(
-            format!(
-                "SELECT
-                    path,
-                    path LIKE '%/' AS is_file
-                FROM {FILES_TABLE}
-                WHERE storage_id = $1 AND path ILIKE $2 || '%' || $3 || '%'
+        let path = if path.is_empty() || path.ends_with('/') {
+            path.to_owned()
+        } else {
+            format!("{path}/")
+        };
+
+        let (pattern, is_matched, rank) = match query.mode {
+            SearchMode::Substring => (
+                Self::escape_like(&query.search_path),
+                "rel_path ILIKE '%' || $3 || '%'",
+                "
+                CASE
+                    WHEN name ILIKE $3 THEN 0
+                    WHEN name ILIKE $3 || '%' THEN 1
+                    WHEN name ILIKE '%' || $3 || '%' THEN 2
+                    ELSE 3
+                END
+                ",
+            ),
+            SearchMode::Glob if query.search_path.contains('/') => (
+                Self::glob_to_regex(&query.search_path),
+                "rel_path ~ $3",
+                "0",
+            ),
+            SearchMode::Glob => (Self::glob_to_regex(&query.search_path), "name ~ $3", "0"),
+            SearchMode::Exact => (query.search_path.clone(), "name = $3", "0"),
+        };
+        let entries = Self::search_entries(query);
+        let mime_type =
+            query
+                .mime_type
+                .as_deref()
+                .map(|mime_type| match mime_type.strip_suffix('*') {
+                    Some(mime_type) => format!("{}%", Self::escape_like(mime_type)),
+                    None => Self::escape_like(mime_type),
+                });
+
+        let elements = sqlx::query_as(&format!(
             "
+            WITH RECURSIVE {entries}
+            matched AS (
+                SELECT *, ({rank})::INT AS rank
+                FROM (
+                    SELECT *, SUBSTRING(path FROM LENGTH($2) + 1) AS rel_path
+                    FROM entries
+                ) AS e
+                WHERE {is_matched}
+                    AND LEFT(path, LENGTH($2)) = $2
+                    AND path <> $2
+                    AND (is_file OR NOT $11)
+                    AND (
+                        CARDINALITY($4::TEXT[]) = 0
+                        OR LOWER(SUBSTRING(name FROM '\\.([^.]*)$')) = ANY($4)
+                    )
+                    AND ($5::TEXT IS NULL OR mime_type LIKE $5)
+                    AND ($6::BIGINT IS NULL OR size >= $6)
+                    AND ($7::BIGINT IS NULL OR size <= $7)
+                    AND ($8::TIMESTAMPTZ IS NULL OR updated_at >= $8)
+                    AND ($9::TIMESTAMPTZ IS NULL OR updated_at <= $9)
+                    AND ($10::UUID IS NULL OR uploaded_by = $10)
+                    AND tags @> $16
+                    AND metadata @> $17
             )
-            .as_str(),
-        )
+            SELECT *
+            FROM matched
+            WHERE $13::INT IS NULL OR (rank, path) > ($13, $14)
+            ORDER BY rank, path
+            LIMIT $12;
+            "
+        ))
         .bind(storage_id)
-        .bind(path)
-        .bind(search_path)
+        .bind(&path)
+        .bind(pattern)
+        .bind(query.extensions())
+        .bind(mime_type)
+        .bind(query.min_size)
+        .bind(query.max_size)
+        .bind(query.updated_after)
+        .bind(query.updated_before)
+        .bind(query.uploaded_by)
+        .bind(query.is_files_only())
+        .bind(limit)
+        .bind(cursor.map(|cursor| cursor.rank))
+        .bind(cursor.map(|cursor| &cursor.path))
+        .bind(Self::glob_to_regex(
+            query.search_path.rsplit('/').next().unwrap_or_default(),
+        ))
+        .bind(query.tags())
+        .bind(Json(query.metadata.clone().unwrap_or_default()))
         .fetch_all(self.db)
         .await
-        .map_err(|e| {
-            tracing::error!("{e}");
-            PentaractError::Unknown
-        })
+        .map_err(|e| match e {
+            // glob patterns may still produce invalid regular expressions, like `[z-a]`
+            sqlx::Error::Database(dbe) if dbe.code().as_deref() == Some("2201B") => {
+                PentaractError::InvalidSearchPattern
+            }
+            _ => {
+                tracing::error!("{e}");
+                PentaractError::Unknown
+            }
+        })?;
+
+        Ok(elements)
     }
 
-    pub async fn get_file_by_path(&self, path: &str, storage_id: Uuid) -> PentaractResult<File> {
-        sqlx::query_as(
-            format!("SELECT * FROM {FILES_TABLE} WHERE storage_id = $1 AND path = $2").as_str(),
+    /// Builds `entries` CTE with all the files and folders the search may find.
+    ///
+    /// Names patterns are looked up with trigram indexes, so paths are built
+    /// for found elements only. Other searches have to walk the whole folder subtree
+    fn search_entries(query: &SearchQuery) -> String {
+        let search_path = &query.search_path;
+        let (is_name_matched, with_subfolders) = match query.mode {
+            SearchMode::Substring if search_path.is_empty() || search_path.contains('/') => {
+                let subtree = FoldersRepository::subtree_cte("folder_id_by_path($1, $2)", "$2");
+                return format!(
+                    "
+                    {subtree},
+                    entries AS (
+                        SELECT
+                            RTRIM(s.path, '/') AS path,
+                            d.name,
+                            false AS is_file,
+                            0::BigInt AS size,
+                            d.created_at,
+                            d.created_at AS updated_at,
+                            NULL::UUID AS uploaded_by,
+                            NULL::VARCHAR AS mime_type,
+                            NULL::VARCHAR AS original_filename,
+                            d.tags,
+                            d.metadata
+                        FROM subtree s
+                        JOIN {FOLDERS_TABLE} d ON d.id = s.id
+                        UNION ALL
+                        SELECT
+                            s.path || f.name,
+                            f.name,
+                            true,
+                            f.size,
+                            f.created_at,
+                            f.updated_at,
+                            f.uploaded_by,
+                            f.mime_type,
+                            f.original_filename,
+                            f.tags,
+                            f.metadata
+                        FROM {FILES_TABLE} f
+                        JOIN subtree s ON s.id = f.folder_id
+                        WHERE f.is_uploaded AND f.trash_id IS NULL
+                    ),
+                    "
+                );
+            }
+            // elements of matched folders are found by their paths
+            SearchMode::Substring => ("ILIKE '%' || $3 || '%'", true),
+            // names are matched by the last part of glob patterns with slashes
+            SearchMode::Glob if search_path.contains('/') => ("~ $15", false),
+            SearchMode::Glob => ("~ $3", false),
+            SearchMode::Exact => ("= $3", false),
+        };
+        let (found_folders, in_found_folders) = if with_subfolders {
+            (
+                format!(
+                    "
+                    SELECT id FROM named_folders
+                    UNION
+                    SELECT d.id
+                    FROM {FOLDERS_TABLE} d
+                    JOIN found_folders s ON d.parent_id = s.id
+                    WHERE d.trash_id IS NULL
+                    "
+                ),
+                format!(
+                    "
+                    UNION
+                    SELECT *
+                    FROM {FILES_TABLE}
+                    WHERE folder_id = ANY(ARRAY(SELECT id FROM found_folders))
+                        AND trash_id IS NULL
+                    "
+                ),
+            )
+        } else {
+            ("SELECT id FROM named_folders".to_owned(), String::new())
+        };
+
+        format!(
+            "
+            named_folders AS (
+                SELECT id
+                FROM {FOLDERS_TABLE} d
+                WHERE d.storage_id = $1
+                    AND d.parent_id IS NOT NULL
+                    AND d.trash_id IS NULL
+                    AND d.name {is_name_matched}
+            ),
+            found_folders AS ({found_folders}),
+            found_files AS (
+                SELECT *
+                FROM {FILES_TABLE}
+                WHERE storage_id = $1 AND trash_id IS NULL AND name {is_name_matched}
+                {in_found_folders}
+            ),
+            -- every folder path is built once
+            paths AS (
+                SELECT id, folder_path(id) AS path
+                FROM (
+                    SELECT id FROM found_folders
+                    UNION
+                    SELECT folder_id FROM found_files
+                ) AS ids
+            ),
+            entries AS (
+                SELECT
+                    RTRIM(p.path, '/') AS path,
+                    d.name,
+                    false AS is_file,
+                    0::BigInt AS size,
+                    d.created_at,
+                    d.created_at AS updated_at,
+                    NULL::UUID AS uploaded_by,
+                    NULL::VARCHAR AS mime_type,
+                    NULL::VARCHAR AS original_filename,
+                    d.tags,
+                    d.metadata
+                FROM {FOLDERS_TABLE} d
+                JOIN found_folders s ON s.id = d.id
+                JOIN paths p ON p.id = d.id
+                UNION ALL
+                SELECT
+                    p.path || f.name,
+                    f.name,
+                    true,
+                    f.size,
+                    f.created_at,
+                    f.updated_at,
+                    f.uploaded_by,
+                    f.mime_type,
+                    f.original_filename,
+                    f.tags,
+                    f.metadata
+                FROM found_files f
+                JOIN paths p ON p.id = f.folder_id
+                WHERE f.is_uploaded
+            ),
+            "
         )
+    }
+
+    pub async fn get_file_by_path(&self, path: &str, storage_id: Uuid) -> PentaractResult<File> {
+        let (parent, name) = Self::split_path(path);
+
+        sqlx::query_as(&format!(
+            "
+            SELECT f.*, $2 || f.name AS path
+            FROM {FILES_TABLE} f
+            WHERE f.folder_id = folder_id_by_path($1, $2) AND f.name = $3 AND f.trash_id IS NULL;
+            "
+        ))
         .bind(storage_id)
-        .bind(path)
+        .bind(parent)
+        .bind(name)
         .fetch_one(self.db)
         .await
         .map_err(|e| map_not_found(e, "file"))
     }
 
-    pub async fn list_chunks_of_file(&self, file_id: Uuid) -> PentaractResult<Vec<FileChunk>> {
-        sqlx::query_as(format!("SELECT * FROM {CHUNKS_TABLE} WHERE file_id = $1").as_str())
-            .bind(file_id)
-            .fetch_all(self.db)
-            .await
-            .map_err(|e| map_not_found(e, "file chunks"))
+    pub async fn get_file_by_id(&self, id: Uuid) -> PentaractResult<File> {
+        sqlx::query_as(&format!(
+            "
+            SELECT f.*, folder_path(f.folder_id) || f.name AS path
+            FROM {FILES_TABLE} f
+            WHERE f.id = $1;
+            "
+        ))
+        .bind(id)
+        .fetch_one(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "file"))
     }
 
-    pub async fn set_as_uploaded(&self, file_id: Uuid) -> PentaractResult<()> {
-        sqlx::query(format!("UPDATE {FILES_TABLE} SET is_uploaded = true WHERE id = $1").as_str())
-            .bind(file_id)
-            .execute(self.db)
+    /// `path` must end with a slash or be empty for the root folder
+    pub async fn get_folder_id(&self, path: &str, storage_id: Uuid) -> PentaractResult<Uuid> {
+        let (folder_id,): (Option<Uuid>,) = sqlx::query_as("SELECT folder_id_by_path($1, $2)")
+            .bind(storage_id)
+            .bind(path)
+            .fetch_one(self.db)
             .await
-            .map_err(|_| PentaractError::Unknown)
-            .map(|_| ())
+            .map_err(|e| map_not_found(e, "folder"))?;
+
+        folder_id.ok_or(PentaractError::DoesNotExist("folder".to_owned()))
+    }
+
+    /// Folders info is aggregated from all the files of their subtrees
+    pub async fn get_folder_stat(
+        &self,
+        path: &str,
+        storage_id: Uuid,
+    ) -> PentaractResult<FSElement> {
+        let folder_id = self.get_folder_id(path, storage_id).await?;
+        let (_, name) = Self::split_path(path);
+        let subtree = FoldersRepository::subtree_cte("$1", "''");
+
+        sqlx::query_as(&format!(
+            "
+            WITH RECURSIVE {subtree}
+            SELECT
+                $2 AS name,
+                false AS is_file,
+                COALESCE(SUM(f.size), 0)::BigInt AS size,
+                d.created_at,
+                GREATEST(d.created_at, MAX(f.updated_at)) AS updated_at,
+                NULL::UUID AS uploaded_by,
+                NULL::VARCHAR AS mime_type,
+                NULL::VARCHAR AS original_filename,
+                d.tags,
+                d.metadata
+            FROM {FOLDERS_TABLE} d
+            CROSS JOIN subtree s
+            LEFT JOIN {FILES_TABLE} f ON f.folder_id = s.id AND f.is_uploaded AND f.trash_id IS NULL
+            WHERE d.id = $1
+            GROUP BY d.id;
+            "
+        ))
+        .bind(folder_id)
+        .bind(name)
+        .fetch_one(self.db)
+        .await
+        .map(|el: DBFSElement| FSElement::new(path.to_owned(), el))
+        .map_err(|e| map_not_found(e, "folder"))
+    }
+
+    /// Lists chunks of the current file content if `version_id` is `None`
+    pub async fn list_chunks_of_file(
+        &self,
+        file_id: Uuid,
+        version_id: Option<Uuid>,
+    ) -> PentaractResult<Vec<FileChunk>> {
+        sqlx::query_as(&format!(
+            "SELECT * FROM {CHUNKS_TABLE} WHERE file_id = $1 AND version_id IS NOT DISTINCT FROM $2"
+        ))
+        .bind(file_id)
+        .bind(version_id)
+        .fetch_all(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "file chunks"))
+    }
+
+    pub async fn set_as_uploaded(&self, file_id: Uuid, content_hash: &str) -> PentaractResult<()> {
+        sqlx::query(
+            format!("UPDATE {FILES_TABLE} SET is_uploaded = true, content_hash = $2 WHERE id = $1")
+                .as_str(),
+        )
+        .bind(file_id)
+        .bind(content_hash)
+        .execute(self.db)
+        .await
+        .map_err(|_| PentaractError::Unknown)
+        .map(|_| ())
     }
 
+    /// Moves a file or a whole folder (if `old_path` ends with a slash) within a storage.
+    ///
+    /// Files and folders at `replaced_paths` are deleted in the same transaction beforehand
     pub async fn update_path(
         &self,
         old_path: &str,
         new_path: &str,
         storage_id: Uuid,
+        replaced_paths: &[String],
     ) -> PentaractResult<()> {
-        let chars_skip = old_path.len();
+        let mut transaction = self.begin().await?;
 
-        sqlx::query(
+        Self::move_path(
+            &mut transaction,
+            old_path,
+            new_path,
+            storage_id,
+            replaced_paths,
+        )
+        .await?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| map_not_found(e, ""))?;
+
+        Ok(())
+    }
+
+    /// Same as `update_path`, but within the given transaction
+    pub async fn move_path(
+        transaction: &mut Transaction<'_, Postgres>,
+        old_path: &str,
+        new_path: &str,
+        storage_id: Uuid,
+        replaced_paths: &[String],
+    ) -> PentaractResult<()> {
+        Self::delete_paths(transaction, replaced_paths, storage_id).await?;
+
+        let (old_parent, old_name) = Self::split_path(old_path);
+        let (new_parent, new_name) = Self::split_path(new_path);
+
+        // the content of a folder goes along with it
+        let query = if old_path.ends_with('/') {
+            format!(
+                "
+                UPDATE {FOLDERS_TABLE}
+                SET parent_id = ensure_folder_path($1, $4), name = $5
+                WHERE id = folder_id_by_path($1, $2 || $3 || '/') AND parent_id IS NOT NULL;
+                "
+            )
+        } else {
             format!(
                 "
                 UPDATE {FILES_TABLE}
-                SET path = $1 || SUBSTRING(path, {chars_skip})
-                WHERE storage_id = $2 AND path LIKE $3 || '%'
-            "
+                SET folder_id = ensure_folder_path($1, $4), name = $5
+                WHERE folder_id = folder_id_by_path($1, $2) AND name = $3 AND trash_id IS NULL;
+                "
             )
-            .as_str(),
+        };
+
+        let result = sqlx::query(&query)
+            .bind(storage_id)
+            .bind(old_parent)
+            .bind(old_name)
+            .bind(new_parent)
+            .bind(new_name)
+            .execute(&mut **transaction)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::Database(dbe) if dbe.is_unique_violation() => {
+                    PentaractError::AlreadyExists("File with such name".to_string())
+                }
+                _ => {
+                    tracing::error!("{e}");
+                    PentaractError::Unknown
+                }
+            })?;
+
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::DoesNotExist(format!("\"{old_path}\"")));
+        }
+
+        Ok(())
+    }
+
+    /// Replaces tags and metadata of a file or a folder (if `path` ends with a slash),
+    /// `None` attributes are left as is
+    pub async fn update_attributes(
+        &self,
+        path: &str,
+        storage_id: Uuid,
+        tags: Option<Vec<String>>,
+        metadata: Option<HashMap<String, String>>,
+    ) -> PentaractResult<()> {
+        let mut transaction = self.begin().await?;
+
+        Self::set_attributes(&mut transaction, path, storage_id, tags, metadata).await?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| map_not_found(e, ""))?;
+
+        Ok(())
+    }
+
+    /// Same as `update_attributes`, but within the given transaction
+    pub async fn set_attributes(
+        transaction: &mut Transaction<'_, Postgres>,
+        path: &str,
+        storage_id: Uuid,
+        tags: Option<Vec<String>>,
+        metadata: Option<HashMap<String, String>>,
+    ) -> PentaractResult<()> {
+        let (parent, name) = Self::split_path(path);
+        let query = if path.ends_with('/') {
+            format!(
+                "
+                UPDATE {FOLDERS_TABLE}
+                SET tags = COALESCE($4, tags), metadata = COALESCE($5, metadata)
+                WHERE id = folder_id_by_path($1, $2 || $3 || '/') AND parent_id IS NOT NULL;
+                "
+            )
+        } else {
+            format!(
+                "
+                UPDATE {FILES_TABLE}
+                SET tags = COALESCE($4, tags), metadata = COALESCE($5, metadata)
+                WHERE folder_id = folder_id_by_path($1, $2)
+                    AND name = $3
+                    AND is_uploaded
+                    AND trash_id IS NULL;
+                "
+            )
+        };
+
+        let result = sqlx::query(&query)
+            .bind(storage_id)
+            .bind(parent)
+            .bind(name)
+            .bind(tags)
+            .bind(metadata.map(Json))
+            .execute(&mut **transaction)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e}");
+                PentaractError::Unknown
+            })?;
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::DoesNotExist(format!("\"{path}\"")));
+        }
+
+        Ok(())
+    }
+
+    /// Saves copies of files with their chunks reusing the same telegram files.
+    ///
+    /// `folders` are created as well, so empty ones are copied too.
+    /// Chunks are not copied if `with_chunks` is false, so copies have to be uploaded later.
+    /// Files and folders at `replaced_paths` are deleted in the same transaction beforehand.
+    ///
+    /// Folders are given as pairs of source folders ids and copies paths,
+    /// and copies get tags and metadata of their sources
+    pub async fn create_copies(
+        &self,
+        copies: Vec<(Uuid, File)>,
+        folders: &[(Uuid, String)],
+        storage_id: Uuid,
+        replaced_paths: &[String],
+        with_chunks: bool,
+    ) -> PentaractResult<()> {
+        if copies.is_empty() && folders.is_empty() {
+            return Ok(());
+        }
+
+        let mut transaction = self.begin().await?;
+
+        Self::save_copies(
+            &mut transaction,
+            copies,
+            folders,
+            storage_id,
+            replaced_paths,
+            with_chunks,
         )
-        .bind(new_path)
-        .bind(old_path)
+        .await?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| map_not_found(e, ""))?;
+
+        Ok(())
+    }
+
+    /// Same as `create_copies`, but within the given transaction
+    pub async fn save_copies(
+        transaction: &mut Transaction<'_, Postgres>,
+        copies: Vec<(Uuid, File)>,
+        folders: &[(Uuid, String)],
+        storage_id: Uuid,
+        replaced_paths: &[String],
+        with_chunks: bool,
+    ) -> PentaractResult<()> {
+        Self::delete_paths(transaction, replaced_paths, storage_id).await?;
+
+        // saving folders
+        let (folders_sources, folders): (Vec<_>, Vec<_>) = folders.iter().cloned().unzip();
+        sqlx::query("SELECT ensure_folder_path($1, p) FROM UNNEST($2::text[]) AS p")
+            .bind(storage_id)
+            .bind(&folders)
+            .execute(&mut **transaction)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e}");
+                PentaractError::Unknown
+            })?;
+        sqlx::query(&format!(
+            "
+            WITH copies AS MATERIALIZED (
+                SELECT c.source_id, folder_id_by_path($1, c.path) AS id
+                FROM UNNEST($2::uuid[], $3::text[]) AS c(source_id, path)
+            )
+            UPDATE {FOLDERS_TABLE} d
+            SET tags = s.tags, metadata = s.metadata
+            FROM copies c
+            JOIN {FOLDERS_TABLE} s ON s.id = c.source_id
+            WHERE d.id = c.id;
+            "
+        ))
         .bind(storage_id)
-        .execute(self.db)
+        .bind(folders_sources)
+        .bind(folders)
+        .execute(&mut **transaction)
         .await
-        .map_err(|_| PentaractError::Unknown)
-        .map(|_| ())
+        .map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })?;
+
+        let mut source_ids = Vec::with_capacity(copies.len());
+        let mut copy_ids = Vec::with_capacity(copies.len());
+        let mut parents = Vec::with_capacity(copies.len());
+        let mut names = Vec::with_capacity(copies.len());
+        let mut sizes = Vec::with_capacity(copies.len());
+        let mut are_uploaded = Vec::with_capacity(copies.len());
+        let mut uploaders = Vec::with_capacity(copies.len());
+        let mut mime_types = Vec::with_capacity(copies.len());
+        let mut original_filenames = Vec::with_capacity(copies.len());
+        for (source_id, copy) in copies {
+            let (parent, name) = Self::split_path(&copy.path);
+            parents.push(parent.to_owned());
+            names.push(name.to_owned());
+            source_ids.push(source_id);
+            copy_ids.push(copy.id);
+            sizes.push(copy.size);
+            are_uploaded.push(copy.is_uploaded);
+            uploaders.push(copy.uploaded_by);
+            mime_types.push(copy.mime_type);
+            original_filenames.push(copy.original_filename);
+        }
+
+        // saving files
+        sqlx::query(&format!(
+            "
+            INSERT INTO {FILES_TABLE} (
+                id,
+                folder_id,
+                name,
+                size,
+                storage_id,
+                is_uploaded,
+                uploaded_by,
+                mime_type,
+                original_filename,
+                tags,
+                metadata,
+                content_hash
+            )
+            SELECT
+                c.id,
+                ensure_folder_path($1, c.parent),
+                c.name,
+                c.size,
+                $1,
+                c.is_uploaded,
+                c.uploaded_by,
+                c.mime_type,
+                c.original_filename,
+                s.tags,
+                s.metadata,
+                s.content_hash
+            FROM UNNEST(
+                $2::uuid[],
+                $3::text[],
+                $4::text[],
+                $5::bigint[],
+                $6::bool[],
+                $7::uuid[],
+                $8::text[],
+                $9::text[],
+                $10::uuid[]
+            ) AS c(id, parent, name, size, is_uploaded, uploaded_by, mime_type, original_filename, source_id)
+            JOIN {FILES_TABLE} s ON s.id = c.source_id;
+            "
+        ))
+        .bind(storage_id)
+        .bind(&copy_ids)
+        .bind(parents)
+        .bind(names)
+        .bind(sizes)
+        .bind(are_uploaded)
+        .bind(uploaders)
+        .bind(mime_types)
+        .bind(original_filenames)
+        .bind(&source_ids)
+        .execute(&mut **transaction)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(dbe) if dbe.is_foreign_key_violation() => {
+                PentaractError::DoesNotExist("such storage".to_string())
+            }
+            sqlx::Error::Database(dbe) if dbe.is_unique_violation() => {
+                PentaractError::AlreadyExists("File with such name".to_string())
+            }
+            _ => {
+                tracing::error!("{e}");
+                PentaractError::Unknown
+            }
+        })?;
+
+        // saving chunks pointing to the same telegram files
+        if with_chunks {
+            sqlx::query(&format!(
+                "
+                INSERT INTO {CHUNKS_TABLE} (id, file_id, telegram_file_id, position, chat_id)
+                SELECT gen_random_uuid(), m.copy_id, c.telegram_file_id, c.position, c.chat_id
+                FROM {CHUNKS_TABLE} c
+                JOIN UNNEST($1::uuid[], $2::uuid[]) AS m(source_id, copy_id) ON c.file_id = m.source_id
+                WHERE c.version_id IS NULL;
+                "
+            ))
+            .bind(source_ids)
+            .bind(copy_ids)
+            .execute(&mut **transaction)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e}");
+                PentaractError::Unknown
+            })?;
+        }
+
+        Ok(())
     }
 
-    pub async fn delete_with_folders(&self, id: Uuid) -> PentaractResult<()> {
+    /// Lists files of the storages having the same content as some other ones.
+    ///
+    /// Files of the same group go one after another, and groups which take
+    /// the most space go first
+    pub async fn list_duplicates(
+        &self,
+        storage_ids: &[Uuid],
+        min_size: i64,
+        groups_limit: i64,
+    ) -> PentaractResult<Vec<DuplicateFile>> {
+        let stored_as = Self::stored_as_query();
+
+        sqlx::query_as(&format!(
+            "
+            WITH hashed AS MATERIALIZED (
+                SELECT
+                    f.storage_id,
+                    f.folder_id,
+                    f.name,
+                    f.size,
+                    f.content_hash,
+                    ({stored_as}) AS stored_as
+                FROM {FILES_TABLE} f
+                WHERE
+                    f.storage_id = ANY($1)
+                    AND f.content_hash IS NOT NULL
+                    AND f.size >= $2
+                    AND f.is_uploaded
+                    AND f.trash_id IS NULL
+            ), groups AS (
+                SELECT content_hash, size, COUNT(DISTINCT stored_as) AS stored_amount
+                FROM hashed
+                GROUP BY content_hash, size
+                HAVING COUNT(*) > 1
+                ORDER BY size * (COUNT(DISTINCT stored_as) - 1) DESC, content_hash, size
+                LIMIT $3
+            )
+            SELECT
+                h.storage_id,
+                folder_path(h.folder_id) || h.name AS path,
+                h.size,
+                h.content_hash,
+                g.stored_amount
+            FROM hashed h
+            JOIN groups g USING (content_hash, size)
+            ORDER BY h.size * (g.stored_amount - 1) DESC, h.content_hash, h.size, h.storage_id, path;
+            "
+        ))
+        .bind(storage_ids)
+        .bind(min_size)
+        .bind(groups_limit)
+        .fetch_all(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "files"))
+    }
+
+    /// Makes files with the same content reuse chunks of the oldest one of them,
+    /// only files with `content_hash` are deduplicated if it's given.
+    ///
+    /// Returns the amount of changed files and the size of contents they don't store anymore
+    pub async fn dedupe(
+        &self,
+        storage_id: Uuid,
+        content_hash: Option<&str>,
+    ) -> PentaractResult<(i64, i64)> {
+        let stored_as = Self::stored_as_query();
+        let filter = "
+            f.storage_id = $1
+            AND f.content_hash IS NOT NULL
+            AND ($2::text IS NULL OR f.content_hash = $2)
+            AND f.size > 0
+            AND f.is_uploaded
+            AND f.trash_id IS NULL
+        ";
+
+        let mut transaction = self.begin().await?;
+
+        // locking the files, so their content is not changed meanwhile
+        sqlx::query(&format!(
+            "SELECT f.id FROM {FILES_TABLE} f WHERE {filter} FOR UPDATE"
+        ))
+        .bind(storage_id)
+        .bind(content_hash)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| map_not_found(e, "files"))?;
+
+        let duplicates: Vec<(Uuid, Uuid, i64, String)> = sqlx::query_as(&format!(
+            "
+            WITH hashed AS MATERIALIZED (
+                SELECT
+                    f.id,
+                    f.size,
+                    FIRST_VALUE(f.id) OVER (
+                        PARTITION BY f.content_hash, f.size ORDER BY f.created_at, f.id
+                    ) AS source_id,
+                    ({stored_as}) AS stored_as
+                FROM {FILES_TABLE} f
+                WHERE {filter}
+            )
+            SELECT d.id, d.source_id, d.size, d.stored_as
+            FROM hashed d
+            JOIN hashed s ON s.id = d.source_id
+            WHERE
+                d.id <> d.source_id
+                AND d.stored_as IS NOT NULL
+                AND s.stored_as IS NOT NULL
+                AND d.stored_as <> s.stored_as;
+            "
+        ))
+        .bind(storage_id)
+        .bind(content_hash)
+        .fetch_all(&mut *transaction)
+        .await
+        .map_err(|e| map_not_found(e, "files"))?;
+
+        if duplicates.is_empty() {
+            return Ok((0, 0));
+        }
+
+        // files can already share their content with each other
+        let mut reclaimed = HashMap::with_capacity(duplicates.len());
+        let (ids, source_ids): (Vec<_>, Vec<_>) = duplicates
+            .into_iter()
+            .map(|(id, source_id, size, stored_as)| {
+                reclaimed.insert(stored_as, size);
+                (id, source_id)
+            })
+            .unzip();
+
+        sqlx::query(&format!(
+            "DELETE FROM {CHUNKS_TABLE} WHERE file_id = ANY($1) AND version_id IS NULL"
+        ))
+        .bind(&ids)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| map_not_found(e, "file chunks"))?;
+
+        sqlx::query(&format!(
+            "
+            INSERT INTO {CHUNKS_TABLE} (id, file_id, telegram_file_id, position, chat_id)
+            SELECT gen_random_uuid(), m.id, c.telegram_file_id, c.position, c.chat_id
+            FROM {CHUNKS_TABLE} c
+            JOIN UNNEST($1::uuid[], $2::uuid[]) AS m(id, source_id) ON c.file_id = m.source_id
+            WHERE c.version_id IS NULL;
+            "
+        ))
+        .bind(&ids)
+        .bind(source_ids)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })?;
+
+        Self::commit(transaction).await?;
+
+        Ok((ids.len() as i64, reclaimed.values().sum()))
+    }
+
+    /// Lists a file or all the files of a folder (if `path` ends with a slash)
+    pub async fn list_by_path(&self, path: &str, storage_id: Uuid) -> PentaractResult<Vec<File>> {
+        if !path.is_empty() && !path.ends_with('/') {
+            return match self.get_file_by_path(path, storage_id).await {
+                Ok(file) => Ok(vec![file]),
+                Err(PentaractError::DoesNotExist(_)) => Ok(vec![]),
+                Err(e) => Err(e),
+            };
+        }
+
+        let subtree = FoldersRepository::subtree_cte("folder_id_by_path($1, $2)", "$2");
+
+        sqlx::query_as(&format!(
+            "
+            WITH RECURSIVE {subtree}
+            SELECT f.*, s.path || f.name AS path
+            FROM {FILES_TABLE} f
+            JOIN subtree s ON s.id = f.folder_id
+            WHERE f.trash_id IS NULL
+            ORDER BY path;
+            "
+        ))
+        .bind(storage_id)
+        .bind(path)
+        .fetch_all(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "file"))
+    }
+
+    /// Checks whether a file or a folder (if `path` ends with a slash) exists
+    pub async fn exists(&self, path: &str, storage_id: Uuid) -> PentaractResult<bool> {
+        let query = if path.ends_with('/') {
+            "SELECT folder_id_by_path($1, $2 || $3 || '/') IS NOT NULL".to_owned()
+        } else {
+            format!(
+                "
+                SELECT EXISTS (
+                    SELECT id
+                    FROM {FILES_TABLE}
+                    WHERE folder_id = folder_id_by_path($1, $2) AND name = $3 AND trash_id IS NULL
+                );
+                "
+            )
+        };
+        let (parent, name) = Self::split_path(path);
+
+        let exists: (_,) = sqlx::query_as(&query)
+            .bind(storage_id)
+            .bind(parent)
+            .bind(name)
+            .fetch_one(self.db)
+            .await
+            .map_err(|e| map_not_found(e, "file"))?;
+
+        Ok(exists.0)
+    }
+
+    pub async fn delete_by_id(&self, id: Uuid) -> PentaractResult<()> {
         sqlx::query(format!("DELETE FROM {FILES_TABLE} WHERE id = $1").as_str())
             .bind(id)
             .execute(self.db)
@@ -342,59 +1212,283 @@ impl<'d> FilesRepository<'d> {
             .map(|_| ())
     }
 
-    pub async fn delete(&self, path: &str, storage_id: Uuid) -> PentaractResult<()> {
-        let mut transaction = self.db.begin().await.map_err(|e| map_not_found(e, ""))?;
+    /// Deletes a file or a whole folder (if `path` ends with a slash)
+    /// Deletes the folder `path` and the folders inside it which have no files left,
+    /// folders keeping files or trashed items are kept with their parents
+    pub async fn delete_empty_folders(&self, path: &str, storage_id: Uuid) -> PentaractResult<()> {
+        sqlx::query(&format!(
+            "
+            WITH RECURSIVE subtree AS (
+                SELECT id, ARRAY[id] AS ancestors
+                FROM {FOLDERS_TABLE}
+                WHERE id = folder_id_by_path($1, $2)
+                UNION ALL
+                SELECT d.id, s.ancestors || d.id
+                FROM {FOLDERS_TABLE} d
+                JOIN subtree s ON d.parent_id = s.id
+            )
+            DELETE FROM {FOLDERS_TABLE}
+            WHERE
+                parent_id IS NOT NULL
+                AND id IN (SELECT id FROM subtree)
+                AND id NOT IN (
+                    SELECT UNNEST(s.ancestors)
+                    FROM subtree s
+                    JOIN {FOLDERS_TABLE} d ON d.id = s.id
+                    WHERE
+                        d.trash_id IS NOT NULL
+                        OR EXISTS (SELECT 1 FROM {FILES_TABLE} f WHERE f.folder_id = s.id)
+                );
+            "
+        ))
+        .bind(storage_id)
+        .bind(path)
+        .execute(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "folder"))
+        .map(|_| ())
+    }
+
+    /// Lists uploaded files whose paths start with `prefix` and go after `after`,
+    /// in the byte order of the paths as S3 clients expect.
+    ///
+    /// If `is_recursive` is not set, only the content of the folder `prefix` points into is listed,
+    /// subfolders are listed as prefixes
+    pub async fn list_objects(
+        &self,
+        storage_id: Uuid,
+        prefix: &str,
+        after: &str,
+        is_recursive: bool,
+        limit: i64,
+    ) -> PentaractResult<Vec<S3Object>> {
+        let (base, _) = Self::split_path(prefix);
+        let base = if prefix.ends_with('/') { prefix } else { base };
 
-        let where_path = if path.ends_with("/") {
-            // for folders
-            "LIKE $2 || '%'"
+        let query = if is_recursive {
+            let subtree = FoldersRepository::subtree_cte("folder_id_by_path($1, $2)", "$2");
+            format!(
+                "
+                WITH RECURSIVE {subtree}
+                SELECT *
+                FROM (
+                    SELECT
+                        s.path || f.name AS key,
+                        false AS is_prefix,
+                        f.size,
+                        f.updated_at,
+                        f.mime_type,
+                        f.content_hash
+                    FROM {FILES_TABLE} f
+                    JOIN subtree s ON s.id = f.folder_id
+                    WHERE f.is_uploaded AND f.trash_id IS NULL
+                ) o
+                WHERE starts_with(o.key, $3) AND o.key > $4::TEXT COLLATE \"C\"
+                ORDER BY o.key COLLATE \"C\"
+                LIMIT $5;
+                "
+            )
         } else {
-            // for files
-            "= $2"
+            format!(
+                "
+                WITH base AS (SELECT folder_id_by_path($1, $2) AS id)
+                SELECT *
+                FROM (
+                    SELECT
+                        $2 || f.name AS key,
+                        false AS is_prefix,
+                        f.size,
+                        f.updated_at,
+                        f.mime_type,
+                        f.content_hash
+                    FROM {FILES_TABLE} f
+                    JOIN base b ON b.id = f.folder_id
+                    WHERE f.is_uploaded AND f.trash_id IS NULL
+                    UNION ALL
+                    SELECT
+                        $2 || d.name || '/' AS key,
+                        true AS is_prefix,
+                        0::BigInt AS size,
+                        d.created_at AS updated_at,
+                        NULL::VARCHAR AS mime_type,
+                        NULL::VARCHAR AS content_hash
+                    FROM {FOLDERS_TABLE} d
+                    JOIN base b ON b.id = d.parent_id
+                    WHERE d.trash_id IS NULL
+                ) o
+                WHERE starts_with(o.key, $3) AND o.key > $4::TEXT COLLATE \"C\"
+                ORDER BY o.key COLLATE \"C\"
+                LIMIT $5;
+                "
+            )
         };
 
-        // deleting file
-        sqlx::query(&format!(
+        sqlx::query_as(&query)
+            .bind(storage_id)
+            .bind(base)
+            .bind(prefix)
+            .bind(after)
+            .bind(limit)
+            .fetch_all(self.db)
+            .await
+            .map_err(|e| map_not_found(e, "file"))
+    }
+
+    pub async fn get_object(&self, path: &str, storage_id: Uuid) -> PentaractResult<S3Object> {
+        let (parent, name) = Self::split_path(path);
+
+        sqlx::query_as(&format!(
             "
-            DELETE FROM {FILES_TABLE}
-            WHERE storage_id = $1 AND path {where_path};
+            SELECT
+                $2 || f.name AS key,
+                false AS is_prefix,
+                f.size,
+                f.updated_at,
+                f.mime_type,
+                f.content_hash
+            FROM {FILES_TABLE} f
+            WHERE f.folder_id = folder_id_by_path($1, $2)
+                AND f.name = $3
+                AND f.is_uploaded
+                AND f.trash_id IS NULL;
             "
         ))
         .bind(storage_id)
-        .bind(path)
-        .execute(&mut *transaction)
+        .bind(parent)
+        .bind(name)
+        .fetch_one(self.db)
         .await
-        .map_err(|e| map_not_found(e, "file"))?;
+        .map_err(|e| map_not_found(e, "file"))
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    ////    Helpers
+    /////////////////////////////////////////////////////////////////////
 
-        // creating a folder if it was the file in the folder
-        if let Some(parent) = Path::new(path).parent().map(|path| path.to_str().unwrap()) {
-            let new_id = Uuid::new_v4();
-            let parent = format!("{parent}/");
+    /// Splits a path into the path of its parent folder and its name:
+    ///
+    /// `a/b/c.txt` -> (`a/b/`, `c.txt`), `a/b/` -> (`a/`, `b`)
+    pub fn split_path(path: &str) -> (&str, &str) {
+        let path = path.strip_suffix('/').unwrap_or(path);
+        match path.rfind('/') {
+            Some(i) => (&path[..=i], &path[i + 1..]),
+            None => ("", path),
+        }
+    }
+
+    /// Escapes `LIKE` wildcards so the text is matched as is
+    fn escape_like(text: &str) -> String {
+        text.replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    }
+
+    /// Converts a glob pattern into an anchored POSIX regular expression.
+    ///
+    /// `*` and `?` don't match slashes while `**` does
+    fn glob_to_regex(glob: &str) -> String {
+        let mut regex = String::from("^");
+        let mut rest = glob;
 
+        while let Some(c) = rest.chars().next() {
+            rest = &rest[c.len_utf8()..];
+            match c {
+                // `**/` matches any amount of folders including none
+                '*' if rest.starts_with("*/") => {
+                    rest = &rest[2..];
+                    regex.push_str("(.*/)?");
+                }
+                '*' if rest.starts_with('*') => {
+                    rest = &rest[1..];
+                    regex.push_str(".*");
+                }
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                '[' => match rest.split_once(']') {
+                    Some((class, after)) if !class.is_empty() => {
+                        rest = after;
+                        let class = class.replace('\\', "\\\\");
+                        match class.strip_prefix('!') {
+                            Some(class) => regex.push_str(&format!("[^{class}]")),
+                            None => regex.push_str(&format!("[{class}]")),
+                        }
+                    }
+                    _ => regex.push_str("\\["),
+                },
+                c if "\\.+()|{}^$]".contains(c) => {
+                    regex.push('\\');
+                    regex.push(c);
+                }
+                c => regex.push(c),
+            }
+        }
+
+        regex.push('$');
+        regex
+    }
+
+    /// Selects the first chunk of the current content of a file `f`,
+    /// files sharing their content share their chunks telegram files too
+    fn stored_as_query() -> String {
+        format!(
+            "
+            SELECT c.telegram_file_id
+            FROM {CHUNKS_TABLE} c
+            WHERE c.file_id = f.id AND c.version_id IS NULL
+            ORDER BY c.position
+            LIMIT 1
+            "
+        )
+    }
+
+    /// Deletes files and whole folders (the paths ending with a slash)
+    pub async fn delete_paths(
+        transaction: &mut Transaction<'_, Postgres>,
+        paths: &[String],
+        storage_id: Uuid,
+    ) -> PentaractResult<()> {
+        let (folders, files): (Vec<_>, Vec<_>) =
+            paths.iter().cloned().partition(|p| p.ends_with('/'));
+
+        // the root folder is never deleted
+        if !folders.is_empty() {
             sqlx::query(&format!(
                 "
-                INSERT INTO {FILES_TABLE} (id, path, size, storage_id, is_uploaded)
-                SELECT $1, $2, 0, $3, true
+                DELETE FROM {FOLDERS_TABLE}
                 WHERE
-                    NOT EXISTS (
-                        SELECT id
-                        FROM {FILES_TABLE}
-                        WHERE storage_id = $3 AND path LIKE $2 || '%'
-                    );
-            "
+                    parent_id IS NOT NULL
+                    AND id IN (SELECT folder_id_by_path($1, p) FROM UNNEST($2::text[]) AS p);
+                "
             ))
-            .bind(new_id)
-            .bind(parent)
             .bind(storage_id)
-            .execute(&mut *transaction)
+            .bind(folders)
+            .execute(&mut **transaction)
             .await
-            .map_err(|e| map_not_found(e, "some entity"))?;
+            .map_err(|e| map_not_found(e, "folder"))?;
         }
 
-        transaction
-            .commit()
+        if !files.is_empty() {
+            let (parents, names): (Vec<_>, Vec<_>) =
+                files.iter().map(|path| Self::split_path(path)).unzip();
+
+            sqlx::query(&format!(
+                "
+                DELETE FROM {FILES_TABLE}
+                WHERE
+                    trash_id IS NULL
+                    AND (folder_id, name) IN (
+                        SELECT folder_id_by_path($1, m.parent), m.name
+                        FROM UNNEST($2::text[], $3::text[]) AS m(parent, name)
+                    );
+                "
+            ))
+            .bind(storage_id)
+            .bind(parents)
+            .bind(names)
+            .execute(&mut **transaction)
             .await
-            .map_err(|e| map_not_found(e, ""))?;
+            .map_err(|e| map_not_found(e, "file"))?;
+        }
 
         Ok(())
     }
@@ -0,0 +1,118 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::common::db::errors::map_not_found;
+use crate::errors::{PentaractError, PentaractResult};
+
+pub const FOLDERS_TABLE: &str = "folders";
+
+/// NOTE:
+///
+/// every storage has a root folder with no parent and an empty name,
+/// and folders paths end with a slash while the root folder path is empty
+pub struct FoldersRepository<'d> {
+    db: &'d PgPool,
+}
+
+impl<'d> FoldersRepository<'d> {
+    pub fn new(db: &'d PgPool) -> Self {
+        Self { db }
+    }
+
+    /// Creates all the missing parent folders as well
+    pub async fn create(&self, path: &str, storage_id: Uuid) -> PentaractResult<Uuid> {
+        let mut transaction = self.db.begin().await.map_err(|e| map_not_found(e, ""))?;
+
+        let (id,): (Option<Uuid>,) = sqlx::query_as("SELECT folder_id_by_path($1, $2)")
+            .bind(storage_id)
+            .bind(path)
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|e| map_not_found(e, "folder"))?;
+        if id.is_some() {
+            return Err(PentaractError::AlreadyExists("Folder".to_string()));
+        }
+
+        let (id,): (Uuid,) = sqlx::query_as("SELECT ensure_folder_path($1, $2)")
+            .bind(storage_id)
+            .bind(path)
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::Database(dbe) if dbe.is_foreign_key_violation() => {
+                    PentaractError::DoesNotExist("such storage".to_string())
+                }
+                _ => {
+                    tracing::error!("{e}");
+                    PentaractError::Unknown
+                }
+            })?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| map_not_found(e, ""))?;
+
+        Ok(id)
+    }
+
+    /// Lists ids and paths of the folder and all the folders inside it
+    pub async fn list_subtree(
+        &self,
+        path: &str,
+        storage_id: Uuid,
+    ) -> PentaractResult<Vec<(Uuid, String)>> {
+        let subtree = Self::subtree_cte("folder_id_by_path($1, $2)", "$2");
+
+        sqlx::query_as(&format!(
+            "WITH RECURSIVE {subtree} SELECT id, path FROM subtree ORDER BY path"
+        ))
+        .bind(storage_id)
+        .bind(path)
+        .fetch_all(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "folder"))
+    }
+
+    /// Storages are created along with their root folders, so it's when the storages are created
+    pub async fn list_roots_created_at(
+        &self,
+        storage_ids: &[Uuid],
+    ) -> PentaractResult<Vec<(Uuid, DateTime<Utc>)>> {
+        sqlx::query_as(&format!(
+            "
+            SELECT storage_id, created_at
+            FROM {FOLDERS_TABLE}
+            WHERE storage_id = ANY($1) AND parent_id IS NULL;
+            "
+        ))
+        .bind(storage_ids)
+        .fetch_all(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "folder"))
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    ////    Helpers
+    /////////////////////////////////////////////////////////////////////
+
+    /// Builds a recursive `subtree (id, path)` CTE of the folder with `id_expr` id
+    /// and all the not trashed folders inside it, `path_expr` is the folder path
+    pub fn subtree_cte(id_expr: &str, path_expr: &str) -> String {
+        format!(
+            "
+            subtree AS (
+                SELECT id, {path_expr}::TEXT AS path
+                FROM {FOLDERS_TABLE}
+                WHERE id = {id_expr}
+                UNION ALL
+                SELECT d.id, s.path || d.name || '/'
+                FROM {FOLDERS_TABLE} d
+                JOIN subtree s ON d.parent_id = s.id
+                WHERE d.trash_id IS NULL
+            )
+            "
+        )
+    }
+}
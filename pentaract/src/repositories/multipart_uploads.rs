@@ -0,0 +1,274 @@
+use sqlx::{PgPool, QueryBuilder};
+use uuid::Uuid;
+
+use crate::common::db::errors::map_not_found;
+use crate::errors::{PentaractError, PentaractResult};
+use crate::models::multipart_uploads::{MultipartPart, MultipartPartChunk, MultipartUpload};
+use crate::repositories::files::CHUNKS_TABLE;
+
+pub const UPLOADS_TABLE: &str = "multipart_uploads";
+pub const PARTS_TABLE: &str = "multipart_parts";
+pub const PART_CHUNKS_TABLE: &str = "multipart_part_chunks";
+
+pub struct MultipartUploadsRepository<'d> {
+    db: &'d PgPool,
+}
+
+impl<'d> MultipartUploadsRepository<'d> {
+    pub fn new(db: &'d PgPool) -> Self {
+        Self { db }
+    }
+
+    pub async fn create(
+        &self,
+        storage_id: Uuid,
+        path: &str,
+        created_by: Uuid,
+    ) -> PentaractResult<MultipartUpload> {
+        sqlx::query_as(&format!(
+            "
+            INSERT INTO {UPLOADS_TABLE} (id, storage_id, path, created_by)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, path;
+            "
+        ))
+        .bind(Uuid::new_v4())
+        .bind(storage_id)
+        .bind(path)
+        .bind(created_by)
+        .fetch_one(self.db)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(dbe) if dbe.is_foreign_key_violation() => {
+                PentaractError::DoesNotExist("such storage".to_string())
+            }
+            _ => {
+                tracing::error!("{e}");
+                PentaractError::Unknown
+            }
+        })
+    }
+
+    pub async fn get(&self, id: Uuid, storage_id: Uuid) -> PentaractResult<MultipartUpload> {
+        sqlx::query_as(&format!(
+            "SELECT id, path FROM {UPLOADS_TABLE} WHERE id = $1 AND storage_id = $2"
+        ))
+        .bind(id)
+        .bind(storage_id)
+        .fetch_one(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "multipart upload"))
+    }
+
+    /// Parts are hidden until their chunks are uploaded
+    pub async fn create_part(
+        &self,
+        upload_id: Uuid,
+        part_number: i32,
+        size: i64,
+        etag: &str,
+    ) -> PentaractResult<Uuid> {
+        let id = Uuid::new_v4();
+
+        sqlx::query(&format!(
+            "
+            INSERT INTO {PARTS_TABLE} (id, upload_id, part_number, size, etag, is_uploaded)
+            VALUES ($1, $2, $3, $4, $5, false);
+            "
+        ))
+        .bind(id)
+        .bind(upload_id)
+        .bind(part_number)
+        .bind(size)
+        .bind(etag)
+        .execute(self.db)
+        .await
+        .map_err(|e| match e {
+            // the upload is completed or aborted meanwhile
+            sqlx::Error::Database(dbe) if dbe.is_foreign_key_violation() => {
+                PentaractError::DoesNotExist("multipart upload".to_string())
+            }
+            _ => {
+                tracing::error!("{e}");
+                PentaractError::Unknown
+            }
+        })?;
+
+        Ok(id)
+    }
+
+    pub async fn create_part_chunks(&self, chunks: Vec<MultipartPartChunk>) -> PentaractResult<()> {
+        // empty parts don't have chunks
+        if chunks.is_empty() {
+            return Ok(());
+        }
+
+        QueryBuilder::new(
+            format!(
+                "INSERT INTO {PART_CHUNKS_TABLE} (id, part_id, telegram_file_id, position, chat_id)"
+            )
+            .as_str(),
+        )
+        .push_values(chunks, |mut q, chunk| {
+            q.push_bind(chunk.id)
+                .push_bind(chunk.part_id)
+                .push_bind(chunk.telegram_file_id)
+                .push_bind(chunk.position)
+                .push_bind(chunk.chat_id);
+        })
+        .build()
+        .execute(self.db)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(dbe) if dbe.is_foreign_key_violation() => {
+                PentaractError::DoesNotExist("multipart upload".to_string())
+            }
+            _ => {
+                tracing::error!("{e}");
+                PentaractError::Unknown
+            }
+        })
+        .map(|_| ())
+    }
+
+    /// Replaces the part with the same number if it's uploaded again
+    pub async fn set_part_as_uploaded(&self, id: Uuid) -> PentaractResult<()> {
+        let mut transaction = self.db.begin().await.map_err(|e| map_not_found(e, ""))?;
+
+        // locking the upload, so parts with the same number don't replace each other at once
+        let part: Option<(Uuid, i32)> = sqlx::query_as(&format!(
+            "
+            SELECT p.upload_id, p.part_number
+            FROM {PARTS_TABLE} p
+            JOIN {UPLOADS_TABLE} u ON u.id = p.upload_id
+            WHERE p.id = $1
+            FOR UPDATE OF u;
+            "
+        ))
+        .bind(id)
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|e| map_not_found(e, "multipart part"))?;
+
+        // the upload is completed or aborted meanwhile
+        let Some((upload_id, part_number)) = part else {
+            return Err(PentaractError::DoesNotExist("multipart upload".to_owned()));
+        };
+
+        sqlx::query(&format!(
+            "DELETE FROM {PARTS_TABLE} WHERE upload_id = $1 AND part_number = $2 AND is_uploaded"
+        ))
+        .bind(upload_id)
+        .bind(part_number)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| map_not_found(e, "multipart part"))?;
+
+        sqlx::query(&format!(
+            "UPDATE {PARTS_TABLE} SET is_uploaded = true WHERE id = $1"
+        ))
+        .bind(id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| map_not_found(e, "multipart part"))?;
+
+        transaction.commit().await.map_err(|e| map_not_found(e, ""))
+    }
+
+    /// Chunks are deleted along with the part
+    pub async fn delete_part(&self, id: Uuid) -> PentaractResult<()> {
+        sqlx::query(&format!("DELETE FROM {PARTS_TABLE} WHERE id = $1"))
+            .bind(id)
+            .execute(self.db)
+            .await
+            .map_err(|e| map_not_found(e, "multipart part"))
+            .map(|_| ())
+    }
+
+    pub async fn list_parts(&self, upload_id: Uuid) -> PentaractResult<Vec<MultipartPart>> {
+        sqlx::query_as(&format!(
+            "
+            SELECT part_number, size, etag
+            FROM {PARTS_TABLE}
+            WHERE upload_id = $1 AND is_uploaded
+            ORDER BY part_number;
+            "
+        ))
+        .bind(upload_id)
+        .fetch_all(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "multipart part"))
+    }
+
+    /// Copies chunks of the parts in the given order to the file,
+    /// to the current content if `version_id` is `None`.
+    ///
+    /// Chunks are shared with the parts until the upload is deleted
+    pub async fn link_chunks(
+        &self,
+        upload_id: Uuid,
+        part_numbers: &[i32],
+        file_id: Uuid,
+        version_id: Option<Uuid>,
+    ) -> PentaractResult<()> {
+        sqlx::query(&format!(
+            "
+            INSERT INTO {CHUNKS_TABLE} (id, file_id, telegram_file_id, position, chat_id, version_id)
+            SELECT
+                gen_random_uuid(),
+                $3,
+                c.telegram_file_id,
+                (ROW_NUMBER() OVER (ORDER BY n.position, c.position) - 1)::SmallInt,
+                c.chat_id,
+                $4
+            FROM UNNEST($2::INT[]) WITH ORDINALITY AS n (part_number, position)
+            JOIN {PARTS_TABLE} p
+                ON p.upload_id = $1 AND p.part_number = n.part_number AND p.is_uploaded
+            JOIN {PART_CHUNKS_TABLE} c ON c.part_id = p.id;
+            "
+        ))
+        .bind(upload_id)
+        .bind(part_numbers)
+        .bind(file_id)
+        .bind(version_id)
+        .execute(self.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })
+        .map(|_| ())
+    }
+
+    /// Parts are deleted along with the upload
+    pub async fn delete(&self, id: Uuid, storage_id: Uuid) -> PentaractResult<()> {
+        let result = sqlx::query(&format!(
+            "DELETE FROM {UPLOADS_TABLE} WHERE id = $1 AND storage_id = $2"
+        ))
+        .bind(id)
+        .bind(storage_id)
+        .execute(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "multipart upload"))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::DoesNotExist("multipart upload".to_owned()));
+        }
+
+        Ok(())
+    }
+
+    /// Deletes uploads started more than `expiry_days` days ago along with their parts.
+    ///
+    /// Returns amount of deleted uploads
+    pub async fn delete_outdated(&self, expiry_days: u16) -> PentaractResult<u64> {
+        sqlx::query(&format!(
+            "DELETE FROM {UPLOADS_TABLE} WHERE created_at < NOW() - make_interval(days => $1)"
+        ))
+        .bind(expiry_days as i32)
+        .execute(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "multipart upload"))
+        .map(|result| result.rows_affected())
+    }
+}
@@ -0,0 +1,99 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::common::db::errors::map_not_found;
+use crate::errors::{PentaractError, PentaractResult};
+use crate::models::s3_keys::{InS3Key, S3Credentials, S3Key};
+
+pub const TABLE: &str = "s3_keys";
+
+pub struct S3KeysRepository<'d> {
+    db: &'d PgPool,
+}
+
+impl<'d> S3KeysRepository<'d> {
+    pub fn new(db: &'d PgPool) -> Self {
+        Self { db }
+    }
+
+    pub async fn create(&self, in_obj: InS3Key) -> PentaractResult<S3Key> {
+        let id = Uuid::new_v4();
+
+        sqlx::query_as(&format!(
+            "
+            INSERT INTO {TABLE} (id, user_id, name, access_key_id, encrypted_secret)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, name, access_key_id, created_at, last_used_at;
+            "
+        ))
+        .bind(id)
+        .bind(in_obj.user_id)
+        .bind(in_obj.name)
+        .bind(in_obj.access_key_id)
+        .bind(in_obj.encrypted_secret)
+        .fetch_one(self.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })
+    }
+
+    pub async fn list(&self, user_id: Uuid) -> PentaractResult<Vec<S3Key>> {
+        sqlx::query_as(&format!(
+            "
+            SELECT id, name, access_key_id, created_at, last_used_at
+            FROM {TABLE}
+            WHERE user_id = $1
+            ORDER BY created_at DESC;
+            "
+        ))
+        .bind(user_id)
+        .fetch_all(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "S3 key"))
+    }
+
+    pub async fn get_credentials(&self, access_key_id: &str) -> PentaractResult<S3Credentials> {
+        sqlx::query_as(&format!(
+            "
+            SELECT k.user_id, u.email, k.encrypted_secret
+            FROM {TABLE} k
+            JOIN users u ON u.id = k.user_id
+            WHERE k.access_key_id = $1;
+            "
+        ))
+        .bind(access_key_id)
+        .fetch_one(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "S3 key"))
+    }
+
+    pub async fn mark_as_used(&self, access_key_id: &str) -> PentaractResult<()> {
+        sqlx::query(&format!(
+            "UPDATE {TABLE} SET last_used_at = NOW() WHERE access_key_id = $1"
+        ))
+        .bind(access_key_id)
+        .execute(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "S3 key"))
+        .map(|_| ())
+    }
+
+    pub async fn delete(&self, id: Uuid, user_id: Uuid) -> PentaractResult<()> {
+        let result = sqlx::query(&format!(
+            "DELETE FROM {TABLE} WHERE id = $1 AND user_id = $2"
+        ))
+        .bind(id)
+        .bind(user_id)
+        .execute(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "S3 key"))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::DoesNotExist("S3 key".to_owned()));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::common::db::errors::map_not_found;
+use crate::errors::{PentaractError, PentaractResult};
+use crate::models::share_links::{InShareLink, ShareLink};
+use crate::repositories::{files::FILES_TABLE, folders::FOLDERS_TABLE};
+
+pub const TABLE: &str = "share_links";
+
+pub struct ShareLinksRepository<'d> {
+    db: &'d PgPool,
+}
+
+impl<'d> ShareLinksRepository<'d> {
+    pub fn new(db: &'d PgPool) -> Self {
+        Self { db }
+    }
+
+    pub async fn create(&self, in_obj: InShareLink) -> PentaractResult<ShareLink> {
+        let id = Uuid::new_v4();
+
+        sqlx::query(&format!(
+            "
+            INSERT INTO {TABLE}
+                (id, storage_id, token, file_id, folder_id, password_hash, expires_at, max_downloads, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9);
+            "
+        ))
+        .bind(id)
+        .bind(in_obj.storage_id)
+        .bind(in_obj.token)
+        .bind(in_obj.file_id)
+        .bind(in_obj.folder_id)
+        .bind(in_obj.password_hash)
+        .bind(in_obj.expires_at)
+        .bind(in_obj.max_downloads)
+        .bind(in_obj.created_by)
+        .execute(self.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })?;
+
+        self.get(id, in_obj.storage_id).await
+    }
+
+    pub async fn list(&self, storage_id: Uuid) -> PentaractResult<Vec<ShareLink>> {
+        let select = Self::select();
+
+        sqlx::query_as(&format!(
+            "{select} WHERE l.storage_id = $1 ORDER BY l.created_at DESC"
+        ))
+        .bind(storage_id)
+        .fetch_all(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "share link"))
+    }
+
+    pub async fn get(&self, id: Uuid, storage_id: Uuid) -> PentaractResult<ShareLink> {
+        let select = Self::select();
+
+        sqlx::query_as(&format!("{select} WHERE l.id = $1 AND l.storage_id = $2"))
+            .bind(id)
+            .bind(storage_id)
+            .fetch_one(self.db)
+            .await
+            .map_err(|e| map_not_found(e, "share link"))
+    }
+
+    pub async fn get_by_token(&self, token: &str) -> PentaractResult<ShareLink> {
+        let select = Self::select();
+
+        sqlx::query_as(&format!("{select} WHERE l.token = $1"))
+            .bind(token)
+            .fetch_one(self.db)
+            .await
+            .map_err(|e| map_not_found(e, "share link"))
+    }
+
+    /// Counts a download before the content is read, if the link has any downloads left,
+    /// so concurrent downloads can't exceed the limit together
+    pub async fn reserve_download(&self, id: Uuid) -> PentaractResult<()> {
+        let downloads_amount: Option<i32> = sqlx::query_scalar(&format!(
+            "
+            UPDATE {TABLE}
+            SET downloads_amount = downloads_amount + 1
+            WHERE id = $1 AND (max_downloads IS NULL OR downloads_amount < max_downloads)
+            RETURNING downloads_amount;
+            "
+        ))
+        .bind(id)
+        .fetch_optional(self.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })?;
+
+        downloads_amount
+            .map(|_| ())
+            .ok_or(PentaractError::ShareLinkDownloadsLimitReached)
+    }
+
+    /// Gives back the download reserved by `reserve_download` if it failed
+    pub async fn release_download(&self, id: Uuid) -> PentaractResult<()> {
+        sqlx::query(&format!(
+            "
+            UPDATE {TABLE}
+            SET downloads_amount = GREATEST(downloads_amount - 1, 0)
+            WHERE id = $1;
+            "
+        ))
+        .bind(id)
+        .execute(self.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })
+        .map(|_| ())
+    }
+
+    pub async fn delete(&self, id: Uuid, storage_id: Uuid) -> PentaractResult<()> {
+        let result = sqlx::query(&format!(
+            "DELETE FROM {TABLE} WHERE id = $1 AND storage_id = $2"
+        ))
+        .bind(id)
+        .bind(storage_id)
+        .execute(self.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })?;
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::DoesNotExist("share link".to_owned()));
+        }
+
+        Ok(())
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    ////    Helpers
+    /////////////////////////////////////////////////////////////////////
+
+    /// Selects links with the current paths of the shared files and folders
+    fn select() -> String {
+        format!(
+            "
+            SELECT
+                l.id,
+                l.storage_id,
+                l.token,
+                CASE
+                    WHEN l.file_id IS NOT NULL THEN (
+                        SELECT folder_path(f.folder_id) || f.name
+                        FROM {FILES_TABLE} f
+                        WHERE f.id = l.file_id AND f.trash_id IS NULL
+                    )
+                    ELSE (
+                        SELECT folder_path(d.id)
+                        FROM {FOLDERS_TABLE} d
+                        WHERE d.id = l.folder_id AND d.trash_id IS NULL
+                    )
+                END AS path,
+                l.file_id IS NOT NULL AS is_file,
+                l.password_hash,
+                l.expires_at,
+                l.max_downloads,
+                l.downloads_amount,
+                l.created_by,
+                l.created_at
+            FROM {TABLE} l
+            "
+        )
+    }
+}
@@ -0,0 +1,86 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::common::db::errors::map_not_found;
+use crate::common::types::ChatId;
+use crate::errors::{PentaractError, PentaractResult};
+use crate::models::storage_chats::{InStorageChat, StorageChat};
+use crate::repositories::storages::TABLE as STORAGES_TABLE;
+
+pub const TABLE: &str = "storage_chats";
+
+pub struct StorageChatsRepository<'d> {
+    db: &'d PgPool,
+}
+
+impl<'d> StorageChatsRepository<'d> {
+    pub fn new(db: &'d PgPool) -> Self {
+        Self { db }
+    }
+
+    pub async fn create(&self, in_obj: InStorageChat) -> PentaractResult<StorageChat> {
+        let id = Uuid::new_v4();
+
+        // chat cannot be a primary chat of any storage as well
+        let result = sqlx::query(&format!(
+            "
+            INSERT INTO {TABLE} (id, storage_id, chat_id)
+            SELECT $1, $2, $3
+            WHERE NOT EXISTS (SELECT id FROM {STORAGES_TABLE} WHERE chat_id = $3);
+        "
+        ))
+        .bind(id)
+        .bind(in_obj.storage_id)
+        .bind(in_obj.chat_id)
+        .execute(self.db)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(dbe) if dbe.is_foreign_key_violation() => {
+                PentaractError::DoesNotExist("such storage".to_string())
+            }
+            sqlx::Error::Database(dbe) if dbe.is_unique_violation() => {
+                PentaractError::StorageChatIdConflict
+            }
+            _ => {
+                tracing::error!("{e}");
+                PentaractError::Unknown
+            }
+        })?;
+
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::StorageChatIdConflict);
+        }
+
+        let chat = StorageChat::new(id, in_obj.storage_id, in_obj.chat_id);
+        Ok(chat)
+    }
+
+    pub async fn list_by_storage_id(&self, storage_id: Uuid) -> PentaractResult<Vec<StorageChat>> {
+        sqlx::query_as(&format!(
+            "SELECT * FROM {TABLE} WHERE storage_id = $1 ORDER BY chat_id"
+        ))
+        .bind(storage_id)
+        .fetch_all(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "storage chats"))
+    }
+
+    pub async fn delete(&self, storage_id: Uuid, chat_id: ChatId) -> PentaractResult<()> {
+        let result = sqlx::query(&format!(
+            "DELETE FROM {TABLE} WHERE storage_id = $1 AND chat_id = $2"
+        ))
+        .bind(storage_id)
+        .bind(chat_id)
+        .execute(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "storage chat"))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::DoesNotExist(format!(
+                "chat with id \"{chat_id}\""
+            )));
+        }
+
+        Ok(())
+    }
+}
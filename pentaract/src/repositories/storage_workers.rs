@@ -2,10 +2,11 @@ use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::common::db::errors::map_not_found;
+use crate::common::types::ChatId;
 use crate::errors::{PentaractError, PentaractResult};
-use crate::models::storage_workers::{InStorageWorker, StorageWorker, StorageWorkerTokenOnly};
+use crate::models::storage_workers::{InStorageWorker, StorageWorker, StorageWorkerToken};
 
-const STORAGE_WORKERS_TABLE: &str = "storage_workers";
+pub const STORAGE_WORKERS_TABLE: &str = "storage_workers";
 const STORAGE_WORKERS_USAGES_TABLE: &str = "storage_workers_usages";
 
 pub struct StorageWorkersRepository<'d> {
@@ -22,13 +23,14 @@ impl<'d> StorageWorkersRepository<'d> {
 
         sqlx::query(&format!(
             "
-            INSERT INTO {STORAGE_WORKERS_TABLE} (id, name, token, user_id, storage_id)
-            VALUES ($1, $2, $3, $4, $5);
+            INSERT INTO {STORAGE_WORKERS_TABLE} (id, name, token, token_hash, user_id, storage_id)
+            VALUES ($1, $2, $3, $4, $5, $6);
         "
         ))
         .bind(id)
         .bind(in_obj.name.clone())
         .bind(in_obj.token.clone())
+        .bind(in_obj.token_hash)
         .bind(in_obj.user_id)
         .bind(in_obj.storage_id)
         .execute(self.db)
@@ -56,6 +58,42 @@ impl<'d> StorageWorkersRepository<'d> {
         Ok(sw)
     }
 
+    /// Lists storage workers whose tokens were saved before tokens encryption was introduced
+    pub async fn list_with_plain_tokens(&self) -> PentaractResult<Vec<StorageWorker>> {
+        sqlx::query_as(&format!(
+            "SELECT * FROM {STORAGE_WORKERS_TABLE} WHERE token_hash IS NULL"
+        ))
+        .fetch_all(self.db)
+        .await
+        .map_err(|_| PentaractError::Unknown)
+    }
+
+    pub async fn update_token(
+        &self,
+        id: Uuid,
+        token: &str,
+        token_hash: &str,
+    ) -> PentaractResult<()> {
+        sqlx::query(&format!(
+            "UPDATE {STORAGE_WORKERS_TABLE} SET token = $2, token_hash = $3 WHERE id = $1"
+        ))
+        .bind(id)
+        .bind(token)
+        .bind(token_hash)
+        .execute(self.db)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(dbe) if dbe.is_unique_violation() => {
+                PentaractError::StorageWorkerTokenConflict
+            }
+            _ => {
+                tracing::error!("{e}");
+                PentaractError::Unknown
+            }
+        })
+        .map(|_| ())
+    }
+
     pub async fn storage_has_any(&self, storage_id: Uuid) -> PentaractResult<bool> {
         let has_sws: (_,) = sqlx::query_as(&format!(
             "SELECT COUNT(*) > 0 FROM {STORAGE_WORKERS_TABLE} WHERE storage_id = $1"
@@ -93,12 +131,15 @@ impl<'d> StorageWorkersRepository<'d> {
         .map_err(|e| map_not_found(e, "storage_worker"))
     }
 
+    /// Usages are counted per chat since Telegram limits bots per chat,
+    /// so `chat_id` is `None` for calls that don't send anything to a chat
     // https://www.db-fiddle.com/f/fHcCh7bRtVSxyDfPvPyDre/11
     pub async fn get_token(
         &self,
         storage_id: Uuid,
+        chat_id: Option<ChatId>,
         limit: u8,
-    ) -> PentaractResult<Option<StorageWorkerTokenOnly>> {
+    ) -> PentaractResult<Option<StorageWorkerToken>> {
         let mut transaction = self.db.begin().await.map_err(|e| map_not_found(e, ""))?;
 
         // deleting old rows
@@ -118,21 +159,22 @@ impl<'d> StorageWorkersRepository<'d> {
         let token = sqlx::query_as(&format!(
             "
             WITH swu AS (
-                INSERT INTO {STORAGE_WORKERS_USAGES_TABLE} (id, storage_worker_id)
+                INSERT INTO {STORAGE_WORKERS_USAGES_TABLE} (id, storage_worker_id, chat_id)
                 WITH sw AS (
                     SELECT sw.id AS storage_worker_id
                     FROM {STORAGE_WORKERS_TABLE} sw
-                    LEFT JOIN {STORAGE_WORKERS_USAGES_TABLE} swu ON sw.id = swu.storage_worker_id
+                    LEFT JOIN {STORAGE_WORKERS_USAGES_TABLE} swu
+                        ON sw.id = swu.storage_worker_id AND swu.chat_id IS NOT DISTINCT FROM $4
                     WHERE sw.storage_id = $1
                     GROUP BY sw.id
                     HAVING COUNT(swu.id) < $2
                     ORDER BY COUNT(swu.id)
                     LIMIT 1
                 )
-                SELECT $3, storage_worker_id FROM sw
+                SELECT $3, storage_worker_id, $4 FROM sw
                 RETURNING storage_worker_id
             )
-            SELECT token
+            SELECT sw.id, sw.token
             FROM swu
             JOIN {STORAGE_WORKERS_TABLE} sw ON swu.storage_worker_id = sw.id;
         "
@@ -140,6 +182,7 @@ impl<'d> StorageWorkersRepository<'d> {
         .bind(storage_id)
         .bind(limit as i16)
         .bind(new_id)
+        .bind(chat_id)
         .fetch_optional(&mut *transaction)
         .await
         .map_err(|e| map_not_found(e, "some entity"))?;
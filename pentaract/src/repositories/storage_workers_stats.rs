@@ -0,0 +1,162 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::errors::{PentaractError, PentaractResult};
+use crate::models::storage_workers_stats::{
+    InStorageWorkerUsage, StatsGranularity, StatsPoint, StorageWorkerStatsPoint,
+};
+use crate::repositories::storage_workers::STORAGE_WORKERS_TABLE;
+
+pub const STORAGE_WORKERS_STATS_TABLE: &str = "storage_workers_stats";
+pub const STORAGES_STATS_TABLE: &str = "storages_stats";
+
+/// Keeps usage statistics aggregated by hours
+pub struct StorageWorkersStatsRepository<'d> {
+    db: &'d PgPool,
+}
+
+impl<'d> StorageWorkersStatsRepository<'d> {
+    pub fn new(db: &'d PgPool) -> Self {
+        Self { db }
+    }
+
+    pub async fn register_usage(
+        &self,
+        storage_worker_id: Uuid,
+        storage_id: Uuid,
+        usage: InStorageWorkerUsage,
+    ) -> PentaractResult<()> {
+        sqlx::query(&format!(
+            "
+            INSERT INTO {STORAGE_WORKERS_STATS_TABLE} AS s
+                (storage_worker_id, storage_id, dt, calls, bytes_uploaded, bytes_downloaded, errors)
+            VALUES ($1, $2, date_trunc('hour', NOW()), 1, $3, $4, $5)
+            ON CONFLICT (storage_worker_id, storage_id, dt)
+            DO
+                UPDATE SET
+                    calls = s.calls + 1,
+                    bytes_uploaded = s.bytes_uploaded + EXCLUDED.bytes_uploaded,
+                    bytes_downloaded = s.bytes_downloaded + EXCLUDED.bytes_downloaded,
+                    errors = s.errors + EXCLUDED.errors;
+        "
+        ))
+        .bind(storage_worker_id)
+        .bind(storage_id)
+        .bind(usage.bytes_uploaded)
+        .bind(usage.bytes_downloaded)
+        .bind(usage.is_error as i64)
+        .execute(self.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })
+        .map(|_| ())
+    }
+
+    pub async fn register_throttled_wait(&self, storage_id: Uuid) -> PentaractResult<()> {
+        sqlx::query(&format!(
+            "
+            INSERT INTO {STORAGES_STATS_TABLE} AS s (storage_id, dt, throttled_waits)
+            VALUES ($1, date_trunc('hour', NOW()), 1)
+            ON CONFLICT (storage_id, dt)
+            DO
+                UPDATE SET throttled_waits = s.throttled_waits + 1;
+        "
+        ))
+        .bind(storage_id)
+        .execute(self.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })
+        .map(|_| ())
+    }
+
+    pub async fn list_by_storage_workers(
+        &self,
+        storage_id: Uuid,
+        granularity: StatsGranularity,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> PentaractResult<Vec<StorageWorkerStatsPoint>> {
+        sqlx::query_as(&format!(
+            "
+            SELECT
+                s.storage_worker_id,
+                sw.name,
+                date_trunc($2, s.dt) AS dt,
+                SUM(s.calls)::BigInt AS calls,
+                SUM(s.bytes_uploaded)::BigInt AS bytes_uploaded,
+                SUM(s.bytes_downloaded)::BigInt AS bytes_downloaded,
+                SUM(s.errors)::BigInt AS errors
+            FROM {STORAGE_WORKERS_STATS_TABLE} s
+            JOIN {STORAGE_WORKERS_TABLE} sw ON sw.id = s.storage_worker_id
+            WHERE s.storage_id = $1 AND s.dt >= $3 AND s.dt < $4
+            GROUP BY s.storage_worker_id, sw.name, date_trunc($2, s.dt)
+            ORDER BY dt;
+        "
+        ))
+        .bind(storage_id)
+        .bind(granularity.as_str())
+        .bind(since)
+        .bind(until)
+        .fetch_all(self.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })
+    }
+
+    pub async fn list_by_storage(
+        &self,
+        storage_id: Uuid,
+        granularity: StatsGranularity,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> PentaractResult<Vec<StatsPoint>> {
+        sqlx::query_as(&format!(
+            "
+            WITH w AS (
+                SELECT
+                    date_trunc($2, dt) AS dt,
+                    SUM(calls) AS calls,
+                    SUM(bytes_uploaded) AS bytes_uploaded,
+                    SUM(bytes_downloaded) AS bytes_downloaded,
+                    SUM(errors) AS errors
+                FROM {STORAGE_WORKERS_STATS_TABLE}
+                WHERE storage_id = $1 AND dt >= $3 AND dt < $4
+                GROUP BY date_trunc($2, dt)
+            ), t AS (
+                SELECT date_trunc($2, dt) AS dt, SUM(throttled_waits) AS throttled_waits
+                FROM {STORAGES_STATS_TABLE}
+                WHERE storage_id = $1 AND dt >= $3 AND dt < $4
+                GROUP BY date_trunc($2, dt)
+            )
+            SELECT
+                COALESCE(w.dt, t.dt) AS dt,
+                COALESCE(w.calls, 0)::BigInt AS calls,
+                COALESCE(w.bytes_uploaded, 0)::BigInt AS bytes_uploaded,
+                COALESCE(w.bytes_downloaded, 0)::BigInt AS bytes_downloaded,
+                COALESCE(w.errors, 0)::BigInt AS errors,
+                COALESCE(t.throttled_waits, 0)::BigInt AS throttled_waits
+            FROM w
+            FULL OUTER JOIN t ON w.dt = t.dt
+            ORDER BY dt;
+        "
+        ))
+        .bind(storage_id)
+        .bind(granularity.as_str())
+        .bind(since)
+        .bind(until)
+        .fetch_all(self.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })
+    }
+}
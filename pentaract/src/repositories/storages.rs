@@ -4,9 +4,16 @@ use uuid::Uuid;
 use crate::common::db::errors::map_not_found;
 use crate::errors::{PentaractError, PentaractResult};
 use crate::models::storages::{InStorage, Storage, StorageWithInfo};
-use crate::repositories::{access::TABLE as ACCESS_TABLE, files::FILES_TABLE};
+use crate::repositories::{
+    access::TABLE as ACCESS_TABLE, file_versions::TABLE as VERSIONS_TABLE, files::FILES_TABLE,
+    storage_chats::TABLE as STORAGE_CHATS_TABLE,
+};
 
 pub const TABLE: &str = "storages";
+pub const RESERVATIONS_TABLE: &str = "quota_reservations";
+
+/// Reservations are expected to be released by then, unless their operations were interrupted
+const RESERVATION_EXPIRY_HOURS: i32 = 24;
 
 pub struct StoragesRepository<'d> {
     db: &'d PgPool,
@@ -20,13 +27,23 @@ impl<'d> StoragesRepository<'d> {
     pub async fn create(&self, in_obj: InStorage) -> PentaractResult<Storage> {
         let id = Uuid::new_v4();
 
-        sqlx::query(
-            format!("INSERT INTO {TABLE} (id, name, chat_id) VALUES ($1, $2, $3)").as_str(),
+        // chat cannot be an additional chat of any storage as well
+        let mut transaction = self.db.begin().await.map_err(|e| map_not_found(e, ""))?;
+
+        let result = sqlx::query(
+            format!(
+                "
+                INSERT INTO {TABLE} (id, name, chat_id)
+                SELECT $1, $2, $3
+                WHERE NOT EXISTS (SELECT id FROM {STORAGE_CHATS_TABLE} WHERE chat_id = $3);
+            "
+            )
+            .as_str(),
         )
         .bind(id)
         .bind(in_obj.name.clone())
         .bind(in_obj.chat_id)
-        .execute(self.db)
+        .execute(&mut *transaction)
         .await
         .map_err(|e| match e {
             sqlx::Error::Database(dbe) if dbe.is_foreign_key_violation() => {
@@ -41,28 +58,46 @@ impl<'d> StoragesRepository<'d> {
             }
         })?;
 
-        let storage = Storage::new(id, in_obj.name, in_obj.chat_id);
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::StorageChatIdConflict);
+        }
+
+        // creating the root folder
+        sqlx::query("SELECT ensure_folder_path($1, '')")
+            .bind(id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| map_not_found(e, "folder"))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| map_not_found(e, ""))?;
+
+        let storage = Storage::new(id, in_obj.name, in_obj.chat_id, false, None, None);
         Ok(storage)
     }
 
     pub async fn list_by_user_id(&self, user_id: Uuid) -> PentaractResult<Vec<StorageWithInfo>> {
-        sqlx::query_as(
-            format!(
-                "
-                SELECT s.*, COUNT(f.id) AS files_amount, COALESCE(SUM(f.size), 0)::BigInt as size 
-                FROM {TABLE} s
-                JOIN {ACCESS_TABLE} a ON s.id = a.storage_id
-                LEFT JOIN {FILES_TABLE} f ON s.id = f.storage_id
-                WHERE a.user_id = $1 AND (f.path NOT LIKE '%/' OR f.path IS NULL)
-                GROUP by s.id
-            "
-            )
-            .as_str(),
-        )
-        .bind(user_id)
-        .fetch_all(self.db)
-        .await
-        .map_err(|e| map_not_found(e, "storages"))
+        let select = Self::select_with_info();
+
+        sqlx::query_as(&format!("{select} GROUP BY s.id, a.id"))
+            .bind(user_id)
+            .fetch_all(self.db)
+            .await
+            .map_err(|e| map_not_found(e, "storages"))
+    }
+
+    /// Gets the storage with the usage and the quotas of the user in it
+    pub async fn get_with_info(&self, id: Uuid, user_id: Uuid) -> PentaractResult<StorageWithInfo> {
+        let select = Self::select_with_info();
+
+        sqlx::query_as(&format!("{select} WHERE s.id = $2 GROUP BY s.id, a.id"))
+            .bind(user_id)
+            .bind(id)
+            .fetch_one(self.db)
+            .await
+            .map_err(|e| map_not_found(e, "storage"))
     }
 
     pub async fn get_by_id(&self, id: Uuid) -> PentaractResult<Storage> {
@@ -106,6 +141,174 @@ impl<'d> StoragesRepository<'d> {
         .map_err(|e| map_not_found(e, "storage"))
     }
 
+    pub async fn set_versioning(&self, id: Uuid, versioning: bool) -> PentaractResult<Storage> {
+        sqlx::query_as(&format!(
+            "UPDATE {TABLE} SET versioning = $2 WHERE id = $1 RETURNING *"
+        ))
+        .bind(id)
+        .bind(versioning)
+        .fetch_one(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "storage"))
+    }
+
+    pub async fn set_quota(
+        &self,
+        id: Uuid,
+        max_size: Option<i64>,
+        max_files: Option<i64>,
+    ) -> PentaractResult<Storage> {
+        sqlx::query_as(&format!(
+            "UPDATE {TABLE} SET max_size = $2, max_files = $3 WHERE id = $1 RETURNING *"
+        ))
+        .bind(id)
+        .bind(max_size)
+        .bind(max_files)
+        .fetch_one(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "storage"))
+    }
+
+    /// Reserves the growth of the storage usage and of the user `user_id` one,
+    /// unless it exceeds any of quotas. Returns the id of the reservation.
+    ///
+    /// The storage is locked while quotas are checked,
+    /// so concurrent reservations can't exceed them together
+    pub async fn reserve_quotas(
+        &self,
+        id: Uuid,
+        user_id: Uuid,
+        size: i64,
+        user_size: i64,
+        files_amount: i64,
+    ) -> PentaractResult<Uuid> {
+        let mut transaction = self.db.begin().await.map_err(|e| map_not_found(e, ""))?;
+
+        sqlx::query(&format!("SELECT id FROM {TABLE} WHERE id = $1 FOR UPDATE"))
+            .bind(id)
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|e| map_not_found(e, "storage"))?;
+
+        // reservations of operations interrupted by restarts are never released
+        sqlx::query(&format!(
+            "
+            DELETE FROM {RESERVATIONS_TABLE}
+            WHERE storage_id = $1 AND created_at < NOW() - make_interval(hours => $2);
+            "
+        ))
+        .bind(id)
+        .bind(RESERVATION_EXPIRY_HOURS)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| map_not_found(e, "storage"))?;
+
+        for (table, filter, quota, reserved, growth, name) in [
+            (TABLE, "id = $1", "size", "size", size, "Storage size"),
+            (
+                TABLE,
+                "id = $1",
+                "files",
+                "files",
+                files_amount,
+                "Storage files amount",
+            ),
+            (
+                ACCESS_TABLE,
+                "storage_id = $1 AND user_id = $3",
+                "size",
+                "user_size",
+                user_size,
+                "User size",
+            ),
+            (
+                ACCESS_TABLE,
+                "storage_id = $1 AND user_id = $3",
+                "files",
+                "files",
+                files_amount,
+                "User files amount",
+            ),
+        ] {
+            if growth <= 0 {
+                continue;
+            }
+
+            let of_user = if table == ACCESS_TABLE {
+                "AND uploaded_by = $3"
+            } else {
+                ""
+            };
+            let reserved_by_user = if table == ACCESS_TABLE {
+                "AND user_id = $3"
+            } else {
+                ""
+            };
+            let content = Self::select_content();
+            let is_allowed: Option<bool> = sqlx::query_scalar(&format!(
+                "
+                SELECT
+                    max_{quota} IS NULL
+                    OR max_{quota} >= $2 + (
+                        SELECT COALESCE(SUM({quota}), 0)::BigInt
+                        FROM ({content}) c
+                        WHERE storage_id = $1 {of_user}
+                    ) + (
+                        SELECT COALESCE(SUM({reserved}), 0)::BigInt
+                        FROM {RESERVATIONS_TABLE}
+                        WHERE storage_id = $1 {reserved_by_user}
+                    )
+                FROM {table}
+                WHERE {filter};
+                "
+            ))
+            .bind(id)
+            .bind(growth)
+            .bind(user_id)
+            .fetch_optional(&mut *transaction)
+            .await
+            .map_err(|e| map_not_found(e, "storage"))?;
+            // the transaction is rolled back once it's dropped
+            if is_allowed != Some(true) {
+                return Err(PentaractError::QuotaExceeded(name.to_owned()));
+            }
+        }
+
+        let reservation_id = Uuid::new_v4();
+        sqlx::query(&format!(
+            "
+            INSERT INTO {RESERVATIONS_TABLE} (id, storage_id, user_id, size, user_size, files)
+            VALUES ($1, $2, $3, $4, $5, $6);
+            "
+        ))
+        .bind(reservation_id)
+        .bind(id)
+        .bind(user_id)
+        .bind(size)
+        .bind(user_size)
+        .bind(files_amount)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| map_not_found(e, "storage"))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| map_not_found(e, "storage"))?;
+
+        Ok(reservation_id)
+    }
+
+    /// Releases the usage reserved by `reserve_quotas`
+    pub async fn release_quotas(&self, reservation_id: Uuid) -> PentaractResult<()> {
+        sqlx::query(&format!("DELETE FROM {RESERVATIONS_TABLE} WHERE id = $1"))
+            .bind(reservation_id)
+            .execute(self.db)
+            .await
+            .map_err(|e| map_not_found(e, "storage"))
+            .map(|_| ())
+    }
+
     pub async fn delete_storage(&self, storage_id: Uuid) -> PentaractResult<()> {
         sqlx::query(format!("DELETE FROM {TABLE} WHERE id = $1").as_str())
             .bind(storage_id)
@@ -114,4 +317,50 @@ impl<'d> StoragesRepository<'d> {
             .map_err(|e| map_not_found(e, "storage"))?;
         Ok(())
     }
+
+    /////////////////////////////////////////////////////////////////////
+    ////    Helpers
+    /////////////////////////////////////////////////////////////////////
+
+    /// Selects storages the user `$1` has access to along with the usage
+    fn select_with_info() -> String {
+        let content = Self::select_content();
+
+        format!(
+            "
+            SELECT
+                s.*,
+                COALESCE(SUM(c.files), 0)::BigInt AS files_amount,
+                COALESCE(SUM(c.size), 0)::BigInt AS size,
+                COALESCE(SUM(c.files) FILTER (WHERE c.uploaded_by = a.user_id), 0)::BigInt
+                    AS user_files_amount,
+                COALESCE(SUM(c.size) FILTER (WHERE c.uploaded_by = a.user_id), 0)::BigInt
+                    AS user_size,
+                a.max_size AS user_max_size,
+                a.max_files AS user_max_files
+            FROM {TABLE} s
+            JOIN {ACCESS_TABLE} a ON s.id = a.storage_id AND a.user_id = $1
+            LEFT JOIN ({content}) c ON s.id = c.storage_id
+            "
+        )
+    }
+
+    /// Selects the content which takes place in storages, `size` and `files` of every piece
+    /// along with its `storage_id` and `uploaded_by`.
+    ///
+    /// Files in trash are counted until they are purged, and versions are counted
+    /// as their uploaders' usage, while the current content is the file uploader's one
+    pub fn select_content() -> String {
+        format!(
+            "
+            SELECT storage_id, uploaded_by, size, 1 AS files
+            FROM {FILES_TABLE}
+            UNION ALL
+            SELECT f.storage_id, v.uploaded_by, v.size, 0 AS files
+            FROM {VERSIONS_TABLE} v
+            JOIN {FILES_TABLE} f ON f.id = v.file_id
+            WHERE v.is_uploaded
+            "
+        )
+    }
 }
@@ -0,0 +1,276 @@
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::common::db::errors::map_not_found;
+use crate::errors::{PentaractError, PentaractResult};
+use crate::models::trash::{Trash, TrashItem};
+use crate::repositories::{
+    files::{FilesRepository, FILES_TABLE},
+    folders::{FoldersRepository, FOLDERS_TABLE},
+};
+
+pub const TABLE: &str = "trash";
+
+pub struct TrashRepository<'d> {
+    db: &'d PgPool,
+}
+
+impl<'d> TrashRepository<'d> {
+    pub fn new(db: &'d PgPool) -> Self {
+        Self { db }
+    }
+
+    /// Moves a file or a whole folder (if `path` ends with a slash) to trash
+    pub async fn create(&self, path: &str, storage_id: Uuid) -> PentaractResult<Uuid> {
+        let mut transaction = self.db.begin().await.map_err(|e| map_not_found(e, ""))?;
+
+        let id = Self::trash_path(&mut transaction, path, storage_id).await?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| map_not_found(e, ""))?;
+
+        Ok(id)
+    }
+
+    /// Same as `create`, but within the given transaction
+    pub async fn trash_path(
+        transaction: &mut Transaction<'_, Postgres>,
+        path: &str,
+        storage_id: Uuid,
+    ) -> PentaractResult<Uuid> {
+        let id = Uuid::new_v4();
+
+        sqlx::query(&format!(
+            "INSERT INTO {TABLE} (id, storage_id, path) VALUES ($1, $2, $3)"
+        ))
+        .bind(id)
+        .bind(storage_id)
+        .bind(path)
+        .execute(&mut **transaction)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(dbe) if dbe.is_foreign_key_violation() => {
+                PentaractError::DoesNotExist("such storage".to_string())
+            }
+            _ => {
+                tracing::error!("{e}");
+                PentaractError::Unknown
+            }
+        })?;
+
+        let (parent, name) = FilesRepository::split_path(path);
+        let result = if path.ends_with('/') {
+            // the whole subtree is marked, so trashed folders content is hidden everywhere
+            let subtree =
+                FoldersRepository::subtree_cte("folder_id_by_path($2, $3 || $4 || '/')", "''");
+            sqlx::query(&format!(
+                "
+                WITH RECURSIVE {subtree}
+                UPDATE {FILES_TABLE}
+                SET trash_id = $1
+                WHERE folder_id IN (SELECT id FROM subtree) AND trash_id IS NULL;
+                "
+            ))
+            .bind(id)
+            .bind(storage_id)
+            .bind(parent)
+            .bind(name)
+            .execute(&mut **transaction)
+            .await
+            .map_err(|e| map_not_found(e, "file"))?;
+
+            // the root folder cannot be trashed
+            sqlx::query(&format!(
+                "
+                WITH RECURSIVE {subtree}
+                UPDATE {FOLDERS_TABLE}
+                SET trash_id = $1
+                WHERE id IN (SELECT id FROM subtree) AND parent_id IS NOT NULL;
+                "
+            ))
+            .bind(id)
+            .bind(storage_id)
+            .bind(parent)
+            .bind(name)
+            .execute(&mut **transaction)
+            .await
+            .map_err(|e| map_not_found(e, "folder"))?
+        } else {
+            sqlx::query(&format!(
+                "
+                UPDATE {FILES_TABLE}
+                SET trash_id = $1
+                WHERE folder_id = folder_id_by_path($2, $3) AND name = $4 AND trash_id IS NULL;
+                "
+            ))
+            .bind(id)
+            .bind(storage_id)
+            .bind(parent)
+            .bind(name)
+            .execute(&mut **transaction)
+            .await
+            .map_err(|e| map_not_found(e, "file"))?
+        };
+
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::DoesNotExist(format!("\"{path}\"")));
+        }
+
+        Ok(id)
+    }
+
+    pub async fn list(&self, storage_id: Uuid) -> PentaractResult<Vec<TrashItem>> {
+        sqlx::query_as(&format!(
+            "
+            SELECT
+                t.id,
+                t.path,
+                t.deleted_at,
+                COUNT(f.id) AS files_amount,
+                COALESCE(SUM(f.size), 0)::BigInt AS size
+            FROM {TABLE} t
+            LEFT JOIN {FILES_TABLE} f ON f.trash_id = t.id
+            WHERE t.storage_id = $1
+            GROUP BY t.id
+            ORDER BY t.deleted_at DESC;
+        "
+        ))
+        .bind(storage_id)
+        .fetch_all(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "trash"))
+    }
+
+    pub async fn get(&self, id: Uuid, storage_id: Uuid) -> PentaractResult<Trash> {
+        sqlx::query_as(&format!(
+            "SELECT * FROM {TABLE} WHERE id = $1 AND storage_id = $2"
+        ))
+        .bind(id)
+        .bind(storage_id)
+        .fetch_one(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "trash item"))
+    }
+
+    /// Brings a file or a folder back to `path`.
+    ///
+    /// Files and folders at `replaced_paths` are deleted in the same transaction beforehand
+    pub async fn restore(
+        &self,
+        trash: &Trash,
+        path: &str,
+        replaced_paths: &[String],
+    ) -> PentaractResult<()> {
+        let mut transaction = self.db.begin().await.map_err(|e| map_not_found(e, ""))?;
+
+        FilesRepository::delete_paths(&mut transaction, replaced_paths, trash.storage_id).await?;
+
+        // moving the trashed file or the top trashed folder, the rest goes along with it
+        let query = if trash.path.ends_with('/') {
+            format!(
+                "
+                UPDATE {FOLDERS_TABLE}
+                SET parent_id = ensure_folder_path($1, $2), name = $3, trash_id = NULL
+                WHERE
+                    trash_id = $4
+                    AND parent_id NOT IN (SELECT id FROM {FOLDERS_TABLE} WHERE trash_id = $4);
+                "
+            )
+        } else {
+            format!(
+                "
+                UPDATE {FILES_TABLE}
+                SET folder_id = ensure_folder_path($1, $2), name = $3, trash_id = NULL
+                WHERE trash_id = $4;
+                "
+            )
+        };
+        let (parent, name) = FilesRepository::split_path(path);
+
+        let result = sqlx::query(&query)
+            .bind(trash.storage_id)
+            .bind(parent)
+            .bind(name)
+            .bind(trash.id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::Database(dbe) if dbe.is_unique_violation() => {
+                    PentaractError::AlreadyExists("File with such name".to_string())
+                }
+                _ => {
+                    tracing::error!("{e}");
+                    PentaractError::Unknown
+                }
+            })?;
+
+        // the item may be purged meanwhile, then nothing is replaced as the transaction is dropped
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::DoesNotExist("trash item".to_owned()));
+        }
+
+        for table in [FOLDERS_TABLE, FILES_TABLE] {
+            sqlx::query(&format!(
+                "UPDATE {table} SET trash_id = NULL WHERE trash_id = $1"
+            ))
+            .bind(trash.id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| map_not_found(e, "trash item"))?;
+        }
+
+        sqlx::query(&format!("DELETE FROM {TABLE} WHERE id = $1"))
+            .bind(trash.id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| map_not_found(e, "trash item"))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| map_not_found(e, ""))?;
+
+        Ok(())
+    }
+
+    /// Deletes trash item with its files for good
+    pub async fn delete(&self, id: Uuid, storage_id: Uuid) -> PentaractResult<()> {
+        let result = sqlx::query(&format!(
+            "DELETE FROM {TABLE} WHERE id = $1 AND storage_id = $2"
+        ))
+        .bind(id)
+        .bind(storage_id)
+        .execute(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "trash item"))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PentaractError::DoesNotExist("such trash item".to_string()));
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete_all(&self, storage_id: Uuid) -> PentaractResult<()> {
+        sqlx::query(&format!("DELETE FROM {TABLE} WHERE storage_id = $1"))
+            .bind(storage_id)
+            .execute(self.db)
+            .await
+            .map_err(|e| map_not_found(e, "trash item"))
+            .map(|_| ())
+    }
+
+    /// Returns amount of purged trash items
+    pub async fn delete_outdated(&self, retention_days: u16) -> PentaractResult<u64> {
+        sqlx::query(&format!(
+            "DELETE FROM {TABLE} WHERE deleted_at < NOW() - make_interval(days => $1)"
+        ))
+        .bind(retention_days as i32)
+        .execute(self.db)
+        .await
+        .map_err(|e| map_not_found(e, "trash item"))
+        .map(|result| result.rows_affected())
+    }
+}
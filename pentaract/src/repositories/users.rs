@@ -42,6 +42,14 @@ impl<'d> UsersRepository<'d> {
         Ok(user)
     }
 
+    pub async fn get_by_id(&self, id: Uuid) -> PentaractResult<User> {
+        sqlx::query_as("SELECT * FROM users WHERE id = $1")
+            .bind(id)
+            .fetch_one(self.db)
+            .await
+            .map_err(|e| map_not_found(e, "user"))
+    }
+
     pub async fn get_by_email(&self, email: &str) -> PentaractResult<User> {
         sqlx::query_as("SELECT * FROM users WHERE email = $1")
             .bind(email)
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::{delete, get},
+    Extension, Json, Router,
+};
+use uuid::Uuid;
+
+use crate::{
+    common::{
+        jwt_manager::AuthUser,
+        routing::{app_state::AppState, middlewares::auth::logged_in_required},
+    },
+    schemas::app_passwords::{AppPasswordsListSchema, InAppPasswordSchema},
+    services::app_passwords::AppPasswordsService,
+};
+
+pub struct AppPasswordsRouter;
+
+impl AppPasswordsRouter {
+    pub fn get_router(state: Arc<AppState>) -> Router {
+        Router::new()
+            .route("/", get(Self::list).post(Self::create))
+            .route("/:app_password_id", delete(Self::revoke))
+            .route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                logged_in_required,
+            ))
+            .with_state(state)
+    }
+
+    async fn create(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        Json(in_schema): Json<InAppPasswordSchema>,
+    ) -> impl IntoResponse {
+        let app_password = AppPasswordsService::new(&state.db)
+            .create(in_schema, &user)
+            .await?;
+        Ok::<_, (StatusCode, String)>((StatusCode::CREATED, Json(app_password)))
+    }
+
+    async fn list(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+    ) -> impl IntoResponse {
+        let app_passwords = AppPasswordsService::new(&state.db)
+            .list(&user)
+            .await
+            .map(AppPasswordsListSchema::new)?;
+        Ok::<_, (StatusCode, String)>(Json(app_passwords))
+    }
+
+    async fn revoke(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        Path(id): Path<Uuid>,
+    ) -> impl IntoResponse {
+        AppPasswordsService::new(&state.db)
+            .revoke(id, &user)
+            .await?;
+        Ok::<_, (StatusCode, String)>(StatusCode::NO_CONTENT)
+    }
+}
@@ -0,0 +1,573 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, OriginalUri, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, Uri},
+    middleware,
+    response::{AppendHeaders, IntoResponse, Response},
+    routing::any,
+    Extension, Router,
+};
+use chrono::Utc;
+use reqwest::header;
+use uuid::Uuid;
+
+use crate::{
+    common::{
+        jwt_manager::AuthUser,
+        routing::{app_state::AppState, middlewares::auth::basic_auth_required},
+        webdav::{self, ActiveLock, DavResource, Multistatus},
+    },
+    errors::PentaractError,
+    models::{dav_locks::DavLock, files::FSElement},
+    routers::files::FilesRouter,
+    schemas::files::{ConflictPolicy, InTransferSchema},
+    services::{dav::DavService, files::FilesService},
+};
+
+const XML_CONTENT_TYPE: &str = "application/xml; charset=utf-8";
+const ALLOWED_METHODS: &str =
+    "OPTIONS, PROPFIND, GET, HEAD, PUT, MKCOL, DELETE, COPY, MOVE, LOCK, UNLOCK";
+
+/// Clients often mount the root with a trailing slash, which nested routers don't match,
+/// so routes are added under this path instead
+const MOUNT_PATH: &str = "/dav";
+
+type DavResult = Result<Response, (StatusCode, String)>;
+
+/// Resource a request is made to, the root lists storages
+enum Target {
+    Root,
+    Resource(Uuid, String),
+}
+
+/// NOTE:
+///
+/// resources are addressed as `/dav/<storage_id>/<path>`,
+/// and folders are collections.
+///
+/// Clients authenticate with HTTP Basic, using app passwords
+pub struct DavRouter;
+
+impl DavRouter {
+    pub fn get_router(state: Arc<AppState>) -> Router {
+        Router::new()
+            .route(MOUNT_PATH, any(Self::handle))
+            .route(&format!("{MOUNT_PATH}/"), any(Self::handle))
+            .route(&format!("{MOUNT_PATH}/*path"), any(Self::handle))
+            .layer(DefaultBodyLimit::disable())
+            .route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                basic_auth_required,
+            ))
+            .with_state(state)
+    }
+
+    async fn handle(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        OriginalUri(original_uri): OriginalUri,
+        method: Method,
+        uri: Uri,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Response {
+        // the router may be nested anywhere, so hrefs are built from the original URI
+        let path = uri.path().strip_prefix(MOUNT_PATH).unwrap_or_default();
+        let original_path = original_uri.path();
+        let prefix = original_path
+            .strip_suffix(path)
+            .unwrap_or(original_path)
+            .trim_end_matches('/')
+            .to_owned();
+
+        let request = DavRequest {
+            state,
+            user,
+            prefix,
+            headers,
+            body,
+        };
+        let target = match Self::parse_target(path) {
+            Ok(target) => target,
+            Err(e) => return <(StatusCode, String)>::from(e).into_response(),
+        };
+
+        let result = match (method.as_str(), target) {
+            ("OPTIONS", _) => Ok(Self::options()),
+            ("PROPFIND", target) => request.propfind(target).await,
+            (_, Target::Root) => Err(Self::method_not_allowed()),
+            ("GET", Target::Resource(storage_id, path)) => {
+                request.get(storage_id, &path, false).await
+            }
+            ("HEAD", Target::Resource(storage_id, path)) => {
+                request.get(storage_id, &path, true).await
+            }
+            ("PUT", Target::Resource(storage_id, path)) => request.put(storage_id, &path).await,
+            ("MKCOL", Target::Resource(storage_id, path)) => request.mkcol(storage_id, &path).await,
+            ("DELETE", Target::Resource(storage_id, path)) => {
+                request.delete(storage_id, &path).await
+            }
+            ("COPY", Target::Resource(storage_id, path)) => {
+                request.transfer(storage_id, &path, false).await
+            }
+            ("MOVE", Target::Resource(storage_id, path)) => {
+                request.transfer(storage_id, &path, true).await
+            }
+            ("LOCK", Target::Resource(storage_id, path)) => request.lock(storage_id, &path).await,
+            ("UNLOCK", Target::Resource(storage_id, _)) => request.unlock(storage_id).await,
+            _ => Err(Self::method_not_allowed()),
+        };
+
+        result.unwrap_or_else(IntoResponse::into_response)
+    }
+
+    fn options() -> Response {
+        let headers = AppendHeaders([
+            (header::HeaderName::from_static("dav"), "1, 2"),
+            (header::ALLOW, ALLOWED_METHODS),
+            (header::HeaderName::from_static("ms-author-via"), "DAV"),
+        ]);
+        (headers, ()).into_response()
+    }
+
+    fn parse_target(path: &str) -> Result<Target, PentaractError> {
+        let path = webdav::decode_href(path)?;
+        let path = path.trim_start_matches('/');
+        if path.is_empty() {
+            return Ok(Target::Root);
+        }
+
+        let (storage_id, path) = path.split_once('/').unwrap_or((path, ""));
+        let storage_id = Uuid::parse_str(storage_id)
+            .map_err(|_| PentaractError::DoesNotExist("such storage".to_owned()))?;
+        Ok(Target::Resource(storage_id, path.to_owned()))
+    }
+
+    fn method_not_allowed() -> (StatusCode, String) {
+        (
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Method is not allowed for this resource".to_owned(),
+        )
+    }
+}
+
+struct DavRequest {
+    state: Arc<AppState>,
+    user: AuthUser,
+    prefix: String,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl DavRequest {
+    fn service(&self) -> DavService<'_> {
+        DavService::new(&self.state.db, self.state.tx.clone())
+    }
+
+    /// Depth of `1` lists collections, infinite depth is not supported
+    async fn propfind(&self, target: Target) -> DavResult {
+        let depth = match self.header(&header::HeaderName::from_static("depth")) {
+            Some("0") => 0,
+            Some("1") => 1,
+            _ => {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    "Depth infinity is not supported".to_owned(),
+                ))
+            }
+        };
+        let request = webdav::parse_propfind(&self.body)?;
+        let service = self.service();
+
+        let mut multistatus = Multistatus::new();
+        match target {
+            Target::Root => {
+                let storages = service.list_storages(&self.user).await?;
+                let now = Utc::now();
+                let root = DavResource {
+                    href: format!("{}/", self.prefix),
+                    name: String::new(),
+                    is_collection: true,
+                    size: storages.iter().map(|storage| storage.size).sum(),
+                    content_type: None,
+                    created_at: now,
+                    updated_at: now,
+                    locks: vec![],
+                };
+                multistatus.add(&root, &request);
+
+                if depth == 1 {
+                    for storage in storages {
+                        let el = service.resolve(storage.id, "", &self.user).await?;
+                        multistatus.add(&self.resource(storage.id, el, &[]), &request);
+                    }
+                }
+            }
+            Target::Resource(storage_id, path) => {
+                let el = service.resolve(storage_id, &path, &self.user).await?;
+                let path = Self::normalize(&el);
+                let is_listed = depth == 1 && !el.is_file;
+
+                let locks = service.list_locks(storage_id, &path, is_listed).await?;
+                multistatus.add(&self.resource(storage_id, el, &locks), &request);
+
+                if is_listed {
+                    for el in service.list(storage_id, &path, &self.user).await? {
+                        multistatus.add(&self.resource(storage_id, el, &locks), &request);
+                    }
+                }
+            }
+        }
+
+        let headers = AppendHeaders([(header::CONTENT_TYPE, XML_CONTENT_TYPE)]);
+        Ok((StatusCode::MULTI_STATUS, headers, multistatus.finish()).into_response())
+    }
+
+    /// Collections can't be downloaded
+    async fn get(&self, storage_id: Uuid, path: &str, is_head: bool) -> DavResult {
+        let service = self.service();
+        let el = service.resolve(storage_id, path, &self.user).await?;
+        if !el.is_file {
+            return Err(DavRouter::method_not_allowed());
+        }
+
+        let resource = self.resource(storage_id, el, &[]);
+        let mut response = if is_head {
+            let content_type = resource
+                .content_type
+                .clone()
+                .unwrap_or("application/octet-stream".to_owned());
+            let headers = AppendHeaders([
+                (header::CONTENT_TYPE, content_type),
+                (header::CONTENT_LENGTH, resource.size.to_string()),
+            ]);
+            (headers, ()).into_response()
+        } else {
+            let data = service.download(storage_id, path, &self.user).await?;
+            FilesRouter::file_response(&resource.name, data)
+        };
+
+        let headers = response.headers_mut();
+        if let Ok(etag) = HeaderValue::from_str(&resource.etag()) {
+            headers.insert(header::ETAG, etag);
+        }
+        if let Ok(last_modified) = HeaderValue::from_str(&webdav::http_date(resource.updated_at)) {
+            headers.insert(header::LAST_MODIFIED, last_modified);
+        }
+        Ok(response)
+    }
+
+    async fn put(&self, storage_id: Uuid, path: &str) -> DavResult {
+        if path.is_empty() || path.ends_with('/') {
+            return Err(DavRouter::method_not_allowed());
+        }
+
+        let service = self.service();
+        let existing = service.find(storage_id, path, &self.user).await?;
+        match existing {
+            Some(ref el) if !el.is_file => return Err(DavRouter::method_not_allowed()),
+            None if !service.parent_exists(storage_id, path).await? => {
+                return Err(Self::missing_parent())
+            }
+            _ => (),
+        }
+        service
+            .check_locks(storage_id, path, false, &self.tokens())
+            .await?;
+
+        service
+            .upload(storage_id, path, self.body.clone(), &self.user)
+            .await?;
+
+        Ok(Self::created_or_replaced(existing.is_some()))
+    }
+
+    async fn mkcol(&self, storage_id: Uuid, path: &str) -> DavResult {
+        if !self.body.is_empty() {
+            return Err((
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "MKCOL body is not supported".to_owned(),
+            ));
+        }
+
+        let service = self.service();
+        if path.is_empty() || service.find(storage_id, path, &self.user).await?.is_some() {
+            return Err(DavRouter::method_not_allowed());
+        }
+        let path = format!("{}/", path.trim_end_matches('/'));
+        if !service.parent_exists(storage_id, &path).await? {
+            return Err(Self::missing_parent());
+        }
+        service
+            .check_locks(storage_id, &path, false, &self.tokens())
+            .await?;
+
+        service.mkcol(storage_id, &path, &self.user).await?;
+
+        Ok(StatusCode::CREATED.into_response())
+    }
+
+    async fn delete(&self, storage_id: Uuid, path: &str) -> DavResult {
+        if path.is_empty() {
+            return Err(DavRouter::method_not_allowed());
+        }
+
+        let service = self.service();
+        let el = service.resolve(storage_id, path, &self.user).await?;
+        let path = Self::normalize(&el);
+        service
+            .check_locks(storage_id, &path, true, &self.tokens())
+            .await?;
+
+        service.delete(storage_id, &path, &self.user).await?;
+
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+
+    /// Destination is replaced unless the `Overwrite` header is `F`
+    async fn transfer(&self, storage_id: Uuid, path: &str, is_move: bool) -> DavResult {
+        let (to_storage_id, to) = self.destination()?;
+        if path.is_empty() || to.is_empty() {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "Storages roots can't be copied or moved".to_owned(),
+            ));
+        }
+        let overwrite = self.header(&header::HeaderName::from_static("overwrite")) != Some("F");
+
+        let service = self.service();
+        let el = service.resolve(storage_id, path, &self.user).await?;
+        let from = Self::normalize(&el);
+        let to = if el.is_file {
+            to.trim_end_matches('/').to_owned()
+        } else {
+            format!("{}/", to.trim_end_matches('/'))
+        };
+
+        let tokens = self.tokens();
+        if is_move {
+            service
+                .check_locks(storage_id, &from, true, &tokens)
+                .await?;
+        }
+        service
+            .check_locks(to_storage_id, &to, true, &tokens)
+            .await?;
+        let is_replaced = service
+            .find(to_storage_id, &to, &self.user)
+            .await?
+            .is_some();
+        if !is_replaced && !service.parent_exists(to_storage_id, &to).await? {
+            return Err(Self::missing_parent());
+        }
+
+        let in_schema = InTransferSchema {
+            storage_id,
+            from,
+            to_storage_id,
+            to,
+            on_conflict: if overwrite {
+                ConflictPolicy::Overwrite
+            } else {
+                ConflictPolicy::Fail
+            },
+        };
+        let outcome = service
+            .transfer(in_schema, is_move, &self.user)
+            .await
+            .map_err(|e| match e {
+                PentaractError::AlreadyExists(_) => (
+                    StatusCode::PRECONDITION_FAILED,
+                    "Destination already exists".to_owned(),
+                ),
+                e => e.into(),
+            })?;
+
+        // clients take the response as done, so transfers between storages are waited for
+        if let Some(transfer) = outcome.background {
+            FilesService::new(&self.state.db, self.state.tx.clone())
+                .transfer(transfer, &self.user)
+                .await?;
+        }
+
+        Ok(Self::created_or_replaced(is_replaced))
+    }
+
+    /// Requests without a body refresh the lock submitted in the `If` header
+    async fn lock(&self, storage_id: Uuid, path: &str) -> DavResult {
+        let timeout_secs = self.timeout();
+        let service = self.service();
+
+        let Some(info) = webdav::parse_lockinfo(&self.body)? else {
+            let token = self.tokens().into_iter().next().ok_or((
+                StatusCode::BAD_REQUEST,
+                "Lock token must be submitted to refresh the lock".to_owned(),
+            ))?;
+            let lock = service
+                .refresh_lock(storage_id, &token, timeout_secs, &self.user)
+                .await
+                .map_err(|e| match e {
+                    PentaractError::DoesNotExist(_) => (
+                        StatusCode::PRECONDITION_FAILED,
+                        "Lock does not exist".to_owned(),
+                    ),
+                    e => e.into(),
+                })?;
+
+            let body = webdav::lock_discovery(&self.active_lock(&lock));
+            let headers = AppendHeaders([(header::CONTENT_TYPE, XML_CONTENT_TYPE)]);
+            return Ok((headers, body).into_response());
+        };
+
+        if service.find(storage_id, path, &self.user).await?.is_none()
+            && !service.parent_exists(storage_id, path).await?
+        {
+            return Err(Self::missing_parent());
+        }
+        let is_deep = self.header(&header::HeaderName::from_static("depth")) != Some("0");
+
+        let (lock, is_created) = service
+            .lock(storage_id, path, info, is_deep, timeout_secs, &self.user)
+            .await?;
+
+        let status = if is_created {
+            StatusCode::CREATED
+        } else {
+            StatusCode::OK
+        };
+        let body = webdav::lock_discovery(&self.active_lock(&lock));
+        let headers = AppendHeaders([
+            (header::CONTENT_TYPE, XML_CONTENT_TYPE.to_owned()),
+            (
+                header::HeaderName::from_static("lock-token"),
+                format!("<{}>", lock.token),
+            ),
+        ]);
+        Ok((status, headers, body).into_response())
+    }
+
+    async fn unlock(&self, storage_id: Uuid) -> DavResult {
+        let token = self
+            .header(&header::HeaderName::from_static("lock-token"))
+            .map(|token| token.trim_matches(|c| c == '<' || c == '>').to_owned())
+            .ok_or((
+                StatusCode::BAD_REQUEST,
+                "Lock-Token header is required".to_owned(),
+            ))?;
+
+        self.service()
+            .unlock(storage_id, &token, &self.user)
+            .await
+            .map_err(|e| match e {
+                PentaractError::DoesNotExist(_) => {
+                    (StatusCode::CONFLICT, "Lock does not exist".to_owned())
+                }
+                e => e.into(),
+            })?;
+
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+
+    /// Storage and path the `Destination` header points to
+    fn destination(&self) -> Result<(Uuid, String), (StatusCode, String)> {
+        let destination = self
+            .header(&header::HeaderName::from_static("destination"))
+            .and_then(|destination| destination.parse::<Uri>().ok())
+            .ok_or((
+                StatusCode::BAD_REQUEST,
+                "Destination header is required".to_owned(),
+            ))?;
+
+        let path = destination.path().strip_prefix(&self.prefix).ok_or((
+            StatusCode::BAD_GATEWAY,
+            "Destination is on another server".to_owned(),
+        ))?;
+        match DavRouter::parse_target(path)? {
+            Target::Resource(storage_id, path) => Ok((storage_id, path)),
+            Target::Root => Err((
+                StatusCode::FORBIDDEN,
+                "Destination can't be the root".to_owned(),
+            )),
+        }
+    }
+
+    /// `Timeout` header, like `Second-3600` or `Infinite`
+    fn timeout(&self) -> Option<i64> {
+        let timeout = self.header(&header::HeaderName::from_static("timeout"))?;
+        let timeout = timeout.split(',').next()?.trim();
+
+        if timeout.eq_ignore_ascii_case("infinite") {
+            return Some(i64::MAX);
+        }
+        timeout.strip_prefix("Second-")?.parse().ok()
+    }
+
+    fn tokens(&self) -> Vec<String> {
+        webdav::submitted_tokens(self.header(&header::HeaderName::from_static("if")))
+    }
+
+    fn header(&self, name: &header::HeaderName) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+
+    fn resource(&self, storage_id: Uuid, el: FSElement, locks: &[DavLock]) -> DavResource {
+        let path = Self::normalize(&el);
+        let locks = locks
+            .iter()
+            .filter(|lock| lock.path == path || (lock.is_deep && path.starts_with(&lock.path)))
+            .map(|lock| self.active_lock(lock))
+            .collect();
+
+        DavResource {
+            href: self.href(storage_id, &path),
+            name: el.name,
+            is_collection: !el.is_file,
+            size: el.size,
+            content_type: el.mime_type,
+            created_at: el.created_at,
+            updated_at: el.updated_at,
+            locks,
+        }
+    }
+
+    fn active_lock(&self, lock: &DavLock) -> ActiveLock {
+        ActiveLock {
+            token: lock.token.clone(),
+            root_href: self.href(lock.storage_id, &lock.path),
+            is_shared: lock.is_shared,
+            is_deep: lock.is_deep,
+            owner: lock.owner.clone(),
+            timeout_secs: (lock.expires_at - Utc::now()).num_seconds().max(0),
+        }
+    }
+
+    fn href(&self, storage_id: Uuid, path: &str) -> String {
+        format!("{}/{storage_id}/{}", self.prefix, webdav::encode_href(path))
+    }
+
+    /// Listed folders paths lack the trailing slash
+    fn normalize(el: &FSElement) -> String {
+        if el.is_file || el.path.is_empty() || el.path.ends_with('/') {
+            el.path.clone()
+        } else {
+            format!("{}/", el.path)
+        }
+    }
+
+    fn created_or_replaced(is_replaced: bool) -> Response {
+        if is_replaced {
+            StatusCode::NO_CONTENT.into_response()
+        } else {
+            StatusCode::CREATED.into_response()
+        }
+    }
+
+    fn missing_parent() -> (StatusCode, String) {
+        (
+            StatusCode::CONFLICT,
+            "Parent collection does not exist".to_owned(),
+        )
+    }
+}
@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, Path as RoutePath, State},
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Extension, Json, Router,
+};
+use uuid::Uuid;
+
+use crate::{
+    common::{
+        jwt_manager::AuthUser,
+        routing::{app_state::AppState, middlewares::auth::logged_in_required},
+    },
+    errors::PentaractError,
+    schemas::file_drops::{FileDropsListSchema, InFileDropSchema},
+    services::file_drops::FileDropsService,
+};
+
+pub struct FileDropsRouter;
+
+impl FileDropsRouter {
+    /// Drops management, it's nested into storages routes
+    pub fn get_router(state: Arc<AppState>) -> Router<Arc<AppState>, axum::body::Body> {
+        Router::new()
+            .route("/", get(Self::list).post(Self::create))
+            .route("/:file_drop_id", delete(Self::revoke))
+            .route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                logged_in_required,
+            ))
+            .with_state(state)
+    }
+
+    /// Files are dropped without authentication.
+    ///
+    /// Dropped files are kept in memory until they are saved,
+    /// so requests are bounded by `MAX_DROP_SIZE` even if drops limit nothing
+    pub fn get_public_router(state: Arc<AppState>) -> Router {
+        Router::new()
+            .route("/:token", get(Self::info).post(Self::drop_files))
+            .layer(DefaultBodyLimit::max(state.config.max_drop_size))
+            .with_state(state)
+    }
+
+    async fn create(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath(storage_id): RoutePath<Uuid>,
+        Json(in_schema): Json<InFileDropSchema>,
+    ) -> Result<impl IntoResponse, (StatusCode, String)> {
+        let file_drop = FileDropsService::new(&state.db, state.tx.clone())
+            .create(storage_id, in_schema, &user)
+            .await?;
+        Ok((StatusCode::CREATED, Json(file_drop)))
+    }
+
+    async fn list(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath(storage_id): RoutePath<Uuid>,
+    ) -> Result<impl IntoResponse, (StatusCode, String)> {
+        let file_drops = FileDropsService::new(&state.db, state.tx.clone())
+            .list(storage_id, &user)
+            .await?;
+        Ok(Json(FileDropsListSchema::new(file_drops)))
+    }
+
+    async fn revoke(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath((storage_id, file_drop_id)): RoutePath<(Uuid, Uuid)>,
+    ) -> Result<StatusCode, (StatusCode, String)> {
+        FileDropsService::new(&state.db, state.tx.clone())
+            .revoke(storage_id, file_drop_id, &user)
+            .await?;
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    async fn info(
+        State(state): State<Arc<AppState>>,
+        RoutePath(token): RoutePath<String>,
+    ) -> Result<impl IntoResponse, (StatusCode, String)> {
+        let info = FileDropsService::new(&state.db, state.tx.clone())
+            .info(&token)
+            .await?;
+        Ok(Json(info))
+    }
+
+    /// Every `file` field is saved by its filename into the drop folder.
+    ///
+    /// Files bigger than the drop allows are not read to the end,
+    /// as well as requests with more files than the drop has places left
+    async fn drop_files(
+        State(state): State<Arc<AppState>>,
+        RoutePath(token): RoutePath<String>,
+        mut multipart: Multipart,
+    ) -> Result<Response, (StatusCode, String)> {
+        let service = FileDropsService::new(&state.db, state.tx.clone());
+        let info = service.info(&token).await?;
+        let max_file_size = info.max_file_size;
+
+        // parsing
+        let mut files = vec![];
+        while let Some(mut field) = multipart
+            .next_field()
+            .await
+            .map_err(|e| (e.status(), e.body_text()))?
+        {
+            if field.name() != Some("file") {
+                continue;
+            }
+            if let Some(files_left) = info.files_left.filter(|left| files.len() as i32 >= *left) {
+                return Err(PentaractError::TooManyDroppedFiles(files_left).into());
+            }
+            let filename = field.file_name().unwrap_or("unnamed").to_owned();
+
+            let mut data = vec![];
+            while let Some(chunk) = field
+                .chunk()
+                .await
+                .map_err(|e| (e.status(), e.body_text()))?
+            {
+                data.extend_from_slice(&chunk);
+                if let Some(max_file_size) = max_file_size.filter(|max| data.len() as i64 > *max) {
+                    return Err(PentaractError::FileTooLarge(max_file_size).into());
+                }
+            }
+
+            files.push((filename, data.into()));
+        }
+        if files.is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "file file is required".to_owned()));
+        }
+
+        let dropped = service.drop_files(&token, files).await?;
+        Ok((StatusCode::CREATED, Json(dropped)).into_response())
+    }
+}
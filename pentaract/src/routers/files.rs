@@ -1,14 +1,15 @@
 use std::{collections::HashMap, path::Path, sync::Arc};
 
 use axum::{
-    body::Full,
+    body::{Full, StreamBody},
     extract::{DefaultBodyLimit, Multipart, Path as RoutePath, Query, State},
     http::StatusCode,
     middleware,
     response::{AppendHeaders, IntoResponse, Response},
-    routing::{get, post},
+    routing::{delete, get, patch, post},
     Extension, Json, Router,
 };
+use futures::channel::mpsc;
 use reqwest::header;
 use tokio_util::bytes::Bytes;
 use uuid::Uuid;
@@ -17,15 +18,22 @@ use crate::{
     common::{
         jwt_manager::AuthUser,
         routing::{app_state::AppState, middlewares::auth::logged_in_required},
+        thumbnails,
     },
-    errors::{PentaractError, PentaractResult},
-    models::files::InFile,
     schemas::files::{
-        InFileSchema, InFolderSchema, SearchQuery, UploadParams, IN_FILE_SCHEMA_FIELDS_AMOUNT,
+        AttributesParams, BackgroundTransfer, BatchParams, BatchSchema, ConflictPolicy,
+        DedupeParams, DownloadQuery, DuplicatesQuery, FolderArchive, InArchiveSchema, InFileSchema,
+        InFilesSchema, InFolderSchema, InTransferSchema, PruneVersionsParams, PrunedVersionsSchema,
+        RestoreParams, SearchQuery, TransferOutcome, TransferParams, TransferredSchema, TreeQuery,
+        UploadParams, UploadedFileSchema, IN_FILE_SCHEMA_FIELDS_AMOUNT,
     },
-    services::files::FilesService,
+    schemas::presigned::InPresignSchema,
+    services::{files::FilesService, presigned::PresignedService},
 };
 
+/// Amount of archive parts waiting to be sent to a client
+const ARCHIVE_BUFFER_SIZE: usize = 4;
+
 pub struct FilesRouter;
 
 impl FilesRouter {
@@ -43,26 +51,53 @@ impl FilesRouter {
             .with_state(state)
     }
 
+    /// Operations on files of a storage, they are kept out of the files paths,
+    /// so files and folders can be named anyhow
+    pub fn get_operations_router(state: Arc<AppState>) -> Router<Arc<AppState>, axum::body::Body> {
+        Router::new()
+            .route("/trash", get(Self::list_trash).delete(Self::empty_trash))
+            .route("/trash/:trash_id", delete(Self::delete_from_trash))
+            .route("/trash/:trash_id/restore", post(Self::restore_from_trash))
+            .route("/copy", post(Self::copy))
+            .route("/move", post(Self::move_))
+            .route("/batch", post(Self::batch))
+            .route("/duplicates", get(Self::find_duplicates))
+            .route("/duplicates/dedupe", post(Self::dedupe))
+            .route("/attributes", patch(Self::update_attributes))
+            .route("/presign", post(Self::presign))
+            .route("/versions/prune", post(Self::prune_versions))
+            .route("/versions/:version_id", delete(Self::delete_version))
+            .route("/versions/:version_id/restore", post(Self::restore_version))
+            .route("/upload_archive", post(Self::upload_archive))
+            .layer(DefaultBodyLimit::disable())
+            .route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                logged_in_required,
+            ))
+            .with_state(state)
+    }
+
     async fn dynamic_get(
         State(state): State<Arc<AppState>>,
         Extension(user): Extension<AuthUser>,
         RoutePath((storage_id, path)): RoutePath<(Uuid, String)>,
         query: Query<SearchQuery>,
+        download_query: Query<DownloadQuery>,
+        tree_query: Query<TreeQuery>,
     ) -> impl IntoResponse {
         let (root_path, path) = path.split_once("/").unwrap_or((&path, ""));
         match root_path {
-            "tree" => Self::tree(state, user, storage_id, path).await,
-            "download" => Self::download(state, user, storage_id, path).await,
-            "search" => {
-                if let Some(search_path) = query.0.search_path {
-                    Self::search(state, user, storage_id, path, &search_path).await
-                } else {
-                    Err((
-                        StatusCode::UNPROCESSABLE_ENTITY,
-                        "search_path query parameter is required".to_owned(),
-                    ))
-                }
+            "tree" => Self::tree(state, user, storage_id, path, tree_query.0).await,
+            "download" if path.is_empty() || path.ends_with('/') => {
+                Self::download_archive(state, user, storage_id, path, download_query.0).await
             }
+            "download" => {
+                Self::download(state, user, storage_id, path, download_query.0.version_id).await
+            }
+            "thumbnail" => Self::thumbnail(state, user, storage_id, path).await,
+            "history" => Self::list_versions(state, user, storage_id, path).await,
+            "stat" => Self::stat(state, user, storage_id, path).await,
+            "search" => Self::search(state, user, storage_id, path, query.0).await,
             _ => Err((StatusCode::NOT_FOUND, "Not found".to_owned())),
         }
     }
@@ -72,68 +107,86 @@ impl FilesRouter {
         user: AuthUser,
         storage_id: Uuid,
         path: &str,
+        query: TreeQuery,
     ) -> Result<Response, (StatusCode, String)> {
         let fs_layer = FilesService::new(&state.db, state.tx.clone())
-            .list_dir(storage_id, path, &user)
+            .list_dir(storage_id, path, query, &user)
             .await?;
         Ok(Json(fs_layer).into_response())
     }
 
+    /// Every `file` field is saved by its filename relatively to `path`,
+    /// so folders structures are kept.
+    ///
+    /// `on_conflict` field is one of `fail`, `overwrite`, `rename` or `skip`
     async fn upload(
         State(state): State<Arc<AppState>>,
         Extension(user): Extension<AuthUser>,
         RoutePath(storage_id): RoutePath<Uuid>,
         mut multipart: Multipart,
-    ) -> Result<StatusCode, (StatusCode, String)> {
+    ) -> Result<Response, (StatusCode, String)> {
         // parsing
-        let (file, path) = {
-            let (mut file, mut filename, mut path) = (None, None, None);
+        let in_schema = {
+            let (mut files, mut path, mut on_conflict) = (vec![], None, None);
 
             // parsing
             while let Some(field) = multipart.next_field().await.unwrap() {
                 let name = field.name().unwrap().to_owned();
-                let field_filename = field.file_name().unwrap_or("unnamed").to_owned();
+                let filename = field.file_name().unwrap_or("unnamed").to_owned();
                 let data = field.bytes().await.unwrap();
 
                 match name.as_str() {
-                    "file" => {
-                        file = Some(data);
-                        filename = Some(field_filename);
-                    }
+                    "file" => files.push((filename, data)),
                     "path" => path = Some(String::from_utf8(data.to_vec()).unwrap()),
+                    "on_conflict" => on_conflict = Some(data),
                     // don't give a fuck about other fields
                     _ => (),
                 }
             }
 
-            let file = file.ok_or((StatusCode::BAD_REQUEST, "file file is required".to_owned()))?;
-            let path = path
-                .ok_or((StatusCode::BAD_REQUEST, "path file is required".to_owned()))
-                .map(|path| Self::construct_path(&path, &filename.unwrap()))??;
-            (file, path)
+            if files.is_empty() {
+                return Err((StatusCode::BAD_REQUEST, "file file is required".to_owned()));
+            }
+            let path = path.ok_or((StatusCode::BAD_REQUEST, "path file is required".to_owned()))?;
+            // the target is always a folder
+            let path = match path.trim_end_matches('/') {
+                "" => String::new(),
+                path => format!("{path}/"),
+            };
+
+            InFilesSchema {
+                storage_id,
+                path,
+                files,
+                on_conflict: Self::parse_on_conflict(on_conflict.as_ref())?,
+            }
         };
-        let size = file.len() as i64;
-        let in_file = InFile::new(path, size, storage_id);
 
-        FilesService::new(&state.db, state.tx.clone())
-            .upload_anyway(in_file, file, &user)
+        let uploaded = FilesService::new(&state.db, state.tx.clone())
+            .upload_many(in_schema, &user)
             .await?;
-        Ok(StatusCode::CREATED)
+        Ok((StatusCode::CREATED, Json(uploaded)).into_response())
     }
 
+    /// `on_conflict` field is one of `fail`, `overwrite`, `rename` or `skip`,
+    /// the response status is 200 if the file is skipped
     async fn upload_to(
         State(state): State<Arc<AppState>>,
         Extension(user): Extension<AuthUser>,
         RoutePath(storage_id): RoutePath<Uuid>,
         mut multipart: Multipart,
-    ) -> Result<StatusCode, (StatusCode, String)> {
+    ) -> Result<Response, (StatusCode, String)> {
         // parsing and validating schema
         let in_schema = {
             let mut body_parts = HashMap::with_capacity(IN_FILE_SCHEMA_FIELDS_AMOUNT);
+            let mut filename = None;
 
             // parsing
             while let Some(field) = multipart.next_field().await.unwrap() {
                 let name = field.name().unwrap().to_string();
+                if name == "file" {
+                    filename = field.file_name().map(|name| name.to_owned());
+                }
                 let data = field.bytes().await.unwrap();
                 body_parts.insert(name, data);
             }
@@ -149,15 +202,89 @@ impl FilesRouter {
                 .get("file")
                 .ok_or((StatusCode::BAD_REQUEST, "File is required".to_owned()))?;
 
-            InFileSchema::new(storage_id, path, file.clone())
+            let on_conflict = Self::parse_on_conflict(body_parts.get("on_conflict"))?;
+
+            InFileSchema::new(storage_id, path, file.clone(), filename, on_conflict)
         };
 
         // do all other stuff
-        FilesService::new(&state.db, state.tx.clone())
+        let filename = in_schema.path.clone();
+        let result = FilesService::new(&state.db, state.tx.clone())
             .upload_to(in_schema, &user)
             .await?;
 
-        Ok(StatusCode::CREATED)
+        let status = match result {
+            Some(_) => StatusCode::CREATED,
+            None => StatusCode::OK,
+        };
+        let uploaded = UploadedFileSchema::new(filename, Ok(result));
+        Ok((status, Json(uploaded)).into_response())
+    }
+
+    fn parse_on_conflict(
+        value: Option<&Bytes>,
+    ) -> Result<Option<ConflictPolicy>, (StatusCode, String)> {
+        value
+            .map(|value| {
+                std::str::from_utf8(value)
+                    .ok()
+                    .and_then(ConflictPolicy::parse)
+                    .ok_or((
+                        StatusCode::BAD_REQUEST,
+                        "Unknown conflict policy".to_owned(),
+                    ))
+            })
+            .transpose()
+    }
+
+    async fn upload_archive(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath(storage_id): RoutePath<Uuid>,
+        mut multipart: Multipart,
+    ) -> Result<Response, (StatusCode, String)> {
+        // parsing and validating schema
+        let in_schema = {
+            let mut body_parts = HashMap::new();
+
+            // parsing
+            while let Some(field) = multipart.next_field().await.unwrap() {
+                let name = field.name().unwrap().to_string();
+                let data = field.bytes().await.unwrap();
+                body_parts.insert(name, data);
+            }
+
+            // validating
+            let path = body_parts
+                .get("path")
+                .map(|path| String::from_utf8(path.to_vec()).map_err(|_| "Path cannot be parsed"))
+                .unwrap_or(Ok(String::new()))
+                .map_err(|e| (StatusCode::BAD_REQUEST, e.to_owned()))?;
+            // the target is always a folder
+            let path = match path.trim_end_matches('/') {
+                "" => String::new(),
+                path => format!("{path}/"),
+            };
+
+            let on_conflict = Self::parse_on_conflict(body_parts.get("on_conflict"))?;
+
+            let file = body_parts
+                .remove("file")
+                .ok_or((StatusCode::BAD_REQUEST, "File is required".to_owned()))?;
+
+            InArchiveSchema {
+                storage_id,
+                path,
+                on_conflict,
+                file,
+            }
+        };
+
+        let report = FilesService::new(&state.db, state.tx.clone())
+            .extract_archive(in_schema, &user)
+            .await?;
+
+        Ok((StatusCode::CREATED, Json(report)).into_response())
     }
 
     async fn create_folder(
@@ -174,13 +301,130 @@ impl FilesRouter {
         Ok(StatusCode::CREATED)
     }
 
-    #[inline]
-    fn construct_path(path: &str, filename: &str) -> PentaractResult<String> {
-        Path::new(path)
-            .join(filename)
-            .to_str()
-            .ok_or(PentaractError::InvalidPath)
-            .map(|p| p.to_string())
+    async fn copy(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath(storage_id): RoutePath<Uuid>,
+        Json(params): Json<TransferParams>,
+    ) -> Result<Response, (StatusCode, String)> {
+        let in_schema = InTransferSchema::new(storage_id, params);
+
+        let outcome = FilesService::new(&state.db, state.tx.clone())
+            .copy(in_schema, &user)
+            .await?;
+        Ok(Self::run_transfer(state, user, outcome))
+    }
+
+    async fn move_(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath(storage_id): RoutePath<Uuid>,
+        Json(params): Json<TransferParams>,
+    ) -> Result<Response, (StatusCode, String)> {
+        let in_schema = InTransferSchema::new(storage_id, params);
+
+        let outcome = FilesService::new(&state.db, state.tx.clone())
+            .move_(in_schema, &user)
+            .await?;
+        Ok(Self::run_transfer(state, user, outcome))
+    }
+
+    /// Always responds with the result of every operation,
+    /// even if the whole batch is rolled back
+    async fn batch(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath(storage_id): RoutePath<Uuid>,
+        Json(params): Json<BatchParams>,
+    ) -> Result<Response, (StatusCode, String)> {
+        let outcome = FilesService::new(&state.db, state.tx.clone())
+            .batch(storage_id, params, &user)
+            .await?;
+
+        for transfer in outcome.background {
+            Self::spawn_transfer(state.clone(), user.clone(), transfer);
+        }
+
+        Ok(Json(BatchSchema {
+            items: outcome.items,
+        })
+        .into_response())
+    }
+
+    async fn find_duplicates(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath(storage_id): RoutePath<Uuid>,
+        Query(query): Query<DuplicatesQuery>,
+    ) -> Result<Response, (StatusCode, String)> {
+        let duplicates = FilesService::new(&state.db, state.tx.clone())
+            .find_duplicates(Some(storage_id), query, &user)
+            .await?;
+        Ok(Json(duplicates).into_response())
+    }
+
+    async fn dedupe(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath(storage_id): RoutePath<Uuid>,
+        params: Option<Json<DedupeParams>>,
+    ) -> Result<Response, (StatusCode, String)> {
+        let Json(params) = params.unwrap_or_default();
+
+        let deduped = FilesService::new(&state.db, state.tx.clone())
+            .dedupe(storage_id, params, &user)
+            .await?;
+        Ok(Json(deduped).into_response())
+    }
+
+    /// Issues a URL which allows to download or upload the file without a session
+    async fn presign(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath(storage_id): RoutePath<Uuid>,
+        Json(in_schema): Json<InPresignSchema>,
+    ) -> Result<Response, (StatusCode, String)> {
+        let presigned =
+            PresignedService::new(&state.db, state.tx.clone(), &state.config.secret_key)
+                .presign(storage_id, in_schema, &user)
+                .await?;
+        Ok((StatusCode::CREATED, Json(presigned)).into_response())
+    }
+
+    async fn update_attributes(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath(storage_id): RoutePath<Uuid>,
+        Json(params): Json<AttributesParams>,
+    ) -> Result<Response, (StatusCode, String)> {
+        let el = FilesService::new(&state.db, state.tx.clone())
+            .update_attributes(storage_id, params, &user)
+            .await?;
+        Ok(Json(el).into_response())
+    }
+
+    /// Transfers between storages take a while, so they are done in background
+    fn run_transfer(state: Arc<AppState>, user: AuthUser, outcome: TransferOutcome) -> Response {
+        let schema = TransferredSchema { path: outcome.path };
+
+        let Some(transfer) = outcome.background else {
+            return (StatusCode::OK, Json(schema)).into_response();
+        };
+
+        Self::spawn_transfer(state, user, transfer);
+
+        (StatusCode::ACCEPTED, Json(schema)).into_response()
+    }
+
+    pub fn spawn_transfer(state: Arc<AppState>, user: AuthUser, transfer: BackgroundTransfer) {
+        tokio::spawn(async move {
+            let result = FilesService::new(&state.db, state.tx.clone())
+                .transfer(transfer, &user)
+                .await;
+            if let Err(e) = result {
+                tracing::error!("{e}");
+            }
+        });
     }
 
     async fn download(
@@ -188,32 +432,99 @@ impl FilesRouter {
         user: AuthUser,
         storage_id: Uuid,
         path: &str,
+        version_id: Option<Uuid>,
     ) -> Result<Response, (StatusCode, String)> {
-        FilesService::new(&state.db, state.tx.clone())
-            .download(path, storage_id, &user)
-            .await
-            .map(|data| {
-                let filename = Path::new(&path)
-                    .file_name()
-                    .map(|name| name.to_str().unwrap_or_default())
-                    .unwrap_or("unnamed.bin");
-                let content_type = mime_guess::from_path(filename)
-                    .first_or_octet_stream()
-                    .to_string();
-                let bytes = Bytes::from(data);
-                let body = Full::new(bytes);
-
-                let headers = AppendHeaders([
-                    (header::CONTENT_TYPE, content_type),
-                    (
-                        header::CONTENT_DISPOSITION,
-                        format!("attachment; filename=\"{filename}\""),
-                    ),
-                ]);
-
-                (headers, body).into_response()
-            })
-            .map_err(|e| <(StatusCode, String)>::from(e))
+        let data = FilesService::new(&state.db, state.tx.clone())
+            .download(path, storage_id, version_id, &user)
+            .await?;
+
+        let filename = Path::new(&path)
+            .file_name()
+            .map(|name| name.to_str().unwrap_or_default())
+            .unwrap_or("unnamed.bin");
+        Ok(Self::file_response(filename, data))
+    }
+
+    pub fn file_response(filename: &str, data: Vec<u8>) -> Response {
+        let content_type = mime_guess::from_path(filename)
+            .first_or_octet_stream()
+            .to_string();
+        let bytes = Bytes::from(data);
+        let body = Full::new(bytes);
+
+        let headers = AppendHeaders([
+            (header::CONTENT_TYPE, content_type),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ]);
+
+        (headers, body).into_response()
+    }
+
+    async fn thumbnail(
+        state: Arc<AppState>,
+        user: AuthUser,
+        storage_id: Uuid,
+        path: &str,
+    ) -> Result<Response, (StatusCode, String)> {
+        let thumbnail = FilesService::new(&state.db, state.tx.clone())
+            .thumbnail(path, storage_id, &state.config.thumbnails_dir, &user)
+            .await?;
+
+        let headers = AppendHeaders([
+            (header::CONTENT_TYPE, thumbnails::content_type(&thumbnail)),
+            (header::CACHE_CONTROL, "private, max-age=3600"),
+        ]);
+        Ok((headers, thumbnail).into_response())
+    }
+
+    /// Archives are streamed while files are being downloaded,
+    /// so the response starts right after the folder is listed
+    async fn download_archive(
+        state: Arc<AppState>,
+        user: AuthUser,
+        storage_id: Uuid,
+        path: &str,
+        query: DownloadQuery,
+    ) -> Result<Response, (StatusCode, String)> {
+        let archive = FilesService::new(&state.db, state.tx.clone())
+            .prepare_archive(path, storage_id, query.format, &user)
+            .await?;
+
+        Ok(Self::archive_response(state, archive))
+    }
+
+    /// Archives are written in background and streamed as they're being written
+    pub fn archive_response(state: Arc<AppState>, archive: FolderArchive) -> Response {
+        let name = if archive.name.is_empty() {
+            "files"
+        } else {
+            &archive.name
+        };
+        let headers = AppendHeaders([
+            (
+                header::CONTENT_TYPE,
+                archive.format.content_type().to_owned(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{name}.{}\"",
+                    archive.format.extension()
+                ),
+            ),
+        ]);
+
+        let (tx, rx) = mpsc::channel(ARCHIVE_BUFFER_SIZE);
+        tokio::spawn(async move {
+            FilesService::new(&state.db, state.tx.clone())
+                .write_archive(archive, tx)
+                .await;
+        });
+
+        (headers, StreamBody::new(rx)).into_response()
     }
 
     ///
@@ -224,10 +535,10 @@ impl FilesRouter {
         user: AuthUser,
         storage_id: Uuid,
         path: &str,
-        search_path: &str,
+        query: SearchQuery,
     ) -> Result<Response, (StatusCode, String)> {
         FilesService::new(&state.db, state.tx.clone())
-            .search(storage_id, path, search_path, &user)
+            .search(storage_id, path, query, &user)
             .await
             .map(|files| Json(files).into_response())
             .map_err(|e| <(StatusCode, String)>::from(e))
@@ -245,4 +556,114 @@ impl FilesRouter {
 
         Ok(())
     }
+
+    async fn list_trash(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath(storage_id): RoutePath<Uuid>,
+    ) -> Result<Response, (StatusCode, String)> {
+        let trash = FilesService::new(&state.db, state.tx.clone())
+            .list_trash(storage_id, &user)
+            .await?;
+        Ok(Json(trash).into_response())
+    }
+
+    async fn restore_from_trash(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath((storage_id, trash_id)): RoutePath<(Uuid, Uuid)>,
+        query: Query<RestoreParams>,
+    ) -> Result<Response, (StatusCode, String)> {
+        let restored = FilesService::new(&state.db, state.tx.clone())
+            .restore_from_trash(trash_id, storage_id, query.0.on_conflict, &user)
+            .await?;
+        Ok(Json(restored).into_response())
+    }
+
+    async fn delete_from_trash(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath((storage_id, trash_id)): RoutePath<(Uuid, Uuid)>,
+    ) -> Result<StatusCode, (StatusCode, String)> {
+        FilesService::new(&state.db, state.tx.clone())
+            .delete_from_trash(trash_id, storage_id, &user)
+            .await?;
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    async fn empty_trash(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath(storage_id): RoutePath<Uuid>,
+    ) -> Result<StatusCode, (StatusCode, String)> {
+        FilesService::new(&state.db, state.tx.clone())
+            .empty_trash(storage_id, &user)
+            .await?;
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    async fn stat(
+        state: Arc<AppState>,
+        user: AuthUser,
+        storage_id: Uuid,
+        path: &str,
+    ) -> Result<Response, (StatusCode, String)> {
+        let stat = FilesService::new(&state.db, state.tx.clone())
+            .stat(path, storage_id, &user)
+            .await?;
+        Ok(Json(stat).into_response())
+    }
+
+    async fn list_versions(
+        state: Arc<AppState>,
+        user: AuthUser,
+        storage_id: Uuid,
+        path: &str,
+    ) -> Result<Response, (StatusCode, String)> {
+        let versions = FilesService::new(&state.db, state.tx.clone())
+            .list_versions(path, storage_id, &user)
+            .await?;
+        Ok(Json(versions).into_response())
+    }
+
+    async fn restore_version(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath((storage_id, version_id)): RoutePath<(Uuid, Uuid)>,
+    ) -> Result<StatusCode, (StatusCode, String)> {
+        FilesService::new(&state.db, state.tx.clone())
+            .restore_version(version_id, storage_id, &user)
+            .await?;
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    async fn delete_version(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath((storage_id, version_id)): RoutePath<(Uuid, Uuid)>,
+    ) -> Result<StatusCode, (StatusCode, String)> {
+        FilesService::new(&state.db, state.tx.clone())
+            .delete_version(version_id, storage_id, &user)
+            .await?;
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    async fn prune_versions(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath(storage_id): RoutePath<Uuid>,
+        Json(params): Json<PruneVersionsParams>,
+    ) -> Result<Response, (StatusCode, String)> {
+        if params.keep_last.is_none() && params.older_than_days.is_none() {
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "keep_last or older_than_days is required".to_owned(),
+            ));
+        }
+
+        let deleted_amount = FilesService::new(&state.db, state.tx.clone())
+            .prune_versions(storage_id, params, &user)
+            .await?;
+        Ok(Json(PrunedVersionsSchema { deleted_amount }).into_response())
+    }
 }
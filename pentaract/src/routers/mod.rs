@@ -1,5 +1,12 @@
+pub mod app_passwords;
 pub mod auth;
+pub mod dav;
+pub mod file_drops;
 pub mod files;
+pub mod presigned;
+pub mod s3;
+pub mod s3_keys;
+pub mod share_links;
 pub mod storage_workers;
 pub mod storages;
 pub mod users;
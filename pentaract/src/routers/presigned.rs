@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{DefaultBodyLimit, Path as RoutePath, Query, State},
+    http::StatusCode,
+    response::Response,
+    routing::get,
+    Router,
+};
+use tokio_util::bytes::Bytes;
+use uuid::Uuid;
+
+use crate::{
+    common::routing::app_state::AppState, schemas::presigned::PresignedQuery,
+    services::presigned::PresignedService,
+};
+
+use super::files::FilesRouter;
+
+pub struct PresignedRouter;
+
+impl PresignedRouter {
+    /// Presigned URLs are checked by their signatures instead of sessions
+    pub fn get_public_router(state: Arc<AppState>) -> Router {
+        Router::new()
+            .route("/:storage_id/*path", get(Self::download).put(Self::upload))
+            .layer(DefaultBodyLimit::disable())
+            .with_state(state)
+    }
+
+    async fn download(
+        State(state): State<Arc<AppState>>,
+        RoutePath((storage_id, path)): RoutePath<(Uuid, String)>,
+        Query(query): Query<PresignedQuery>,
+    ) -> Result<Response, (StatusCode, String)> {
+        let (name, data) =
+            PresignedService::new(&state.db, state.tx.clone(), &state.config.secret_key)
+                .download(storage_id, &path, query)
+                .await?;
+        Ok(FilesRouter::file_response(&name, data))
+    }
+
+    /// File content is the whole request body, like in S3
+    async fn upload(
+        State(state): State<Arc<AppState>>,
+        RoutePath((storage_id, path)): RoutePath<(Uuid, String)>,
+        Query(query): Query<PresignedQuery>,
+        body: Bytes,
+    ) -> Result<StatusCode, (StatusCode, String)> {
+        PresignedService::new(&state.db, state.tx.clone(), &state.config.secret_key)
+            .upload(storage_id, &path, body, query)
+            .await?;
+        Ok(StatusCode::CREATED)
+    }
+}
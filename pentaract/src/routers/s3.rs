@@ -0,0 +1,490 @@
+use std::sync::Arc;
+
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{OriginalUri, Query, RawBody, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri},
+    response::{AppendHeaders, IntoResponse, Response},
+    routing::any,
+    Router,
+};
+use uuid::Uuid;
+
+use crate::{
+    common::{
+        jwt_manager::AuthUser,
+        routing::app_state::AppState,
+        s3::{self, S3Error},
+        sigv4::{BodyDecoder, Signature},
+        webdav,
+    },
+    errors::PentaractError,
+    models::files::S3Object,
+    schemas::s3::S3Query,
+    services::{s3::S3Service, s3_keys::S3KeysService},
+};
+
+/// S3 clients are given an endpoint with no trailing slash and add it themselves,
+/// which nested routers don't match, so routes are added under this path instead
+const MOUNT_PATH: &str = "/s3";
+
+/// Objects up to 5 GiB can be put by a single request, as in AWS,
+/// and `aws-chunked` encoding adds signatures to every chunk of them
+const MAX_BODY_SIZE: usize = 5 * 1024 * 1024 * 1024 + 64 * 1024 * 1024;
+
+type S3Result = Result<Response, S3Error>;
+
+/// NOTE:
+///
+/// an S3-compatible gateway with path-style addressing: `/s3/<storage_id>/<key>`.
+///
+/// Requests are authenticated with AWS Signature Version 4 made with S3 keys,
+/// both signed headers and presigned URLs are supported
+pub struct S3Router;
+
+impl S3Router {
+    pub fn get_router(state: Arc<AppState>) -> Router {
+        Router::new()
+            .route(MOUNT_PATH, any(Self::handle))
+            .route(&format!("{MOUNT_PATH}/"), any(Self::handle))
+            .route(&format!("{MOUNT_PATH}/*path"), any(Self::handle))
+            .with_state(state)
+    }
+
+    async fn handle(
+        State(state): State<Arc<AppState>>,
+        OriginalUri(original_uri): OriginalUri,
+        method: Method,
+        uri: Uri,
+        headers: HeaderMap,
+        RawBody(body): RawBody,
+    ) -> Response {
+        let request = match Self::authenticate(state, &original_uri, &method, headers, body).await {
+            Ok(request) => request,
+            Err(e) => return e.into_response(),
+        };
+
+        request
+            .dispatch(&method, &uri)
+            .await
+            .unwrap_or_else(IntoResponse::into_response)
+    }
+
+    /// Signatures are made of the path as clients send it, so it's taken from the original URI.
+    ///
+    /// The body is read only once the request is signed by a known key
+    async fn authenticate(
+        state: Arc<AppState>,
+        original_uri: &Uri,
+        method: &Method,
+        headers: HeaderMap,
+        body: Body,
+    ) -> Result<S3Request, S3Error> {
+        let signature = Signature::parse(&headers, original_uri.query())?
+            .ok_or(PentaractError::NotAuthenticated)?;
+
+        let keys_service = S3KeysService::new(&state.db, &state.config.tokens_encryption_key);
+        let (user, secret) = keys_service.get_signer(&signature.access_key_id).await?;
+        signature.verify(
+            method.as_str(),
+            original_uri.path(),
+            original_uri.query(),
+            &headers,
+            &secret,
+        )?;
+        let body = Self::read_body(signature.body_decoder(&headers, &secret), body).await?;
+        keys_service.mark_as_used(&signature.access_key_id).await?;
+
+        Ok(S3Request {
+            state,
+            user,
+            headers,
+            body,
+        })
+    }
+
+    async fn read_body(mut decoder: BodyDecoder, mut body: Body) -> Result<Bytes, S3Error> {
+        let mut received = 0;
+        while let Some(data) = body.data().await {
+            let data = data.map_err(|e| {
+                S3Error::new(StatusCode::BAD_REQUEST, "IncompleteBody", &e.to_string())
+            })?;
+
+            received += data.len();
+            if received > MAX_BODY_SIZE {
+                return Err(PentaractError::FileTooLarge(MAX_BODY_SIZE as i64).into());
+            }
+            decoder.push(&data)?;
+        }
+
+        Ok(decoder.finish()?)
+    }
+}
+
+struct S3Request {
+    state: Arc<AppState>,
+    user: AuthUser,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl S3Request {
+    fn service(&self) -> S3Service<'_> {
+        S3Service::new(&self.state.db, self.state.tx.clone())
+    }
+
+    async fn dispatch(&self, method: &Method, uri: &Uri) -> S3Result {
+        let Query(query) = Query::<S3Query>::try_from_uri(uri).map_err(|e| {
+            S3Error::new(StatusCode::BAD_REQUEST, "InvalidArgument", &e.to_string())
+        })?;
+        let path = uri.path().strip_prefix(MOUNT_PATH).unwrap_or_default();
+        let path = webdav::decode_href(path.trim_start_matches('/'))?;
+
+        let (bucket, key) = path.split_once('/').unwrap_or((&path, ""));
+        if bucket.is_empty() {
+            return match *method {
+                Method::GET => self.list_buckets().await,
+                _ => Err(S3Error::not_implemented()),
+            };
+        }
+        let storage_id = Self::parse_bucket(bucket)?;
+
+        let copy_source = self.header("x-amz-copy-source");
+        match (method.clone(), key.is_empty()) {
+            (Method::HEAD, true) => self.head_bucket(storage_id).await,
+            (Method::GET, true) if query.location.is_some() => {
+                self.get_bucket_location(storage_id).await
+            }
+            (Method::GET, true) if query.uploads.is_none() => {
+                self.list_objects(storage_id, query).await
+            }
+            (_, true) => Err(S3Error::not_implemented()),
+            (Method::GET, false) if query.upload_id.is_none() => {
+                self.get_object(storage_id, key).await
+            }
+            (Method::HEAD, false) => self.head_object(storage_id, key).await,
+            (Method::PUT, false) => match (query.upload_id, query.part_number, copy_source) {
+                (Some(upload_id), Some(part_number), None) => {
+                    self.upload_part(storage_id, key, &upload_id, part_number)
+                        .await
+                }
+                (None, None, Some(copy_source)) => {
+                    self.copy_object(storage_id, key, copy_source).await
+                }
+                (None, None, None) => self.put_object(storage_id, key).await,
+                _ => Err(S3Error::not_implemented()),
+            },
+            (Method::POST, false) if query.uploads.is_some() => {
+                self.create_multipart_upload(storage_id, bucket, key).await
+            }
+            (Method::POST, false) => match query.upload_id {
+                Some(upload_id) => {
+                    self.complete_multipart_upload(storage_id, bucket, key, &upload_id)
+                        .await
+                }
+                None => Err(S3Error::not_implemented()),
+            },
+            (Method::DELETE, false) => match query.upload_id {
+                Some(upload_id) => {
+                    self.abort_multipart_upload(storage_id, key, &upload_id)
+                        .await
+                }
+                None => self.delete_object(storage_id, key).await,
+            },
+            _ => Err(S3Error::not_implemented()),
+        }
+    }
+
+    async fn list_buckets(&self) -> S3Result {
+        let storages = self.service().list_buckets(&self.user).await?;
+        let xml = s3::list_buckets(&storages, &self.user.email);
+        Ok((s3::xml_headers(), xml).into_response())
+    }
+
+    async fn head_bucket(&self, storage_id: Uuid) -> S3Result {
+        self.service().head_bucket(storage_id, &self.user).await?;
+        Ok(StatusCode::OK.into_response())
+    }
+
+    /// Buckets are not bound to regions, so the default one is responded
+    async fn get_bucket_location(&self, storage_id: Uuid) -> S3Result {
+        self.service().head_bucket(storage_id, &self.user).await?;
+        Ok((s3::xml_headers(), s3::location_constraint()).into_response())
+    }
+
+    async fn list_objects(&self, storage_id: Uuid, query: S3Query) -> S3Result {
+        if query
+            .delimiter
+            .as_deref()
+            .is_some_and(|delimiter| delimiter != "/")
+        {
+            return Err(S3Error::not_implemented());
+        }
+
+        let listing = self
+            .service()
+            .list_objects(storage_id, query, &self.user)
+            .await?;
+        Ok((s3::xml_headers(), s3::list_objects(&listing)).into_response())
+    }
+
+    async fn head_object(&self, storage_id: Uuid, key: &str) -> S3Result {
+        let object = self
+            .service()
+            .head_object(storage_id, key, &self.user)
+            .await?;
+
+        let headers = AppendHeaders([(header::CONTENT_LENGTH, object.size.to_string())]);
+        let mut response = (headers, ()).into_response();
+        Self::set_object_headers(&mut response, &object);
+        Ok(response)
+    }
+
+    /// Single byte ranges are supported, other ones are ignored
+    async fn get_object(&self, storage_id: Uuid, key: &str) -> S3Result {
+        let (object, mut data) = self
+            .service()
+            .get_object(storage_id, key, &self.user)
+            .await?;
+
+        let range = match self.header(header::RANGE.as_str()) {
+            Some(range) => Self::parse_range(range, data.len())?,
+            None => None,
+        };
+        let mut response = match range {
+            Some((start, end)) => {
+                let content_range = format!("bytes {start}-{end}/{}", data.len());
+                data.truncate(end + 1);
+                data.drain(..start);
+                let headers = AppendHeaders([(header::CONTENT_RANGE, content_range)]);
+                (StatusCode::PARTIAL_CONTENT, headers, data).into_response()
+            }
+            None => data.into_response(),
+        };
+
+        Self::set_object_headers(&mut response, &object);
+        Ok(response)
+    }
+
+    async fn put_object(&self, storage_id: Uuid, key: &str) -> S3Result {
+        let etag = self
+            .service()
+            .put_object(storage_id, key, self.body.clone(), &self.user)
+            .await?;
+
+        Ok((AppendHeaders([(header::ETAG, etag)]), ()).into_response())
+    }
+
+    /// The source is `<bucket>/<key>`, url encoded
+    async fn copy_object(&self, storage_id: Uuid, key: &str, copy_source: &str) -> S3Result {
+        if copy_source.contains("?versionId=") {
+            return Err(S3Error::not_implemented());
+        }
+        let copy_source = webdav::decode_href(copy_source.trim_start_matches('/'))?;
+        let (from_bucket, from) = copy_source.split_once('/').ok_or(S3Error::new(
+            StatusCode::BAD_REQUEST,
+            "InvalidArgument",
+            "Copy source must be in the form of bucket/key",
+        ))?;
+
+        let object = self
+            .service()
+            .copy_object(
+                Self::parse_bucket(from_bucket)?,
+                from,
+                storage_id,
+                key,
+                &self.user,
+            )
+            .await?;
+        Ok((s3::xml_headers(), s3::copy_object_result(&object)).into_response())
+    }
+
+    /// Deleting is idempotent, so missing objects are not reported
+    async fn delete_object(&self, storage_id: Uuid, key: &str) -> S3Result {
+        self.service()
+            .delete_object(storage_id, key, &self.user)
+            .await?;
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+
+    async fn create_multipart_upload(&self, storage_id: Uuid, bucket: &str, key: &str) -> S3Result {
+        let upload_id = self
+            .service()
+            .create_multipart_upload(storage_id, key, &self.user)
+            .await?;
+
+        let xml = s3::initiate_multipart_upload(bucket, key, &upload_id.to_string());
+        Ok((s3::xml_headers(), xml).into_response())
+    }
+
+    async fn upload_part(
+        &self,
+        storage_id: Uuid,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+    ) -> S3Result {
+        let etag = self
+            .service()
+            .upload_part(
+                storage_id,
+                key,
+                upload_id,
+                part_number,
+                self.body.clone(),
+                &self.user,
+            )
+            .await?;
+
+        Ok((AppendHeaders([(header::ETAG, etag)]), ()).into_response())
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        storage_id: Uuid,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+    ) -> S3Result {
+        let parts = s3::parse_complete_multipart_upload(&self.body)?;
+        let object = self
+            .service()
+            .complete_multipart_upload(storage_id, key, upload_id, parts, &self.user)
+            .await?;
+
+        let xml = s3::complete_multipart_upload(bucket, key, &object);
+        Ok((s3::xml_headers(), xml).into_response())
+    }
+
+    async fn abort_multipart_upload(
+        &self,
+        storage_id: Uuid,
+        key: &str,
+        upload_id: &str,
+    ) -> S3Result {
+        self.service()
+            .abort_multipart_upload(storage_id, key, upload_id, &self.user)
+            .await?;
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+
+    /// Buckets are named by storages ids
+    fn parse_bucket(bucket: &str) -> Result<Uuid, S3Error> {
+        Uuid::parse_str(bucket).map_err(|_| {
+            S3Error::new(
+                StatusCode::NOT_FOUND,
+                "NoSuchBucket",
+                "The specified bucket does not exist",
+            )
+        })
+    }
+
+    /// Parses `bytes=<start>-<end>`, `bytes=<start>-` and `bytes=-<suffix length>`
+    /// into inclusive bounds
+    fn parse_range(range: &str, size: usize) -> Result<Option<(usize, usize)>, S3Error> {
+        let Some((start, end)) = range
+            .strip_prefix("bytes=")
+            .filter(|range| !range.contains(','))
+            .and_then(|range| range.split_once('-'))
+        else {
+            return Ok(None);
+        };
+        let parse = |bound: &str| bound.trim().parse::<usize>().ok();
+
+        let bounds = match (parse(start), parse(end)) {
+            (Some(start), Some(end)) if start <= end => {
+                Some((start, end.min(size.wrapping_sub(1))))
+            }
+            (Some(start), None) if end.trim().is_empty() => Some((start, size.wrapping_sub(1))),
+            (None, Some(suffix)) if start.trim().is_empty() && suffix > 0 => {
+                Some((size.saturating_sub(suffix), size.wrapping_sub(1)))
+            }
+            _ => return Ok(None),
+        };
+
+        match bounds {
+            Some((start, end)) if start < size && start <= end => Ok(Some((start, end))),
+            _ => Err(S3Error::new(
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                "InvalidRange",
+                "The requested range is not satisfiable",
+            )),
+        }
+    }
+
+    fn set_object_headers(response: &mut Response, object: &S3Object) {
+        let content_type = object
+            .mime_type
+            .as_deref()
+            .unwrap_or("application/octet-stream");
+
+        let headers = response.headers_mut();
+        for (name, value) in [
+            (header::CONTENT_TYPE, content_type.to_owned()),
+            (header::ETAG, object.etag()),
+            (header::LAST_MODIFIED, webdav::http_date(object.updated_at)),
+            (header::ACCEPT_RANGES, "bytes".to_owned()),
+        ] {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(name, value);
+            }
+        }
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(range: &str, size: usize) -> Result<Option<(usize, usize)>, StatusCode> {
+        S3Request::parse_range(range, size).map_err(|e| e.status)
+    }
+
+    #[test]
+    fn parse_range() {
+        assert_eq!(range("bytes=0-9", 100), Ok(Some((0, 9))));
+        assert_eq!(range("bytes=90-200", 100), Ok(Some((90, 99))));
+        assert_eq!(range("bytes=95-", 100), Ok(Some((95, 99))));
+        assert_eq!(range("bytes=-10", 100), Ok(Some((90, 99))));
+        assert_eq!(range("bytes=-200", 100), Ok(Some((0, 99))));
+        assert_eq!(range("bytes=99-99", 100), Ok(Some((99, 99))));
+    }
+
+    #[test]
+    fn parse_unsatisfiable_range() {
+        let unsatisfiable = Err(StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(range("bytes=100-", 100), unsatisfiable);
+        assert_eq!(range("bytes=100-200", 100), unsatisfiable);
+    }
+
+    /// Invalid ranges are ignored, so the whole object is responded
+    #[test]
+    fn parse_invalid_range() {
+        for invalid in [
+            "items=0-9",
+            "bytes=0-9,20-29",
+            "bytes=9-0",
+            "bytes=-0",
+            "bytes=a-b",
+            "bytes=",
+            "bytes=-",
+        ] {
+            assert_eq!(range(invalid, 100), Ok(None), "{invalid}");
+        }
+    }
+
+    /// Empty objects don't have any byte to respond, as in AWS
+    #[test]
+    fn parse_range_of_empty_object() {
+        let unsatisfiable = Err(StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(range("bytes=0-9", 0), unsatisfiable);
+        assert_eq!(range("bytes=0-", 0), unsatisfiable);
+        assert_eq!(range("bytes=-10", 0), unsatisfiable);
+        assert_eq!(range("bytes=9-0", 0), Ok(None));
+    }
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::{delete, get},
+    Extension, Json, Router,
+};
+use uuid::Uuid;
+
+use crate::{
+    common::{
+        jwt_manager::AuthUser,
+        routing::{app_state::AppState, middlewares::auth::logged_in_required},
+    },
+    schemas::s3_keys::{InS3KeySchema, S3KeysListSchema},
+    services::s3_keys::S3KeysService,
+};
+
+pub struct S3KeysRouter;
+
+impl S3KeysRouter {
+    pub fn get_router(state: Arc<AppState>) -> Router {
+        Router::new()
+            .route("/", get(Self::list).post(Self::create))
+            .route("/:s3_key_id", delete(Self::revoke))
+            .route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                logged_in_required,
+            ))
+            .with_state(state)
+    }
+
+    async fn create(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        Json(in_schema): Json<InS3KeySchema>,
+    ) -> impl IntoResponse {
+        let s3_key = S3KeysService::new(&state.db, &state.config.tokens_encryption_key)
+            .create(in_schema, &user)
+            .await?;
+        Ok::<_, (StatusCode, String)>((StatusCode::CREATED, Json(s3_key)))
+    }
+
+    async fn list(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+    ) -> impl IntoResponse {
+        let s3_keys = S3KeysService::new(&state.db, &state.config.tokens_encryption_key)
+            .list(&user)
+            .await
+            .map(S3KeysListSchema::new)?;
+        Ok::<_, (StatusCode, String)>(Json(s3_keys))
+    }
+
+    async fn revoke(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        Path(id): Path<Uuid>,
+    ) -> impl IntoResponse {
+        S3KeysService::new(&state.db, &state.config.tokens_encryption_key)
+            .revoke(id, &user)
+            .await?;
+        Ok::<_, (StatusCode, String)>(StatusCode::NO_CONTENT)
+    }
+}
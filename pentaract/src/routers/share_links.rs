@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path as RoutePath, Query, State},
+    http::{HeaderMap, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Extension, Json, Router,
+};
+use uuid::Uuid;
+
+use crate::{
+    common::{
+        jwt_manager::AuthUser,
+        routing::{app_state::AppState, middlewares::auth::logged_in_required},
+    },
+    schemas::{
+        files::{DownloadQuery, TreeQuery},
+        share_links::{InShareLinkSchema, ShareLinksListSchema, SharedDownload},
+    },
+    services::share_links::ShareLinksService,
+};
+
+use super::files::FilesRouter;
+
+const PASSWORD_HEADER: &str = "X-Share-Password";
+
+pub struct ShareLinksRouter;
+
+impl ShareLinksRouter {
+    /// Links management, it's nested into storages routes
+    pub fn get_router(state: Arc<AppState>) -> Router<Arc<AppState>, axum::body::Body> {
+        Router::new()
+            .route("/", get(Self::list).post(Self::create))
+            .route("/:share_link_id", delete(Self::revoke))
+            .route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                logged_in_required,
+            ))
+            .with_state(state)
+    }
+
+    /// Shared content is available without authentication
+    pub fn get_public_router(state: Arc<AppState>) -> Router {
+        Router::new()
+            .route("/:token", get(Self::stat))
+            .route("/:token/*path", get(Self::dynamic_get))
+            .with_state(state)
+    }
+
+    async fn create(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath(storage_id): RoutePath<Uuid>,
+        Json(in_schema): Json<InShareLinkSchema>,
+    ) -> Result<impl IntoResponse, (StatusCode, String)> {
+        let share_link = ShareLinksService::new(&state.db, state.tx.clone())
+            .create(storage_id, in_schema, &user)
+            .await?;
+        Ok((StatusCode::CREATED, Json(share_link)))
+    }
+
+    async fn list(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath(storage_id): RoutePath<Uuid>,
+    ) -> Result<impl IntoResponse, (StatusCode, String)> {
+        let share_links = ShareLinksService::new(&state.db, state.tx.clone())
+            .list(storage_id, &user)
+            .await?;
+        Ok(Json(ShareLinksListSchema::new(share_links)))
+    }
+
+    async fn revoke(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        RoutePath((storage_id, share_link_id)): RoutePath<(Uuid, Uuid)>,
+    ) -> Result<StatusCode, (StatusCode, String)> {
+        ShareLinksService::new(&state.db, state.tx.clone())
+            .revoke(storage_id, share_link_id, &user)
+            .await?;
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    async fn stat(
+        State(state): State<Arc<AppState>>,
+        RoutePath(token): RoutePath<String>,
+        headers: HeaderMap,
+    ) -> Result<Response, (StatusCode, String)> {
+        let password = Self::password(&headers);
+
+        let el = ShareLinksService::new(&state.db, state.tx.clone())
+            .stat(&token, password)
+            .await?;
+        Ok(Json(el).into_response())
+    }
+
+    async fn dynamic_get(
+        State(state): State<Arc<AppState>>,
+        RoutePath((token, path)): RoutePath<(String, String)>,
+        headers: HeaderMap,
+        tree_query: Query<TreeQuery>,
+        download_query: Query<DownloadQuery>,
+    ) -> Result<Response, (StatusCode, String)> {
+        let password = Self::password(&headers);
+        let service = ShareLinksService::new(&state.db, state.tx.clone());
+
+        let (root_path, path) = path.split_once('/').unwrap_or((&path, ""));
+        match root_path {
+            "tree" => {
+                let fs_layer = service
+                    .list_dir(&token, path, tree_query.0, password)
+                    .await?;
+                Ok(Json(fs_layer).into_response())
+            }
+            "download" => {
+                let shared = service
+                    .download(&token, path, download_query.format, password)
+                    .await?;
+                let response = match shared {
+                    SharedDownload::File { name, data } => FilesRouter::file_response(&name, data),
+                    SharedDownload::Archive(archive) => {
+                        FilesRouter::archive_response(state.clone(), archive)
+                    }
+                };
+                Ok(response)
+            }
+            _ => Err((StatusCode::NOT_FOUND, "Not found".to_owned())),
+        }
+    }
+
+    /// Passwords are taken from the header only, as query strings end up in logs and histories
+    #[inline]
+    fn password(headers: &HeaderMap) -> Option<&str> {
+        headers
+            .get(PASSWORD_HEADER)
+            .and_then(|value| value.to_str().ok())
+    }
+}
@@ -15,7 +15,8 @@ use crate::{
         routing::{app_state::AppState, middlewares::auth::logged_in_required},
     },
     schemas::storage_workers::{
-        HasStorageWorkers, InStorageWorkerSchema, StorageWorkersStorageIDQuery,
+        HasStorageWorkers, InStorageWorkerSchema, StorageWorkersStatsQuery,
+        StorageWorkersStorageIDQuery,
     },
     services::storage_workers::StorageWorkersService,
 };
@@ -27,6 +28,7 @@ impl StorageWorkersRouter {
         Router::new()
             .route("/", get(Self::list).post(Self::create))
             .route("/has_workers", get(Self::has_storages_workers))
+            .route("/stats", get(Self::stats))
             .route_layer(middleware::from_fn_with_state(
                 state.clone(),
                 logged_in_required,
@@ -39,7 +41,7 @@ impl StorageWorkersRouter {
         Extension(user): Extension<AuthUser>,
         Json(in_schema): Json<InStorageWorkerSchema>,
     ) -> impl IntoResponse {
-        let sw = StorageWorkersService::new(&state.db)
+        let sw = StorageWorkersService::new(&state.db, &state.config.tokens_encryption_key)
             .create(in_schema, &user)
             .await?;
         Ok::<_, (StatusCode, String)>((StatusCode::CREATED, Json(sw)))
@@ -49,7 +51,9 @@ impl StorageWorkersRouter {
         State(state): State<Arc<AppState>>,
         Extension(user): Extension<AuthUser>,
     ) -> impl IntoResponse {
-        let sws = StorageWorkersService::new(&state.db).list(&user).await?;
+        let sws = StorageWorkersService::new(&state.db, &state.config.tokens_encryption_key)
+            .list(&user)
+            .await?;
         Ok::<_, (StatusCode, String)>((StatusCode::OK, Json(sws)))
     }
 
@@ -58,9 +62,20 @@ impl StorageWorkersRouter {
         Extension(user): Extension<AuthUser>,
         query: Query<StorageWorkersStorageIDQuery>,
     ) -> Result<Response, (StatusCode, String)> {
-        let has = StorageWorkersService::new(&state.db)
+        let has = StorageWorkersService::new(&state.db, &state.config.tokens_encryption_key)
             .has_storage_workers(query.0.storage_id, &user)
             .await?;
         Ok(Json(HasStorageWorkers { has }).into_response())
     }
+
+    async fn stats(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        query: Query<StorageWorkersStatsQuery>,
+    ) -> Result<Response, (StatusCode, String)> {
+        let stats = StorageWorkersService::new(&state.db, &state.config.tokens_encryption_key)
+            .stats(query.0, state.config.telegram_rate_limit, &user)
+            .await?;
+        Ok(Json(stats).into_response())
+    }
 }
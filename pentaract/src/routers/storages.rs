@@ -1,11 +1,11 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     middleware,
     response::IntoResponse,
-    routing::get,
+    routing::{delete, get, put},
     Extension, Json, Router,
 };
 use uuid::Uuid;
@@ -14,32 +14,57 @@ use crate::{
     common::{
         jwt_manager::AuthUser,
         routing::{app_state::AppState, middlewares::auth::logged_in_required},
+        types::ChatId,
     },
     models::storages::Storage,
+    schemas::files::DuplicatesQuery,
     schemas::{
         access::{GrantAccess, RestrictAccess},
-        storages::{InStorageSchema, StoragesListSchema},
+        storages::{
+            InStorageChatSchema, InStorageSchema, QuotaSchema, StoragesListSchema,
+            UpdateStorageSchema,
+        },
     },
-    services::storages::StoragesService,
+    services::{files::FilesService, storages::StoragesService},
 };
 
-use super::files::FilesRouter;
+use super::{file_drops::FileDropsRouter, files::FilesRouter, share_links::ShareLinksRouter};
 
 pub struct StoragesRouter;
 
 impl StoragesRouter {
     pub fn get_router(state: Arc<AppState>) -> Router {
         let files_router = FilesRouter::get_router(state.clone());
+        let files_operations_router = FilesRouter::get_operations_router(state.clone());
+        let share_links_router = ShareLinksRouter::get_router(state.clone());
+        let file_drops_router = FileDropsRouter::get_router(state.clone());
         Router::new()
             .route("/", get(Self::list).post(Self::create))
-            .route("/:storage_id", get(Self::get).delete(Self::delete))
+            .route("/duplicates", get(Self::find_duplicates))
+            .route(
+                "/:storage_id",
+                get(Self::get).patch(Self::update).delete(Self::delete),
+            )
             .route(
                 "/:storage_id/access",
                 get(Self::list_users_with_access)
                     .post(Self::grant_access)
                     .delete(Self::restrict_access),
             )
+            .route("/:storage_id/quota", put(Self::set_quota))
+            .route(
+                "/:storage_id/access/:user_id/quota",
+                put(Self::set_user_quota),
+            )
+            .route(
+                "/:storage_id/chats",
+                get(Self::list_chats).post(Self::add_chat),
+            )
+            .route("/:storage_id/chats/:chat_id", delete(Self::remove_chat))
             .nest("/:storage_id/files", files_router)
+            .nest("/:storage_id", files_operations_router)
+            .nest("/:storage_id/share_links", share_links_router)
+            .nest("/:storage_id/file_drops", file_drops_router)
             .route_layer(middleware::from_fn_with_state(
                 state.clone(),
                 logged_in_required,
@@ -69,6 +94,18 @@ impl StoragesRouter {
         Ok::<_, (StatusCode, String)>(Json(storages))
     }
 
+    /// Finds duplicates across all the storages the user has access to
+    async fn find_duplicates(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        Query(query): Query<DuplicatesQuery>,
+    ) -> impl IntoResponse {
+        let duplicates = FilesService::new(&state.db, state.tx.clone())
+            .find_duplicates(None, query, &user)
+            .await?;
+        Ok::<_, (StatusCode, String)>(Json(duplicates))
+    }
+
     async fn get(
         State(state): State<Arc<AppState>>,
         Extension(user): Extension<AuthUser>,
@@ -78,6 +115,42 @@ impl StoragesRouter {
         Ok(Json(storage))
     }
 
+    async fn update(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        Path(id): Path<Uuid>,
+        Json(in_schema): Json<UpdateStorageSchema>,
+    ) -> Result<Json<Storage>, (StatusCode, String)> {
+        let storage = StoragesService::new(&state.db)
+            .update(id, in_schema, &user)
+            .await?;
+        Ok(Json(storage))
+    }
+
+    async fn set_quota(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        Path(id): Path<Uuid>,
+        Json(in_schema): Json<QuotaSchema>,
+    ) -> Result<Json<Storage>, (StatusCode, String)> {
+        let storage = StoragesService::new(&state.db)
+            .set_quota(id, in_schema, &user)
+            .await?;
+        Ok(Json(storage))
+    }
+
+    async fn set_user_quota(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        Path((id, user_id)): Path<(Uuid, Uuid)>,
+        Json(in_schema): Json<QuotaSchema>,
+    ) -> Result<StatusCode, (StatusCode, String)> {
+        StoragesService::new(&state.db)
+            .set_user_quota(id, user_id, in_schema, &user)
+            .await?;
+        Ok(StatusCode::NO_CONTENT)
+    }
+
     async fn delete(
         State(state): State<Arc<AppState>>,
         Extension(user): Extension<AuthUser>,
@@ -121,4 +194,38 @@ impl StoragesRouter {
             .await?;
         Ok(StatusCode::NO_CONTENT)
     }
+
+    async fn add_chat(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        Path(id): Path<Uuid>,
+        Json(in_schema): Json<InStorageChatSchema>,
+    ) -> impl IntoResponse {
+        let chat = StoragesService::new(&state.db)
+            .add_chat(id, in_schema, &user)
+            .await?;
+        Ok::<_, (StatusCode, String)>((StatusCode::CREATED, Json(chat)))
+    }
+
+    async fn list_chats(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        Path(id): Path<Uuid>,
+    ) -> impl IntoResponse {
+        let chats = StoragesService::new(&state.db)
+            .list_chats(id, &user)
+            .await?;
+        Ok::<_, (StatusCode, String)>(Json(chats))
+    }
+
+    async fn remove_chat(
+        State(state): State<Arc<AppState>>,
+        Extension(user): Extension<AuthUser>,
+        Path((id, chat_id)): Path<(Uuid, ChatId)>,
+    ) -> Result<StatusCode, (StatusCode, String)> {
+        StoragesService::new(&state.db)
+            .remove_chat(id, chat_id, &user)
+            .await?;
+        Ok(StatusCode::NO_CONTENT)
+    }
 }
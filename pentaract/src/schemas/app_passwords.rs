@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::app_passwords::AppPassword;
+
+#[derive(Deserialize)]
+pub struct InAppPasswordSchema {
+    pub name: String,
+}
+
+#[derive(Serialize)]
+pub struct AppPasswordsListSchema {
+    pub app_passwords: Vec<AppPassword>,
+}
+
+impl AppPasswordsListSchema {
+    pub fn new(app_passwords: Vec<AppPassword>) -> Self {
+        Self { app_passwords }
+    }
+}
+
+/// The password is shown only once, right after it's created
+#[derive(Serialize)]
+pub struct CreatedAppPasswordSchema {
+    #[serde(flatten)]
+    pub app_password: AppPassword,
+    pub password: String,
+}
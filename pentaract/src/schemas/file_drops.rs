@@ -0,0 +1,56 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::file_drops::FileDrop;
+
+/// NOTE:
+///
+/// `path` is a folder, it's empty for the storage root or ends with a slash
+#[derive(Deserialize)]
+pub struct InFileDropSchema {
+    pub path: String,
+    pub max_file_size: Option<i64>,
+    pub max_files: Option<i32>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct FileDropsListSchema {
+    pub file_drops: Vec<FileDrop>,
+}
+
+impl FileDropsListSchema {
+    pub fn new(file_drops: Vec<FileDrop>) -> Self {
+        Self { file_drops }
+    }
+}
+
+/// What uploaders know about a drop, the folder stays hidden from them
+#[derive(Serialize)]
+pub struct FileDropInfoSchema {
+    pub max_file_size: Option<i64>,
+    pub files_left: Option<i32>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl From<FileDrop> for FileDropInfoSchema {
+    fn from(value: FileDrop) -> Self {
+        Self {
+            max_file_size: value.max_file_size,
+            files_left: value.files_left(),
+            expires_at: value.expires_at,
+        }
+    }
+}
+
+/// Saved paths are not reported, so uploaders cannot find out what else is in the folder
+#[derive(Serialize)]
+pub struct DroppedSchema {
+    pub files: Vec<DroppedFileSchema>,
+}
+
+#[derive(Serialize)]
+pub struct DroppedFileSchema {
+    pub filename: String,
+    pub error: Option<String>,
+}
@@ -1,8 +1,16 @@
+use std::collections::HashMap;
+
 use axum::body::Bytes;
-use serde::{Deserialize, Serialize};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64, Engine};
+use chrono::{DateTime, Utc};
+use serde::{de, de::DeserializeOwned, Deserialize, Deserializer, Serialize};
 use uuid::Uuid;
 
-use crate::{common::types::Position, models::files::File};
+use crate::{
+    common::types::Position,
+    errors::{PentaractError, PentaractResult},
+    models::files::{FSElement, File, SearchFSElement},
+};
 
 #[derive(Deserialize)]
 pub struct UploadParams {
@@ -10,26 +18,112 @@ pub struct UploadParams {
     pub folder_name: String,
 }
 
+/// `on_conflict` is resolved like the one of any upload, see `ConflictPolicy`
 pub struct InFileSchema {
     pub storage_id: Uuid,
     pub path: String,
     pub size: i64,
     pub file: Bytes,
+    pub original_filename: Option<String>,
+    pub on_conflict: Option<ConflictPolicy>,
 }
 
 impl InFileSchema {
-    pub fn new(storage_id: Uuid, path: String, file: Bytes) -> Self {
+    pub fn new(
+        storage_id: Uuid,
+        path: String,
+        file: Bytes,
+        original_filename: Option<String>,
+        on_conflict: Option<ConflictPolicy>,
+    ) -> Self {
         let size = file.len() as i64;
         Self {
             storage_id,
             path,
             size,
             file,
+            original_filename,
+            on_conflict,
+        }
+    }
+}
+
+pub const IN_FILE_SCHEMA_FIELDS_AMOUNT: usize = 3;
+
+/// NOTE:
+///
+/// `path` is a folder, it's empty for the storage root or ends with a slash.
+///
+/// Files names are paths relative to it, like the ones browsers send on folders uploads.
+///
+/// `on_conflict` is resolved like the one of any upload, see `ConflictPolicy`
+pub struct InFilesSchema {
+    pub storage_id: Uuid,
+    pub path: String,
+    pub files: Vec<(String, Bytes)>,
+    pub on_conflict: Option<ConflictPolicy>,
+}
+
+#[derive(Serialize)]
+pub struct UploadedSchema {
+    pub files: Vec<UploadedFileSchema>,
+}
+
+/// `path` is where the file is saved, it differs from the requested one if the name was taken.
+///
+/// Skipped files have neither `path` nor `error`
+#[derive(Serialize)]
+pub struct UploadedFileSchema {
+    pub filename: String,
+    pub path: Option<String>,
+    pub error: Option<String>,
+    pub skipped: bool,
+}
+
+impl UploadedFileSchema {
+    pub fn new(filename: String, result: PentaractResult<Option<String>>) -> Self {
+        let (path, error) = match result {
+            Ok(path) => (path, None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        let skipped = path.is_none() && error.is_none();
+
+        Self {
+            filename,
+            path,
+            error,
+            skipped,
         }
     }
 }
 
-pub const IN_FILE_SCHEMA_FIELDS_AMOUNT: usize = 2;
+/// NOTE:
+///
+/// `path` is a folder to extract the archive into, it's empty for the storage root
+/// or ends with a slash.
+///
+/// `on_conflict` is resolved like the one of any upload, see `ConflictPolicy`
+pub struct InArchiveSchema {
+    pub storage_id: Uuid,
+    pub path: String,
+    pub on_conflict: Option<ConflictPolicy>,
+    pub file: Bytes,
+}
+
+/// NOTE:
+///
+/// entries which are not read from the archive are listed by their names in it
+#[derive(Default, Serialize)]
+pub struct ExtractedSchema {
+    pub created: Vec<String>,
+    pub skipped: Vec<SkippedEntrySchema>,
+}
+
+#[derive(Serialize)]
+pub struct SkippedEntrySchema {
+    pub path: String,
+    pub reason: String,
+}
 
 pub struct InFolderSchema {
     pub storage_id: Uuid,
@@ -58,7 +152,508 @@ impl DownloadedChunkSchema {
     }
 }
 
+/// How `search_path` is matched against files and folders
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchMode {
+    /// Case insensitive substring of the path relative to the search folder
+    #[default]
+    Substring,
+    /// `*`, `**`, `?` and `[...]` wildcards matched against the name,
+    /// or against the relative path if the pattern has slashes
+    Glob,
+    /// Exact name
+    Exact,
+}
+
+/// NOTE:
+///
+/// `extensions` is a comma separated list like `pdf,png`.
+///
+/// `mime_type` may end with `/*` to match any subtype, e.g. `image/*`.
+///
+/// `tags` is a comma separated list of tags that all must be set,
+/// and `metadata` is a JSON object with attributes that all must match.
+///
+/// Folders are never found if any of files only filters is given
+/// (`extensions`, `mime_type`, `min_size`, `max_size` and `uploaded_by`)
 #[derive(Deserialize)]
 pub struct SearchQuery {
-    pub search_path: Option<String>,
+    #[serde(default)]
+    pub search_path: String,
+    #[serde(default)]
+    pub mode: SearchMode,
+    pub extensions: Option<String>,
+    pub mime_type: Option<String>,
+    pub min_size: Option<i64>,
+    pub max_size: Option<i64>,
+    pub updated_after: Option<DateTime<Utc>>,
+    pub updated_before: Option<DateTime<Utc>>,
+    pub uploaded_by: Option<Uuid>,
+    pub tags: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_json_query")]
+    pub metadata: Option<HashMap<String, String>>,
+    pub limit: Option<u32>,
+    pub cursor: Option<String>,
+}
+
+impl SearchQuery {
+    pub fn is_files_only(&self) -> bool {
+        self.extensions.is_some()
+            || self.mime_type.is_some()
+            || self.min_size.is_some()
+            || self.max_size.is_some()
+            || self.uploaded_by.is_some()
+    }
+
+    pub fn tags(&self) -> Vec<String> {
+        self.tags
+            .as_deref()
+            .map(|tags| parse_tags(tags.split(',')))
+            .unwrap_or_default()
+    }
+
+    pub fn extensions(&self) -> Vec<String> {
+        self.extensions
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+            .filter(|ext| !ext.is_empty())
+            .collect()
+    }
+}
+
+fn deserialize_json_query<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| serde_json::from_str(&value).map_err(de::Error::custom))
+        .transpose()
+}
+
+/// Trims tags and drops empty and repeated ones
+pub fn parse_tags<'a>(tags: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut parsed: Vec<String> = Vec::new();
+    for tag in tags.into_iter().map(str::trim) {
+        if !tag.is_empty() && !parsed.iter().any(|parsed_tag| parsed_tag == tag) {
+            parsed.push(tag.to_owned());
+        }
+    }
+    parsed
+}
+
+/// Position right after the last found element.
+///
+/// Paths are unique, so they break ties of the rank
+pub struct SearchCursor {
+    pub rank: i32,
+    pub path: String,
+}
+
+impl SearchCursor {
+    pub fn new(el: &SearchFSElement) -> Self {
+        Self {
+            rank: el.rank,
+            path: el.path.clone(),
+        }
+    }
+
+    /// Encodes the cursor as `<rank>.<path>` with base64 encoded path
+    pub fn encode(&self) -> String {
+        format!("{}.{}", self.rank, BASE64.encode(&self.path))
+    }
+
+    pub fn decode(cursor: &str) -> PentaractResult<Self> {
+        let (rank, path) = cursor
+            .split_once('.')
+            .ok_or(PentaractError::InvalidCursor)?;
+        let rank = rank.parse().map_err(|_| PentaractError::InvalidCursor)?;
+        let path = BASE64
+            .decode(path)
+            .ok()
+            .and_then(|path| String::from_utf8(path).ok())
+            .ok_or(PentaractError::InvalidCursor)?;
+
+        Ok(Self { rank, path })
+    }
+}
+
+#[derive(Serialize)]
+pub struct SearchResultsSchema {
+    pub items: Vec<SearchFSElement>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FSSortField {
+    #[default]
+    Name,
+    Size,
+    CreatedAt,
+    UpdatedAt,
+    /// MIME type
+    Type,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// NOTE:
+///
+/// folders always go first regardless of the order
+#[derive(Deserialize)]
+pub struct TreeQuery {
+    #[serde(default)]
+    pub sort_by: FSSortField,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub limit: Option<u32>,
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub with_total: bool,
+}
+
+/// Position right after the last listed element.
+///
+/// Names are unique among files and among folders of a folder,
+/// so they break ties of the sort key
+pub struct FSCursor {
+    pub is_file: bool,
+    pub key: String,
+    pub name: String,
+}
+
+impl FSCursor {
+    pub fn new(el: &FSElement, sort_by: FSSortField) -> Self {
+        let key = match sort_by {
+            FSSortField::Name => el.name.clone(),
+            FSSortField::Size => el.size.to_string(),
+            FSSortField::CreatedAt => el.created_at.to_rfc3339(),
+            FSSortField::UpdatedAt => el.updated_at.to_rfc3339(),
+            FSSortField::Type => el.mime_type.clone().unwrap_or_default(),
+        };
+
+        Self {
+            is_file: el.is_file,
+            key,
+            name: el.name.clone(),
+        }
+    }
+
+    /// Encodes the cursor as `<is_file>.<key>.<name>` with base64 encoded key and name
+    pub fn encode(&self) -> String {
+        format!(
+            "{}.{}.{}",
+            self.is_file as u8,
+            BASE64.encode(&self.key),
+            BASE64.encode(&self.name)
+        )
+    }
+
+    pub fn decode(cursor: &str) -> PentaractResult<Self> {
+        let decode_part = |part: &str| {
+            BASE64
+                .decode(part)
+                .ok()
+                .and_then(|part| String::from_utf8(part).ok())
+        };
+
+        let mut parts = cursor.split('.');
+        let (is_file, key, name) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(is_file), Some(key), Some(name), None) => (is_file, key, name),
+            _ => return Err(PentaractError::InvalidCursor),
+        };
+        let is_file = match is_file {
+            "0" => false,
+            "1" => true,
+            _ => return Err(PentaractError::InvalidCursor),
+        };
+
+        match (decode_part(key), decode_part(name)) {
+            (Some(key), Some(name)) => Ok(Self { is_file, key, name }),
+            _ => Err(PentaractError::InvalidCursor),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct FSLayerSchema {
+    pub items: Vec<FSElement>,
+    pub next_cursor: Option<String>,
+    pub total: Option<i64>,
+}
+
+/// NOTE:
+///
+/// folders paths must end with a slash.
+///
+/// Only given attributes are replaced
+#[derive(Deserialize)]
+pub struct AttributesParams {
+    pub path: String,
+    pub tags: Option<Vec<String>>,
+    pub metadata: Option<HashMap<String, String>>,
+}
+
+/// NOTE:
+///
+/// `format` is used only for folders downloads
+#[derive(Deserialize)]
+pub struct DownloadQuery {
+    pub version_id: Option<Uuid>,
+    #[serde(default)]
+    pub format: ArchiveFormat,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveFormat {
+    #[default]
+    Zip,
+    Tar,
+}
+
+impl ArchiveFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::Tar => "tar",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Zip => "application/zip",
+            Self::Tar => "application/x-tar",
+        }
+    }
+}
+
+/// Folder contents to be streamed as an archive.
+///
+/// Paths of `folders` and `files` are relative to the archive root
+/// and start with the folder name
+pub struct FolderArchive {
+    pub name: String,
+    pub format: ArchiveFormat,
+    pub folders: Vec<String>,
+    pub files: Vec<File>,
+}
+
+/// NOTE:
+///
+/// `path` may be a file, a folder (with trailing slash) or empty for the whole storage
+#[derive(Deserialize)]
+pub struct PruneVersionsParams {
+    #[serde(default)]
+    pub path: String,
+    pub keep_last: Option<u32>,
+    pub older_than_days: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct PrunedVersionsSchema {
+    pub deleted_amount: u64,
+}
+
+/// What to do if the path of an uploaded, extracted, transferred or restored file
+/// is already taken.
+///
+/// Overwritten files keep their history in versioned storages,
+/// skipped ones are left as they are.
+///
+/// It's `fail` by default. The only exception is uploads and extracted archives
+/// into versioned storages, they `overwrite` files by default since nothing is lost then
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    #[default]
+    Fail,
+    Overwrite,
+    Rename,
+    Skip,
+}
+
+impl ConflictPolicy {
+    /// Parses plain text values, like multipart fields
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::deserialize(de::value::StrDeserializer::<de::value::Error>::new(value)).ok()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RestoreParams {
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+}
+
+/// NOTE:
+///
+/// folders paths must end with a slash
+#[derive(Deserialize)]
+pub struct TransferParams {
+    pub from: String,
+    pub to: String,
+    pub to_storage_id: Option<Uuid>,
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+}
+
+pub struct InTransferSchema {
+    pub storage_id: Uuid,
+    pub from: String,
+    pub to_storage_id: Uuid,
+    pub to: String,
+    pub on_conflict: ConflictPolicy,
+}
+
+impl InTransferSchema {
+    pub fn new(storage_id: Uuid, params: TransferParams) -> Self {
+        Self {
+            storage_id,
+            from: params.from,
+            to_storage_id: params.to_storage_id.unwrap_or(storage_id),
+            to: params.to,
+            on_conflict: params.on_conflict,
+        }
+    }
+}
+
+/// Files that have to be re-uploaded to another storage in background
+pub struct BackgroundTransfer {
+    pub storage_id: Uuid,
+    pub path: String,
+    pub files: Vec<(File, File)>,
+    pub delete_source: bool,
+}
+
+pub struct TransferOutcome {
+    pub path: String,
+    pub background: Option<BackgroundTransfer>,
+}
+
+#[derive(Serialize)]
+pub struct TransferredSchema {
+    pub path: String,
+}
+
+/// NOTE:
+///
+/// in the transactional mode either all the operations are applied or none of them,
+/// while in the best-effort mode every operation is applied on its own
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchMode {
+    #[default]
+    Transactional,
+    BestEffort,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOperation {
+    Delete { path: String },
+    Move(TransferParams),
+    Copy(TransferParams),
+    Tag(AttributesParams),
+}
+
+#[derive(Deserialize)]
+pub struct BatchParams {
+    pub operations: Vec<BatchOperation>,
+    #[serde(default)]
+    pub mode: BatchMode,
+}
+
+/// NOTE:
+///
+/// `accepted` operations are transfers to other storages which are done in background,
+/// `skipped` ones are not run since a transactional batch has failed before them
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchItemStatus {
+    Done,
+    Accepted,
+    Failed,
+    RolledBack,
+    Skipped,
+}
+
+/// `path` is where a file or a folder is after the operation
+#[derive(Serialize)]
+pub struct BatchItemSchema {
+    pub status: BatchItemStatus,
+    pub path: Option<String>,
+    pub error: Option<String>,
+}
+
+impl BatchItemSchema {
+    pub fn new(status: BatchItemStatus) -> Self {
+        Self {
+            status,
+            path: None,
+            error: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BatchSchema {
+    pub items: Vec<BatchItemSchema>,
+}
+
+pub struct BatchOutcome {
+    pub items: Vec<BatchItemSchema>,
+    pub background: Vec<BackgroundTransfer>,
+}
+
+/// NOTE:
+///
+/// empty files are never reported, since they don't take any space
+#[derive(Deserialize)]
+pub struct DuplicatesQuery {
+    pub min_size: Option<i64>,
+    pub limit: Option<u32>,
+}
+
+/// `reclaimable_size` is how much space is taken by extra copies of contents
+#[derive(Serialize)]
+pub struct DuplicatesSchema {
+    pub groups: Vec<DuplicatesGroupSchema>,
+    pub reclaimable_size: i64,
+}
+
+#[derive(Serialize)]
+pub struct DuplicatesGroupSchema {
+    pub content_hash: String,
+    pub size: i64,
+    pub reclaimable_size: i64,
+    pub files: Vec<DuplicateFileSchema>,
+}
+
+#[derive(Serialize)]
+pub struct DuplicateFileSchema {
+    pub storage_id: Uuid,
+    pub path: String,
+}
+
+/// All the duplicates of the storage are deduplicated if `content_hash` is not given
+#[derive(Deserialize, Default)]
+pub struct DedupeParams {
+    pub content_hash: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct DedupedSchema {
+    pub files_amount: i64,
+    pub reclaimed_size: i64,
 }
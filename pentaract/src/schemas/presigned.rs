@@ -0,0 +1,50 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum PresignedAction {
+    Download,
+    Upload,
+}
+
+impl PresignedAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Download => "download",
+            Self::Upload => "upload",
+        }
+    }
+
+    pub fn method(&self) -> &'static str {
+        match self {
+            Self::Download => "GET",
+            Self::Upload => "PUT",
+        }
+    }
+}
+
+/// `expires_in` is in seconds
+#[derive(Deserialize)]
+pub struct InPresignSchema {
+    pub path: String,
+    pub action: PresignedAction,
+    pub expires_in: Option<i64>,
+}
+
+/// `url` is relative to the server origin and must be requested with `method`
+#[derive(Serialize)]
+pub struct PresignedUrlSchema {
+    pub url: String,
+    pub method: &'static str,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// `expires` is a unix timestamp
+#[derive(Deserialize)]
+pub struct PresignedQuery {
+    pub user_id: Uuid,
+    pub expires: i64,
+    pub signature: String,
+}
@@ -0,0 +1,67 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64, Engine};
+use serde::Deserialize;
+
+use crate::{
+    errors::{PentaractError, PentaractResult},
+    models::files::S3Object,
+};
+
+/// Query parameters of S3 requests, they choose the operation as much as methods do
+#[derive(Deserialize)]
+pub struct S3Query {
+    #[serde(rename = "list-type")]
+    pub list_type: Option<u8>,
+    pub prefix: Option<String>,
+    pub delimiter: Option<String>,
+    #[serde(rename = "max-keys")]
+    pub max_keys: Option<i64>,
+    #[serde(rename = "continuation-token")]
+    pub continuation_token: Option<String>,
+    #[serde(rename = "start-after")]
+    pub start_after: Option<String>,
+    pub marker: Option<String>,
+    #[serde(rename = "encoding-type")]
+    pub encoding_type: Option<String>,
+    #[serde(rename = "uploadId")]
+    pub upload_id: Option<String>,
+    #[serde(rename = "partNumber")]
+    pub part_number: Option<i32>,
+    pub uploads: Option<String>,
+    pub location: Option<String>,
+}
+
+/// NOTE:
+///
+/// a page of `ListObjects` or `ListObjectsV2` results.
+///
+/// V1 pages go on after `next_marker`, V2 ones after `next_token`,
+/// which is the last listed key encoded
+pub struct ObjectsListing {
+    pub bucket: String,
+    pub prefix: String,
+    pub delimiter: Option<String>,
+    pub max_keys: i64,
+    pub is_url_encoded: bool,
+    pub is_v2: bool,
+    pub marker: Option<String>,
+    pub start_after: Option<String>,
+    pub continuation_token: Option<String>,
+    pub objects: Vec<S3Object>,
+    pub is_truncated: bool,
+    pub next_marker: Option<String>,
+    pub next_token: Option<String>,
+}
+
+impl ObjectsListing {
+    pub fn encode_token(key: &str) -> String {
+        BASE64.encode(key)
+    }
+
+    pub fn decode_token(token: &str) -> PentaractResult<String> {
+        BASE64
+            .decode(token)
+            .ok()
+            .and_then(|key| String::from_utf8(key).ok())
+            .ok_or(PentaractError::InvalidCursor)
+    }
+}
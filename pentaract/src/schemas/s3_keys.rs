@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::s3_keys::S3Key;
+
+#[derive(Deserialize)]
+pub struct InS3KeySchema {
+    pub name: String,
+}
+
+#[derive(Serialize)]
+pub struct S3KeysListSchema {
+    pub s3_keys: Vec<S3Key>,
+}
+
+impl S3KeysListSchema {
+    pub fn new(s3_keys: Vec<S3Key>) -> Self {
+        Self { s3_keys }
+    }
+}
+
+/// The secret is shown only once, right after the key is created
+#[derive(Serialize)]
+pub struct CreatedS3KeySchema {
+    #[serde(flatten)]
+    pub s3_key: S3Key,
+    pub secret_access_key: String,
+}
@@ -0,0 +1,86 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    models::{
+        files::{FSElement, Metadata},
+        share_links::ShareLink,
+    },
+    schemas::files::{FSLayerSchema, FolderArchive},
+};
+
+/// NOTE:
+///
+/// folders paths must end with a slash, and an empty path shares the whole storage
+#[derive(Deserialize)]
+pub struct InShareLinkSchema {
+    pub path: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub password: Option<String>,
+    pub max_downloads: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct ShareLinksListSchema {
+    pub share_links: Vec<ShareLink>,
+}
+
+impl ShareLinksListSchema {
+    pub fn new(share_links: Vec<ShareLink>) -> Self {
+        Self { share_links }
+    }
+}
+
+/// Shared files and folders don't tell who uploaded them
+#[derive(Serialize)]
+pub struct SharedElementSchema {
+    pub path: String,
+    pub name: String,
+    pub size: i64,
+    pub is_file: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub mime_type: Option<String>,
+    pub original_filename: Option<String>,
+    pub tags: Vec<String>,
+    pub metadata: Metadata,
+}
+
+impl From<FSElement> for SharedElementSchema {
+    fn from(el: FSElement) -> Self {
+        Self {
+            path: el.path,
+            name: el.name,
+            size: el.size,
+            is_file: el.is_file,
+            created_at: el.created_at,
+            updated_at: el.updated_at,
+            mime_type: el.mime_type,
+            original_filename: el.original_filename,
+            tags: el.tags,
+            metadata: el.metadata,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SharedLayerSchema {
+    pub items: Vec<SharedElementSchema>,
+    pub next_cursor: Option<String>,
+    pub total: Option<i64>,
+}
+
+impl From<FSLayerSchema> for SharedLayerSchema {
+    fn from(fs_layer: FSLayerSchema) -> Self {
+        Self {
+            items: fs_layer.items.into_iter().map(Into::into).collect(),
+            next_cursor: fs_layer.next_cursor,
+            total: fs_layer.total,
+        }
+    }
+}
+
+pub enum SharedDownload {
+    File { name: String, data: Vec<u8> },
+    Archive(FolderArchive),
+}
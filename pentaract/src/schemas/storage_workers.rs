@@ -1,6 +1,12 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::models::{
+    storage_workers::StorageWorker,
+    storage_workers_stats::{StatsGranularity, StatsPoint, StorageWorkerStatsPoint},
+};
+
 #[derive(Deserialize)]
 pub struct InStorageWorkerSchema {
     pub name: String,
@@ -8,6 +14,28 @@ pub struct InStorageWorkerSchema {
     pub storage_id: Option<Uuid>,
 }
 
+/// Storage worker as it's shown to users, with the token being masked
+#[derive(Serialize)]
+pub struct StorageWorkerSchema {
+    pub id: Uuid,
+    pub name: String,
+    pub user_id: Uuid,
+    pub token: String,
+    pub storage_id: Option<Uuid>,
+}
+
+impl StorageWorkerSchema {
+    pub fn new(storage_worker: StorageWorker, masked_token: String) -> Self {
+        Self {
+            id: storage_worker.id,
+            name: storage_worker.name,
+            user_id: storage_worker.user_id,
+            token: masked_token,
+            storage_id: storage_worker.storage_id,
+        }
+    }
+}
+
 #[derive(Deserialize)]
 pub struct StorageWorkersStorageIDQuery {
     pub storage_id: Uuid,
@@ -17,3 +45,50 @@ pub struct StorageWorkersStorageIDQuery {
 pub struct HasStorageWorkers {
     pub has: bool,
 }
+
+#[derive(Deserialize)]
+pub struct StorageWorkersStatsQuery {
+    pub storage_id: Uuid,
+    #[serde(default)]
+    pub granularity: StatsGranularity,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct StorageWorkerUsagePoint {
+    pub dt: DateTime<Utc>,
+    pub calls: i64,
+    pub bytes_uploaded: i64,
+    pub bytes_downloaded: i64,
+    pub errors: i64,
+}
+
+impl From<StorageWorkerStatsPoint> for StorageWorkerUsagePoint {
+    fn from(point: StorageWorkerStatsPoint) -> Self {
+        Self {
+            dt: point.dt,
+            calls: point.calls,
+            bytes_uploaded: point.bytes_uploaded,
+            bytes_downloaded: point.bytes_downloaded,
+            errors: point.errors,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct StorageWorkerStatsSchema {
+    pub storage_worker_id: Uuid,
+    pub name: String,
+    pub stats: Vec<StorageWorkerUsagePoint>,
+}
+
+/// Statistics of a storage and its storage workers
+///
+/// `rate_limit` is the amount of calls a single storage worker can do per minute
+#[derive(Serialize)]
+pub struct StorageStatsSchema {
+    pub rate_limit: u8,
+    pub stats: Vec<StatsPoint>,
+    pub storage_workers: Vec<StorageWorkerStatsSchema>,
+}
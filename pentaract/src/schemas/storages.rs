@@ -8,6 +8,23 @@ pub struct InStorageSchema {
     pub chat_id: ChatId,
 }
 
+#[derive(Deserialize)]
+pub struct InStorageChatSchema {
+    pub chat_id: ChatId,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateStorageSchema {
+    pub versioning: bool,
+}
+
+/// Quotas are unlimited when they are not set
+#[derive(Deserialize)]
+pub struct QuotaSchema {
+    pub max_size: Option<i64>,
+    pub max_files: Option<i64>,
+}
+
 #[derive(Serialize)]
 pub struct StoragesListSchema {
     pub storages: Vec<StorageWithInfo>,
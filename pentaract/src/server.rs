@@ -10,8 +10,10 @@ use tower_http::{
 use crate::{
     common::routing::app_state::AppState,
     routers::{
-        auth::AuthRouter, storage_workers::StorageWorkersRouter, storages::StoragesRouter,
-        users::UsersRouter,
+        app_passwords::AppPasswordsRouter, auth::AuthRouter, dav::DavRouter,
+        file_drops::FileDropsRouter, presigned::PresignedRouter, s3::S3Router,
+        s3_keys::S3KeysRouter, share_links::ShareLinksRouter,
+        storage_workers::StorageWorkersRouter, storages::StoragesRouter, users::UsersRouter,
     },
 };
 
@@ -42,13 +44,33 @@ impl Server {
         Router::new()
             .nest("/users", UsersRouter::get_router(app_state.clone()))
             .nest("/auth", AuthRouter::get_router(app_state.clone()))
+            .nest(
+                "/app_passwords",
+                AppPasswordsRouter::get_router(app_state.clone()),
+            )
+            .nest("/s3_keys", S3KeysRouter::get_router(app_state.clone()))
             .nest("/storages", StoragesRouter::get_router(app_state.clone()))
+            .nest(
+                "/drops",
+                FileDropsRouter::get_public_router(app_state.clone()),
+            )
+            .nest(
+                "/presigned",
+                PresignedRouter::get_public_router(app_state.clone()),
+            )
+            .nest(
+                "/shared",
+                ShareLinksRouter::get_public_router(app_state.clone()),
+            )
             .nest(
                 "/storage_workers",
                 StorageWorkersRouter::get_router(app_state.clone()),
             )
-            .layer(ConcurrencyLimitLayer::new(workers.into()))
+            .merge(S3Router::get_router(app_state.clone()))
             .layer(app_cors)
+            // WebDAV clients send `OPTIONS` requests, which the CORS layer takes for preflights
+            .merge(DavRouter::get_router(app_state))
+            .layer(ConcurrencyLimitLayer::new(workers.into()))
     }
 
     pub async fn run(self, addr: &SocketAddr) {
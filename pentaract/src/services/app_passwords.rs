@@ -0,0 +1,57 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    common::{jwt_manager::AuthUser, links, password_manager::PasswordManager},
+    errors::{PentaractError, PentaractResult},
+    models::app_passwords::{AppPassword, InAppPassword},
+    repositories::app_passwords::AppPasswordsRepository,
+    schemas::app_passwords::{CreatedAppPasswordSchema, InAppPasswordSchema},
+};
+
+/// NOTE:
+///
+/// app passwords are meant for clients which can't log in with JWT, like WebDAV ones,
+/// so they can be revoked without changing the account password
+pub struct AppPasswordsService<'d> {
+    repo: AppPasswordsRepository<'d>,
+}
+
+impl<'d> AppPasswordsService<'d> {
+    pub fn new(db: &'d PgPool) -> Self {
+        let repo = AppPasswordsRepository::new(db);
+        Self { repo }
+    }
+
+    pub async fn create(
+        &self,
+        in_schema: InAppPasswordSchema,
+        user: &AuthUser,
+    ) -> PentaractResult<CreatedAppPasswordSchema> {
+        let name = in_schema.name.trim();
+        if name.is_empty() {
+            return Err(PentaractError::InvalidAppPasswordName);
+        }
+
+        let password = links::generate_token();
+        let in_obj = InAppPassword {
+            user_id: user.id,
+            name: name.to_owned(),
+            password_hash: PasswordManager::hash_token(&password),
+        };
+        let app_password = self.repo.create(in_obj).await?;
+
+        Ok(CreatedAppPasswordSchema {
+            app_password,
+            password,
+        })
+    }
+
+    pub async fn list(&self, user: &AuthUser) -> PentaractResult<Vec<AppPassword>> {
+        self.repo.list(user.id).await
+    }
+
+    pub async fn revoke(&self, id: Uuid, user: &AuthUser) -> PentaractResult<()> {
+        self.repo.delete(id, user.id).await
+    }
+}
@@ -9,18 +9,23 @@ use crate::{
     },
     config::Config,
     errors::{PentaractError, PentaractResult},
-    repositories::users::UsersRepository,
+    repositories::{app_passwords::AppPasswordsRepository, users::UsersRepository},
     schemas::auth::LoginSchema,
 };
 
 pub struct AuthService<'d> {
     repo: UsersRepository<'d>,
+    app_passwords_repo: AppPasswordsRepository<'d>,
 }
 
 impl<'d> AuthService<'d> {
     pub fn new(db: &'d PgPool) -> Self {
         let repo = UsersRepository::new(db);
-        Self { repo }
+        let app_passwords_repo = AppPasswordsRepository::new(db);
+        Self {
+            repo,
+            app_passwords_repo,
+        }
     }
 
     pub async fn login(
@@ -46,4 +51,23 @@ impl<'d> AuthService<'d> {
 
         // TODO: add generating refresh token
     }
+
+    /// Authenticates clients which can't get access tokens, like WebDAV ones.
+    ///
+    /// Only app passwords are accepted, as they are checked by a hash lookup,
+    /// while verifying account passwords is too slow to be done on every request
+    pub async fn authenticate_basic(
+        &self,
+        email: &str,
+        password: &str,
+    ) -> PentaractResult<AuthUser> {
+        let password_hash = PasswordManager::hash_token(password);
+        let user = self
+            .app_passwords_repo
+            .use_password(email, &password_hash)
+            .await
+            .map_err(|_| PentaractError::NotAuthenticated)?;
+
+        Ok(AuthUser::new(user.id, user.email))
+    }
 }
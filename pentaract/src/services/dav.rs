@@ -0,0 +1,341 @@
+use axum::body::Bytes;
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    common::{
+        access::check_access, channels::ClientSender, jwt_manager::AuthUser, webdav::LockInfo,
+    },
+    errors::{PentaractError, PentaractResult},
+    models::{
+        access::AccessType,
+        dav_locks::{DavLock, InDavLock},
+        files::FSElement,
+        storages::StorageWithInfo,
+    },
+    repositories::{
+        access::AccessRepository, dav_locks::DavLocksRepository, files::FilesRepository,
+        storages::StoragesRepository,
+    },
+    schemas::files::{
+        ConflictPolicy, InFileSchema, InFolderSchema, InTransferSchema, TransferOutcome, TreeQuery,
+    },
+    services::files::FilesService,
+};
+
+/// Lock timeout if a client doesn't ask for one
+const DEFAULT_LOCK_TIMEOUT_SECS: i64 = 3600;
+const MAX_LOCK_TIMEOUT_SECS: i64 = 7 * 24 * 3600;
+
+/// NOTE:
+///
+/// maps WebDAV resources onto files and folders.
+///
+/// Paths are the same as everywhere else: folders ones end with a slash,
+/// and the storage root is an empty path
+pub struct DavService<'d> {
+    files_service: FilesService<'d>,
+    files_repo: FilesRepository<'d>,
+    storages_repo: StoragesRepository<'d>,
+    locks_repo: DavLocksRepository<'d>,
+    access_repo: AccessRepository<'d>,
+}
+
+impl<'d> DavService<'d> {
+    pub fn new(db: &'d PgPool, tx: ClientSender) -> Self {
+        let files_service = FilesService::new(db, tx);
+        let files_repo = FilesRepository::new(db);
+        let storages_repo = StoragesRepository::new(db);
+        let locks_repo = DavLocksRepository::new(db);
+        let access_repo = AccessRepository::new(db);
+        Self {
+            files_service,
+            files_repo,
+            storages_repo,
+            locks_repo,
+            access_repo,
+        }
+    }
+
+    pub async fn list_storages(&self, user: &AuthUser) -> PentaractResult<Vec<StorageWithInfo>> {
+        self.storages_repo.list_by_user_id(user.id).await
+    }
+
+    /// Finds the file or the folder, `path` may lack the trailing slash of a folder.
+    ///
+    /// The storage root is named after the storage
+    pub async fn resolve(
+        &self,
+        storage_id: Uuid,
+        path: &str,
+        user: &AuthUser,
+    ) -> PentaractResult<FSElement> {
+        if path.is_empty() {
+            check_access(&self.access_repo, user.id, storage_id, &AccessType::R).await?;
+
+            let storage = self.storages_repo.get_by_id(storage_id).await?;
+            let mut root = self.files_repo.get_folder_stat("", storage_id).await?;
+            root.name = storage.name;
+            return Ok(root);
+        }
+
+        if !path.ends_with('/') {
+            match self.files_service.stat(path, storage_id, user).await {
+                Err(PentaractError::DoesNotExist(_)) => (),
+                result => return result,
+            }
+        }
+
+        let folder_path = format!("{}/", path.trim_end_matches('/'));
+        self.files_service
+            .stat(&folder_path, storage_id, user)
+            .await
+    }
+
+    /// Same as `resolve`, but a missing resource is not an error
+    pub async fn find(
+        &self,
+        storage_id: Uuid,
+        path: &str,
+        user: &AuthUser,
+    ) -> PentaractResult<Option<FSElement>> {
+        match self.resolve(storage_id, path, user).await {
+            Ok(el) => Ok(Some(el)),
+            Err(PentaractError::DoesNotExist(_)) => {
+                // telling missing storages apart from missing files
+                check_access(&self.access_repo, user.id, storage_id, &AccessType::R).await?;
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Lists the whole folder, `path` must be empty or end with a slash
+    pub async fn list(
+        &self,
+        storage_id: Uuid,
+        path: &str,
+        user: &AuthUser,
+    ) -> PentaractResult<Vec<FSElement>> {
+        let prefix = path.trim_end_matches('/');
+
+        let mut items = vec![];
+        let mut cursor = None;
+        loop {
+            let query = TreeQuery {
+                sort_by: Default::default(),
+                order: Default::default(),
+                limit: None,
+                cursor,
+                with_total: false,
+            };
+            let layer = self
+                .files_service
+                .list_dir(storage_id, prefix, query, user)
+                .await?;
+            items.extend(layer.items);
+
+            match layer.next_cursor {
+                Some(next_cursor) => cursor = Some(next_cursor),
+                None => break,
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// Whether the folder the resource would be put in exists
+    pub async fn parent_exists(&self, storage_id: Uuid, path: &str) -> PentaractResult<bool> {
+        let (parent, _) = FilesRepository::split_path(path);
+        if parent.is_empty() {
+            return Ok(true);
+        }
+
+        self.files_repo.exists(parent, storage_id).await
+    }
+
+    pub async fn download(
+        &self,
+        storage_id: Uuid,
+        path: &str,
+        user: &AuthUser,
+    ) -> PentaractResult<Vec<u8>> {
+        self.files_service
+            .download(path, storage_id, None, user)
+            .await
+    }
+
+    /// Files are always overwritten, as `PUT` replaces the resource
+    pub async fn upload(
+        &self,
+        storage_id: Uuid,
+        path: &str,
+        data: Bytes,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        let in_schema = InFileSchema::new(
+            storage_id,
+            path.to_owned(),
+            data,
+            None,
+            Some(ConflictPolicy::Overwrite),
+        );
+
+        self.files_service
+            .upload_to(in_schema, user)
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn mkcol(
+        &self,
+        storage_id: Uuid,
+        path: &str,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        let (parent, name) = FilesRepository::split_path(path);
+        let in_schema = InFolderSchema::new(
+            storage_id,
+            parent.trim_end_matches('/').to_owned(),
+            name.to_owned(),
+        );
+
+        self.files_service.create_folder(in_schema, user).await
+    }
+
+    /// Moves the file or the folder to trash, dropping locks of it
+    pub async fn delete(
+        &self,
+        storage_id: Uuid,
+        path: &str,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        self.files_service.delete(path, storage_id, user).await?;
+        self.locks_repo.delete_by_path(storage_id, path).await
+    }
+
+    /// Moved resources leave their locks behind, so they are dropped
+    pub async fn transfer(
+        &self,
+        in_schema: InTransferSchema,
+        is_move: bool,
+        user: &AuthUser,
+    ) -> PentaractResult<TransferOutcome> {
+        if !is_move {
+            return self.files_service.copy(in_schema, user).await;
+        }
+
+        let (storage_id, from) = (in_schema.storage_id, in_schema.from.clone());
+        let outcome = self.files_service.move_(in_schema, user).await?;
+        self.locks_repo.delete_by_path(storage_id, &from).await?;
+        Ok(outcome)
+    }
+
+    /// Locks of the resource, including locks of its content if `with_content` is set
+    pub async fn list_locks(
+        &self,
+        storage_id: Uuid,
+        path: &str,
+        with_content: bool,
+    ) -> PentaractResult<Vec<DavLock>> {
+        self.locks_repo
+            .list_overlapping(storage_id, path, with_content)
+            .await
+    }
+
+    /// Fails if the resource is locked by a lock whose token is not submitted
+    pub async fn check_locks(
+        &self,
+        storage_id: Uuid,
+        path: &str,
+        with_content: bool,
+        tokens: &[String],
+    ) -> PentaractResult<()> {
+        let locks = self.list_locks(storage_id, path, with_content).await?;
+        if locks.iter().any(|lock| !tokens.contains(&lock.token)) {
+            return Err(PentaractError::Locked);
+        }
+
+        Ok(())
+    }
+
+    /// Locks the resource, an empty file is created for unmapped paths.
+    ///
+    /// Returns the lock and whether the file is created
+    pub async fn lock(
+        &self,
+        storage_id: Uuid,
+        path: &str,
+        info: LockInfo,
+        is_deep: bool,
+        timeout_secs: Option<i64>,
+        user: &AuthUser,
+    ) -> PentaractResult<(DavLock, bool)> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        let (path, is_created) = match self.find(storage_id, path, user).await? {
+            Some(el) if el.is_file => (el.path, false),
+            Some(el) => (format!("{}/", el.path.trim_end_matches('/')), false),
+            None => (path.trim_end_matches('/').to_owned(), true),
+        };
+        let is_deep = is_deep && (path.is_empty() || path.ends_with('/'));
+
+        let in_obj = InDavLock {
+            storage_id,
+            path,
+            token: format!("opaquelocktoken:{}", Uuid::new_v4()),
+            is_shared: info.is_shared,
+            is_deep,
+            owner: info.owner,
+            created_by: user.id,
+            expires_at: Self::expires_at(timeout_secs),
+        };
+        let lock = self.locks_repo.create(in_obj).await?;
+
+        // the file is created once it's locked, so it's not created if the lock conflicts
+        if is_created {
+            if let Err(e) = self
+                .upload(storage_id, &lock.path, Bytes::new(), user)
+                .await
+            {
+                let _ = self.locks_repo.delete(&lock.token, storage_id).await;
+                return Err(e);
+            }
+        }
+
+        Ok((lock, is_created))
+    }
+
+    pub async fn refresh_lock(
+        &self,
+        storage_id: Uuid,
+        token: &str,
+        timeout_secs: Option<i64>,
+        user: &AuthUser,
+    ) -> PentaractResult<DavLock> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        self.locks_repo
+            .refresh(token, storage_id, Self::expires_at(timeout_secs))
+            .await
+    }
+
+    pub async fn unlock(
+        &self,
+        storage_id: Uuid,
+        token: &str,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        self.locks_repo.delete(token, storage_id).await
+    }
+
+    fn expires_at(timeout_secs: Option<i64>) -> chrono::DateTime<Utc> {
+        let timeout_secs = timeout_secs
+            .unwrap_or(DEFAULT_LOCK_TIMEOUT_SECS)
+            .clamp(1, MAX_LOCK_TIMEOUT_SECS);
+        Utc::now() + Duration::seconds(timeout_secs)
+    }
+}
@@ -0,0 +1,219 @@
+use chrono::Utc;
+use sqlx::PgPool;
+use tokio_util::bytes::Bytes;
+use uuid::Uuid;
+
+use crate::{
+    common::{access::check_access, archive, channels::ClientSender, jwt_manager::AuthUser, links},
+    errors::{PentaractError, PentaractResult},
+    models::{
+        access::AccessType,
+        file_drops::{FileDrop, InFileDrop},
+    },
+    repositories::{
+        access::AccessRepository, file_drops::FileDropsRepository, files::FilesRepository,
+        users::UsersRepository,
+    },
+    schemas::{
+        file_drops::{DroppedFileSchema, DroppedSchema, FileDropInfoSchema, InFileDropSchema},
+        files::{ConflictPolicy, InFileSchema},
+    },
+    services::files::FilesService,
+};
+
+/// NOTE:
+///
+/// files are dropped on behalf of drops creators,
+/// so drops stop working once their creators lose write access to storages.
+///
+/// Uploaders can only add new files, they cannot see or overwrite anything
+pub struct FileDropsService<'d> {
+    repo: FileDropsRepository<'d>,
+    files_repo: FilesRepository<'d>,
+    users_repo: UsersRepository<'d>,
+    access_repo: AccessRepository<'d>,
+    db: &'d PgPool,
+    tx: ClientSender,
+}
+
+impl<'d> FileDropsService<'d> {
+    pub fn new(db: &'d PgPool, tx: ClientSender) -> Self {
+        let repo = FileDropsRepository::new(db);
+        let files_repo = FilesRepository::new(db);
+        let users_repo = UsersRepository::new(db);
+        let access_repo = AccessRepository::new(db);
+        Self {
+            repo,
+            files_repo,
+            users_repo,
+            access_repo,
+            db,
+            tx,
+        }
+    }
+
+    pub async fn create(
+        &self,
+        storage_id: Uuid,
+        in_schema: InFileDropSchema,
+        user: &AuthUser,
+    ) -> PentaractResult<FileDrop> {
+        // 0. checking access
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        // 1. validation
+        let path = in_schema.path.as_str();
+        if path.starts_with('/') || path.contains("//") || !(path.is_empty() || path.ends_with('/'))
+        {
+            return Err(PentaractError::InvalidPath);
+        }
+        if in_schema
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+            || in_schema.max_files.is_some_and(|max| max < 1)
+            || in_schema.max_file_size.is_some_and(|max| max < 1)
+        {
+            return Err(PentaractError::InvalidFileDropLimits);
+        }
+
+        // 2. getting the folder
+        let folder_id = self.files_repo.get_folder_id(path, storage_id).await?;
+
+        // 3. saving
+        let in_obj = InFileDrop {
+            storage_id,
+            token: links::generate_token(),
+            folder_id,
+            max_file_size: in_schema.max_file_size,
+            max_files: in_schema.max_files,
+            expires_at: in_schema.expires_at,
+            created_by: user.id,
+        };
+
+        self.repo.create(in_obj).await
+    }
+
+    pub async fn list(&self, storage_id: Uuid, user: &AuthUser) -> PentaractResult<Vec<FileDrop>> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        self.repo.list(storage_id).await
+    }
+
+    pub async fn revoke(&self, storage_id: Uuid, id: Uuid, user: &AuthUser) -> PentaractResult<()> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        self.repo.delete(id, storage_id).await
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    ////    Dropping
+    /////////////////////////////////////////////////////////////////////
+
+    /// Shows the drop limits to uploaders
+    pub async fn info(&self, token: &str) -> PentaractResult<FileDropInfoSchema> {
+        let (file_drop, _, _) = self.open(token).await?;
+
+        Ok(file_drop.into())
+    }
+
+    /// Saves files into the drop folder under free names.
+    ///
+    /// Only names of files are taken, and files which cannot be saved
+    /// are listed in the report along with the reason
+    pub async fn drop_files(
+        &self,
+        token: &str,
+        files: Vec<(String, Bytes)>,
+    ) -> PentaractResult<DroppedSchema> {
+        let (file_drop, folder_path, user) = self.open(token).await?;
+        let files_service = FilesService::new(self.db, self.tx.clone());
+
+        let mut dropped = Vec::with_capacity(files.len());
+        for (filename, file_data) in files {
+            let result = self
+                .drop_file(
+                    &file_drop,
+                    &folder_path,
+                    &filename,
+                    file_data,
+                    &files_service,
+                    &user,
+                )
+                .await;
+
+            dropped.push(DroppedFileSchema {
+                filename,
+                error: result.err().map(|e| e.to_string()),
+            });
+        }
+
+        Ok(DroppedSchema { files: dropped })
+    }
+
+    async fn drop_file(
+        &self,
+        file_drop: &FileDrop,
+        folder_path: &str,
+        filename: &str,
+        file_data: Bytes,
+        files_service: &FilesService<'_>,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        // 1. validation
+        let name = archive::sanitize_path(filename)
+            .and_then(|path| path.rsplit('/').next().map(str::to_owned))
+            .filter(|name| !name.is_empty())
+            .ok_or(PentaractError::InvalidPath)?;
+        let size = file_data.len() as i64;
+        if let Some(max_file_size) = file_drop.max_file_size.filter(|max| size > *max) {
+            return Err(PentaractError::FileTooLarge(max_file_size));
+        }
+
+        // 2. taking a place in the drop
+        self.repo.take_place(file_drop.id).await?;
+
+        // 3. uploading under a free name, so nothing is overwritten
+        let in_schema = InFileSchema::new(
+            file_drop.storage_id,
+            format!("{folder_path}{name}"),
+            file_data,
+            Some(name),
+            Some(ConflictPolicy::Rename),
+        );
+        if let Err(e) = files_service.upload_to(in_schema, user).await {
+            let _ = self.repo.release_place(file_drop.id).await;
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the drop, the current path of its folder and the drop creator
+    async fn open(&self, token: &str) -> PentaractResult<(FileDrop, String, AuthUser)> {
+        let file_drop = self.repo.get_by_token(token).await?;
+        if file_drop.is_expired() {
+            return Err(PentaractError::FileDropExpired);
+        }
+
+        // the folder is in trash
+        let path = file_drop
+            .path
+            .clone()
+            .ok_or(PentaractError::DoesNotExist("file drop".to_owned()))?;
+
+        let user = self
+            .users_repo
+            .get_by_id(file_drop.created_by)
+            .await
+            .map(|user| AuthUser::new(user.id, user.email))?;
+        check_access(
+            &self.access_repo,
+            user.id,
+            file_drop.storage_id,
+            &AccessType::W,
+        )
+        .await?;
+
+        Ok((file_drop, path, user))
+    }
+}
@@ -1,30 +1,122 @@
+use std::io;
+
 use axum::body::Bytes;
-use sqlx::PgPool;
+use chrono::Utc;
+use futures::{channel::mpsc, SinkExt};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
 use tokio::sync::oneshot;
 use uuid::Uuid;
 
 use crate::{
     common::{
         access::check_access,
+        archive::{
+            self, ArchiveEntry, ArchiveWriter, ExtractLimits, ExtractedEntry, TarWriter, ZipWriter,
+        },
         channels::{
-            ClientData, ClientMessage, ClientSender, DownloadFileData, StorageManagerData,
-            UploadFileData,
+            ClientData, ClientMessage, ClientSender, DownloadChunkData, DownloadFileData,
+            StorageManagerData, UploadFileData,
         },
         jwt_manager::AuthUser,
+        thumbnails::{self, ThumbnailsCache},
     },
     errors::{PentaractError, PentaractResult},
     models::{
         access::AccessType,
-        files::{FSElement, File, InFile, SearchFSElement},
+        file_chunks::FileChunk,
+        file_versions::FileVersion,
+        files::{FSElement, File, InFile},
+        trash::TrashItem,
     },
     repositories::{
-        access::AccessRepository, files::FilesRepository, storage_workers::StorageWorkersRepository,
+        access::AccessRepository, file_versions::FileVersionsRepository, files::FilesRepository,
+        folders::FoldersRepository, multipart_uploads::MultipartUploadsRepository,
+        storage_workers::StorageWorkersRepository, storages::StoragesRepository,
+        trash::TrashRepository,
+    },
+    schemas::files::{
+        parse_tags, ArchiveFormat, AttributesParams, BackgroundTransfer, BatchItemSchema,
+        BatchItemStatus, BatchMode, BatchOperation, BatchOutcome, BatchParams, ConflictPolicy,
+        DedupeParams, DedupedSchema, DuplicateFileSchema, DuplicatesGroupSchema, DuplicatesQuery,
+        DuplicatesSchema, ExtractedSchema, FSCursor, FSLayerSchema, FolderArchive, InArchiveSchema,
+        InFileSchema, InFilesSchema, InFolderSchema, InTransferSchema, PruneVersionsParams,
+        SearchCursor, SearchQuery, SearchResultsSchema, SkippedEntrySchema, TransferOutcome,
+        TransferredSchema, TreeQuery, UploadedFileSchema, UploadedSchema,
     },
-    schemas::files::{InFileSchema, InFolderSchema},
 };
 
+/// Amount of elements of a folder listed at once by default
+const DEFAULT_PAGE_SIZE: u32 = 100;
+const MAX_PAGE_SIZE: u32 = 1000;
+
+/// Amount of operations a batch can contain at most
+const MAX_BATCH_SIZE: usize = 1000;
+
+/// Uploaded archives are extracted into that much at most, whatever quotas are
+const MAX_EXTRACTED_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+const MAX_EXTRACTED_ENTRIES: u64 = 100_000;
+
+pub type ArchiveSender = mpsc::Sender<io::Result<Bytes>>;
+
+/// Files and folders to copy or move, along with what they replace
+struct TransferPlan {
+    storage_id: Uuid,
+    to_storage_id: Uuid,
+    from: String,
+    to: String,
+    copies: Vec<(File, File)>,
+    folders: Vec<(Uuid, String)>,
+    replaced_paths: Vec<String>,
+}
+
+impl TransferPlan {
+    fn is_same_storage(&self) -> bool {
+        self.storage_id == self.to_storage_id
+    }
+
+    fn size(&self) -> i64 {
+        self.copies.iter().map(|(file, _)| file.size).sum()
+    }
+}
+
+/// Content of a file to save, parts of a multipart upload are already uploaded by chunks
+enum FileContent {
+    Data(Bytes),
+    Parts(UploadedParts),
+}
+
+impl FileContent {
+    fn size(&self) -> i64 {
+        match self {
+            FileContent::Data(data) => data.len() as i64,
+            FileContent::Parts(parts) => parts.size,
+        }
+    }
+
+    fn content_hash(&self) -> String {
+        match self {
+            FileContent::Data(data) => FilesService::content_hash(data),
+            FileContent::Parts(parts) => parts.content_hash.clone(),
+        }
+    }
+}
+
+/// Parts of a multipart upload in the order they are joined
+pub struct UploadedParts {
+    pub upload_id: Uuid,
+    pub part_numbers: Vec<i32>,
+    pub size: i64,
+    pub content_hash: String,
+}
+
 pub struct FilesService<'d> {
     repo: FilesRepository<'d>,
+    folders_repo: FoldersRepository<'d>,
+    trash_repo: TrashRepository<'d>,
+    versions_repo: FileVersionsRepository<'d>,
+    uploads_repo: MultipartUploadsRepository<'d>,
+    storages_repo: StoragesRepository<'d>,
     storage_workers_repo: StorageWorkersRepository<'d>,
     access_repo: AccessRepository<'d>,
     tx: ClientSender,
@@ -33,10 +125,20 @@ pub struct FilesService<'d> {
 impl<'d> FilesService<'d> {
     pub fn new(db: &'d PgPool, tx: ClientSender) -> Self {
         let repo = FilesRepository::new(db);
+        let folders_repo = FoldersRepository::new(db);
+        let trash_repo = TrashRepository::new(db);
+        let versions_repo = FileVersionsRepository::new(db);
+        let uploads_repo = MultipartUploadsRepository::new(db);
+        let storages_repo = StoragesRepository::new(db);
         let storage_workers_repo = StorageWorkersRepository::new(db);
         let access_repo = AccessRepository::new(db);
         Self {
             repo,
+            folders_repo,
+            trash_repo,
+            versions_repo,
+            uploads_repo,
+            storages_repo,
             access_repo,
             storage_workers_repo,
             tx,
@@ -71,13 +173,83 @@ impl<'d> FilesService<'d> {
         } else {
             format!("{}/", in_schema.folder_name)
         };
-        let in_file = InFile::new(path, 0, in_schema.storage_id);
 
         // 3. saving to db
-        self.repo.create_folder(in_file).await.map(|_| ())
+        self.folders_repo
+            .create(&path, in_schema.storage_id)
+            .await
+            .map(|_| ())
+    }
+
+    /// Returns the path the file is saved at, or `None` if it's skipped
+    pub async fn upload_to(
+        &self,
+        in_schema: InFileSchema,
+        user: &AuthUser,
+    ) -> PentaractResult<Option<String>> {
+        // 0. checking access
+        check_access(
+            &self.access_repo,
+            user.id,
+            in_schema.storage_id,
+            &AccessType::W,
+        )
+        .await?;
+
+        // 1. check whether storage got workers
+        self.check_storage_workers(in_schema.storage_id).await?;
+
+        // 2. choosing the conflict policy
+        let on_conflict = self
+            .conflict_policy(in_schema.on_conflict, in_schema.storage_id)
+            .await?;
+
+        let in_file = InFile::new(
+            in_schema.path,
+            in_schema.size,
+            in_schema.storage_id,
+            user.id,
+            in_schema.original_filename,
+        );
+
+        // 3. uploading
+        self.upload_resolving(
+            in_file,
+            FileContent::Data(in_schema.file),
+            on_conflict,
+            user,
+        )
+        .await
+    }
+
+    /// Saves parts of a multipart upload as the file overwriting an existing one,
+    /// the upload itself is kept
+    pub async fn upload_parts_to(
+        &self,
+        storage_id: Uuid,
+        path: &str,
+        parts: UploadedParts,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        let in_file = InFile::new(path.to_owned(), parts.size, storage_id, user.id, None);
+        self.upload_resolving(
+            in_file,
+            FileContent::Parts(parts),
+            ConflictPolicy::Overwrite,
+            user,
+        )
+        .await
+        .map(|_| ())
     }
 
-    pub async fn upload_to(&self, in_schema: InFileSchema, user: &AuthUser) -> PentaractResult<()> {
+    /// Files are uploaded independently, so the status of every file is returned
+    pub async fn upload_many(
+        &self,
+        in_schema: InFilesSchema,
+        user: &AuthUser,
+    ) -> PentaractResult<UploadedSchema> {
         // 0. checking access
         check_access(
             &self.access_repo,
@@ -88,52 +260,375 @@ impl<'d> FilesService<'d> {
         .await?;
 
         // 1. check whether storage got workers
-        Self::check_storage_workers(&self, in_schema.storage_id).await?;
+        self.check_storage_workers(in_schema.storage_id).await?;
 
         // 2. path validation
-        if !Self::validate_filepath(&in_schema.path) {
+        let prefix = in_schema.path;
+        if !Self::validate_path(&prefix) || !(prefix.is_empty() || prefix.ends_with('/')) {
             return Err(PentaractError::InvalidPath);
         }
 
-        let in_file = InFile::new(in_schema.path, in_schema.size, in_schema.storage_id);
+        // 3. choosing the conflict policy
+        let on_conflict = self
+            .conflict_policy(in_schema.on_conflict, in_schema.storage_id)
+            .await?;
+
+        // 4. uploading files one by one, folders are created along with files
+        let mut files = Vec::with_capacity(in_schema.files.len());
+        for (filename, file_data) in in_schema.files {
+            let result = match archive::sanitize_path(&filename) {
+                Some(relative_path) if !relative_path.is_empty() => {
+                    let original_filename = relative_path.rsplit('/').next().map(str::to_owned);
+                    let in_file = InFile::new(
+                        format!("{prefix}{relative_path}"),
+                        file_data.len() as i64,
+                        in_schema.storage_id,
+                        user.id,
+                        original_filename,
+                    );
+                    self.upload_resolving(in_file, FileContent::Data(file_data), on_conflict, user)
+                        .await
+                }
+                _ => Err(PentaractError::InvalidPath),
+            };
 
-        // 3. saving file to db
-        let file = self.repo.create_file(in_file).await?;
+            files.push(UploadedFileSchema::new(filename, result));
+        }
 
-        self._upload(file, in_schema.file, user).await
+        Ok(UploadedSchema { files })
     }
 
-    pub async fn upload_anyway(
+    /// Saves the file resolving a conflict with an existing one by the policy.
+    ///
+    /// Overwritten files get the new content only once it's uploaded,
+    /// so readers never see half-written files.
+    ///
+    /// Returns the path the file is saved at, or `None` if it's skipped
+    async fn upload_resolving(
         &self,
         in_file: InFile,
-        file_data: Bytes,
+        content: FileContent,
+        on_conflict: ConflictPolicy,
         user: &AuthUser,
-    ) -> PentaractResult<()> {
+    ) -> PentaractResult<Option<String>> {
+        if !Self::validate_filepath(&in_file.path) || in_file.path.is_empty() {
+            return Err(PentaractError::InvalidPath);
+        }
+
+        let existing = match self
+            .repo
+            .get_file_by_path(&in_file.path, in_file.storage_id)
+            .await
+        {
+            Ok(file) => Some(file),
+            Err(PentaractError::DoesNotExist(_)) => None,
+            Err(e) => return Err(e),
+        };
+
+        let path = match (existing, on_conflict) {
+            (None, _) => in_file.path.clone(),
+            (Some(_), ConflictPolicy::Skip) => return Ok(None),
+            (Some(_), ConflictPolicy::Rename) => {
+                self.free_path(&in_file.path, in_file.storage_id).await?
+            }
+            // files which are being uploaded right now cannot be overwritten
+            (Some(file), ConflictPolicy::Overwrite) if file.is_uploaded => {
+                // the file keeps its history in a versioned storage
+                let keep_previous = self
+                    .storages_repo
+                    .get_by_id(file.storage_id)
+                    .await?
+                    .versioning;
+                self.upload_content(file, content, keep_previous, user)
+                    .await?;
+                return Ok(Some(in_file.path));
+            }
+            (Some(_), _) => {
+                return Err(PentaractError::AlreadyExists(format!(
+                    "\"{}\"",
+                    in_file.path
+                )))
+            }
+        };
+
+        // the file is counted in the usage once it's created
+        let (storage_id, size) = (in_file.storage_id, in_file.size);
+        let reservation = self.reserve_quotas(storage_id, size, size, 1, user).await?;
+        let result = self.repo.create_file(InFile { path, ..in_file }).await;
+        self.release_quotas(reservation).await;
+        let file = result?;
+        let path = file.path.clone();
+
+        self._upload(file, content, user).await?;
+        Ok(Some(path))
+    }
+
+    /// Resolves the conflict policy of uploads,
+    /// files are overwritten by default in versioned storages since they keep their history
+    async fn conflict_policy(
+        &self,
+        on_conflict: Option<ConflictPolicy>,
+        storage_id: Uuid,
+    ) -> PentaractResult<ConflictPolicy> {
+        if let Some(on_conflict) = on_conflict {
+            return Ok(on_conflict);
+        }
+
+        let storage = self.storages_repo.get_by_id(storage_id).await?;
+        Ok(match storage.versioning {
+            true => ConflictPolicy::Overwrite,
+            false => ConflictPolicy::default(),
+        })
+    }
+
+    /// Extracts an uploaded archive into a folder entry by entry.
+    ///
+    /// The conflict policy is applied to every file separately, and entries which
+    /// cannot be extracted are skipped and listed in the report along with the reason
+    pub async fn extract_archive(
+        &self,
+        in_schema: InArchiveSchema,
+        user: &AuthUser,
+    ) -> PentaractResult<ExtractedSchema> {
         // 0. checking access
         check_access(
             &self.access_repo,
             user.id,
-            in_file.storage_id,
+            in_schema.storage_id,
             &AccessType::W,
         )
         .await?;
 
         // 1. check whether storage got workers
-        Self::check_storage_workers(&self, in_file.storage_id).await?;
+        self.check_storage_workers(in_schema.storage_id).await?;
+
+        // 2. path validation
+        let prefix = in_schema.path;
+        if !Self::validate_path(&prefix) || !(prefix.is_empty() || prefix.ends_with('/')) {
+            return Err(PentaractError::InvalidPath);
+        }
+
+        // 3. choosing the conflict policy
+        let on_conflict = self
+            .conflict_policy(in_schema.on_conflict, in_schema.storage_id)
+            .await?;
+
+        // 4. archives can't be extracted into more than quotas leave
+        let info = self
+            .storages_repo
+            .get_with_info(in_schema.storage_id, user.id)
+            .await?;
+        let left =
+            |usage: i64, max: Option<i64>| max.map_or(u64::MAX, |max| (max - usage).max(0) as u64);
+        let limits = ExtractLimits {
+            max_size: left(info.size, info.max_size)
+                .min(left(info.user_size, info.user_max_size))
+                .min(MAX_EXTRACTED_SIZE),
+            max_files: left(info.files_amount, info.max_files)
+                .min(left(info.user_files_amount, info.user_max_files)),
+            max_entries: MAX_EXTRACTED_ENTRIES,
+        };
+
+        // 5. reading the archive in a separate thread and saving entries as they come
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let reader =
+            tokio::task::spawn_blocking(move || archive::extract(in_schema.file, limits, tx));
+
+        let mut report = ExtractedSchema::default();
+        while let Some(entry) = rx.recv().await {
+            let result = match entry {
+                // the archive root itself, like `./`
+                ExtractedEntry::Folder(path) if path.is_empty() => continue,
+                ExtractedEntry::Folder(path) => {
+                    let path = format!("{prefix}{path}/");
+                    match self.folders_repo.create(&path, in_schema.storage_id).await {
+                        Ok(_) => Ok(path),
+                        // folders are merged
+                        Err(PentaractError::AlreadyExists(_)) => continue,
+                        Err(e) => Err((path, e.to_string())),
+                    }
+                }
+                ExtractedEntry::File(path, data) => {
+                    let path = format!("{prefix}{path}");
+                    self.extract_file(&path, data, on_conflict, in_schema.storage_id, user)
+                        .await
+                        .map_err(|e| e.to_string())
+                        .and_then(|saved| saved.ok_or("path is already taken".to_owned()))
+                        .map_err(|reason| (path, reason))
+                }
+                // paths may be unsafe, so they are left as they are in the archive
+                ExtractedEntry::Skipped(name, reason) => Err((name, reason)),
+            };
+
+            match result {
+                Ok(path) => report.created.push(path),
+                Err((path, reason)) => report.skipped.push(SkippedEntrySchema { path, reason }),
+            }
+        }
+
+        reader.await.map_err(|e| {
+            tracing::error!("{e}");
+            PentaractError::Unknown
+        })??;
+
+        Ok(report)
+    }
+
+    /// Returns the path the file is saved at, or `None` if it's skipped
+    async fn extract_file(
+        &self,
+        path: &str,
+        data: Vec<u8>,
+        on_conflict: ConflictPolicy,
+        storage_id: Uuid,
+        user: &AuthUser,
+    ) -> PentaractResult<Option<String>> {
+        let in_file = InFile::new(
+            path.to_owned(),
+            data.len() as i64,
+            storage_id,
+            user.id,
+            None,
+        );
+
+        self.upload_resolving(in_file, FileContent::Data(data.into()), on_conflict, user)
+            .await
+    }
+
+    async fn _upload(
+        &self,
+        file: File,
+        content: FileContent,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        let content_hash = content.content_hash();
+
+        let result = match self.store_content(file.id, None, content, user).await {
+            Ok(_) => {
+                tracing::debug!("file loaded successfully");
+
+                // setting file as uploaded
+                self.repo.set_as_uploaded(file.id, &content_hash).await
+            }
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            tracing::error!("{e}");
+
+            // fallback logic: deleting file
+            let _ = self.repo.delete_by_id(file.id).await;
+
+            return Err(e);
+        };
+
+        Ok(())
+    }
 
-        // 2. saving file in db
-        let file = self.repo.create_file_anyway(in_file).await?;
+    /// Uploads new content of the file as a pending version and swaps it in once it's uploaded.
+    ///
+    /// The previous content is kept as a version if `keep_previous` is set
+    async fn upload_content(
+        &self,
+        file: File,
+        content: FileContent,
+        keep_previous: bool,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        // the new content is the usage of the user who uploads it,
+        // and the previous one stays the usage of its uploader while it's kept as a version
+        let size = content.size();
+        let (growth, user_growth) = match keep_previous {
+            true => (size, size),
+            false if file.uploaded_by == Some(user.id) => (size - file.size, size - file.size),
+            false => (size - file.size, size),
+        };
+        let reservation = self
+            .reserve_quotas(file.storage_id, growth, user_growth, 0, user)
+            .await?;
+        let result = self.save_content(&file, content, keep_previous, user).await;
+        self.release_quotas(reservation).await;
+
+        result
+    }
+
+    /// The file size is changed once the version becomes current
+    async fn save_content(
+        &self,
+        file: &File,
+        content: FileContent,
+        keep_previous: bool,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        let version_id = self
+            .versions_repo
+            .create_pending(file.id, content.size(), &content.content_hash())
+            .await?;
+
+        let result = match self
+            .store_content(file.id, Some(version_id), content, user)
+            .await
+        {
+            Ok(_) if keep_previous => {
+                self.versions_repo
+                    .make_current(version_id, file.id, user.id)
+                    .await
+            }
+            Ok(_) => {
+                self.versions_repo
+                    .replace_current(version_id, file.id, user.id)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            tracing::error!("{e}");
+
+            // fallback logic: deleting the new version with its chunks
+            let _ = self.versions_repo.delete(version_id, file.storage_id).await;
+
+            return Err(e);
+        }
+
+        Ok(())
+    }
 
-        self._upload(file, file_data, user).await
+    /// Data is uploaded by chunks, and chunks of uploaded parts are linked to the file
+    async fn store_content(
+        &self,
+        file_id: Uuid,
+        version_id: Option<Uuid>,
+        content: FileContent,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        match content {
+            FileContent::Data(file_data) => {
+                self.send_to_upload(file_id, version_id, file_data, user)
+                    .await
+            }
+            FileContent::Parts(parts) => {
+                self.uploads_repo
+                    .link_chunks(parts.upload_id, &parts.part_numbers, file_id, version_id)
+                    .await
+            }
+        }
     }
 
-    async fn _upload(&self, file: File, file_data: Bytes, user: &AuthUser) -> PentaractResult<()> {
-        // 2. sending file to storage manager
+    async fn send_to_upload(
+        &self,
+        file_id: Uuid,
+        version_id: Option<Uuid>,
+        file_data: Bytes,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        // sending file to storage manager
         let (resp_tx, resp_rx) = oneshot::channel();
 
         let message = {
             let upload_file_data = UploadFileData {
-                file_id: file.id,
+                file_id,
+                version_id,
                 user_id: user.id,
                 file_data: file_data.as_ref().into(),
             };
@@ -146,26 +641,52 @@ impl<'d> FilesService<'d> {
         tracing::debug!("sending task to manager");
         let _ = self.tx.send(message).await;
 
-        // 3. waiting for a storage manager result
-        let message_back = match resp_rx.await.unwrap().data {
+        // waiting for a storage manager result
+        match resp_rx.await.unwrap().data {
             StorageManagerData::UploadFile(r) => r,
             _ => unimplemented!(),
-        };
-        if let Err(e) = message_back.and({
-            tracing::debug!("file loaded successfully");
+        }
+    }
 
-            // 4. setting file as uploaded
-            self.repo.set_as_uploaded(file.id).await
-        }) {
-            tracing::error!("{e}");
+    /// Reserves `size` bytes and `files_amount` files in the storage quotas,
+    /// and `user_size` bytes and `files_amount` files in the user ones,
+    /// so concurrent operations can't exceed them together.
+    ///
+    /// Reservations must be released once the usage is saved or the operation is failed.
+    /// Files in trash and versions are counted until they are purged, just like in the storage info
+    async fn reserve_quotas(
+        &self,
+        storage_id: Uuid,
+        size: i64,
+        user_size: i64,
+        files_amount: i64,
+        user: &AuthUser,
+    ) -> PentaractResult<Option<Uuid>> {
+        if size <= 0 && user_size <= 0 && files_amount <= 0 {
+            return Ok(None);
+        }
 
-            // fallback logic: deleting file
-            let _ = self.repo.delete_with_folders(file.id).await;
+        self.storages_repo
+            .reserve_quotas(
+                storage_id,
+                user.id,
+                size.max(0),
+                user_size.max(0),
+                files_amount.max(0),
+            )
+            .await
+            .map(Some)
+    }
 
-            return Err(e);
+    async fn release_quotas(&self, reservation_id: Option<Uuid>) {
+        let Some(reservation_id) = reservation_id else {
+            return;
         };
 
-        Ok(())
+        let result = self.storages_repo.release_quotas(reservation_id).await;
+        if let Err(e) = result {
+            tracing::error!("failed to release quota reservation \"{reservation_id}\": {e}");
+        }
     }
 
     async fn check_storage_workers(&self, storage_id: Uuid) -> PentaractResult<()> {
@@ -180,10 +701,12 @@ impl<'d> FilesService<'d> {
         }
     }
 
+    /// Downloads the current file content if `version_id` is `None`
     pub async fn download(
         &self,
         path: &str,
         storage_id: Uuid,
+        version_id: Option<Uuid>,
         user: &AuthUser,
     ) -> PentaractResult<Vec<u8>> {
         // 0. checking access
@@ -197,13 +720,33 @@ impl<'d> FilesService<'d> {
         // 2. getting file by path
         let file = self.repo.get_file_by_path(path, storage_id).await?;
 
-        // 3. sending task to storage manager
+        // 3. checking the version belongs to the file
+        if let Some(version_id) = version_id {
+            let version = self.versions_repo.get(version_id, storage_id).await?;
+            if version.file_id != file.id {
+                return Err(PentaractError::DoesNotExist(
+                    "such file version".to_string(),
+                ));
+            }
+        }
+
+        self._download(&file, version_id, user).await
+    }
+
+    async fn _download(
+        &self,
+        file: &File,
+        version_id: Option<Uuid>,
+        user: &AuthUser,
+    ) -> PentaractResult<Vec<u8>> {
+        // 4. sending task to storage manager
         let (resp_tx, resp_rx) = oneshot::channel();
 
         let message = {
             let download_file_data = DownloadFileData {
                 file_id: file.id,
-                storage_id,
+                version_id,
+                storage_id: file.storage_id,
                 user_id: user.id,
             };
             ClientMessage {
@@ -215,76 +758,1172 @@ impl<'d> FilesService<'d> {
         tracing::debug!("sending task to manager");
         let _ = self.tx.send(message).await;
 
-        // 4. waiting for a storage manager result
+        // 5. waiting for a storage manager result
         match resp_rx.await.unwrap().data {
             StorageManagerData::DownloadFile(r) => r,
             _ => unimplemented!(),
         }
     }
 
-    pub async fn list_dir(
-        self,
-        storage_id: Uuid,
+    /// Thumbnails are generated on upload, and the ones which are missing or outdated
+    /// are generated from the downloaded file
+    pub async fn thumbnail(
+        &self,
         path: &str,
+        storage_id: Uuid,
+        thumbnails_dir: &str,
         user: &AuthUser,
-    ) -> PentaractResult<Vec<FSElement>> {
+    ) -> PentaractResult<Vec<u8>> {
+        // 0. checking access
         check_access(&self.access_repo, user.id, storage_id, &AccessType::R).await?;
 
-        self.repo.list_dir(storage_id, path).await
-    }
+        // 1. path validation
+        if !Self::validate_filepath(path) {
+            return Err(PentaractError::InvalidPath);
+        }
 
-    pub async fn search(
-        self,
-        storage_id: Uuid,
-        path: &str,
-        search_path: &str,
-        user: &AuthUser,
-    ) -> PentaractResult<Vec<SearchFSElement>> {
-        check_access(&self.access_repo, user.id, storage_id, &AccessType::R).await?;
+        // 2. getting file by path
+        let file = self.repo.get_file_by_path(path, storage_id).await?;
+        if !file.is_uploaded || !thumbnails::is_supported(file.mime_type.as_deref(), file.size) {
+            return Err(PentaractError::DoesNotExist(
+                "thumbnail of such file".to_owned(),
+            ));
+        }
+
+        // 3. serving from cache
+        let cache = ThumbnailsCache::new(thumbnails_dir);
+        if let Some(thumbnail) = cache.get(file.id, file.updated_at).await {
+            return Ok(thumbnail);
+        }
 
-        self.repo.search(search_path, path, storage_id).await
+        // 4. generating otherwise
+        let data = self._download(&file, None, user).await?;
+        cache.generate_and_save(file.id, data).await
     }
 
-    pub async fn rename(
+    /// Collects the folder subtree to be archived, `path` must be empty or end with a slash
+    pub async fn prepare_archive(
         &self,
-        old_path: &str,
-        new_path: &str,
+        path: &str,
         storage_id: Uuid,
+        format: ArchiveFormat,
         user: &AuthUser,
-    ) -> PentaractResult<()> {
+    ) -> PentaractResult<FolderArchive> {
         // 0. checking access
-        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::R).await?;
 
         // 1. path validation
-        if !Self::validate_path(old_path) || !Self::validate_path(new_path) {
+        if !Self::validate_path(path) || !(path.is_empty() || path.ends_with('/')) {
             return Err(PentaractError::InvalidPath);
         }
 
-        // 2. renaming file
-        self.repo.update_path(old_path, new_path, storage_id).await
+        // 2. checking the folder exists
+        self.repo.get_folder_id(path, storage_id).await?;
+
+        // 3. listing the subtree relatively to the folder parent
+        let name = path
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or_default();
+        let parent_len = path.len() - name.len() - usize::from(!name.is_empty());
+
+        let folders = self
+            .folders_repo
+            .list_subtree(path, storage_id)
+            .await?
+            .into_iter()
+            .map(|(_, folder)| folder[parent_len..].to_owned())
+            .filter(|folder| !folder.is_empty())
+            .collect();
+        let files = self
+            .repo
+            .list_by_path(path, storage_id)
+            .await?
+            .into_iter()
+            .filter(|file| file.is_uploaded)
+            .map(|file| File {
+                path: file.path[parent_len..].to_owned(),
+                ..file
+            })
+            .collect();
+
+        Ok(FolderArchive {
+            name: name.to_owned(),
+            format,
+            folders,
+            files,
+        })
     }
 
-    pub async fn delete(
+    /// Streams the archive to `sender` downloading files chunk by chunk,
+    /// so nothing but a single chunk is kept in memory.
+    ///
+    /// It's meant to be run in background, errors are sent to the stream
+    pub async fn write_archive(&self, archive: FolderArchive, mut sender: ArchiveSender) {
+        if let Err(e) = self._write_archive(archive, &mut sender).await {
+            tracing::error!("failed to archive folder: {e}");
+            let error = io::Error::other(e.to_string());
+            let _ = sender.send(Err(error)).await;
+        }
+    }
+
+    async fn _write_archive(
         &self,
-        path: &str,
-        storage_id: Uuid,
-        user: &AuthUser,
+        archive: FolderArchive,
+        sender: &mut ArchiveSender,
     ) -> PentaractResult<()> {
-        // 0. checking access
-        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+        let mut writer: Box<dyn ArchiveWriter> = match archive.format {
+            ArchiveFormat::Zip => Box::new(ZipWriter::new()),
+            ArchiveFormat::Tar => Box::new(TarWriter::new()),
+        };
+        let now = Utc::now();
 
-        // 1. path validation
-        if !Self::validate_path(path) {
-            return Err(PentaractError::InvalidPath);
+        for folder in archive.folders {
+            let entry = ArchiveEntry {
+                path: folder,
+                size: 0,
+                modified_at: now,
+            };
+            let mut part = writer.start_entry(&entry);
+            part.extend(writer.finish_entry()?);
+            if !Self::send_archive_part(sender, part).await {
+                return Ok(());
+            }
+        }
+
+        for file in archive.files {
+            let entry = ArchiveEntry {
+                path: file.path.clone(),
+                size: file.size as u64,
+                modified_at: file.updated_at,
+            };
+            if !Self::send_archive_part(sender, writer.start_entry(&entry)).await {
+                return Ok(());
+            }
+
+            let mut chunks = self.repo.list_chunks_of_file(file.id, None).await?;
+            chunks.sort_by_key(|chunk| chunk.position);
+
+            for chunk in chunks {
+                let data = self._download_chunk(file.storage_id, chunk).await?;
+                writer.write(&data);
+                if !Self::send_archive_part(sender, data).await {
+                    return Ok(());
+                }
+            }
+
+            if !Self::send_archive_part(sender, writer.finish_entry()?).await {
+                return Ok(());
+            }
         }
 
-        // 2. deleting file
-        self.repo.delete(path, storage_id).await
+        Self::send_archive_part(sender, writer.finish()).await;
+        Ok(())
     }
 
-    /////////////////////////////////////////////////////////////////////
-    ////    Helpers
-    /////////////////////////////////////////////////////////////////////
+    async fn _download_chunk(
+        &self,
+        storage_id: Uuid,
+        chunk: FileChunk,
+    ) -> PentaractResult<Vec<u8>> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+
+        let message = ClientMessage {
+            data: ClientData::DownloadChunk(DownloadChunkData { storage_id, chunk }),
+            tx: resp_tx,
+        };
+        // archives are streamed in background, so the manager going away must not panic them
+        self.tx
+            .send(message)
+            .await
+            .map_err(|_| PentaractError::Unknown)?;
+
+        match resp_rx.await.map_err(|_| PentaractError::Unknown)?.data {
+            StorageManagerData::DownloadChunk(r) => r,
+            _ => Err(PentaractError::Unknown),
+        }
+    }
+
+    /// Returns `false` if the client is gone
+    async fn send_archive_part(sender: &mut ArchiveSender, part: Vec<u8>) -> bool {
+        part.is_empty() || sender.send(Ok(Bytes::from(part))).await.is_ok()
+    }
+
+    pub async fn list_dir(
+        &self,
+        storage_id: Uuid,
+        path: &str,
+        query: TreeQuery,
+        user: &AuthUser,
+    ) -> PentaractResult<FSLayerSchema> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::R).await?;
+
+        let cursor = query
+            .cursor
+            .as_deref()
+            .filter(|cursor| !cursor.is_empty())
+            .map(FSCursor::decode)
+            .transpose()?;
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE) as usize;
+
+        // taking one more element to know whether there is the next page
+        let mut items = self
+            .repo
+            .list_dir(
+                storage_id,
+                path,
+                query.sort_by,
+                query.order,
+                cursor.as_ref(),
+                limit as i64 + 1,
+            )
+            .await?;
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items
+                .last()
+                .map(|el| FSCursor::new(el, query.sort_by).encode())
+        } else {
+            None
+        };
+
+        let total = if query.with_total {
+            Some(self.repo.count_dir(storage_id, path).await?)
+        } else {
+            None
+        };
+
+        Ok(FSLayerSchema {
+            items,
+            next_cursor,
+            total,
+        })
+    }
+
+    /// Shows info of a file, or of a folder if `path` ends with a slash
+    pub async fn stat(
+        &self,
+        path: &str,
+        storage_id: Uuid,
+        user: &AuthUser,
+    ) -> PentaractResult<FSElement> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::R).await?;
+
+        if !Self::validate_path(path) || path.is_empty() {
+            return Err(PentaractError::InvalidPath);
+        }
+
+        if path.ends_with('/') {
+            return self.repo.get_folder_stat(path, storage_id).await;
+        }
+
+        match self.repo.get_file_by_path(path, storage_id).await? {
+            file if file.is_uploaded => Ok(file.into()),
+            _ => Err(PentaractError::DoesNotExist("file".to_owned())),
+        }
+    }
+
+    /// Returns the file or the folder with updated attributes
+    pub async fn update_attributes(
+        &self,
+        storage_id: Uuid,
+        params: AttributesParams,
+        user: &AuthUser,
+    ) -> PentaractResult<FSElement> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        let path = params.path;
+        if !Self::validate_path(&path) || path.is_empty() {
+            return Err(PentaractError::InvalidPath);
+        }
+
+        let tags = params
+            .tags
+            .map(|tags| parse_tags(tags.iter().map(String::as_str)));
+        self.repo
+            .update_attributes(&path, storage_id, tags, params.metadata)
+            .await?;
+
+        self.stat(&path, storage_id, user).await
+    }
+
+    pub async fn search(
+        self,
+        storage_id: Uuid,
+        path: &str,
+        query: SearchQuery,
+        user: &AuthUser,
+    ) -> PentaractResult<SearchResultsSchema> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::R).await?;
+
+        let cursor = query
+            .cursor
+            .as_deref()
+            .filter(|cursor| !cursor.is_empty())
+            .map(SearchCursor::decode)
+            .transpose()?;
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE) as usize;
+
+        // taking one more element to know whether there is the next page
+        let mut items = self
+            .repo
+            .search(storage_id, path, &query, cursor.as_ref(), limit as i64 + 1)
+            .await?;
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(|el| SearchCursor::new(el).encode())
+        } else {
+            None
+        };
+
+        Ok(SearchResultsSchema { items, next_cursor })
+    }
+
+    pub async fn rename(
+        &self,
+        old_path: &str,
+        new_path: &str,
+        storage_id: Uuid,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        // 0. checking access
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        // 1. path validation
+        if !Self::validate_path(old_path) || !Self::validate_path(new_path) {
+            return Err(PentaractError::InvalidPath);
+        }
+
+        // 2. renaming file
+        self.repo
+            .update_path(old_path, new_path, storage_id, &[])
+            .await
+    }
+
+    pub async fn copy(
+        &self,
+        in_schema: InTransferSchema,
+        user: &AuthUser,
+    ) -> PentaractResult<TransferOutcome> {
+        self.transfer_files(in_schema, false, user).await
+    }
+
+    pub async fn move_(
+        &self,
+        in_schema: InTransferSchema,
+        user: &AuthUser,
+    ) -> PentaractResult<TransferOutcome> {
+        self.transfer_files(in_schema, true, user).await
+    }
+
+    async fn transfer_files(
+        &self,
+        in_schema: InTransferSchema,
+        is_move: bool,
+        user: &AuthUser,
+    ) -> PentaractResult<TransferOutcome> {
+        let to = in_schema.to.clone();
+        let Some(plan) = self.plan_transfer(in_schema, is_move, user).await? else {
+            return Ok(TransferOutcome {
+                path: to,
+                background: None,
+            });
+        };
+
+        // moves within a storage don't change its usage
+        let (size, files_amount) = if plan.is_same_storage() && is_move {
+            (0, 0)
+        } else {
+            (plan.size(), plan.copies.len() as i64)
+        };
+        let to_storage_id = plan.to_storage_id;
+        let reservation = self
+            .reserve_quotas(to_storage_id, size, size, files_amount, user)
+            .await?;
+        let result = self.save_transfer(plan, is_move).await;
+        self.release_quotas(reservation).await;
+
+        result
+    }
+
+    /// Copies are counted in the usage once they are created
+    async fn save_transfer(
+        &self,
+        plan: TransferPlan,
+        is_move: bool,
+    ) -> PentaractResult<TransferOutcome> {
+        // 4. within a storage files are reusing already uploaded chunks
+        if plan.is_same_storage() {
+            if is_move {
+                self.repo
+                    .update_path(&plan.from, &plan.to, plan.storage_id, &plan.replaced_paths)
+                    .await?;
+            } else {
+                let copies = plan
+                    .copies
+                    .into_iter()
+                    .map(|(file, copy)| (file.id, copy))
+                    .collect();
+                self.repo
+                    .create_copies(
+                        copies,
+                        &plan.folders,
+                        plan.to_storage_id,
+                        &plan.replaced_paths,
+                        true,
+                    )
+                    .await?;
+            }
+
+            return Ok(TransferOutcome {
+                path: plan.to,
+                background: None,
+            });
+        }
+
+        // 5. otherwise they have to be re-uploaded to the target storage chats
+        self.check_storage_workers(plan.to_storage_id).await?;
+
+        let files_copies = plan
+            .copies
+            .iter()
+            .map(|(file, copy)| (file.id, copy.clone()))
+            .collect();
+        self.repo
+            .create_copies(
+                files_copies,
+                &plan.folders,
+                plan.to_storage_id,
+                &plan.replaced_paths,
+                false,
+            )
+            .await?;
+
+        let files = plan
+            .copies
+            .into_iter()
+            .filter(|(_, copy)| !copy.is_uploaded)
+            .collect();
+        let background = BackgroundTransfer {
+            storage_id: plan.storage_id,
+            path: plan.from,
+            files,
+            delete_source: is_move,
+        };
+
+        Ok(TransferOutcome {
+            path: plan.to,
+            background: Some(background),
+        })
+    }
+
+    /// Checks the transfer and lists what has to be copied or moved,
+    /// `None` means it's skipped since the destination is taken
+    async fn plan_transfer(
+        &self,
+        in_schema: InTransferSchema,
+        is_move: bool,
+        user: &AuthUser,
+    ) -> PentaractResult<Option<TransferPlan>> {
+        // 0. checking access
+        let source_access_type = if is_move {
+            AccessType::W
+        } else {
+            AccessType::R
+        };
+        check_access(
+            &self.access_repo,
+            user.id,
+            in_schema.storage_id,
+            &source_access_type,
+        )
+        .await?;
+        check_access(
+            &self.access_repo,
+            user.id,
+            in_schema.to_storage_id,
+            &AccessType::W,
+        )
+        .await?;
+
+        // 1. path validation
+        let (from, to) = (in_schema.from.as_str(), in_schema.to.as_str());
+        let is_same_storage = in_schema.storage_id == in_schema.to_storage_id;
+        if !Self::validate_path(from)
+            || !Self::validate_path(to)
+            || from.is_empty()
+            || to.is_empty()
+            || from.ends_with('/') != to.ends_with('/')
+            || (is_same_storage && (to == from || from.ends_with('/') && to.starts_with(from)))
+        {
+            return Err(PentaractError::InvalidPath);
+        }
+        // overwriting a folder containing the source
+        if is_same_storage
+            && in_schema.on_conflict == ConflictPolicy::Overwrite
+            && to.ends_with('/')
+            && from.starts_with(to)
+        {
+            return Err(PentaractError::InvalidPath);
+        }
+
+        // 2. getting files and folders to transfer
+        if !self.repo.exists(from, in_schema.storage_id).await? {
+            return Err(PentaractError::DoesNotExist(format!("\"{from}\"")));
+        }
+        let files: Vec<_> = self
+            .repo
+            .list_by_path(from, in_schema.storage_id)
+            .await?
+            .into_iter()
+            .filter(|file| file.is_uploaded)
+            .collect();
+        let folders = if from.ends_with('/') {
+            self.folders_repo
+                .list_subtree(from, in_schema.storage_id)
+                .await?
+        } else {
+            vec![]
+        };
+
+        // 3. resolving conflicts
+        let to = match in_schema.on_conflict {
+            ConflictPolicy::Fail => {
+                if self.repo.exists(to, in_schema.to_storage_id).await? {
+                    return Err(PentaractError::AlreadyExists(format!("\"{to}\"")));
+                }
+                to.to_owned()
+            }
+            ConflictPolicy::Overwrite => to.to_owned(),
+            ConflictPolicy::Rename => self.free_path(to, in_schema.to_storage_id).await?,
+            ConflictPolicy::Skip => {
+                if self.repo.exists(to, in_schema.to_storage_id).await? {
+                    return Ok(None);
+                }
+                to.to_owned()
+            }
+        };
+
+        let copies: Vec<_> = files
+            .into_iter()
+            .map(|file| {
+                let now = Utc::now();
+                let copy = File {
+                    id: Uuid::new_v4(),
+                    path: format!("{to}{}", &file.path[from.len()..]),
+                    storage_id: in_schema.to_storage_id,
+                    is_uploaded: is_same_storage,
+                    created_at: now,
+                    updated_at: now,
+                    uploaded_by: Some(user.id),
+                    ..file.clone()
+                };
+                (file, copy)
+            })
+            .collect();
+        let folders: Vec<_> = folders
+            .into_iter()
+            .map(|(id, folder)| (id, format!("{to}{}", &folder[from.len()..])))
+            .collect();
+        // the whole target is replaced, so folders are not merged
+        let replaced_paths = if in_schema.on_conflict == ConflictPolicy::Overwrite {
+            vec![to.clone()]
+        } else {
+            vec![]
+        };
+
+        Ok(Some(TransferPlan {
+            storage_id: in_schema.storage_id,
+            to_storage_id: in_schema.to_storage_id,
+            from: from.to_owned(),
+            to,
+            copies,
+            folders,
+            replaced_paths,
+        }))
+    }
+
+    /// Re-uploads files to another storage, it's meant to be run in background.
+    ///
+    /// Failed files don't stop the rest from being transferred, but the transfer fails then
+    pub async fn transfer(
+        &self,
+        transfer: BackgroundTransfer,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        let mut failed = 0;
+
+        for (file, copy) in transfer.files {
+            let copy_id = copy.id;
+
+            let result = match self._download(&file, None, user).await {
+                Ok(data) => {
+                    self._upload(copy, FileContent::Data(data.into()), user)
+                        .await
+                }
+                Err(e) => {
+                    // fallback logic: deleting the copy
+                    let _ = self.repo.delete_by_id(copy_id).await;
+                    Err(e)
+                }
+            };
+
+            // the source is deleted once it's copied, but the rest of files are transferred anyway
+            let result = match result {
+                Ok(_) if transfer.delete_source => self.repo.delete_by_id(file.id).await,
+                result => result,
+            };
+
+            match result {
+                Ok(_) => (),
+                Err(e) => {
+                    tracing::error!("failed to transfer file \"{}\": {e}", file.path);
+                    failed += 1;
+                }
+            }
+        }
+
+        if failed > 0 {
+            return Err(PentaractError::TransferFailed(failed));
+        }
+
+        // removing moved folders, unless files were added to them during the transfer
+        if transfer.delete_source && transfer.path.ends_with('/') {
+            self.repo
+                .delete_empty_folders(&transfer.path, transfer.storage_id)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete(
+        &self,
+        path: &str,
+        storage_id: Uuid,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        // 0. checking access
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        // 1. path validation
+        if !Self::validate_path(path) {
+            return Err(PentaractError::InvalidPath);
+        }
+
+        // 2. moving file to trash
+        self.trash_repo.create(path, storage_id).await.map(|_| ())
+    }
+
+    pub async fn list_trash(
+        &self,
+        storage_id: Uuid,
+        user: &AuthUser,
+    ) -> PentaractResult<Vec<TrashItem>> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::R).await?;
+
+        self.trash_repo.list(storage_id).await
+    }
+
+    pub async fn restore_from_trash(
+        &self,
+        trash_id: Uuid,
+        storage_id: Uuid,
+        on_conflict: ConflictPolicy,
+        user: &AuthUser,
+    ) -> PentaractResult<TransferredSchema> {
+        // 0. checking access
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        // 1. getting trash item
+        let trash = self.trash_repo.get(trash_id, storage_id).await?;
+
+        // 2. resolving conflicts
+        let (path, replaced_paths) = match on_conflict {
+            ConflictPolicy::Fail => {
+                if self.repo.exists(&trash.path, storage_id).await? {
+                    return Err(PentaractError::AlreadyExists(format!("\"{}\"", trash.path)));
+                }
+                (trash.path.clone(), vec![])
+            }
+            ConflictPolicy::Overwrite => (trash.path.clone(), vec![trash.path.clone()]),
+            ConflictPolicy::Rename => (self.free_path(&trash.path, storage_id).await?, vec![]),
+            // the item stays in trash
+            ConflictPolicy::Skip => {
+                if self.repo.exists(&trash.path, storage_id).await? {
+                    return Ok(TransferredSchema { path: trash.path });
+                }
+                (trash.path.clone(), vec![])
+            }
+        };
+
+        // 3. restoring
+        self.trash_repo
+            .restore(&trash, &path, &replaced_paths)
+            .await?;
+
+        Ok(TransferredSchema { path })
+    }
+
+    pub async fn delete_from_trash(
+        &self,
+        trash_id: Uuid,
+        storage_id: Uuid,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        self.trash_repo.delete(trash_id, storage_id).await
+    }
+
+    pub async fn empty_trash(&self, storage_id: Uuid, user: &AuthUser) -> PentaractResult<()> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        self.trash_repo.delete_all(storage_id).await
+    }
+
+    pub async fn list_versions(
+        &self,
+        path: &str,
+        storage_id: Uuid,
+        user: &AuthUser,
+    ) -> PentaractResult<Vec<FileVersion>> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::R).await?;
+
+        if !Self::validate_filepath(path) {
+            return Err(PentaractError::InvalidPath);
+        }
+
+        let file = self.repo.get_file_by_path(path, storage_id).await?;
+        self.versions_repo.list(file.id).await
+    }
+
+    pub async fn restore_version(
+        &self,
+        version_id: Uuid,
+        storage_id: Uuid,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        // the restored content is a copy of the version, which is the usage of the user
+        let version = self.versions_repo.get(version_id, storage_id).await?;
+        let reservation = self
+            .reserve_quotas(storage_id, version.size, version.size, 0, user)
+            .await?;
+        let result = self.versions_repo.restore(&version, user.id).await;
+        self.release_quotas(reservation).await;
+
+        result
+    }
+
+    pub async fn delete_version(
+        &self,
+        version_id: Uuid,
+        storage_id: Uuid,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        self.versions_repo.delete(version_id, storage_id).await
+    }
+
+    /// Returns amount of deleted versions
+    pub async fn prune_versions(
+        &self,
+        storage_id: Uuid,
+        params: PruneVersionsParams,
+        user: &AuthUser,
+    ) -> PentaractResult<u64> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        if !Self::validate_path(&params.path) {
+            return Err(PentaractError::InvalidPath);
+        }
+
+        self.versions_repo
+            .prune(
+                storage_id,
+                &params.path,
+                params.keep_last,
+                params.older_than_days,
+            )
+            .await
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    ////    Batches
+    /////////////////////////////////////////////////////////////////////
+
+    /// Runs operations one by one and reports the result of each of them
+    pub async fn batch(
+        &self,
+        storage_id: Uuid,
+        params: BatchParams,
+        user: &AuthUser,
+    ) -> PentaractResult<BatchOutcome> {
+        if params.operations.len() > MAX_BATCH_SIZE {
+            return Err(PentaractError::TooManyOperations(MAX_BATCH_SIZE));
+        }
+
+        match params.mode {
+            BatchMode::Transactional => {
+                self.batch_in_transaction(storage_id, params.operations, user)
+                    .await
+            }
+            BatchMode::BestEffort => Ok(self
+                .batch_best_effort(storage_id, params.operations, user)
+                .await),
+        }
+    }
+
+    async fn batch_best_effort(
+        &self,
+        storage_id: Uuid,
+        operations: Vec<BatchOperation>,
+        user: &AuthUser,
+    ) -> BatchOutcome {
+        let mut items = Vec::with_capacity(operations.len());
+        let mut background = vec![];
+
+        for operation in operations {
+            let result = match operation {
+                BatchOperation::Delete { path } => self
+                    .delete(&path, storage_id, user)
+                    .await
+                    .map(|_| (None, None)),
+                BatchOperation::Move(params) => self
+                    .move_(InTransferSchema::new(storage_id, params), user)
+                    .await
+                    .map(|outcome| (Some(outcome.path), outcome.background)),
+                BatchOperation::Copy(params) => self
+                    .copy(InTransferSchema::new(storage_id, params), user)
+                    .await
+                    .map(|outcome| (Some(outcome.path), outcome.background)),
+                BatchOperation::Tag(params) => self
+                    .update_attributes(storage_id, params, user)
+                    .await
+                    .map(|el| (Some(el.path), None)),
+            };
+
+            let item = match result {
+                Ok((path, None)) => BatchItemSchema {
+                    path,
+                    ..BatchItemSchema::new(BatchItemStatus::Done)
+                },
+                Ok((path, Some(transfer))) => {
+                    background.push(transfer);
+                    BatchItemSchema {
+                        path,
+                        ..BatchItemSchema::new(BatchItemStatus::Accepted)
+                    }
+                }
+                Err(e) => BatchItemSchema {
+                    error: Some(e.to_string()),
+                    ..BatchItemSchema::new(BatchItemStatus::Failed)
+                },
+            };
+            items.push(item);
+        }
+
+        BatchOutcome { items, background }
+    }
+
+    /// NOTE:
+    ///
+    /// operations are checked against the storage as it was before the batch,
+    /// so they should not depend on each other
+    async fn batch_in_transaction(
+        &self,
+        storage_id: Uuid,
+        operations: Vec<BatchOperation>,
+        user: &AuthUser,
+    ) -> PentaractResult<BatchOutcome> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        let mut transaction = self.repo.begin().await?;
+        let mut items = Vec::with_capacity(operations.len());
+        let mut reservations = vec![];
+        let mut is_failed = false;
+
+        for operation in operations {
+            if is_failed {
+                items.push(BatchItemSchema::new(BatchItemStatus::Skipped));
+                continue;
+            }
+
+            let item = match self
+                .apply_operation(
+                    &mut transaction,
+                    storage_id,
+                    operation,
+                    &mut reservations,
+                    user,
+                )
+                .await
+            {
+                Ok(path) => BatchItemSchema {
+                    path,
+                    ..BatchItemSchema::new(BatchItemStatus::Done)
+                },
+                Err(e) => {
+                    is_failed = true;
+                    BatchItemSchema {
+                        error: Some(e.to_string()),
+                        ..BatchItemSchema::new(BatchItemStatus::Failed)
+                    }
+                }
+            };
+            items.push(item);
+        }
+
+        let result = if is_failed {
+            // the transaction is rolled back once it's dropped
+            drop(transaction);
+            for item in items.iter_mut() {
+                if matches!(item.status, BatchItemStatus::Done) {
+                    *item = BatchItemSchema::new(BatchItemStatus::RolledBack);
+                }
+            }
+            Ok(())
+        } else {
+            FilesRepository::commit(transaction).await
+        };
+        for reservation in reservations {
+            self.release_quotas(Some(reservation)).await;
+        }
+        result?;
+
+        Ok(BatchOutcome {
+            items,
+            background: vec![],
+        })
+    }
+
+    /// Returns the path of the file or the folder after the operation.
+    ///
+    /// `reservations` are the quota reservations of the batch copies so far,
+    /// they are released once the batch transaction is over
+    async fn apply_operation(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+        storage_id: Uuid,
+        operation: BatchOperation,
+        reservations: &mut Vec<Uuid>,
+        user: &AuthUser,
+    ) -> PentaractResult<Option<String>> {
+        let (in_schema, is_move) = match operation {
+            BatchOperation::Delete { path } => {
+                if !Self::validate_path(&path) {
+                    return Err(PentaractError::InvalidPath);
+                }
+
+                TrashRepository::trash_path(transaction, &path, storage_id).await?;
+                return Ok(None);
+            }
+            BatchOperation::Tag(params) => {
+                if !Self::validate_path(&params.path) || params.path.is_empty() {
+                    return Err(PentaractError::InvalidPath);
+                }
+
+                let tags = params
+                    .tags
+                    .map(|tags| parse_tags(tags.iter().map(String::as_str)));
+                FilesRepository::set_attributes(
+                    transaction,
+                    &params.path,
+                    storage_id,
+                    tags,
+                    params.metadata,
+                )
+                .await?;
+                return Ok(Some(params.path));
+            }
+            BatchOperation::Move(params) => (InTransferSchema::new(storage_id, params), true),
+            BatchOperation::Copy(params) => (InTransferSchema::new(storage_id, params), false),
+        };
+
+        // other storages chats cannot be rolled back
+        if in_schema.to_storage_id != storage_id {
+            return Err(PentaractError::TransferNotTransactional);
+        }
+
+        let Some(plan) = self.plan_transfer(in_schema, is_move, user).await? else {
+            return Ok(None);
+        };
+        if is_move {
+            FilesRepository::move_path(
+                transaction,
+                &plan.from,
+                &plan.to,
+                storage_id,
+                &plan.replaced_paths,
+            )
+            .await?;
+        } else {
+            let size = plan.size();
+            let reservation = self
+                .reserve_quotas(storage_id, size, size, plan.copies.len() as i64, user)
+                .await?;
+            reservations.extend(reservation);
+
+            let copies = plan
+                .copies
+                .into_iter()
+                .map(|(file, copy)| (file.id, copy))
+                .collect();
+            FilesRepository::save_copies(
+                transaction,
+                copies,
+                &plan.folders,
+                storage_id,
+                &plan.replaced_paths,
+                true,
+            )
+            .await?;
+        }
+
+        Ok(Some(plan.to))
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    ////    Duplicates
+    /////////////////////////////////////////////////////////////////////
+
+    /// Finds files with the same content in the storage,
+    /// or in all the storages the user has access to if it's not given.
+    ///
+    /// Files uploaded before contents were hashed are not taken into account
+    pub async fn find_duplicates(
+        &self,
+        storage_id: Option<Uuid>,
+        query: DuplicatesQuery,
+        user: &AuthUser,
+    ) -> PentaractResult<DuplicatesSchema> {
+        let storage_ids = match storage_id {
+            Some(storage_id) => {
+                check_access(&self.access_repo, user.id, storage_id, &AccessType::R).await?;
+                vec![storage_id]
+            }
+            None => self
+                .storages_repo
+                .list_by_user_id(user.id)
+                .await?
+                .into_iter()
+                .map(|storage| storage.id)
+                .collect(),
+        };
+        let min_size = query.min_size.unwrap_or(1).max(1);
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+
+        let files = self
+            .repo
+            .list_duplicates(&storage_ids, min_size, limit.into())
+            .await?;
+
+        // files of a group go one after another
+        let mut groups: Vec<DuplicatesGroupSchema> = vec![];
+        for file in files {
+            let duplicate = DuplicateFileSchema {
+                storage_id: file.storage_id,
+                path: file.path,
+            };
+
+            match groups.last_mut() {
+                Some(group)
+                    if group.content_hash == file.content_hash && group.size == file.size =>
+                {
+                    group.files.push(duplicate)
+                }
+                _ => groups.push(DuplicatesGroupSchema {
+                    content_hash: file.content_hash,
+                    size: file.size,
+                    reclaimable_size: file.size * (file.stored_amount - 1),
+                    files: vec![duplicate],
+                }),
+            }
+        }
+        let reclaimable_size = groups.iter().map(|group| group.reclaimable_size).sum();
+
+        Ok(DuplicatesSchema {
+            groups,
+            reclaimable_size,
+        })
+    }
+
+    /// Replaces contents of duplicates in the storage with references to the same chunks
+    pub async fn dedupe(
+        &self,
+        storage_id: Uuid,
+        params: DedupeParams,
+        user: &AuthUser,
+    ) -> PentaractResult<DedupedSchema> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        let (files_amount, reclaimed_size) = self
+            .repo
+            .dedupe(storage_id, params.content_hash.as_deref())
+            .await?;
+
+        Ok(DedupedSchema {
+            files_amount,
+            reclaimed_size,
+        })
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    ////    Helpers
+    /////////////////////////////////////////////////////////////////////
+
+    /// Finds a free path by adding a number to the name like `name (1).ext`
+    async fn free_path(&self, path: &str, storage_id: Uuid) -> PentaractResult<String> {
+        let (path, slash) = match path.strip_suffix('/') {
+            Some(path) => (path, "/"),
+            None => (path, ""),
+        };
+        let name_start = path.rfind('/').map(|i| i + 1).unwrap_or(0);
+        let name = &path[name_start..];
+        let (stem, suffix) = match (slash, name.char_indices().nth(1)) {
+            // dots at the start of names are parts of stems, like in `.bashrc`
+            ("", Some((second, _))) => match name[second..].find('.') {
+                Some(i) => path.split_at(name_start + second + i),
+                None => (path, ""),
+            },
+            _ => (path, ""),
+        };
+
+        let mut i = 0;
+        loop {
+            let candidate = if i == 0 {
+                format!("{stem}{suffix}{slash}")
+            } else {
+                format!("{stem} ({i}){suffix}{slash}")
+            };
+
+            if !self.repo.exists(&candidate, storage_id).await? {
+                return Ok(candidate);
+            }
+            i += 1;
+        }
+    }
+
+    /// Hex encoded SHA-256 of the file content
+    pub fn content_hash(data: &[u8]) -> String {
+        Sha256::digest(data)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
 
     fn validate_filepath(path: &str) -> bool {
         Self::validate_path(path) && !path.ends_with(r"/")
@@ -0,0 +1,204 @@
+use chrono::{Duration, Utc};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use sqlx::PgPool;
+use tokio_util::bytes::Bytes;
+use uuid::Uuid;
+
+use crate::{
+    common::{
+        access::check_access, channels::ClientSender, jwt_manager::AuthUser, url_signer::UrlSigner,
+    },
+    errors::{PentaractError, PentaractResult},
+    models::access::AccessType,
+    repositories::{access::AccessRepository, files::FilesRepository, users::UsersRepository},
+    schemas::{
+        files::InFileSchema,
+        presigned::{InPresignSchema, PresignedAction, PresignedQuery, PresignedUrlSchema},
+    },
+    services::files::FilesService,
+};
+
+/// Presigned URLs are served under this prefix
+const URL_PREFIX: &str = "/api/presigned";
+
+/// One hour
+const DEFAULT_EXPIRES_IN: i64 = 60 * 60;
+
+/// One week, like S3 does
+const MAX_EXPIRES_IN: i64 = 7 * 24 * 60 * 60;
+
+/// Characters kept as is in paths segments
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// NOTE:
+///
+/// actions are done on behalf of URLs signers,
+/// so URLs stop working once their signers lose access to storages
+pub struct PresignedService<'d> {
+    files_repo: FilesRepository<'d>,
+    users_repo: UsersRepository<'d>,
+    access_repo: AccessRepository<'d>,
+    db: &'d PgPool,
+    tx: ClientSender,
+    secret_key: &'d str,
+}
+
+impl<'d> PresignedService<'d> {
+    pub fn new(db: &'d PgPool, tx: ClientSender, secret_key: &'d str) -> Self {
+        let files_repo = FilesRepository::new(db);
+        let users_repo = UsersRepository::new(db);
+        let access_repo = AccessRepository::new(db);
+        Self {
+            files_repo,
+            users_repo,
+            access_repo,
+            db,
+            tx,
+            secret_key,
+        }
+    }
+
+    pub async fn presign(
+        &self,
+        storage_id: Uuid,
+        in_schema: InPresignSchema,
+        user: &AuthUser,
+    ) -> PentaractResult<PresignedUrlSchema> {
+        // 0. checking access
+        let access_type = Self::access_type(in_schema.action);
+        check_access(&self.access_repo, user.id, storage_id, &access_type).await?;
+
+        // 1. validation
+        let path = in_schema.path.as_str();
+        if !Self::validate_filepath(path) {
+            return Err(PentaractError::InvalidPath);
+        }
+        let expires_in = in_schema.expires_in.unwrap_or(DEFAULT_EXPIRES_IN);
+        if !(1..=MAX_EXPIRES_IN).contains(&expires_in) {
+            return Err(PentaractError::InvalidUrlExpiration(MAX_EXPIRES_IN));
+        }
+        if let PresignedAction::Download = in_schema.action {
+            let file = self.files_repo.get_file_by_path(path, storage_id).await?;
+            if !file.is_uploaded {
+                return Err(PentaractError::DoesNotExist("file".to_owned()));
+            }
+        }
+
+        // 2. signing
+        let expires_at = Utc::now() + Duration::seconds(expires_in);
+        let expires = expires_at.timestamp();
+        let signature = UrlSigner::sign(
+            in_schema.action.as_str(),
+            storage_id,
+            path,
+            user.id,
+            expires,
+            self.secret_key,
+        );
+
+        let path: Vec<_> = path
+            .split('/')
+            .map(|segment| utf8_percent_encode(segment, PATH_SEGMENT).to_string())
+            .collect();
+        let url = format!(
+            "{URL_PREFIX}/{storage_id}/{}?user_id={}&expires={expires}&signature={signature}",
+            path.join("/"),
+            user.id,
+        );
+
+        Ok(PresignedUrlSchema {
+            url,
+            method: in_schema.action.method(),
+            expires_at,
+        })
+    }
+
+    /// Returns the filename and the file content
+    pub async fn download(
+        &self,
+        storage_id: Uuid,
+        path: &str,
+        query: PresignedQuery,
+    ) -> PentaractResult<(String, Vec<u8>)> {
+        let user = self
+            .open(PresignedAction::Download, storage_id, path, query)
+            .await?;
+
+        let data = FilesService::new(self.db, self.tx.clone())
+            .download(path, storage_id, None, &user)
+            .await?;
+
+        let name = path.rsplit('/').next().unwrap_or_default().to_owned();
+        Ok((name, data))
+    }
+
+    pub async fn upload(
+        &self,
+        storage_id: Uuid,
+        path: &str,
+        file: Bytes,
+        query: PresignedQuery,
+    ) -> PentaractResult<()> {
+        let user = self
+            .open(PresignedAction::Upload, storage_id, path, query)
+            .await?;
+
+        let in_schema = InFileSchema::new(storage_id, path.to_owned(), file, None, None);
+        FilesService::new(self.db, self.tx.clone())
+            .upload_to(in_schema, &user)
+            .await
+            .map(|_| ())
+    }
+
+    /// Verifies the URL and returns its signer
+    async fn open(
+        &self,
+        action: PresignedAction,
+        storage_id: Uuid,
+        path: &str,
+        query: PresignedQuery,
+    ) -> PentaractResult<AuthUser> {
+        UrlSigner::verify(
+            &query.signature,
+            action.as_str(),
+            storage_id,
+            path,
+            query.user_id,
+            query.expires,
+            self.secret_key,
+        )?;
+        if query.expires <= Utc::now().timestamp() {
+            return Err(PentaractError::UrlExpired);
+        }
+
+        let user = self
+            .users_repo
+            .get_by_id(query.user_id)
+            .await
+            .map(|user| AuthUser::new(user.id, user.email))?;
+        check_access(
+            &self.access_repo,
+            user.id,
+            storage_id,
+            &Self::access_type(action),
+        )
+        .await?;
+
+        Ok(user)
+    }
+
+    fn access_type(action: PresignedAction) -> AccessType {
+        match action {
+            PresignedAction::Download => AccessType::R,
+            PresignedAction::Upload => AccessType::W,
+        }
+    }
+
+    fn validate_filepath(path: &str) -> bool {
+        !path.is_empty() && !path.starts_with('/') && !path.ends_with('/') && !path.contains("//")
+    }
+}
@@ -0,0 +1,458 @@
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::{
+    common::{
+        access::check_access,
+        channels::{
+            ClientData, ClientMessage, ClientSender, StorageManagerData, StorageManagerMessage,
+            UploadPartData,
+        },
+        jwt_manager::AuthUser,
+    },
+    errors::{PentaractError, PentaractResult},
+    models::{access::AccessType, files::S3Object, storages::StorageWithInfo},
+    repositories::{
+        access::AccessRepository, files::FilesRepository, folders::FoldersRepository,
+        multipart_uploads::MultipartUploadsRepository, storages::StoragesRepository,
+    },
+    schemas::{
+        files::{ConflictPolicy, InFileSchema, InFolderSchema, InTransferSchema},
+        s3::{ObjectsListing, S3Query},
+    },
+    services::files::{FilesService, UploadedParts},
+};
+
+const DEFAULT_MAX_KEYS: i64 = 1000;
+pub const MAX_PARTS: i32 = 10000;
+/// Parts but the last one must be at least that big, as in AWS
+const MIN_PART_SIZE: i64 = 5 * 1024 * 1024;
+
+/// NOTE:
+///
+/// maps the S3 API onto storages and files: buckets are storages named by their ids
+/// and object keys are files paths.
+///
+/// Keys ending with a slash are folders, they are listed as common prefixes only.
+///
+/// Parts of multipart uploads are uploaded by chunks as they come, and their chunks
+/// become the file chunks once the upload is completed
+pub struct S3Service<'d> {
+    files_service: FilesService<'d>,
+    files_repo: FilesRepository<'d>,
+    folders_repo: FoldersRepository<'d>,
+    storages_repo: StoragesRepository<'d>,
+    uploads_repo: MultipartUploadsRepository<'d>,
+    access_repo: AccessRepository<'d>,
+    tx: ClientSender,
+}
+
+impl<'d> S3Service<'d> {
+    pub fn new(db: &'d PgPool, tx: ClientSender) -> Self {
+        let files_service = FilesService::new(db, tx.clone());
+        let files_repo = FilesRepository::new(db);
+        let folders_repo = FoldersRepository::new(db);
+        let storages_repo = StoragesRepository::new(db);
+        let uploads_repo = MultipartUploadsRepository::new(db);
+        let access_repo = AccessRepository::new(db);
+        Self {
+            files_service,
+            files_repo,
+            folders_repo,
+            storages_repo,
+            uploads_repo,
+            access_repo,
+            tx,
+        }
+    }
+
+    /// Storages along with their creation dates
+    pub async fn list_buckets(
+        &self,
+        user: &AuthUser,
+    ) -> PentaractResult<Vec<(StorageWithInfo, DateTime<Utc>)>> {
+        let storages = self.storages_repo.list_by_user_id(user.id).await?;
+        let ids: Vec<Uuid> = storages.iter().map(|storage| storage.id).collect();
+        let roots = self.folders_repo.list_roots_created_at(&ids).await?;
+
+        Ok(storages
+            .into_iter()
+            .map(|storage| {
+                let created_at = roots
+                    .iter()
+                    .find(|(id, _)| *id == storage.id)
+                    .map(|(_, created_at)| *created_at)
+                    .unwrap_or_else(Utc::now);
+                (storage, created_at)
+            })
+            .collect())
+    }
+
+    pub async fn head_bucket(&self, storage_id: Uuid, user: &AuthUser) -> PentaractResult<()> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::R).await
+    }
+
+    /// Lists objects after the continuation token (or `start-after`) for V2 requests
+    /// and after the marker for V1 ones.
+    ///
+    /// Only `/` is supported as a delimiter
+    pub async fn list_objects(
+        &self,
+        storage_id: Uuid,
+        query: S3Query,
+        user: &AuthUser,
+    ) -> PentaractResult<ObjectsListing> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::R).await?;
+
+        let is_v2 = query.list_type == Some(2);
+        let prefix = query.prefix.unwrap_or_default();
+        let max_keys = query
+            .max_keys
+            .unwrap_or(DEFAULT_MAX_KEYS)
+            .clamp(0, DEFAULT_MAX_KEYS);
+        let after = match (is_v2, &query.continuation_token) {
+            (true, Some(token)) => ObjectsListing::decode_token(token)?,
+            (true, None) => query.start_after.clone().unwrap_or_default(),
+            (false, _) => query.marker.clone().unwrap_or_default(),
+        };
+
+        let mut objects = if max_keys > 0 {
+            self.files_repo
+                .list_objects(
+                    storage_id,
+                    &prefix,
+                    &after,
+                    query.delimiter.is_none(),
+                    max_keys + 1,
+                )
+                .await?
+        } else {
+            vec![]
+        };
+
+        let is_truncated = objects.len() as i64 > max_keys;
+        objects.truncate(max_keys as usize);
+        let last_key = objects
+            .last()
+            .filter(|_| is_truncated)
+            .map(|object| object.key.clone());
+
+        Ok(ObjectsListing {
+            bucket: storage_id.to_string(),
+            prefix,
+            delimiter: query.delimiter,
+            max_keys,
+            is_url_encoded: query.encoding_type.as_deref() == Some("url"),
+            is_v2,
+            marker: query.marker,
+            start_after: query.start_after,
+            continuation_token: query.continuation_token,
+            objects,
+            is_truncated,
+            next_token: last_key.as_deref().map(ObjectsListing::encode_token),
+            next_marker: last_key,
+        })
+    }
+
+    pub async fn head_object(
+        &self,
+        storage_id: Uuid,
+        key: &str,
+        user: &AuthUser,
+    ) -> PentaractResult<S3Object> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::R).await?;
+
+        self.files_repo.get_object(key, storage_id).await
+    }
+
+    pub async fn get_object(
+        &self,
+        storage_id: Uuid,
+        key: &str,
+        user: &AuthUser,
+    ) -> PentaractResult<(S3Object, Vec<u8>)> {
+        let object = self.head_object(storage_id, key, user).await?;
+        let data = self
+            .files_service
+            .download(key, storage_id, None, user)
+            .await?;
+
+        Ok((object, data))
+    }
+
+    /// Objects are always overwritten, keys ending with a slash create folders.
+    ///
+    /// Returns the ETag of the object
+    pub async fn put_object(
+        &self,
+        storage_id: Uuid,
+        key: &str,
+        data: Bytes,
+        user: &AuthUser,
+    ) -> PentaractResult<String> {
+        let etag = format!("\"{}\"", FilesService::content_hash(&data));
+
+        if key.ends_with('/') {
+            let (parent, name) = FilesRepository::split_path(key);
+            let in_schema = InFolderSchema::new(
+                storage_id,
+                parent.trim_end_matches('/').to_owned(),
+                name.to_owned(),
+            );
+
+            return match self.files_service.create_folder(in_schema, user).await {
+                Ok(_) | Err(PentaractError::AlreadyExists(_)) => Ok(etag),
+                Err(e) => Err(e),
+            };
+        }
+
+        let in_schema = InFileSchema::new(
+            storage_id,
+            key.to_owned(),
+            data,
+            None,
+            Some(ConflictPolicy::Overwrite),
+        );
+        self.files_service.upload_to(in_schema, user).await?;
+
+        Ok(etag)
+    }
+
+    /// Deleting missing objects is not an error, folders are deleted only if they are empty
+    pub async fn delete_object(
+        &self,
+        storage_id: Uuid,
+        key: &str,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        if key.ends_with('/') {
+            let content = self
+                .files_repo
+                .list_objects(storage_id, key, "", false, 1)
+                .await?;
+            if !content.is_empty() || !self.files_repo.exists(key, storage_id).await? {
+                return Ok(());
+            }
+        }
+
+        match self.files_service.delete(key, storage_id, user).await {
+            Ok(_) | Err(PentaractError::DoesNotExist(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Copies between storages are waited for, as the copy must exist once it's responded
+    pub async fn copy_object(
+        &self,
+        from_storage_id: Uuid,
+        from: &str,
+        storage_id: Uuid,
+        key: &str,
+        user: &AuthUser,
+    ) -> PentaractResult<S3Object> {
+        if from.ends_with('/') || key.ends_with('/') {
+            return Err(PentaractError::InvalidPath);
+        }
+
+        // copying an object onto itself only changes its metadata, which is not supported
+        if from_storage_id != storage_id || from != key {
+            let in_schema = InTransferSchema {
+                storage_id: from_storage_id,
+                from: from.to_owned(),
+                to_storage_id: storage_id,
+                to: key.to_owned(),
+                on_conflict: ConflictPolicy::Overwrite,
+            };
+            let outcome = self.files_service.copy(in_schema, user).await?;
+            if let Some(background) = outcome.background {
+                self.files_service.transfer(background, user).await?;
+            }
+        }
+
+        self.head_object(storage_id, key, user).await
+    }
+
+    pub async fn create_multipart_upload(
+        &self,
+        storage_id: Uuid,
+        key: &str,
+        user: &AuthUser,
+    ) -> PentaractResult<Uuid> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        if key.is_empty() || key.ends_with('/') || key.starts_with('/') || key.contains("//") {
+            return Err(PentaractError::InvalidPath);
+        }
+
+        self.uploads_repo
+            .create(storage_id, key, user.id)
+            .await
+            .map(|upload| upload.id)
+    }
+
+    /// Parts are uploaded by chunks right away, and the part replaces the previous one
+    /// with the same number once it's uploaded.
+    ///
+    /// Returns the ETag of the part
+    pub async fn upload_part(
+        &self,
+        storage_id: Uuid,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        data: Bytes,
+        user: &AuthUser,
+    ) -> PentaractResult<String> {
+        if !(1..=MAX_PARTS).contains(&part_number) {
+            return Err(PentaractError::InvalidPartNumber(MAX_PARTS));
+        }
+        let upload_id = self.get_upload_id(storage_id, key, upload_id, user).await?;
+
+        let etag = format!("\"{}\"", FilesService::content_hash(&data));
+        let part_id = self
+            .uploads_repo
+            .create_part(upload_id, part_number, data.len() as i64, &etag)
+            .await?;
+
+        let result = match self.send_to_upload(part_id, storage_id, data).await {
+            Ok(_) => self.uploads_repo.set_part_as_uploaded(part_id).await,
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            tracing::error!("{e}");
+
+            // fallback logic: deleting the part with its chunks
+            let _ = self.uploads_repo.delete_part(part_id).await;
+
+            return Err(e);
+        }
+
+        Ok(etag)
+    }
+
+    /// Links chunks of the parts to the file, parts must be listed in ascending order
+    /// with the ETags they were uploaded with
+    pub async fn complete_multipart_upload(
+        &self,
+        storage_id: Uuid,
+        key: &str,
+        upload_id: &str,
+        parts: Vec<(i32, String)>,
+        user: &AuthUser,
+    ) -> PentaractResult<S3Object> {
+        let upload_id = self.get_upload_id(storage_id, key, upload_id, user).await?;
+
+        if parts.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(PentaractError::InvalidPartOrder);
+        }
+
+        let uploaded = self.uploads_repo.list_parts(upload_id).await?;
+        let mut size = 0;
+        let mut etags = String::new();
+        for (i, (number, etag)) in parts.iter().enumerate() {
+            let part = uploaded
+                .iter()
+                .find(|part| part.part_number == *number)
+                .filter(|part| part.etag.trim_matches('"') == etag.trim_matches('"'))
+                .ok_or(PentaractError::InvalidPart(*number))?;
+
+            if i + 1 < parts.len() && part.size < MIN_PART_SIZE {
+                return Err(PentaractError::PartTooSmall(*number));
+            }
+
+            size += part.size;
+            etags.push_str(part.etag.trim_matches('"'));
+        }
+
+        // objects are hashed by hashes of their parts, like in AWS,
+        // since the parts are never joined in memory
+        let content_hash = format!(
+            "{}-{}",
+            FilesService::content_hash(etags.as_bytes()),
+            parts.len()
+        );
+        let parts = UploadedParts {
+            upload_id,
+            part_numbers: parts.iter().map(|(number, _)| *number).collect(),
+            size,
+            content_hash,
+        };
+        self.files_service
+            .upload_parts_to(storage_id, key, parts, user)
+            .await?;
+        self.uploads_repo.delete(upload_id, storage_id).await?;
+
+        self.files_repo.get_object(key, storage_id).await
+    }
+
+    pub async fn abort_multipart_upload(
+        &self,
+        storage_id: Uuid,
+        key: &str,
+        upload_id: &str,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        let upload_id = self.get_upload_id(storage_id, key, upload_id, user).await?;
+
+        self.uploads_repo.delete(upload_id, storage_id).await
+    }
+
+    async fn send_to_upload(
+        &self,
+        part_id: Uuid,
+        storage_id: Uuid,
+        part_data: Bytes,
+    ) -> PentaractResult<()> {
+        // sending part to storage manager
+        let (resp_tx, resp_rx) = oneshot::channel();
+
+        let message = {
+            let upload_part_data = UploadPartData {
+                part_id,
+                storage_id,
+                part_data: part_data.as_ref().into(),
+            };
+            ClientMessage {
+                data: ClientData::UploadPart(upload_part_data),
+                tx: resp_tx,
+            }
+        };
+
+        tracing::debug!("sending task to manager");
+        let _ = self.tx.send(message).await;
+
+        // waiting for a storage manager result
+        match resp_rx.await {
+            Ok(StorageManagerMessage {
+                data: StorageManagerData::UploadPart(result),
+            }) => result,
+            _ => Err(PentaractError::Unknown),
+        }
+    }
+
+    /// Uploads are found by their ids along with their storages and keys
+    async fn get_upload_id(
+        &self,
+        storage_id: Uuid,
+        key: &str,
+        upload_id: &str,
+        user: &AuthUser,
+    ) -> PentaractResult<Uuid> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        let not_found = || PentaractError::DoesNotExist("such multipart upload".to_owned());
+        let upload_id = Uuid::parse_str(upload_id).map_err(|_| not_found())?;
+        let upload = self.uploads_repo.get(upload_id, storage_id).await?;
+        if upload.path != key {
+            return Err(not_found());
+        }
+
+        Ok(upload.id)
+    }
+}
@@ -0,0 +1,103 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    common::{jwt_manager::AuthUser, links, token_cipher::TokenCipher},
+    errors::{PentaractError, PentaractResult},
+    models::s3_keys::{InS3Key, S3Key},
+    repositories::s3_keys::S3KeysRepository,
+    schemas::s3_keys::{CreatedS3KeySchema, InS3KeySchema},
+};
+
+const ACCESS_KEY_ID_PREFIX: &str = "PK";
+const ACCESS_KEY_ID_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const ACCESS_KEY_ID_LEN: usize = 20;
+
+/// NOTE:
+///
+/// S3 keys are pairs of access key ids and secrets S3 clients sign requests with.
+///
+/// Like app passwords, they can be revoked without changing the account password
+pub struct S3KeysService<'d> {
+    repo: S3KeysRepository<'d>,
+    encryption_key: &'d str,
+}
+
+impl<'d> S3KeysService<'d> {
+    pub fn new(db: &'d PgPool, encryption_key: &'d str) -> Self {
+        let repo = S3KeysRepository::new(db);
+        Self {
+            repo,
+            encryption_key,
+        }
+    }
+
+    pub async fn create(
+        &self,
+        in_schema: InS3KeySchema,
+        user: &AuthUser,
+    ) -> PentaractResult<CreatedS3KeySchema> {
+        let name = in_schema.name.trim();
+        if name.is_empty() {
+            return Err(PentaractError::InvalidS3KeyName);
+        }
+
+        let secret_access_key = links::generate_token();
+        let in_obj = InS3Key {
+            user_id: user.id,
+            name: name.to_owned(),
+            access_key_id: Self::generate_access_key_id(),
+            encrypted_secret: TokenCipher::encrypt(&secret_access_key, self.encryption_key)?,
+        };
+        let s3_key = self.repo.create(in_obj).await?;
+
+        Ok(CreatedS3KeySchema {
+            s3_key,
+            secret_access_key,
+        })
+    }
+
+    pub async fn list(&self, user: &AuthUser) -> PentaractResult<Vec<S3Key>> {
+        self.repo.list(user.id).await
+    }
+
+    pub async fn revoke(&self, id: Uuid, user: &AuthUser) -> PentaractResult<()> {
+        self.repo.delete(id, user.id).await
+    }
+
+    /// Returns the owner of the key and the secret to check the request signature with
+    pub async fn get_signer(&self, access_key_id: &str) -> PentaractResult<(AuthUser, String)> {
+        let credentials = self
+            .repo
+            .get_credentials(access_key_id)
+            .await
+            .map_err(|e| match e {
+                PentaractError::DoesNotExist(_) => PentaractError::InvalidAccessKeyId,
+                e => e,
+            })?;
+        let secret = TokenCipher::decrypt(&credentials.encrypted_secret, self.encryption_key)?;
+
+        Ok((
+            AuthUser::new(credentials.user_id, credentials.email),
+            secret,
+        ))
+    }
+
+    pub async fn mark_as_used(&self, access_key_id: &str) -> PentaractResult<()> {
+        self.repo.mark_as_used(access_key_id).await
+    }
+
+    /// Access key ids look like AWS ones: upper case letters and digits
+    fn generate_access_key_id() -> String {
+        let random: String = [Uuid::new_v4(), Uuid::new_v4()]
+            .iter()
+            .flat_map(|id| id.into_bytes())
+            .map(|byte| {
+                ACCESS_KEY_ID_ALPHABET[byte as usize % ACCESS_KEY_ID_ALPHABET.len()] as char
+            })
+            .take(ACCESS_KEY_ID_LEN - ACCESS_KEY_ID_PREFIX.len())
+            .collect();
+
+        format!("{ACCESS_KEY_ID_PREFIX}{random}")
+    }
+}
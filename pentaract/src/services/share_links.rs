@@ -0,0 +1,266 @@
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    common::{
+        access::check_access, channels::ClientSender, jwt_manager::AuthUser, links,
+        password_manager::PasswordManager,
+    },
+    errors::{PentaractError, PentaractResult},
+    models::{
+        access::AccessType,
+        files::FSElement,
+        share_links::{InShareLink, ShareLink},
+    },
+    repositories::{
+        access::AccessRepository, files::FilesRepository, share_links::ShareLinksRepository,
+        users::UsersRepository,
+    },
+    schemas::{
+        files::{ArchiveFormat, TreeQuery},
+        share_links::{InShareLinkSchema, SharedDownload, SharedElementSchema, SharedLayerSchema},
+    },
+    services::files::FilesService,
+};
+
+/// NOTE:
+///
+/// shared content is accessed on behalf of links creators,
+/// so links stop working once their creators lose access to storages.
+///
+/// Paths of shared folders contents are relative to the shared folders
+pub struct ShareLinksService<'d> {
+    repo: ShareLinksRepository<'d>,
+    files_repo: FilesRepository<'d>,
+    users_repo: UsersRepository<'d>,
+    access_repo: AccessRepository<'d>,
+    db: &'d PgPool,
+    tx: ClientSender,
+}
+
+impl<'d> ShareLinksService<'d> {
+    pub fn new(db: &'d PgPool, tx: ClientSender) -> Self {
+        let repo = ShareLinksRepository::new(db);
+        let files_repo = FilesRepository::new(db);
+        let users_repo = UsersRepository::new(db);
+        let access_repo = AccessRepository::new(db);
+        Self {
+            repo,
+            files_repo,
+            users_repo,
+            access_repo,
+            db,
+            tx,
+        }
+    }
+
+    pub async fn create(
+        &self,
+        storage_id: Uuid,
+        in_schema: InShareLinkSchema,
+        user: &AuthUser,
+    ) -> PentaractResult<ShareLink> {
+        // 0. checking access
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        // 1. validation
+        let path = in_schema.path.as_str();
+        if !Self::validate_path(path) {
+            return Err(PentaractError::InvalidPath);
+        }
+        if in_schema
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+            || in_schema.max_downloads.is_some_and(|max| max < 1)
+        {
+            return Err(PentaractError::InvalidShareLinkLimits);
+        }
+
+        // 2. getting the shared file or folder
+        let (file_id, folder_id) = if path.is_empty() || path.ends_with('/') {
+            let folder_id = self.files_repo.get_folder_id(path, storage_id).await?;
+            (None, Some(folder_id))
+        } else {
+            match self.files_repo.get_file_by_path(path, storage_id).await? {
+                file if file.is_uploaded => (Some(file.id), None),
+                _ => return Err(PentaractError::DoesNotExist("file".to_owned())),
+            }
+        };
+
+        // 3. saving
+        let password_hash = in_schema
+            .password
+            .filter(|password| !password.is_empty())
+            .map(|password| PasswordManager::generate(&password))
+            .transpose()?;
+        let in_obj = InShareLink {
+            storage_id,
+            token: links::generate_token(),
+            file_id,
+            folder_id,
+            password_hash,
+            expires_at: in_schema.expires_at,
+            max_downloads: in_schema.max_downloads,
+            created_by: user.id,
+        };
+
+        self.repo.create(in_obj).await
+    }
+
+    pub async fn list(&self, storage_id: Uuid, user: &AuthUser) -> PentaractResult<Vec<ShareLink>> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        self.repo.list(storage_id).await
+    }
+
+    pub async fn revoke(&self, storage_id: Uuid, id: Uuid, user: &AuthUser) -> PentaractResult<()> {
+        check_access(&self.access_repo, user.id, storage_id, &AccessType::W).await?;
+
+        self.repo.delete(id, storage_id).await
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    ////    Shared content
+    /////////////////////////////////////////////////////////////////////
+
+    /// Shows info of the shared file or folder
+    pub async fn stat(
+        &self,
+        token: &str,
+        password: Option<&str>,
+    ) -> PentaractResult<SharedElementSchema> {
+        let (link, path, _) = self.open(token, password).await?;
+
+        let mut el: FSElement = if link.is_file {
+            self.files_repo
+                .get_file_by_path(&path, link.storage_id)
+                .await?
+                .into()
+        } else {
+            self.files_repo
+                .get_folder_stat(&path, link.storage_id)
+                .await?
+        };
+        el.path = String::new();
+
+        Ok(el.into())
+    }
+
+    pub async fn list_dir(
+        &self,
+        token: &str,
+        path: &str,
+        query: TreeQuery,
+        password: Option<&str>,
+    ) -> PentaractResult<SharedLayerSchema> {
+        let (link, shared_path, user) = self.open(token, password).await?;
+        if link.is_file {
+            return Err(PentaractError::DoesNotExist("folder".to_owned()));
+        }
+        if !Self::validate_path(path) {
+            return Err(PentaractError::InvalidPath);
+        }
+
+        let full_path = format!("{shared_path}{path}");
+        let mut fs_layer = FilesService::new(self.db, self.tx.clone())
+            .list_dir(
+                link.storage_id,
+                full_path.trim_end_matches('/'),
+                query,
+                &user,
+            )
+            .await?;
+        for el in fs_layer.items.iter_mut() {
+            if let Some(relative_path) = el.path.strip_prefix(&shared_path) {
+                el.path = relative_path.to_owned();
+            }
+        }
+
+        Ok(fs_layer.into())
+    }
+
+    /// Files of shared folders can be downloaded one by one, and folders as archives.
+    ///
+    /// Every download counts against the link downloads limit,
+    /// it's counted before the content is read and given back if reading fails
+    pub async fn download(
+        &self,
+        token: &str,
+        path: &str,
+        format: ArchiveFormat,
+        password: Option<&str>,
+    ) -> PentaractResult<SharedDownload> {
+        let (link, shared_path, user) = self.open(token, password).await?;
+        if !Self::validate_path(path) || (link.is_file && !path.is_empty()) {
+            return Err(PentaractError::InvalidPath);
+        }
+
+        let path = format!("{shared_path}{path}");
+        let files_service = FilesService::new(self.db, self.tx.clone());
+
+        self.repo.reserve_download(link.id).await?;
+        let result = if !link.is_file && (path.is_empty() || path.ends_with('/')) {
+            files_service
+                .prepare_archive(&path, link.storage_id, format, &user)
+                .await
+                .map(SharedDownload::Archive)
+        } else {
+            files_service
+                .download(&path, link.storage_id, None, &user)
+                .await
+                .map(|data| {
+                    let name = path.rsplit('/').next().unwrap_or_default().to_owned();
+                    SharedDownload::File { name, data }
+                })
+        };
+
+        // fallback logic: giving the download back
+        if result.is_err() {
+            let _ = self.repo.release_download(link.id).await;
+        }
+
+        result
+    }
+
+    /// Returns the link, the current path of the shared file or folder and the link creator
+    async fn open(
+        &self,
+        token: &str,
+        password: Option<&str>,
+    ) -> PentaractResult<(ShareLink, String, AuthUser)> {
+        let link = self.repo.get_by_token(token).await?;
+        if link.is_expired() {
+            return Err(PentaractError::ShareLinkExpired);
+        }
+        if link.is_downloads_limit_reached() {
+            return Err(PentaractError::ShareLinkDownloadsLimitReached);
+        }
+
+        if let Some(password_hash) = link.password_hash.as_deref() {
+            password
+                .ok_or(PentaractError::InvalidShareLinkPassword)
+                .and_then(|password| PasswordManager::verify(password, password_hash))
+                .map_err(|_| PentaractError::InvalidShareLinkPassword)?;
+        }
+
+        // the shared file or folder is in trash
+        let path = link
+            .path
+            .clone()
+            .ok_or(PentaractError::DoesNotExist("shared file".to_owned()))?;
+
+        let user = self
+            .users_repo
+            .get_by_id(link.created_by)
+            .await
+            .map(|user| AuthUser::new(user.id, user.email))?;
+        check_access(&self.access_repo, user.id, link.storage_id, &AccessType::R).await?;
+
+        Ok((link, path, user))
+    }
+
+    fn validate_path(path: &str) -> bool {
+        !path.starts_with('/') && !path.contains("//")
+    }
+}
@@ -4,13 +4,16 @@ use uuid::Uuid;
 
 use crate::{
     common::{
-        channels::{DownloadFileData, UploadFileData},
+        channels::{DownloadFileData, UploadFileData, UploadPartData},
         telegram_api::bot_api::TelegramBotApi,
         types::ChatId,
     },
     errors::PentaractResult,
-    models::file_chunks::FileChunk,
-    repositories::{files::FilesRepository, storages::StoragesRepository},
+    models::{file_chunks::FileChunk, multipart_uploads::MultipartPartChunk, storages::Storage},
+    repositories::{
+        files::FilesRepository, multipart_uploads::MultipartUploadsRepository,
+        storage_chats::StorageChatsRepository, storages::StoragesRepository,
+    },
     schemas::files::DownloadedChunkSchema,
 };
 
@@ -18,67 +21,143 @@ use super::storage_workers_scheduler::StorageWorkersScheduler;
 
 pub struct StorageManagerService<'d> {
     storages_repo: StoragesRepository<'d>,
+    storage_chats_repo: StorageChatsRepository<'d>,
     files_repo: FilesRepository<'d>,
+    uploads_repo: MultipartUploadsRepository<'d>,
     telegram_baseurl: &'d str,
     db: &'d PgPool,
     chunk_size: usize,
     rate_limit: u8,
+    encryption_key: &'d str,
 }
 
 impl<'d> StorageManagerService<'d> {
-    pub fn new(db: &'d PgPool, telegram_baseurl: &'d str, rate_limit: u8) -> Self {
+    pub fn new(
+        db: &'d PgPool,
+        telegram_baseurl: &'d str,
+        rate_limit: u8,
+        encryption_key: &'d str,
+    ) -> Self {
         let files_repo = FilesRepository::new(db);
         let storages_repo = StoragesRepository::new(db);
+        let storage_chats_repo = StorageChatsRepository::new(db);
+        let uploads_repo = MultipartUploadsRepository::new(db);
         let chunk_size = 20 * 1024 * 1024;
         Self {
             storages_repo,
+            storage_chats_repo,
             files_repo,
+            uploads_repo,
             chunk_size,
             telegram_baseurl,
             db,
             rate_limit,
+            encryption_key,
         }
     }
 
-    pub async fn upload(&self, data: UploadFileData) -> PentaractResult<()> {
+    pub async fn upload(&self, data: &UploadFileData) -> PentaractResult<()> {
         // 1. getting storage
         let storage = self.storages_repo.get_by_file_id(data.file_id).await?;
 
-        // 2. dividing file into chunks
-        let bytes_chunks = data.file_data.chunks(self.chunk_size);
+        // 2. uploading by chunks
+        let documents = self
+            .upload_chunks(&storage, data.file_id, &data.file_data)
+            .await?;
 
-        // 3. uploading by chunks
+        // 3. saving chunks to db
+        let chunks = documents
+            .into_iter()
+            .enumerate()
+            .map(|(position, (chat_id, telegram_file_id))| {
+                FileChunk::new(
+                    Uuid::new_v4(),
+                    data.file_id,
+                    telegram_file_id,
+                    position as i16,
+                    Some(chat_id),
+                    data.version_id,
+                )
+            })
+            .collect();
+        self.files_repo.create_chunks_batch(chunks).await
+    }
+
+    pub async fn upload_part(&self, data: &UploadPartData) -> PentaractResult<()> {
+        // 1. getting storage
+        let storage = self.storages_repo.get_by_id(data.storage_id).await?;
+
+        // 2. uploading by chunks
+        let documents = self
+            .upload_chunks(&storage, data.part_id, &data.part_data)
+            .await?;
+
+        // 3. saving chunks to db
+        let chunks = documents
+            .into_iter()
+            .enumerate()
+            .map(|(position, (chat_id, telegram_file_id))| {
+                MultipartPartChunk::new(
+                    Uuid::new_v4(),
+                    data.part_id,
+                    telegram_file_id,
+                    position as i16,
+                    chat_id,
+                )
+            })
+            .collect();
+        self.uploads_repo.create_part_chunks(chunks).await
+    }
+
+    /// Uploads the data by chunks spread across the storage chats.
+    ///
+    /// Returns chats and Telegram file ids of the chunks in their order
+    async fn upload_chunks(
+        &self,
+        storage: &Storage,
+        id: Uuid,
+        data: &[u8],
+    ) -> PentaractResult<Vec<(ChatId, String)>> {
+        // 1. getting all the storage chats to spread chunks across them
+        let chats: Vec<_> = std::iter::once(storage.chat_id)
+            .chain(
+                self.storage_chats_repo
+                    .list_by_storage_id(storage.id)
+                    .await?
+                    .into_iter()
+                    .map(|chat| chat.chat_id),
+            )
+            .collect();
+
+        // 2. dividing data into chunks
+        let bytes_chunks = data.chunks(self.chunk_size);
+
+        // 3. uploading by chunks, starting from a different chat for every file
+        // so single-chunk files don't pile up in the primary chat
+        let offset = (id.as_u128() % chats.len() as u128) as usize;
         let futures_: Vec<_> = bytes_chunks
             .enumerate()
             .map(|(position, bytes_chunk)| {
                 self.upload_chunk(
                     storage.id,
-                    storage.chat_id,
-                    data.file_id,
+                    chats[(offset + position) % chats.len()],
                     position,
                     bytes_chunk,
                 )
             })
             .collect();
 
-        let chunks = join_all(futures_)
-            .await
-            .into_iter()
-            .collect::<PentaractResult<Vec<_>>>()?;
-
-        // 4. saving chunks to db
-        self.files_repo.create_chunks_batch(chunks).await
+        join_all(futures_).await.into_iter().collect()
     }
 
     async fn upload_chunk(
         &self,
         storage_id: Uuid,
         chat_id: ChatId,
-        file_id: Uuid,
         position: usize,
         bytes_chunk: &[u8],
-    ) -> PentaractResult<FileChunk> {
-        let scheduler = StorageWorkersScheduler::new(self.db, self.rate_limit);
+    ) -> PentaractResult<(ChatId, String)> {
+        let scheduler = StorageWorkersScheduler::new(self.db, self.rate_limit, self.encryption_key);
 
         let document = TelegramBotApi::new(self.telegram_baseurl, scheduler)
             .upload(bytes_chunk, chat_id, storage_id)
@@ -90,13 +169,15 @@ impl<'d> StorageManagerService<'d> {
             position
         );
 
-        let chunk = FileChunk::new(Uuid::new_v4(), file_id, document.file_id, position as i16);
-        Ok(chunk)
+        Ok((chat_id, document.file_id))
     }
 
     pub async fn download(&self, data: DownloadFileData) -> PentaractResult<Vec<u8>> {
         // 1. getting chunks
-        let chunks = self.files_repo.list_chunks_of_file(data.file_id).await?;
+        let chunks = self
+            .files_repo
+            .list_chunks_of_file(data.file_id, data.version_id)
+            .await?;
 
         // 2. downloading by chunks
         let futures_: Vec<_> = chunks
@@ -114,12 +195,12 @@ impl<'d> StorageManagerService<'d> {
         Ok(file)
     }
 
-    async fn download_chunk(
+    pub async fn download_chunk(
         &self,
         storage_id: Uuid,
         chunk: FileChunk,
     ) -> PentaractResult<DownloadedChunkSchema> {
-        let scheduler = StorageWorkersScheduler::new(self.db, self.rate_limit);
+        let scheduler = StorageWorkersScheduler::new(self.db, self.rate_limit, self.encryption_key);
 
         let file = TelegramBotApi::new(self.telegram_baseurl, scheduler)
             .download(&chunk.telegram_file_id, storage_id)
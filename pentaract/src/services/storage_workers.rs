@@ -1,34 +1,46 @@
+use chrono::{Duration, Utc};
 use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::{
-    common::{access::check_access, jwt_manager::AuthUser},
+    common::{access::check_access, jwt_manager::AuthUser, token_cipher::TokenCipher},
     errors::{PentaractError, PentaractResult},
-    models::{
-        access::AccessType,
-        storage_workers::{InStorageWorker, StorageWorker},
+    models::{access::AccessType, storage_workers::InStorageWorker},
+    repositories::{
+        access::AccessRepository, storage_workers::StorageWorkersRepository,
+        storage_workers_stats::StorageWorkersStatsRepository,
+    },
+    schemas::storage_workers::{
+        InStorageWorkerSchema, StorageStatsSchema, StorageWorkerSchema, StorageWorkerStatsSchema,
+        StorageWorkersStatsQuery,
     },
-    repositories::{access::AccessRepository, storage_workers::StorageWorkersRepository},
-    schemas::storage_workers::InStorageWorkerSchema,
 };
 
 pub struct StorageWorkersService<'d> {
     repo: StorageWorkersRepository<'d>,
+    stats_repo: StorageWorkersStatsRepository<'d>,
     access_repo: AccessRepository<'d>,
+    encryption_key: &'d str,
 }
 
 impl<'d> StorageWorkersService<'d> {
-    pub fn new(db: &'d PgPool) -> Self {
+    pub fn new(db: &'d PgPool, encryption_key: &'d str) -> Self {
         let repo = StorageWorkersRepository::new(db);
+        let stats_repo = StorageWorkersStatsRepository::new(db);
         let access_repo = AccessRepository::new(db);
-        Self { repo, access_repo }
+        Self {
+            repo,
+            stats_repo,
+            access_repo,
+            encryption_key,
+        }
     }
 
     pub async fn create(
         &self,
         in_schema: InStorageWorkerSchema,
         user: &AuthUser,
-    ) -> PentaractResult<StorageWorker> {
+    ) -> PentaractResult<StorageWorkerSchema> {
         // checking if user already has a storage worker with such name
         if let Ok(_) = self
             .repo
@@ -38,18 +50,35 @@ impl<'d> StorageWorkersService<'d> {
             return Err(PentaractError::StorageWorkerNameConflict);
         }
 
+        // encrypting token
+        let token = TokenCipher::encrypt(&in_schema.token, self.encryption_key)?;
+        let token_hash = TokenCipher::hash(&in_schema.token, self.encryption_key);
+
         // creating storage worker
         let in_model = InStorageWorker::new(
             in_schema.name,
             user.id,
-            in_schema.token,
+            token,
+            token_hash,
             in_schema.storage_id,
         );
-        self.repo.create(in_model).await
+        let sw = self.repo.create(in_model).await?;
+
+        Ok(StorageWorkerSchema::new(
+            sw,
+            TokenCipher::mask(&in_schema.token),
+        ))
     }
 
-    pub async fn list(&self, user: &AuthUser) -> PentaractResult<Vec<StorageWorker>> {
-        self.repo.list_by_user_id(user.id).await
+    pub async fn list(&self, user: &AuthUser) -> PentaractResult<Vec<StorageWorkerSchema>> {
+        let sws = self.repo.list_by_user_id(user.id).await?;
+
+        sws.into_iter()
+            .map(|sw| {
+                let token = TokenCipher::decrypt(&sw.token, self.encryption_key)?;
+                Ok(StorageWorkerSchema::new(sw, TokenCipher::mask(&token)))
+            })
+            .collect()
     }
 
     pub async fn has_storage_workers(
@@ -62,4 +91,51 @@ impl<'d> StorageWorkersService<'d> {
 
         self.repo.storage_has_any(storage_id).await
     }
+
+    pub async fn stats(
+        &self,
+        query: StorageWorkersStatsQuery,
+        rate_limit: u8,
+        user: &AuthUser,
+    ) -> PentaractResult<StorageStatsSchema> {
+        // 0. checking access
+        check_access(&self.access_repo, user.id, query.storage_id, &AccessType::R).await?;
+
+        // 1. a day before by default
+        let until = query.until.unwrap_or_else(Utc::now);
+        let since = query.since.unwrap_or(until - Duration::days(1));
+
+        // 2. getting stats of the storage and of its workers
+        let stats = self
+            .stats_repo
+            .list_by_storage(query.storage_id, query.granularity, since, until)
+            .await?;
+        let points = self
+            .stats_repo
+            .list_by_storage_workers(query.storage_id, query.granularity, since, until)
+            .await?;
+
+        // 3. grouping points by storage workers
+        let mut storage_workers: Vec<StorageWorkerStatsSchema> = vec![];
+        for point in points {
+            let index = storage_workers
+                .iter()
+                .position(|sw| sw.storage_worker_id == point.storage_worker_id)
+                .unwrap_or_else(|| {
+                    storage_workers.push(StorageWorkerStatsSchema {
+                        storage_worker_id: point.storage_worker_id,
+                        name: point.name.clone(),
+                        stats: vec![],
+                    });
+                    storage_workers.len() - 1
+                });
+            storage_workers[index].stats.push(point.into());
+        }
+
+        Ok(StorageStatsSchema {
+            rate_limit,
+            stats,
+            storage_workers,
+        })
+    }
 }
@@ -4,32 +4,72 @@ use sqlx::PgPool;
 use tokio::time::sleep;
 use uuid::Uuid;
 
-use crate::{errors::PentaractResult, repositories::storage_workers::StorageWorkersRepository};
+use crate::{
+    common::{token_cipher::TokenCipher, types::ChatId},
+    errors::PentaractResult,
+    models::{storage_workers::StorageWorkerToken, storage_workers_stats::InStorageWorkerUsage},
+    repositories::{
+        storage_workers::StorageWorkersRepository,
+        storage_workers_stats::StorageWorkersStatsRepository,
+    },
+};
 
 /// Manages storage workers by limiting their usage
 pub struct StorageWorkersScheduler<'d> {
     repo: StorageWorkersRepository<'d>,
+    stats_repo: StorageWorkersStatsRepository<'d>,
     rate: u8,
+    encryption_key: &'d str,
 }
 
 impl<'d> StorageWorkersScheduler<'d> {
-    pub fn new(db: &'d PgPool, rate: u8) -> Self {
+    pub fn new(db: &'d PgPool, rate: u8, encryption_key: &'d str) -> Self {
         let repo = StorageWorkersRepository::new(db);
-        Self { repo, rate }
+        let stats_repo = StorageWorkersStatsRepository::new(db);
+        Self {
+            repo,
+            stats_repo,
+            rate,
+            encryption_key,
+        }
     }
 
-    pub async fn get_token(&self, storage_id: Uuid) -> PentaractResult<String> {
+    pub async fn get_token(
+        &self,
+        storage_id: Uuid,
+        chat_id: Option<ChatId>,
+    ) -> PentaractResult<StorageWorkerToken> {
         loop {
             // attempting
-            if let Some(schema) = self.repo.get_token(storage_id, self.rate).await? {
-                return Ok(schema.token);
+            if let Some(schema) = self.repo.get_token(storage_id, chat_id, self.rate).await? {
+                let token = TokenCipher::decrypt(&schema.token, self.encryption_key)?;
+                return Ok(StorageWorkerToken {
+                    id: schema.id,
+                    token,
+                });
             };
 
             // waiting for a while
             tracing::debug!(
                 "[TELEGRAM API] waiting for getting a token for a storage with id \"{storage_id}\"",
             );
+            let _ = self.stats_repo.register_throttled_wait(storage_id).await;
             sleep(Duration::from_secs(1)).await;
         }
     }
+
+    /// Registers a storage worker usage for statistics
+    ///
+    /// Statistics are not critical, so failures are only logged
+    pub async fn report_usage(
+        &self,
+        storage_worker_id: Uuid,
+        storage_id: Uuid,
+        usage: InStorageWorkerUsage,
+    ) {
+        let _ = self
+            .stats_repo
+            .register_usage(storage_worker_id, storage_id, usage)
+            .await;
+    }
 }
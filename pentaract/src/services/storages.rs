@@ -2,29 +2,39 @@ use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::{
-    common::{access::check_access, jwt_manager::AuthUser},
+    common::{access::check_access, jwt_manager::AuthUser, types::ChatId},
     errors::{PentaractError, PentaractResult},
     models::{
         access::{AccessType, UserWithAccess},
+        storage_chats::{InStorageChat, StorageChat},
         storages::{InStorage, Storage, StorageWithInfo},
     },
-    repositories::{access::AccessRepository, storages::StoragesRepository},
+    repositories::{
+        access::AccessRepository, storage_chats::StorageChatsRepository,
+        storages::StoragesRepository,
+    },
     schemas::{
         access::{GrantAccess, RestrictAccess},
-        storages::InStorageSchema,
+        storages::{InStorageChatSchema, InStorageSchema, QuotaSchema, UpdateStorageSchema},
     },
 };
 
 pub struct StoragesService<'d> {
     repo: StoragesRepository<'d>,
+    chats_repo: StorageChatsRepository<'d>,
     access_repo: AccessRepository<'d>,
 }
 
 impl<'d> StoragesService<'d> {
     pub fn new(db: &'d PgPool) -> Self {
         let repo = StoragesRepository::new(db);
+        let chats_repo = StorageChatsRepository::new(db);
         let access_repo = AccessRepository::new(db);
-        Self { repo, access_repo }
+        Self {
+            repo,
+            chats_repo,
+            access_repo,
+        }
     }
 
     pub async fn create(
@@ -68,6 +78,47 @@ impl<'d> StoragesService<'d> {
         self.repo.get_by_id(id).await
     }
 
+    pub async fn update(
+        &self,
+        id: Uuid,
+        in_schema: UpdateStorageSchema,
+        user: &AuthUser,
+    ) -> PentaractResult<Storage> {
+        check_access(&self.access_repo, user.id, id, &AccessType::A).await?;
+
+        self.repo.set_versioning(id, in_schema.versioning).await
+    }
+
+    pub async fn set_quota(
+        &self,
+        id: Uuid,
+        in_schema: QuotaSchema,
+        user: &AuthUser,
+    ) -> PentaractResult<Storage> {
+        check_access(&self.access_repo, user.id, id, &AccessType::A).await?;
+        Self::validate_quota(&in_schema)?;
+
+        self.repo
+            .set_quota(id, in_schema.max_size, in_schema.max_files)
+            .await
+    }
+
+    /// Quotas of users are applied to files they upload in the storage
+    pub async fn set_user_quota(
+        &self,
+        id: Uuid,
+        user_id: Uuid,
+        in_schema: QuotaSchema,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        check_access(&self.access_repo, user.id, id, &AccessType::A).await?;
+        Self::validate_quota(&in_schema)?;
+
+        self.access_repo
+            .set_quota(user_id, id, in_schema.max_size, in_schema.max_files)
+            .await
+    }
+
     pub async fn delete(&self, id: Uuid, user: &AuthUser) -> PentaractResult<()> {
         check_access(&self.access_repo, user.id, id, &AccessType::A).await?;
 
@@ -113,4 +164,44 @@ impl<'d> StoragesService<'d> {
 
         self.access_repo.delete_access(in_schema.user_id, id).await
     }
+
+    pub async fn add_chat(
+        &self,
+        id: Uuid,
+        in_schema: InStorageChatSchema,
+        user: &AuthUser,
+    ) -> PentaractResult<StorageChat> {
+        check_access(&self.access_repo, user.id, id, &AccessType::A).await?;
+
+        let in_model = InStorageChat::new(id, in_schema.chat_id);
+        self.chats_repo.create(in_model).await
+    }
+
+    pub async fn list_chats(&self, id: Uuid, user: &AuthUser) -> PentaractResult<Vec<StorageChat>> {
+        check_access(&self.access_repo, user.id, id, &AccessType::R).await?;
+
+        self.chats_repo.list_by_storage_id(id).await
+    }
+
+    /// Already uploaded chunks stay in the chat, it just won't get new ones
+    pub async fn remove_chat(
+        &self,
+        id: Uuid,
+        chat_id: ChatId,
+        user: &AuthUser,
+    ) -> PentaractResult<()> {
+        check_access(&self.access_repo, user.id, id, &AccessType::A).await?;
+
+        self.chats_repo.delete(id, chat_id).await
+    }
+
+    fn validate_quota(in_schema: &QuotaSchema) -> PentaractResult<()> {
+        if in_schema.max_size.is_some_and(|max| max < 0)
+            || in_schema.max_files.is_some_and(|max| max < 0)
+        {
+            return Err(PentaractError::InvalidQuota);
+        }
+
+        Ok(())
+    }
 }
@@ -1,13 +1,15 @@
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, QueryBuilder};
+use uuid::Uuid;
 
 use crate::{
-    common::{db::pool::get_pool, password_manager::PasswordManager},
+    common::{db::pool::get_pool, password_manager::PasswordManager, token_cipher::TokenCipher},
     config::Config,
     errors::PentaractError,
     models::users::InDBUser,
-    repositories::users::UsersRepository,
+    repositories::{storage_workers::StorageWorkersRepository, users::UsersRepository},
 };
 
 #[inline]
@@ -66,12 +68,16 @@ pub async fn init_db(db: &PgPool) {
             id         UUID         PRIMARY KEY,
             name       VARCHAR(255) NOT NULL,
             token      VARCHAR(255) NOT NULL UNIQUE,
+            token_hash VARCHAR(64)  UNIQUE,
             user_id    UUID         NOT NULL REFERENCES users
                                             ON DELETE CASCADE 
                                             ON UPDATE CASCADE,
             storage_id UUID         REFERENCES storages
         );
 
+    ",
+        "
+        ALTER TABLE storage_workers ADD COLUMN IF NOT EXISTS token_hash VARCHAR(64) UNIQUE;
     ",
         "
         DO
@@ -110,9 +116,7 @@ pub async fn init_db(db: &PgPool) {
             storage_id  UUID         NOT NULL REFERENCES storages
                                             ON DELETE CASCADE 
                                             ON UPDATE CASCADE,
-            is_uploaded bool         NOT NULL,
-
-            UNIQUE (path, storage_id)
+            is_uploaded bool         NOT NULL
         );
     ",
         "
@@ -133,6 +137,476 @@ pub async fn init_db(db: &PgPool) {
                                                 ON UPDATE CASCADE,
             dt                 TIMESTAMP DEFAULT NOW()
         );
+    ",
+        "
+        CREATE TABLE IF NOT EXISTS storage_chats (
+            id         UUID   PRIMARY KEY,
+            storage_id UUID   NOT NULL REFERENCES storages
+                                    ON DELETE CASCADE
+                                    ON UPDATE CASCADE,
+            chat_id    BigInt NOT NULL UNIQUE
+        );
+    ",
+        "
+        ALTER TABLE file_chunks ADD COLUMN IF NOT EXISTS chat_id BigInt;
+    ",
+        "
+        CREATE TABLE IF NOT EXISTS trash (
+            id         UUID        PRIMARY KEY,
+            storage_id UUID        NOT NULL REFERENCES storages
+                                        ON DELETE CASCADE
+                                        ON UPDATE CASCADE,
+            path       VARCHAR     NOT NULL,
+            deleted_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+    ",
+        "
+        ALTER TABLE files ADD COLUMN IF NOT EXISTS trash_id UUID REFERENCES trash
+                                                                ON DELETE CASCADE
+                                                                ON UPDATE CASCADE;
+    ",
+        "
+        CREATE INDEX IF NOT EXISTS files_trash_id_idx ON files (trash_id);
+    ",
+        // trashed files keep their paths, so paths are unique among not trashed files only
+        "
+        ALTER TABLE files DROP CONSTRAINT IF EXISTS files_path_storage_id_key;
+    ",
+        // paths were replaced with folders, see `migrate_paths_to_folders`
+        "
+        DO
+        $$
+        BEGIN
+        IF EXISTS (
+            SELECT *
+            FROM information_schema.columns
+            WHERE table_schema = current_schema() AND table_name = 'files' AND column_name = 'path'
+        ) THEN
+            CREATE UNIQUE INDEX IF NOT EXISTS files_path_storage_id_idx
+            ON files (path, storage_id)
+            WHERE trash_id IS NULL;
+        END IF;
+        END;
+        $$;
+    ",
+        "
+        ALTER TABLE storage_workers_usages ADD COLUMN IF NOT EXISTS chat_id BigInt;
+    ",
+        "
+        CREATE TABLE IF NOT EXISTS storage_workers_stats (
+            storage_worker_id UUID        NOT NULL REFERENCES storage_workers
+                                                ON DELETE CASCADE
+                                                ON UPDATE CASCADE,
+            storage_id        UUID        NOT NULL REFERENCES storages
+                                                ON DELETE CASCADE
+                                                ON UPDATE CASCADE,
+            dt                TIMESTAMPTZ NOT NULL,
+            calls             BigInt      NOT NULL DEFAULT 0,
+            bytes_uploaded    BigInt      NOT NULL DEFAULT 0,
+            bytes_downloaded  BigInt      NOT NULL DEFAULT 0,
+            errors            BigInt      NOT NULL DEFAULT 0,
+
+            PRIMARY KEY (storage_worker_id, storage_id, dt)
+        );
+    ",
+        "
+        CREATE INDEX IF NOT EXISTS storage_workers_stats_storage_id_dt_idx
+        ON storage_workers_stats (storage_id, dt);
+    ",
+        "
+        CREATE TABLE IF NOT EXISTS storages_stats (
+            storage_id      UUID        NOT NULL REFERENCES storages
+                                              ON DELETE CASCADE
+                                              ON UPDATE CASCADE,
+            dt              TIMESTAMPTZ NOT NULL,
+            throttled_waits BigInt      NOT NULL DEFAULT 0,
+
+            PRIMARY KEY (storage_id, dt)
+        );
+    ",
+        "
+        ALTER TABLE storages ADD COLUMN IF NOT EXISTS versioning bool NOT NULL DEFAULT false;
+    ",
+        // the current file content is stored in chunks without version
+        "
+        CREATE TABLE IF NOT EXISTS file_versions (
+            id          UUID        PRIMARY KEY,
+            file_id     UUID        NOT NULL REFERENCES files
+                                          ON DELETE CASCADE
+                                          ON UPDATE CASCADE,
+            size        BigInt      NOT NULL,
+            is_uploaded bool        NOT NULL,
+            replaced_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+    ",
+        "
+        CREATE INDEX IF NOT EXISTS file_versions_file_id_idx ON file_versions (file_id);
+    ",
+        "
+        ALTER TABLE file_chunks ADD COLUMN IF NOT EXISTS version_id UUID REFERENCES file_versions
+                                                                        ON DELETE CASCADE
+                                                                        ON UPDATE CASCADE;
+    ",
+        "
+        CREATE INDEX IF NOT EXISTS file_chunks_file_id_version_id_idx
+        ON file_chunks (file_id, version_id);
+    ",
+        "
+        ALTER TABLE files
+            ADD COLUMN IF NOT EXISTS created_at        TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            ADD COLUMN IF NOT EXISTS updated_at        TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            ADD COLUMN IF NOT EXISTS uploaded_by       UUID REFERENCES users
+                                                            ON DELETE SET NULL
+                                                            ON UPDATE CASCADE,
+            ADD COLUMN IF NOT EXISTS mime_type         VARCHAR,
+            ADD COLUMN IF NOT EXISTS original_filename VARCHAR;
+    ",
+        "
+        ALTER TABLE file_versions
+            ADD COLUMN IF NOT EXISTS created_at  TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            ADD COLUMN IF NOT EXISTS uploaded_by UUID REFERENCES users
+                                                      ON DELETE SET NULL
+                                                      ON UPDATE CASCADE;
+    ",
+        // every storage has a root folder with no parent and an empty name
+        "
+        CREATE TABLE IF NOT EXISTS folders (
+            id         UUID        PRIMARY KEY,
+            storage_id UUID        NOT NULL REFERENCES storages
+                                        ON DELETE CASCADE
+                                        ON UPDATE CASCADE,
+            parent_id  UUID        REFERENCES folders
+                                        ON DELETE CASCADE
+                                        ON UPDATE CASCADE,
+            name       VARCHAR     NOT NULL,
+            trash_id   UUID        REFERENCES trash
+                                        ON DELETE CASCADE
+                                        ON UPDATE CASCADE,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+    ",
+        "
+        CREATE UNIQUE INDEX IF NOT EXISTS folders_storage_id_root_idx
+        ON folders (storage_id)
+        WHERE parent_id IS NULL;
+    ",
+        "
+        CREATE UNIQUE INDEX IF NOT EXISTS folders_parent_id_name_idx
+        ON folders (parent_id, name)
+        WHERE trash_id IS NULL;
+    ",
+        "
+        CREATE INDEX IF NOT EXISTS folders_trash_id_idx ON folders (trash_id);
+    ",
+        "
+        INSERT INTO folders (id, storage_id, parent_id, name)
+        SELECT gen_random_uuid(), id, NULL, ''
+        FROM storages
+        ON CONFLICT (storage_id) WHERE parent_id IS NULL DO NOTHING;
+    ",
+        "
+        ALTER TABLE files
+            ADD COLUMN IF NOT EXISTS folder_id UUID REFERENCES folders
+                                                    ON DELETE CASCADE
+                                                    ON UPDATE CASCADE,
+            ADD COLUMN IF NOT EXISTS name      VARCHAR;
+    ",
+        "
+        CREATE UNIQUE INDEX IF NOT EXISTS files_folder_id_name_idx
+        ON files (folder_id, name)
+        WHERE trash_id IS NULL;
+    ",
+        // path of a folder with a trailing slash, it's empty for root folders
+        "
+        CREATE OR REPLACE FUNCTION folder_path(_folder_id UUID)
+            RETURNS TEXT
+            LANGUAGE sql
+            STABLE
+        AS $$
+            WITH RECURSIVE ancestors AS (
+                SELECT parent_id, name, 0 AS depth
+                FROM folders
+                WHERE id = _folder_id
+                UNION ALL
+                SELECT f.parent_id, f.name, a.depth + 1
+                FROM folders f
+                JOIN ancestors a ON f.id = a.parent_id
+            )
+            SELECT COALESCE(
+                STRING_AGG(name || '/', '' ORDER BY depth DESC) FILTER (WHERE parent_id IS NOT NULL),
+                ''
+            )
+            FROM ancestors;
+        $$;
+    ",
+        // returns NULL if there is no such folder
+        "
+        CREATE OR REPLACE FUNCTION folder_id_by_path(_storage_id UUID, _path TEXT)
+            RETURNS UUID
+            LANGUAGE plpgsql
+            STABLE
+        AS $$
+        DECLARE
+            _id   UUID;
+            _name TEXT;
+        BEGIN
+            SELECT id INTO _id FROM folders WHERE storage_id = _storage_id AND parent_id IS NULL;
+
+            FOREACH _name IN ARRAY STRING_TO_ARRAY(TRIM(TRAILING '/' FROM _path), '/') LOOP
+                SELECT id INTO _id
+                FROM folders
+                WHERE parent_id = _id AND name = _name AND trash_id IS NULL;
+
+                EXIT WHEN _id IS NULL;
+            END LOOP;
+
+            RETURN _id;
+        END;
+        $$;
+    ",
+        // creates all the missing folders of the path like `mkdir -p`
+        "
+        CREATE OR REPLACE FUNCTION ensure_folder_path(_storage_id UUID, _path TEXT)
+            RETURNS UUID
+            LANGUAGE plpgsql
+        AS $$
+        DECLARE
+            _id        UUID;
+            _parent_id UUID;
+            _name      TEXT;
+        BEGIN
+            INSERT INTO folders (id, storage_id, parent_id, name)
+            VALUES (gen_random_uuid(), _storage_id, NULL, '')
+            ON CONFLICT (storage_id) WHERE parent_id IS NULL DO NOTHING;
+
+            SELECT id INTO _id FROM folders WHERE storage_id = _storage_id AND parent_id IS NULL;
+
+            FOREACH _name IN ARRAY STRING_TO_ARRAY(TRIM(TRAILING '/' FROM _path), '/') LOOP
+                _parent_id := _id;
+
+                INSERT INTO folders (id, storage_id, parent_id, name)
+                VALUES (gen_random_uuid(), _storage_id, _parent_id, _name)
+                ON CONFLICT (parent_id, name) WHERE trash_id IS NULL DO NOTHING;
+
+                SELECT id INTO _id
+                FROM folders
+                WHERE parent_id = _parent_id AND name = _name AND trash_id IS NULL;
+            END LOOP;
+
+            RETURN _id;
+        END;
+        $$;
+    ",
+        "
+        ALTER TABLE files
+            ADD COLUMN IF NOT EXISTS tags     TEXT[] NOT NULL DEFAULT '{}',
+            ADD COLUMN IF NOT EXISTS metadata JSONB  NOT NULL DEFAULT '{}';
+    ",
+        "
+        ALTER TABLE folders
+            ADD COLUMN IF NOT EXISTS tags     TEXT[] NOT NULL DEFAULT '{}',
+            ADD COLUMN IF NOT EXISTS metadata JSONB  NOT NULL DEFAULT '{}';
+    ",
+        // links point to a file or a folder by id, so they keep working after moves
+        "
+        CREATE TABLE IF NOT EXISTS share_links (
+            id               UUID        PRIMARY KEY,
+            storage_id       UUID        NOT NULL REFERENCES storages
+                                              ON DELETE CASCADE
+                                              ON UPDATE CASCADE,
+            token            VARCHAR     NOT NULL UNIQUE,
+            file_id          UUID        REFERENCES files
+                                              ON DELETE CASCADE
+                                              ON UPDATE CASCADE,
+            folder_id        UUID        REFERENCES folders
+                                              ON DELETE CASCADE
+                                              ON UPDATE CASCADE,
+            password_hash    VARCHAR,
+            expires_at       TIMESTAMPTZ,
+            max_downloads    INT,
+            downloads_amount INT         NOT NULL DEFAULT 0,
+            created_by       UUID        NOT NULL REFERENCES users
+                                              ON DELETE CASCADE
+                                              ON UPDATE CASCADE,
+            created_at       TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            CHECK ((file_id IS NULL) <> (folder_id IS NULL))
+        );
+    ",
+        "
+        CREATE INDEX IF NOT EXISTS share_links_storage_id_idx ON share_links (storage_id);
+    ",
+        // quotas are unlimited when they are not set
+        "
+        ALTER TABLE storages
+            ADD COLUMN IF NOT EXISTS max_size  BigInt,
+            ADD COLUMN IF NOT EXISTS max_files BigInt;
+    ",
+        "
+        ALTER TABLE access
+            ADD COLUMN IF NOT EXISTS max_size  BigInt,
+            ADD COLUMN IF NOT EXISTS max_files BigInt;
+    ",
+        // usage reserved by uploads and copies in progress, so they can't exceed quotas together.
+        // Reservations expire by age, so operations interrupted by restarts don't hold them forever
+        "
+        CREATE TABLE IF NOT EXISTS quota_reservations (
+            id          UUID        PRIMARY KEY,
+            storage_id  UUID        NOT NULL REFERENCES storages
+                                            ON DELETE CASCADE
+                                            ON UPDATE CASCADE,
+            user_id     UUID        NOT NULL REFERENCES users
+                                            ON DELETE CASCADE
+                                            ON UPDATE CASCADE,
+            size        BigInt      NOT NULL,
+            user_size   BigInt      NOT NULL,
+            files       BigInt      NOT NULL,
+            created_at  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+    ",
+        "
+        CREATE INDEX IF NOT EXISTS quota_reservations_storage_id_idx
+            ON quota_reservations (storage_id, user_id);
+    ",
+        // drops are bound to a folder by id, so they keep working after moves
+        "
+        CREATE TABLE IF NOT EXISTS file_drops (
+            id               UUID        PRIMARY KEY,
+            storage_id       UUID        NOT NULL REFERENCES storages
+                                              ON DELETE CASCADE
+                                              ON UPDATE CASCADE,
+            token            VARCHAR     NOT NULL UNIQUE,
+            folder_id        UUID        NOT NULL REFERENCES folders
+                                              ON DELETE CASCADE
+                                              ON UPDATE CASCADE,
+            max_file_size    BIGINT,
+            max_files        INT,
+            files_amount     INT         NOT NULL DEFAULT 0,
+            expires_at       TIMESTAMPTZ,
+            created_by       UUID        NOT NULL REFERENCES users
+                                              ON DELETE CASCADE
+                                              ON UPDATE CASCADE,
+            created_at       TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+    ",
+        "
+        CREATE INDEX IF NOT EXISTS file_drops_storage_id_idx ON file_drops (storage_id);
+    ",
+        // SHA-256 of the content, it's not set for files uploaded before hashes were introduced
+        "
+        ALTER TABLE files ADD COLUMN IF NOT EXISTS content_hash VARCHAR;
+    ",
+        "
+        ALTER TABLE file_versions ADD COLUMN IF NOT EXISTS content_hash VARCHAR;
+    ",
+        "
+        CREATE INDEX IF NOT EXISTS files_storage_id_content_hash_idx
+        ON files (storage_id, content_hash)
+        WHERE content_hash IS NOT NULL;
+    ",
+        "
+        CREATE TABLE IF NOT EXISTS app_passwords (
+            id            UUID        PRIMARY KEY,
+            user_id       UUID        NOT NULL REFERENCES users
+                                           ON DELETE CASCADE
+                                           ON UPDATE CASCADE,
+            name          VARCHAR     NOT NULL,
+            password_hash VARCHAR     NOT NULL UNIQUE,
+            created_at    TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            last_used_at  TIMESTAMPTZ
+        );
+    ",
+        "
+        CREATE INDEX IF NOT EXISTS app_passwords_user_id_idx ON app_passwords (user_id);
+    ",
+        // expired locks are ignored and cleared once the storage is locked again
+        "
+        CREATE TABLE IF NOT EXISTS dav_locks (
+            id         UUID        PRIMARY KEY,
+            storage_id UUID        NOT NULL REFERENCES storages
+                                        ON DELETE CASCADE
+                                        ON UPDATE CASCADE,
+            path       VARCHAR     NOT NULL,
+            token      VARCHAR     NOT NULL UNIQUE,
+            is_shared  bool        NOT NULL,
+            is_deep    bool        NOT NULL,
+            owner      VARCHAR,
+            created_by UUID        NOT NULL REFERENCES users
+                                        ON DELETE CASCADE
+                                        ON UPDATE CASCADE,
+            expires_at TIMESTAMPTZ NOT NULL
+        );
+    ",
+        "
+        CREATE INDEX IF NOT EXISTS dav_locks_storage_id_idx ON dav_locks (storage_id);
+    ",
+        // secrets are needed to check signatures, so they are encrypted instead of hashed
+        "
+        CREATE TABLE IF NOT EXISTS s3_keys (
+            id               UUID        PRIMARY KEY,
+            user_id          UUID        NOT NULL REFERENCES users
+                                              ON DELETE CASCADE
+                                              ON UPDATE CASCADE,
+            name             VARCHAR     NOT NULL,
+            access_key_id    VARCHAR     NOT NULL UNIQUE,
+            encrypted_secret VARCHAR     NOT NULL,
+            created_at       TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            last_used_at     TIMESTAMPTZ
+        );
+    ",
+        "
+        CREATE INDEX IF NOT EXISTS s3_keys_user_id_idx ON s3_keys (user_id);
+    ",
+        "
+        CREATE TABLE IF NOT EXISTS multipart_uploads (
+            id         UUID        PRIMARY KEY,
+            storage_id UUID        NOT NULL REFERENCES storages
+                                        ON DELETE CASCADE
+                                        ON UPDATE CASCADE,
+            path       VARCHAR     NOT NULL,
+            created_by UUID        NOT NULL REFERENCES users
+                                        ON DELETE CASCADE
+                                        ON UPDATE CASCADE,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+    ",
+        "
+        CREATE INDEX IF NOT EXISTS multipart_uploads_created_at_idx
+        ON multipart_uploads (created_at);
+    ",
+        // parts are kept until the upload is completed, aborted or expired,
+        // a part uploaded again replaces the previous one once its chunks are uploaded
+        "
+        CREATE TABLE IF NOT EXISTS multipart_parts (
+            id          UUID        PRIMARY KEY,
+            upload_id   UUID        NOT NULL REFERENCES multipart_uploads
+                                         ON DELETE CASCADE
+                                         ON UPDATE CASCADE,
+            part_number INT         NOT NULL,
+            size        BigInt      NOT NULL,
+            etag        VARCHAR     NOT NULL,
+            is_uploaded bool        NOT NULL,
+            created_at  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+    ",
+        "
+        CREATE UNIQUE INDEX IF NOT EXISTS multipart_parts_upload_id_part_number_idx
+        ON multipart_parts (upload_id, part_number)
+        WHERE is_uploaded;
+    ",
+        "
+        CREATE TABLE IF NOT EXISTS multipart_part_chunks (
+            id               UUID         PRIMARY KEY,
+            part_id          UUID         NOT NULL REFERENCES multipart_parts
+                                               ON DELETE CASCADE
+                                               ON UPDATE CASCADE,
+            telegram_file_id VARCHAR(255) NOT NULL,
+            position         SmallInt     NOT NULL,
+            chat_id          BigInt       NOT NULL
+        );
+    ",
+        "
+        CREATE INDEX IF NOT EXISTS multipart_part_chunks_part_id_idx
+        ON multipart_part_chunks (part_id);
     ",
         r#"
         CREATE OR REPLACE FUNCTION public.regexp_quote(IN TEXT)
@@ -171,6 +645,48 @@ pub async fn init_db(db: &PgPool) {
     }
 
     transaction.commit().await.unwrap();
+
+    create_trigram_indexes(db).await;
+}
+
+/// Trigram indexes let searching by names with wildcards and regular expressions
+/// avoid scanning all the files.
+///
+/// They are optional, as managed databases may not allow creating the `pg_trgm` extension,
+/// then searching works anyway, just slower
+async fn create_trigram_indexes(db: &PgPool) {
+    let is_installed: bool =
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'pg_trgm')")
+            .fetch_one(db)
+            .await
+            .unwrap();
+
+    if !is_installed {
+        if let Err(e) = sqlx::query("CREATE EXTENSION IF NOT EXISTS pg_trgm")
+            .execute(db)
+            .await
+        {
+            tracing::warn!("pg_trgm extension is unavailable, skipping trigram indexes: {e}");
+            return;
+        }
+    }
+
+    for statement in [
+        "
+        CREATE INDEX IF NOT EXISTS files_name_trgm_idx
+        ON files USING GIN (name gin_trgm_ops)
+        WHERE trash_id IS NULL;
+    ",
+        "
+        CREATE INDEX IF NOT EXISTS folders_name_trgm_idx
+        ON folders USING GIN (name gin_trgm_ops)
+        WHERE trash_id IS NULL;
+    ",
+    ] {
+        if let Err(e) = sqlx::query(statement).execute(db).await {
+            tracing::warn!("can't create trigram index, skipping it: {e}");
+        }
+    }
 }
 
 #[inline]
@@ -193,3 +709,186 @@ pub async fn create_superuser(db: &PgPool, config: &Config) {
         }
     };
 }
+
+/// Encrypts storage workers tokens that were saved as a plain text
+#[inline]
+pub async fn encrypt_storage_workers_tokens(db: &PgPool, config: &Config) {
+    let repo = StorageWorkersRepository::new(db);
+    let storage_workers = repo.list_with_plain_tokens().await.unwrap();
+
+    for sw in storage_workers {
+        let token = TokenCipher::encrypt(&sw.token, &config.tokens_encryption_key).unwrap();
+        let token_hash = TokenCipher::hash(&sw.token, &config.tokens_encryption_key);
+        repo.update_token(sw.id, &token, &token_hash).await.unwrap();
+
+        tracing::debug!("encrypted token of storage worker with id \"{}\"", sw.id);
+    }
+}
+
+/// Moves files from paths to the folders hierarchy.
+///
+/// Folders used to be implied by paths prefixes and placeholders like `foo/`,
+/// so they are created for every prefix and placeholders are removed.
+/// Folders of trashed folders are created as trashed ones,
+/// and folders are considered created along with their earliest files.
+pub async fn migrate_paths_to_folders(db: &PgPool) {
+    let mut transaction = db.begin().await.unwrap();
+
+    let (is_migrated,): (bool,) = sqlx::query_as(
+        "
+        SELECT NOT EXISTS (
+            SELECT *
+            FROM information_schema.columns
+            WHERE table_schema = current_schema() AND table_name = 'files' AND column_name = 'path'
+        );
+    ",
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .unwrap();
+    if is_migrated {
+        return;
+    }
+
+    tracing::debug!("migrating files paths to folders");
+
+    // (id, storage id, path, trash id, created at)
+    type FileRow = (Uuid, Uuid, String, Option<Uuid>, DateTime<Utc>);
+    // (id, storage id, parent id, name, trash id, created at)
+    type NewFolder = (Uuid, Uuid, Uuid, String, Option<Uuid>, DateTime<Utc>);
+
+    let files: Vec<FileRow> =
+        sqlx::query_as("SELECT id, storage_id, path, trash_id, created_at FROM files")
+            .fetch_all(&mut *transaction)
+            .await
+            .unwrap();
+    let trash: HashMap<Uuid, String> = sqlx::query_as("SELECT id, path FROM trash")
+        .fetch_all(&mut *transaction)
+        .await
+        .unwrap()
+        .into_iter()
+        .collect();
+
+    // (storage id, path, trash id) -> folder id, root folders are already created
+    let mut folders_ids: HashMap<(Uuid, String, Option<Uuid>), Uuid> =
+        sqlx::query_as::<_, (Uuid, Uuid)>(
+            "SELECT storage_id, id FROM folders WHERE parent_id IS NULL",
+        )
+        .fetch_all(&mut *transaction)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(storage_id, id)| ((storage_id, String::new(), None), id))
+        .collect();
+    // parents go before their children
+    let mut new_folders: Vec<NewFolder> = vec![];
+    let mut new_folders_positions: HashMap<Uuid, usize> = HashMap::new();
+    let mut files_folders: Vec<(Uuid, Uuid, String)> = vec![];
+    let mut placeholders_ids = vec![];
+
+    for (id, storage_id, path, trash_id, created_at) in files {
+        let (parent, name) = match path.rsplit_once('/') {
+            Some((parent, name)) => (format!("{parent}/"), name),
+            None => (String::new(), path.as_str()),
+        };
+        let trashed_folder = trash_id
+            .and_then(|trash_id| trash.get(&trash_id))
+            .filter(|trash_path| trash_path.ends_with('/'));
+
+        let mut folder_id = folders_ids[&(storage_id, String::new(), None)];
+        let mut folder_path = String::new();
+        for folder_name in parent.split_terminator('/') {
+            folder_path = format!("{folder_path}{folder_name}/");
+            let folder_trash_id = trashed_folder
+                .filter(|trash_path| folder_path.starts_with(trash_path.as_str()))
+                .and(trash_id);
+
+            folder_id = *folders_ids
+                .entry((storage_id, folder_path.clone(), folder_trash_id))
+                .or_insert_with(|| {
+                    let id = Uuid::new_v4();
+                    new_folders_positions.insert(id, new_folders.len());
+                    new_folders.push((
+                        id,
+                        storage_id,
+                        folder_id,
+                        folder_name.to_owned(),
+                        folder_trash_id,
+                        created_at,
+                    ));
+                    id
+                });
+
+            let folder_created_at = &mut new_folders[new_folders_positions[&folder_id]].5;
+            *folder_created_at = created_at.min(*folder_created_at);
+        }
+
+        if name.is_empty() {
+            placeholders_ids.push(id);
+        } else {
+            files_folders.push((id, folder_id, name.to_owned()));
+        }
+    }
+
+    for chunk in new_folders.chunks(10_000) {
+        QueryBuilder::new(
+            "INSERT INTO folders (id, storage_id, parent_id, name, trash_id, created_at)",
+        )
+        .push_values(
+            chunk,
+            |mut q, (id, storage_id, parent_id, name, trash_id, created_at)| {
+                q.push_bind(id)
+                    .push_bind(storage_id)
+                    .push_bind(parent_id)
+                    .push_bind(name)
+                    .push_bind(trash_id)
+                    .push_bind(created_at);
+            },
+        )
+        .build()
+        .execute(&mut *transaction)
+        .await
+        .unwrap();
+    }
+
+    let (ids, (files_folders_ids, names)): (Vec<_>, (Vec<_>, Vec<_>)) = files_folders
+        .into_iter()
+        .map(|(id, folder_id, name)| (id, (folder_id, name)))
+        .unzip();
+    sqlx::query(
+        "
+        UPDATE files f
+        SET folder_id = m.folder_id, name = m.name
+        FROM UNNEST($1::uuid[], $2::uuid[], $3::text[]) AS m(id, folder_id, name)
+        WHERE f.id = m.id;
+    ",
+    )
+    .bind(ids)
+    .bind(files_folders_ids)
+    .bind(names)
+    .execute(&mut *transaction)
+    .await
+    .unwrap();
+
+    sqlx::query("DELETE FROM files WHERE id = ANY($1)")
+        .bind(placeholders_ids)
+        .execute(&mut *transaction)
+        .await
+        .unwrap();
+
+    sqlx::query(
+        "
+        ALTER TABLE files
+            ALTER COLUMN folder_id SET NOT NULL,
+            ALTER COLUMN name SET NOT NULL,
+            DROP COLUMN path;
+    ",
+    )
+    .execute(&mut *transaction)
+    .await
+    .unwrap();
+
+    transaction.commit().await.unwrap();
+
+    tracing::debug!("migrated files paths to folders");
+}
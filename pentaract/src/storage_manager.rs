@@ -2,10 +2,12 @@ use sqlx::PgPool;
 
 use crate::{
     common::channels::{
-        ClientData, ClientMessage, DownloadFileData, StorageManagerData, StorageManagerListener,
-        StorageManagerMessage, UploadFileData,
+        ClientData, ClientMessage, DownloadChunkData, DownloadFileData, StorageManagerData,
+        StorageManagerListener, StorageManagerMessage, UploadFileData, UploadPartData,
     },
+    common::thumbnails::{self, ThumbnailsCache},
     config::Config,
+    repositories::files::FilesRepository,
     services::storage_manager::StorageManagerService,
 };
 
@@ -32,7 +34,9 @@ impl StorageManager {
     async fn handle_msg(&self, msg: ClientMessage) {
         let result = match msg.data {
             ClientData::UploadFile(data) => self.upload(data).await,
+            ClientData::UploadPart(data) => self.upload_part(data).await,
             ClientData::DownloadFile(data) => self.download(data).await,
+            ClientData::DownloadChunk(data) => self.download_chunk(data).await,
         };
         let msg_back = StorageManagerMessage::new(result);
 
@@ -44,22 +48,76 @@ impl StorageManager {
             &self.db,
             &self.config.telegram_api_base_url,
             self.config.telegram_rate_limit,
+            &self.config.tokens_encryption_key,
         )
-        .upload(data)
+        .upload(&data)
         .await;
 
+        if result.is_ok() {
+            self.generate_thumbnail(data).await;
+        }
+
         StorageManagerData::UploadFile(result)
     }
 
+    async fn upload_part(&self, data: UploadPartData) -> StorageManagerData {
+        let result = StorageManagerService::new(
+            &self.db,
+            &self.config.telegram_api_base_url,
+            self.config.telegram_rate_limit,
+            &self.config.tokens_encryption_key,
+        )
+        .upload_part(&data)
+        .await;
+
+        StorageManagerData::UploadPart(result)
+    }
+
+    /// Thumbnails of uploaded images are generated in background, so uploads don't wait for them
+    async fn generate_thumbnail(&self, data: UploadFileData) {
+        let Ok(file) = FilesRepository::new(&self.db)
+            .get_file_by_id(data.file_id)
+            .await
+        else {
+            return;
+        };
+        let size = data.file_data.len() as i64;
+        if !thumbnails::is_supported(file.mime_type.as_deref(), size) {
+            return;
+        }
+
+        let thumbnails_dir = self.config.thumbnails_dir.clone();
+        tokio::spawn(async move {
+            let _ = ThumbnailsCache::new(&thumbnails_dir)
+                .generate_and_save(data.file_id, data.file_data.into())
+                .await;
+        });
+    }
+
     async fn download(&self, data: DownloadFileData) -> StorageManagerData {
         let result = StorageManagerService::new(
             &self.db,
             &self.config.telegram_api_base_url,
             self.config.telegram_rate_limit,
+            &self.config.tokens_encryption_key,
         )
         .download(data)
         .await;
 
         StorageManagerData::DownloadFile(result)
     }
+
+    async fn download_chunk(&self, data: DownloadChunkData) -> StorageManagerData {
+        let result = StorageManagerService::new(
+            &self.db,
+            &self.config.telegram_api_base_url,
+            self.config.telegram_rate_limit,
+            &self.config.tokens_encryption_key,
+        )
+        .download_chunk(data.storage_id, data.chunk)
+        .await
+        .map(|chunk| chunk.data);
+
+        StorageManagerData::DownloadChunk(result)
+    }
 }
@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tokio::time;
+
+use crate::repositories::trash::TrashRepository;
+
+/// Purges trash items that are older than the retention period
+pub struct TrashCleaner {
+    db: PgPool,
+    retention_days: u16,
+}
+
+impl TrashCleaner {
+    pub fn new(db: PgPool, retention_days: u16) -> Self {
+        Self { db, retention_days }
+    }
+
+    pub async fn run(&self) {
+        let mut interval = time::interval(Duration::from_secs(60 * 60));
+
+        loop {
+            interval.tick().await;
+
+            match TrashRepository::new(&self.db)
+                .delete_outdated(self.retention_days)
+                .await
+            {
+                Ok(amount) => tracing::debug!("purged {amount} outdated trash items"),
+                Err(e) => tracing::error!("{e}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tokio::time;
+
+use crate::repositories::multipart_uploads::MultipartUploadsRepository;
+
+/// Aborts multipart uploads which are not completed within the expiry period
+pub struct UploadsCleaner {
+    db: PgPool,
+    expiry_days: u16,
+}
+
+impl UploadsCleaner {
+    pub fn new(db: PgPool, expiry_days: u16) -> Self {
+        Self { db, expiry_days }
+    }
+
+    pub async fn run(&self) {
+        let mut interval = time::interval(Duration::from_secs(60 * 60));
+
+        loop {
+            interval.tick().await;
+
+            match MultipartUploadsRepository::new(&self.db)
+                .delete_outdated(self.expiry_days)
+                .await
+            {
+                Ok(amount) => tracing::debug!("aborted {amount} expired multipart uploads"),
+                Err(e) => tracing::error!("{e}"),
+            }
+        }
+    }
+}
@@ -74,6 +74,12 @@ const createStorage = async (name, chat_id) => {
  * @typedef {Object} StorageWithInfoProperties
  * @property {number} size
  * @property {number} files_amount
+ * @property {number | null} max_size
+ * @property {number | null} max_files
+ * @property {number} user_size
+ * @property {number} user_files_amount
+ * @property {number | null} user_max_size
+ * @property {number | null} user_max_files
  * @typedef {Storage & StorageWithInfoProperties} StorageWithInfo
  */
 
@@ -213,16 +219,28 @@ const createFolder = async (storage_id, path, folderName) => {
 }
 
 /**
+ * @typedef {Object} UploadedFile
+ * @property {string} filename
+ * @property {string | null} path
+ * @property {string | null} error
+ */
+
+/**
+ * Files are saved by their relative paths, so folders keep their structure,
+ * files are renamed if their names are taken
  *
  * @param {string} storage_id
  * @param {string} path
- * @param {any} file
- * @returns
+ * @param {File[]} files
+ * @returns {Promise<{files: UploadedFile[]}>}
  */
-const uploadFile = async (storage_id, path, file) => {
+const uploadFiles = async (storage_id, path, files) => {
 	const form = new FormData()
-	form.append('file', file)
+	for (const file of files) {
+		form.append('file', file, file.webkitRelativePath || file.name)
+	}
 	form.append('path', path)
+	form.append('on_conflict', 'rename')
 
 	return await apiMultipartRequest(
 		`/storages/${storage_id}/files/upload`,
@@ -231,17 +249,33 @@ const uploadFile = async (storage_id, path, file) => {
 	)
 }
 
+/**
+ * @typedef {'fail' | 'overwrite' | 'rename' | 'skip'} ConflictPolicy
+ */
+
+/**
+ * @typedef {Object} UploadedFile
+ * @property {string} filename
+ * @property {string | null} path
+ * @property {string | null} error
+ * @property {boolean} skipped
+ */
+
 /**
  *
  * @param {string} storage_id
  * @param {string} path
  * @param {any} file
- * @returns
+ * @param {ConflictPolicy} [on_conflict]
+ * @returns {Promise<UploadedFile>}
  */
-const uploadFileTo = async (storage_id, path, file) => {
+const uploadFileTo = async (storage_id, path, file, on_conflict) => {
 	const form = new FormData()
 	form.append('file', file)
 	form.append('path', path)
+	if (on_conflict) {
+		form.append('on_conflict', on_conflict)
+	}
 
 	return await apiMultipartRequest(
 		`/storages/${storage_id}/files/upload_to`,
@@ -258,15 +292,25 @@ const uploadFileTo = async (storage_id, path, file) => {
  * @property {number} size
  */
 
+/**
+ * @typedef {Object} FSLayer
+ * @property {FSElement[]} items
+ * @property {string | null} next_cursor
+ * @property {number | null} total
+ */
+
 /**
  *
  * @param {string} storage_id
  * @param {string} path
- * @returns {Promise<FSElement[]>}
+ * @param {string | undefined} cursor
+ * @returns {Promise<FSLayer>}
  */
-const getFSLayer = async (storage_id, path) => {
+const getFSLayer = async (storage_id, path, cursor) => {
+	const query = cursor ? `?cursor=${encodeURIComponent(cursor)}` : ''
+
 	return await apiRequest(
-		`/storages/${storage_id}/files/tree/${path}`,
+		`/storages/${storage_id}/files/tree/${path}${query}`,
 		'get',
 		getAuthToken()
 	)
@@ -303,6 +347,41 @@ const deleteFile = async (storage_id, path) => {
 	)
 }
 
+/**
+ * @typedef {Object} BatchOperation
+ * @property {'delete' | 'move' | 'copy' | 'tag'} op
+ * @property {string} [path]
+ * @property {string} [from]
+ * @property {string} [to]
+ * @property {string} [to_storage_id]
+ * @property {ConflictPolicy} [on_conflict]
+ * @property {string[]} [tags]
+ * @property {Object<string, string>} [metadata]
+ */
+
+/**
+ * @typedef {Object} BatchItem
+ * @property {'done' | 'accepted' | 'failed' | 'rolled_back' | 'skipped'} status
+ * @property {string | null} path
+ * @property {string | null} error
+ */
+
+/**
+ *
+ * @param {string} storage_id
+ * @param {BatchOperation[]} operations
+ * @param {'transactional' | 'best_effort'} [mode]
+ * @returns {Promise<{items: BatchItem[]}>}
+ */
+const batch = async (storage_id, operations, mode = 'transactional') => {
+	return await apiRequest(
+		`/storages/${storage_id}/batch`,
+		'post',
+		getAuthToken(),
+		{ operations, mode }
+	)
+}
+
 /////////////////////////////////////////////////////////////
 ////  API
 /////////////////////////////////////////////////////////////
@@ -330,11 +409,12 @@ const API = {
 	},
 	files: {
 		createFolder,
-		uploadFile,
+		uploadFiles,
 		uploadFileTo,
 		getFSLayer,
 		download,
 		deleteFile,
+		batch,
 	},
 }
 
@@ -2,6 +2,10 @@ import Divider from '@suid/material/Divider'
 import Box from '@suid/material/Box'
 import Button from '@suid/material/Button'
 import TextField from '@suid/material/TextField'
+import Select from '@suid/material/Select'
+import InputLabel from '@suid/material/InputLabel'
+import FormControl from '@suid/material/FormControl'
+import MenuItem from '@suid/material/MenuItem'
 import Typography from '@suid/material/Typography'
 import { useNavigate, useParams } from '@solidjs/router'
 import Stack from '@suid/material/Stack'
@@ -30,10 +34,23 @@ const UploadFileTo = () => {
 
 		const path = data.get('path')
 		const file = data.get('file')
+		const onConflict = data.get('on_conflict') || undefined
 
-		await API.files.uploadFileTo(params.id, path, file)
+		const uploaded = await API.files.uploadFileTo(
+			params.id,
+			path,
+			file,
+			onConflict
+		)
+		if (!uploaded) {
+			return
+		}
 
-		addAlert(`Uploaded file to "${path}"`, 'success')
+		if (uploaded.skipped) {
+			addAlert(`"${path}" already exists, skipped`, 'info')
+		} else {
+			addAlert(`Uploaded file to "${uploaded.path}"`, 'success')
+		}
 
 		navigateToFiles()
 	}
@@ -83,6 +100,19 @@ const UploadFileTo = () => {
 					fullWidth
 					required
 				/>
+				<FormControl fullWidth variant="standard">
+					<InputLabel id="on-conflict-select-label">If exists</InputLabel>
+					<Select
+						labelId="on-conflict-select-label"
+						label="If exists"
+						name="on_conflict"
+					>
+						<MenuItem value="fail">Fail</MenuItem>
+						<MenuItem value="overwrite">Overwrite</MenuItem>
+						<MenuItem value="rename">Rename</MenuItem>
+						<MenuItem value="skip">Skip</MenuItem>
+					</Select>
+				</FormControl>
 				<Button type="submit" variant="contained" color="secondary">
 					Upload
 				</Button>
@@ -16,6 +16,7 @@ import Fab from '@suid/material/Fab'
 import ToggleButton from '@suid/material/ToggleButton'
 import ToggleButtonGroup from '@suid/material/ToggleButtonGroup'
 import AddIcon from '@suid/icons-material/Add'
+import Button from '@suid/material/Button'
 
 import API from '../../api'
 import FSListItem from '../../components/FSListItem'
@@ -31,6 +32,7 @@ const Files = () => {
 	 * @type {[import("solid-js").Accessor<import("../../api").FSElement[]>, any]}
 	 */
 	const [fsLayer, setFsLayer] = createSignal([])
+	const [nextCursor, setNextCursor] = createSignal(null)
 	/**
 	 * @type {[import("solid-js").Accessor<import("../../api").Storage>, any]}
 	 */
@@ -50,6 +52,7 @@ const Files = () => {
 	const basePath = `/storages/${params.id}/files`
 
 	let uploadFileInputElement
+	let uploadFolderInputElement
 
 	const fetchUsersWithAccess = async () => {
 		try {
@@ -70,15 +73,28 @@ const Files = () => {
 
 	const fetchFSLayer = async (path = params.path) => {
 		const fsLayerRes = await API.files.getFSLayer(params.id, path)
+		const items = fsLayerRes.items
 
 		if (path.length) {
 			const parentPath = path.split('/').slice(0, -1).join('/')
 			const backToParent = { is_file: false, name: '..', path: parentPath }
 
-			fsLayerRes.splice(0, 0, backToParent)
+			items.splice(0, 0, backToParent)
 		}
 
-		setFsLayer(fsLayerRes)
+		setFsLayer(items)
+		setNextCursor(fsLayerRes.next_cursor)
+	}
+
+	const fetchMore = async () => {
+		const fsLayerRes = await API.files.getFSLayer(
+			params.id,
+			params.path,
+			nextCursor()
+		)
+
+		setFsLayer([...fsLayer(), ...fsLayerRes.items])
+		setNextCursor(fsLayerRes.next_cursor)
 	}
 
 	const reload = async () => {
@@ -134,20 +150,32 @@ const Files = () => {
 		uploadFileInputElement.click()
 	}
 
+	const uploadFolderClickHandler = () => {
+		uploadFolderInputElement.click()
+	}
+
 	/**
 	 *
 	 * @param {Event} event
 	 */
-	const uploadFile = async (event) => {
-		const file = event.target.files[0]
-		if (file === undefined) {
+	const uploadFiles = async (event) => {
+		const files = [...event.target.files]
+		if (!files.length) {
 			return
 		}
 
 		event.target.value = null
 
-		await API.files.uploadFile(params.id, params.path, file)
-		addAlert(`Uploaded file "${file.name}"`, 'success')
+		const result = await API.files.uploadFiles(params.id, params.path, files)
+		const failed = result.files.filter((file) => file.error)
+		if (failed.length) {
+			addAlert(
+				`Failed to upload ${failed.length} of ${files.length} files`,
+				'error'
+			)
+		} else {
+			addAlert(`Uploaded ${files.length} files`, 'success')
+		}
 		await fetchFSLayer()
 	}
 
@@ -214,7 +242,13 @@ const Files = () => {
 									<ListItemIcon>
 										<UploadFileIcon />
 									</ListItemIcon>
-									<ListItemText>Upload file</ListItemText>
+									<ListItemText>Upload files</ListItemText>
+								</MenuItem>
+								<MenuItem onClick={uploadFolderClickHandler}>
+									<ListItemIcon>
+										<UploadFolderIcon />
+									</ListItemIcon>
+									<ListItemText>Upload folder</ListItemText>
 								</MenuItem>
 								<MenuItem
 									onClick={() => navigate(`/storages/${params.id}/upload_to`)}
@@ -255,6 +289,13 @@ const Files = () => {
 									</>
 								))}
 							</List>
+							<Show when={nextCursor()}>
+								<Stack sx={{ alignItems: 'center', mt: 2 }}>
+									<Button variant="outlined" onClick={fetchMore}>
+										Load more
+									</Button>
+								</Stack>
+							</Show>
 						</Show>
 					</Grid>
 
@@ -266,8 +307,16 @@ const Files = () => {
 					<input
 						ref={uploadFileInputElement}
 						type="file"
+						multiple
+						style="display: none"
+						onChange={uploadFiles}
+					/>
+					<input
+						ref={uploadFolderInputElement}
+						type="file"
+						webkitdirectory
 						style="display: none"
-						onChange={uploadFile}
+						onChange={uploadFiles}
 					/>
 				</Show>
 			</Stack>
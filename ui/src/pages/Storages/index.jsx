@@ -72,8 +72,18 @@ const Storages = () => {
 											{storage.name}
 										</TableCell>
 										<TableCell>{storage.chat_id}</TableCell>
-										<TableCell>{convertSize(storage.size)}</TableCell>
-										<TableCell>{storage.files_amount}</TableCell>
+										<TableCell>
+											{convertSize(storage.size)}
+											<Show when={storage.max_size !== null}>
+												{` of ${convertSize(storage.max_size)}`}
+											</Show>
+										</TableCell>
+										<TableCell>
+											{storage.files_amount}
+											<Show when={storage.max_files !== null}>
+												{` of ${storage.max_files}`}
+											</Show>
+										</TableCell>
 									</TableRow>
 								))}
 							</TableBody>